@@ -0,0 +1,225 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::chain;
+use crate::file_lock;
+use crate::ledger::Ledger;
+
+/// How many ledger records have been delivered.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cursor {
+    delivered: usize,
+}
+
+/// Delivers closed round trips to an accounting webhook in ledger order. The append-only
+/// trade ledger is the outbox and a cursor file records how far delivery got, so each
+/// trade is sent once across restarts. A crash after the webhook accepted a trade but
+/// before the cursor was saved sends it again with the same `Idempotency-Key`, which
+/// receivers use to drop the duplicate. Delivery holds the cursor file's lock, so the
+/// final flush at exit and other instances sharing the ledger never post the same trade.
+#[derive(Debug, Clone)]
+pub struct AccountingConfig {
+    url: String,
+    token: Option<String>,
+    cursor: PathBuf,
+    interval: Duration,
+}
+
+impl AccountingConfig {
+    /// Enabled by `ACCOUNTING_WEBHOOK_URL`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(url) = env::var("ACCOUNTING_WEBHOOK_URL").ok() else {
+            return Ok(None);
+        };
+        let secs: u64 = env::var("ACCOUNTING_POLL_SECS")
+            .ok()
+            .map(|v| v.parse().context("invalid ACCOUNTING_POLL_SECS"))
+            .transpose()?
+            .unwrap_or(10);
+        Ok(Some(Self {
+            url,
+            token: env::var("ACCOUNTING_WEBHOOK_TOKEN").ok(),
+            cursor: PathBuf::from(
+                env::var("ACCOUNTING_CURSOR_FILE")
+                    .unwrap_or_else(|_| "accounting_cursor.json".into()),
+            ),
+            interval: Duration::from_secs(secs.max(1)),
+        }))
+    }
+
+    fn load_cursor(&self) -> Result<Cursor> {
+        match fs::read_to_string(&self.cursor) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("corrupt accounting cursor {}", self.cursor.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Cursor::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save_cursor(&self, cursor: &Cursor) -> Result<()> {
+        let tmp = self.cursor.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(cursor)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.cursor)
+            .with_context(|| format!("failed to replace {}", self.cursor.display()))
+    }
+}
+
+/// Delivers new ledger records every `ACCOUNTING_POLL_SECS` in the background.
+pub fn start(config: &AccountingConfig, ledger: Ledger) {
+    let config = config.clone();
+    let client = reqwest::Client::new();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(config.interval);
+        loop {
+            ticker.tick().await;
+            if let Err(err) = deliver(&config, &client, &ledger).await {
+                warn!("Accounting webhook delivery stopped for now: {:#}", err);
+            }
+        }
+    });
+}
+
+/// Sends every ledger record past the cursor, in order, stopping at the first failure so
+/// none is skipped.
+pub async fn deliver(
+    config: &AccountingConfig,
+    client: &reqwest::Client,
+    ledger: &Ledger,
+) -> Result<()> {
+    let _lock = file_lock::exclusive_async(&config.cursor).await?;
+    let mut cursor = config.load_cursor()?;
+    let records = ledger.load()?;
+    if cursor.delivered > records.len() {
+        return Err(anyhow!(
+            "cursor {} is past the {} ledger records; was the ledger rewritten?",
+            cursor.delivered,
+            records.len()
+        ));
+    }
+
+    let chain_id = chain::profile().chain_id;
+    for record in &records[cursor.delivered..] {
+        // Adopted positions have no buy transaction, so the key is what the state store
+        // keys a position by plus when it was opened.
+        let id = format!("{:?}:{:?}:{}", record.wallet, record.token, record.opened_at);
+        let mut request = client
+            .post(&config.url)
+            .header("Idempotency-Key", &id)
+            .json(&json!({
+                "id": id,
+                "chain_id": chain_id,
+                "trade": record,
+                "pnl": record.pnl().to_string(),
+            }));
+        if let Some(token) = &config.token {
+            request = request.bearer_auth(token);
+        }
+        request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("failed to deliver trade {}", id))?;
+        cursor.delivered += 1;
+        config.save_cursor(&cursor)?;
+        info!("Delivered trade {} to the accounting webhook", id);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use ethers::types::{Address, U256};
+
+    use super::*;
+    use crate::ledger::TradeRecord;
+
+    /// Records each Idempotency-Key it accepts and fails the request after `fail_next`.
+    #[derive(Default)]
+    struct Webhook {
+        keys: Mutex<Vec<String>>,
+        fail_next: AtomicBool,
+    }
+
+    async fn receive(State(hook): State<Arc<Webhook>>, headers: HeaderMap) -> StatusCode {
+        if hook.fail_next.swap(false, Ordering::SeqCst) {
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+        let key = headers["idempotency-key"].to_str().unwrap().to_string();
+        hook.keys.lock().unwrap().push(key);
+        StatusCode::OK
+    }
+
+    fn trade(opened_at: u64) -> TradeRecord {
+        TradeRecord {
+            token: Address::repeat_byte(2),
+            wallet: Address::repeat_byte(1),
+            buy_tx: None,
+            opened_at,
+            closed_at: opened_at + 60,
+            amount_in: U256::from(100u64),
+            proceeds: U256::from(120u64),
+            gas_spent: U256::from(1u64),
+            exploration: None,
+            strategy: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn delivery_advances_the_cursor_and_resumes_after_a_failure() {
+        let hook = Arc::new(Webhook::default());
+        let app = axum::Router::new()
+            .route("/", axum::routing::post(receive))
+            .with_state(hook.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let dir = std::env::temp_dir().join(format!("nadfun-accounting-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let ledger = Ledger::new(dir.join("trades.jsonl"));
+        let config = AccountingConfig {
+            url,
+            token: None,
+            cursor: dir.join("accounting_cursor.json"),
+            interval: Duration::from_secs(1),
+        };
+        fs::remove_file(dir.join("trades.jsonl")).ok();
+        fs::remove_file(&config.cursor).ok();
+        let client = reqwest::Client::new();
+
+        ledger.record(&trade(1)).unwrap();
+        deliver(&config, &client, &ledger).await.unwrap();
+        assert_eq!(config.load_cursor().unwrap().delivered, 1);
+
+        ledger.record(&trade(2)).unwrap();
+        ledger.record(&trade(3)).unwrap();
+        hook.fail_next.store(true, Ordering::SeqCst);
+        assert!(deliver(&config, &client, &ledger).await.is_err());
+        assert_eq!(config.load_cursor().unwrap().delivered, 1);
+
+        deliver(&config, &client, &ledger).await.unwrap();
+        deliver(&config, &client, &ledger).await.unwrap();
+        assert_eq!(config.load_cursor().unwrap().delivered, 3);
+        let opened: Vec<String> = hook
+            .keys
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|key| key.rsplit(':').next().unwrap().to_string())
+            .collect();
+        assert_eq!(opened, ["1", "2", "3"]);
+    }
+}
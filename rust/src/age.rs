@@ -0,0 +1,287 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Context, Result};
+use ethers::contract::EthEvent;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, Filter};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::caches;
+use crate::chain;
+use crate::curve::{CurveBuyFilter, CurveCreateFilter};
+
+/// Index blocks a token may stay unknown for before its entry is refused.
+const UNKNOWN_GRACE_BLOCKS: u64 = 5;
+
+/// When a token was created and first bought into, from the curve's event logs.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct TokenBlocks {
+    created: u64,
+    #[serde(default)]
+    first_buy: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    /// First block indexed; tokens missing from the index were created before it.
+    from_block: u64,
+    /// Last block indexed, which the gate uses as the current block.
+    indexed_to: u64,
+    tokens: BTreeMap<Address, TokenBlocks>,
+}
+
+/// Entry gating on token age: blocks since the `CurveCreate` and since the first
+/// `CurveBuy` put real MON in the curve. Both come from an index of the bonding curve's
+/// logs kept by a background task and saved to `TOKEN_INDEX_FILE`, so an entry check
+/// makes no RPC call of its own.
+pub struct AgeConfig {
+    /// Wait until the token is this many blocks old.
+    pub min_blocks: Option<u64>,
+    /// Refuse tokens older than this many blocks.
+    pub max_blocks: Option<u64>,
+    /// Wait until this many blocks after the first buy.
+    pub min_blocks_after_liquidity: Option<u64>,
+    /// Refuse once this many blocks have passed since the first buy.
+    pub max_blocks_after_liquidity: Option<u64>,
+    path: PathBuf,
+    backfill_blocks: u64,
+    chunk_blocks: u64,
+    index: Arc<RwLock<Index>>,
+}
+
+enum Verdict {
+    Eligible,
+    Wait(String),
+    Refuse(String),
+}
+
+impl AgeConfig {
+    /// Enabled by any of `MIN_TOKEN_AGE_BLOCKS`, `MAX_TOKEN_AGE_BLOCKS`,
+    /// `MIN_BLOCKS_AFTER_LIQUIDITY` and `MAX_BLOCKS_AFTER_LIQUIDITY`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let blocks = |name: &str| -> Result<Option<u64>> {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {name}")))
+                .transpose()
+        };
+        let min_blocks = blocks("MIN_TOKEN_AGE_BLOCKS")?;
+        let max_blocks = blocks("MAX_TOKEN_AGE_BLOCKS")?;
+        let min_blocks_after_liquidity = blocks("MIN_BLOCKS_AFTER_LIQUIDITY")?;
+        let max_blocks_after_liquidity = blocks("MAX_BLOCKS_AFTER_LIQUIDITY")?;
+        if min_blocks.is_none()
+            && max_blocks.is_none()
+            && min_blocks_after_liquidity.is_none()
+            && max_blocks_after_liquidity.is_none()
+        {
+            return Ok(None);
+        }
+
+        let path = PathBuf::from(
+            env::var("TOKEN_INDEX_FILE").unwrap_or_else(|_| "token_index.json".into()),
+        );
+        let index = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("corrupt token index {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Index::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Some(Self {
+            min_blocks,
+            max_blocks,
+            min_blocks_after_liquidity,
+            max_blocks_after_liquidity,
+            path,
+            backfill_blocks: blocks("TOKEN_INDEX_BACKFILL_BLOCKS")?.unwrap_or(50_000),
+            chunk_blocks: blocks("TOKEN_INDEX_CHUNK_BLOCKS")?.unwrap_or(2_000).max(1),
+            index: Arc::new(RwLock::new(index)),
+        }))
+    }
+
+    /// Waits while the token is too young, and fails once it is too old or unknown.
+    pub async fn wait_until_eligible(&self, token: Address) -> Result<()> {
+        let first_seen_head = self.head();
+        let mut waiting_on = String::new();
+        loop {
+            match self.verdict(token, first_seen_head) {
+                Verdict::Eligible => return Ok(()),
+                Verdict::Refuse(reason) => return Err(anyhow!(reason)),
+                Verdict::Wait(reason) => {
+                    if reason != waiting_on {
+                        info!("Waiting on {:?}: {}", token, reason);
+                        waiting_on = reason;
+                    }
+                    tokio::time::sleep(chain::profile().block_time).await;
+                }
+            }
+        }
+    }
+
+    fn head(&self) -> u64 {
+        self.index.read().map(|index| index.indexed_to).unwrap_or(0)
+    }
+
+    fn verdict(&self, token: Address, first_seen_head: u64) -> Verdict {
+        let Ok(index) = self.index.read() else {
+            return Verdict::Refuse("token index lock poisoned".into());
+        };
+        let head = index.indexed_to;
+        let Some(blocks) = index.tokens.get(&token).copied() else {
+            let indexed_span = head.saturating_sub(index.from_block);
+            if index.from_block > 0 && self.max_blocks.is_some_and(|max| indexed_span > max) {
+                return Verdict::Refuse(format!(
+                    "not in the token index, so created before block {}",
+                    index.from_block
+                ));
+            }
+            if head.saturating_sub(first_seen_head) > UNKNOWN_GRACE_BLOCKS {
+                return Verdict::Refuse("not in the token index; creation block unknown".into());
+            }
+            return Verdict::Wait("not indexed yet".into());
+        };
+
+        let age = head.saturating_sub(blocks.created);
+        if let Some(max) = self.max_blocks.filter(|max| age > *max) {
+            let reason = format!("{} blocks old, over MAX_TOKEN_AGE_BLOCKS {}", age, max);
+            return Verdict::Refuse(reason);
+        }
+        if let Some(min) = self.min_blocks.filter(|min| age < *min) {
+            return Verdict::Wait(format!("{} blocks old, waiting for {}", age, min));
+        }
+        if self.min_blocks_after_liquidity.is_none() && self.max_blocks_after_liquidity.is_none() {
+            return Verdict::Eligible;
+        }
+        let Some(first_buy) = blocks.first_buy else {
+            return Verdict::Wait("no liquidity yet".into());
+        };
+        let since = head.saturating_sub(first_buy);
+        if let Some(max) = self.max_blocks_after_liquidity.filter(|max| since > *max) {
+            return Verdict::Refuse(format!(
+                "{} blocks since first liquidity, over MAX_BLOCKS_AFTER_LIQUIDITY {}",
+                since, max
+            ));
+        }
+        if let Some(min) = self.min_blocks_after_liquidity.filter(|min| since < *min) {
+            let reason = format!("{} blocks since first liquidity, waiting for {}", since, min);
+            return Verdict::Wait(reason);
+        }
+        Verdict::Eligible
+    }
+
+    /// Drops tokens that are refused whatever the head, so the index only grows with the
+    /// launches of the last `MAX_TOKEN_AGE_BLOCKS` or so. Dropped tokens are refused as
+    /// too old or, without an age limit, as unknown.
+    fn prune(&self, index: &mut Index) {
+        let head = index.indexed_to;
+        let too_old = |blocks: &TokenBlocks| {
+            self.max_blocks.is_some_and(|max| head.saturating_sub(blocks.created) > max)
+                || blocks.first_buy.is_some_and(|first_buy| {
+                    self.max_blocks_after_liquidity
+                        .is_some_and(|max| head.saturating_sub(first_buy) > max)
+                })
+        };
+        index.tokens.retain(|_, blocks| !too_old(blocks));
+        if let Some(max) = self.max_blocks {
+            index.from_block = index.from_block.max(head.saturating_sub(max));
+        }
+        caches::observe("token_index", index.tokens.len());
+    }
+
+    fn save(&self) -> Result<()> {
+        let json = {
+            let index = self.index.read().map_err(|_| anyhow!("token index lock poisoned"))?;
+            serde_json::to_string(&*index)?
+        };
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json).with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to replace {}", self.path.display()))
+    }
+}
+
+/// Keeps the token index up to date with the curve's logs every block.
+pub fn start(rpc_url: &str, curve: Address, age: &AgeConfig) -> Result<()> {
+    let provider = Provider::<Http>::try_from(rpc_url).context("invalid RPC_URL")?;
+    let age = AgeConfig {
+        path: age.path.clone(),
+        index: age.index.clone(),
+        ..*age
+    };
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(chain::profile().block_time);
+        loop {
+            ticker.tick().await;
+            match index_new_blocks(&provider, curve, &age).await {
+                Ok(true) => {
+                    if let Err(err) = age.save() {
+                        warn!("Failed to save the token index: {:#}", err);
+                    }
+                }
+                Ok(false) => {}
+                Err(err) => warn!("Token index update failed: {:#}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Indexes the blocks since the last run; returns whether anything changed.
+async fn index_new_blocks(
+    provider: &Provider<Http>,
+    curve: Address,
+    age: &AgeConfig,
+) -> Result<bool> {
+    let head = provider.get_block_number().await?.as_u64();
+    let mut from = {
+        let mut index = age.index.write().map_err(|_| anyhow!("token index lock poisoned"))?;
+        if index.indexed_to == 0 {
+            index.from_block = head.saturating_sub(age.backfill_blocks);
+            index.indexed_to = index.from_block.saturating_sub(1);
+            info!("Indexing token creations from block {}", index.from_block);
+        }
+        index.indexed_to + 1
+    };
+    if from > head {
+        return Ok(false);
+    }
+
+    let create = CurveCreateFilter::signature();
+    let buy = CurveBuyFilter::signature();
+    while from <= head {
+        let to = (from + age.chunk_blocks - 1).min(head);
+        let filter = Filter::new()
+            .address(curve)
+            .from_block(from)
+            .to_block(to)
+            .topic0(vec![create, buy]);
+        let logs = provider.get_logs(&filter).await?;
+
+        let mut index = age.index.write().map_err(|_| anyhow!("token index lock poisoned"))?;
+        for log in logs {
+            let (Some(topic), Some(block)) = (log.topics.first(), log.block_number) else {
+                continue;
+            };
+            // The token is the second indexed argument of both events.
+            let Some(token) = log.topics.get(2).map(|topic| Address::from(*topic)) else {
+                continue;
+            };
+            let block = block.as_u64();
+            if *topic == create {
+                index.tokens.entry(token).or_default().created = block;
+            } else if let Some(blocks) = index.tokens.get_mut(&token) {
+                blocks.first_buy.get_or_insert(block);
+            }
+        }
+        index.indexed_to = to;
+        from = to + 1;
+    }
+    if let Ok(mut index) = age.index.write() {
+        age.prune(&mut index);
+    }
+    Ok(true)
+}
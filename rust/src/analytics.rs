@@ -0,0 +1,413 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate};
+use clap::{Args, Subcommand, ValueEnum};
+use ethers::types::{Address, I256, U256};
+
+use crate::chain;
+use crate::execstats::{ExecLog, ExecRecord, Side};
+use crate::ledger::{self, Ledger, TradeRecord};
+
+#[derive(Debug, Args)]
+pub struct AnalyticsArgs {
+    #[command(subcommand)]
+    pub query: Query,
+
+    /// Print CSV instead of an aligned table.
+    #[arg(long, global = true)]
+    pub csv: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Query {
+    /// Tokens with the highest realized PnL, net of gas.
+    Best {
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Tokens with the lowest realized PnL, net of gas.
+    Worst {
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Executions and native volume per UTC hour of the day.
+    Hourly,
+    /// How far fills landed from their quotes, in bps buckets.
+    Slippage,
+    /// Gas spent on closed round trips per UTC day.
+    Gas,
+    /// Every record of the ledger or the execution log, amounts in wei, for queries this
+    /// command doesn't have (DuckDB and pandas read the CSV as is).
+    Export {
+        #[arg(value_enum)]
+        source: Source,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Source {
+    Ledger,
+    Execution,
+}
+
+/// Slippage bucket upper bounds in bps, after a bucket for fills that beat the quote.
+const SLIPPAGE_BUCKETS: [f64; 6] = [25.0, 50.0, 100.0, 200.0, 500.0, f64::INFINITY];
+
+/// Rows under a header, printed aligned or as CSV.
+struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new(headers: Vec<&'static str>) -> Self {
+        Self {
+            headers,
+            rows: Vec::new(),
+        }
+    }
+
+    fn print(&self, csv: bool) -> Result<()> {
+        if csv {
+            let mut writer = csv::Writer::from_writer(io::stdout());
+            writer.write_record(&self.headers)?;
+            for row in &self.rows {
+                writer.write_record(row)?;
+            }
+            return writer.flush().context("failed to write CSV");
+        }
+        let mut widths: Vec<usize> = self.headers.iter().map(|header| header.len()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+        let line = |cells: Vec<&str>| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(column, (cell, width))| match column {
+                    0 => format!("{:<width$}", cell),
+                    _ => format!("{:>width$}", cell),
+                })
+                .collect();
+            cells.join("  ")
+        };
+        println!("{}", line(self.headers.clone()));
+        for row in &self.rows {
+            println!("{}", line(row.iter().map(String::as_str).collect()));
+        }
+        Ok(())
+    }
+}
+
+/// Runs a canned query over the trade ledger and the execution log.
+pub fn run(ledger: &Ledger, exec_log: &ExecLog, args: &AnalyticsArgs) -> Result<()> {
+    let table = match &args.query {
+        Query::Best { limit } => tokens(&ledger.load()?, *limit, false),
+        Query::Worst { limit } => tokens(&ledger.load()?, *limit, true),
+        Query::Hourly => hourly(&ledger.load()?, &exec_log.load()?),
+        Query::Slippage => slippage(&exec_log.load()?),
+        Query::Gas => gas(&ledger.load()?),
+        Query::Export { source: Source::Ledger } => export_ledger(&ledger.load()?),
+        Query::Export { source: Source::Execution } => export_executions(&exec_log.load()?),
+    };
+    table.print(args.csv)
+}
+
+#[derive(Default)]
+struct TokenTotals {
+    trades: usize,
+    amount_in: U256,
+    gas: U256,
+    pnl: I256,
+}
+
+fn tokens(records: &[TradeRecord], limit: usize, worst: bool) -> Table {
+    let mut by_token: BTreeMap<Address, TokenTotals> = BTreeMap::new();
+    for record in records {
+        let totals = by_token.entry(record.token).or_default();
+        totals.trades += 1;
+        totals.amount_in += record.amount_in;
+        totals.gas += record.gas_spent;
+        totals.pnl += record.pnl();
+    }
+    let mut ranked: Vec<(Address, TokenTotals)> = by_token.into_iter().collect();
+    ranked.sort_by(|(_, a), (_, b)| if worst { a.pnl.cmp(&b.pnl) } else { b.pnl.cmp(&a.pnl) });
+
+    let profile = chain::profile();
+    let mut table = Table::new(vec!["token", "trades", "in", "gas", "pnl"]);
+    for (token, totals) in ranked.into_iter().take(limit) {
+        table.rows.push(vec![
+            format!("{:?}", token),
+            totals.trades.to_string(),
+            profile.format_native(totals.amount_in),
+            profile.format_native(totals.gas),
+            ledger::signed_native(totals.pnl),
+        ]);
+    }
+    table
+}
+
+/// Executions come from the execution log; native volume from the ledger, counting buys
+/// at the hour their position opened and sells at the hour it closed.
+fn hourly(records: &[TradeRecord], executions: &[ExecRecord]) -> Table {
+    let hour = |at: u64| (at / 3600 % 24) as usize;
+    let mut buys = [0usize; 24];
+    let mut sells = [0usize; 24];
+    let mut bought = [U256::zero(); 24];
+    let mut sold = [U256::zero(); 24];
+    for execution in executions {
+        match execution.side {
+            Side::Buy => buys[hour(execution.at)] += 1,
+            Side::Sell => sells[hour(execution.at)] += 1,
+        }
+    }
+    for record in records {
+        bought[hour(record.opened_at)] += record.amount_in;
+        sold[hour(record.closed_at)] += record.proceeds;
+    }
+
+    let profile = chain::profile();
+    let mut table = Table::new(vec!["hour (UTC)", "buys", "sells", "bought", "sold"]);
+    for hour in 0..24 {
+        table.rows.push(vec![
+            format!("{:02}:00", hour),
+            buys[hour].to_string(),
+            sells[hour].to_string(),
+            profile.format_native(bought[hour]),
+            profile.format_native(sold[hour]),
+        ]);
+    }
+    table
+}
+
+fn slippage(executions: &[ExecRecord]) -> Table {
+    let buckets = SLIPPAGE_BUCKETS.len() + 1;
+    let mut counts = vec![[0usize; 2]; buckets];
+    let mut reverted = [0usize; 2];
+    for execution in executions {
+        let side = match execution.side {
+            Side::Buy => 0,
+            Side::Sell => 1,
+        };
+        let Some(bps) = execution.slippage_bps() else {
+            if execution.reverted {
+                reverted[side] += 1;
+            }
+            continue;
+        };
+        let bucket = if bps < 0.0 {
+            0
+        } else {
+            1 + SLIPPAGE_BUCKETS.iter().position(|bound| bps < *bound).unwrap_or(0)
+        };
+        counts[bucket][side] += 1;
+    }
+
+    let mut table = Table::new(vec!["slippage bps", "buys", "sells"]);
+    let mut lower = 0.0;
+    for (bucket, [buys, sells]) in counts.into_iter().enumerate() {
+        let label = match bucket {
+            0 => "better than quote".to_string(),
+            _ if SLIPPAGE_BUCKETS[bucket - 1].is_infinite() => format!("{}+", lower),
+            _ => {
+                let upper = SLIPPAGE_BUCKETS[bucket - 1];
+                let label = format!("{}-{}", lower, upper);
+                lower = upper;
+                label
+            }
+        };
+        table.rows.push(vec![label, buys.to_string(), sells.to_string()]);
+    }
+    table.rows.push(vec!["reverted".into(), reverted[0].to_string(), reverted[1].to_string()]);
+    table
+}
+
+fn gas(records: &[TradeRecord]) -> Table {
+    let mut by_day: BTreeMap<Option<NaiveDate>, (usize, U256)> = BTreeMap::new();
+    for record in records {
+        let day = DateTime::from_timestamp(record.closed_at as i64, 0).map(|at| at.date_naive());
+        let (trades, gas) = by_day.entry(day).or_default();
+        *trades += 1;
+        *gas += record.gas_spent;
+    }
+
+    let profile = chain::profile();
+    let mut table = Table::new(vec!["day (UTC)", "trades", "gas", "per trade"]);
+    for (day, (trades, gas)) in by_day {
+        table.rows.push(vec![
+            day.map_or_else(|| "unknown".to_string(), |day| day.to_string()),
+            trades.to_string(),
+            profile.format_native(gas),
+            profile.format_native(gas / U256::from(trades)),
+        ]);
+    }
+    table
+}
+
+fn export_ledger(records: &[TradeRecord]) -> Table {
+    let mut table = Table::new(vec![
+        "token",
+        "wallet",
+        "buy_tx",
+        "opened_at",
+        "closed_at",
+        "amount_in",
+        "proceeds",
+        "gas_spent",
+        "pnl",
+        "exploration",
+        "strategy",
+    ]);
+    for record in records {
+        table.rows.push(vec![
+            format!("{:?}", record.token),
+            format!("{:?}", record.wallet),
+            record.buy_tx.map(|tx| format!("{:?}", tx)).unwrap_or_default(),
+            record.opened_at.to_string(),
+            record.closed_at.to_string(),
+            record.amount_in.to_string(),
+            record.proceeds.to_string(),
+            record.gas_spent.to_string(),
+            record.pnl().to_string(),
+            record.exploration.clone().unwrap_or_default(),
+            record.strategy.clone().unwrap_or_default(),
+        ]);
+    }
+    table
+}
+
+fn export_executions(executions: &[ExecRecord]) -> Table {
+    let mut table = Table::new(vec![
+        "token",
+        "side",
+        "at",
+        "quoted",
+        "received",
+        "reverted",
+        "inclusion_ms",
+        "slippage_bps",
+    ]);
+    for execution in executions {
+        table.rows.push(vec![
+            format!("{:?}", execution.token),
+            match execution.side {
+                Side::Buy => "buy".into(),
+                Side::Sell => "sell".into(),
+            },
+            execution.at.to_string(),
+            execution.quoted.to_string(),
+            execution.received.to_string(),
+            execution.reverted.to_string(),
+            execution.inclusion_ms.to_string(),
+            execution.slippage_bps().map(|bps| format!("{:.1}", bps)).unwrap_or_default(),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(token: u8, amount_in: u64, proceeds: u64, closed_at: u64) -> TradeRecord {
+        TradeRecord {
+            token: Address::repeat_byte(token),
+            wallet: Address::repeat_byte(9),
+            buy_tx: None,
+            opened_at: closed_at - 60,
+            closed_at,
+            amount_in: U256::from(amount_in),
+            proceeds: U256::from(proceeds),
+            gas_spent: U256::from(10u64),
+            exploration: None,
+            strategy: None,
+        }
+    }
+
+    fn execution(side: Side, quoted: u64, received: u64, at: u64) -> ExecRecord {
+        ExecRecord {
+            token: Address::repeat_byte(1),
+            side,
+            at,
+            quoted: U256::from(quoted),
+            received: U256::from(received),
+            reverted: received == 0,
+            inclusion_ms: 400,
+        }
+    }
+
+    fn column(table: &Table, index: usize) -> Vec<&str> {
+        table.rows.iter().map(|row| row[index].as_str()).collect()
+    }
+
+    #[test]
+    fn tokens_rank_by_pnl_net_of_gas() {
+        let records = [
+            trade(1, 100, 300, 1_000),
+            trade(2, 100, 50, 1_000),
+            trade(3, 100, 150, 1_000),
+            trade(1, 100, 90, 2_000),
+        ];
+        let best = tokens(&records, 2, false);
+        let token = |byte| format!("{:?}", Address::repeat_byte(byte));
+        assert_eq!(column(&best, 0), [token(1), token(3)]);
+        assert_eq!(column(&best, 1), ["2", "1"]);
+        let worst = tokens(&records, 1, true);
+        assert_eq!(column(&worst, 0), [token(2)]);
+    }
+
+    #[test]
+    fn hourly_counts_by_utc_hour_of_day() {
+        let executions = [
+            execution(Side::Buy, 100, 99, 3_600),
+            execution(Side::Sell, 100, 99, 3_600 * 25 + 59),
+            execution(Side::Buy, 100, 99, 3_600 * 5),
+        ];
+        let table = hourly(&[], &executions);
+        assert_eq!(table.rows.len(), 24);
+        assert_eq!(table.rows[1][..3], ["01:00", "1", "1"]);
+        assert_eq!(table.rows[5][..3], ["05:00", "1", "0"]);
+        assert_eq!(table.rows[0][..3], ["00:00", "0", "0"]);
+    }
+
+    #[test]
+    fn slippage_falls_into_bps_buckets() {
+        let executions = [
+            execution(Side::Buy, 10_000, 10_010, 0),
+            execution(Side::Buy, 10_000, 9_990, 0),
+            execution(Side::Buy, 10_000, 9_900, 0),
+            execution(Side::Sell, 10_000, 9_000, 0),
+            execution(Side::Sell, 10_000, 0, 0),
+        ];
+        let table = slippage(&executions);
+        assert_eq!(
+            column(&table, 0),
+            [
+                "better than quote",
+                "0-25",
+                "25-50",
+                "50-100",
+                "100-200",
+                "200-500",
+                "500+",
+                "reverted"
+            ]
+        );
+        assert_eq!(column(&table, 1), ["1", "1", "0", "0", "1", "0", "0", "0"]);
+        assert_eq!(column(&table, 2), ["0", "0", "0", "0", "0", "0", "1", "1"]);
+    }
+
+    #[test]
+    fn gas_adds_up_per_day() {
+        let day = 86_400 * 19_000;
+        let records = [trade(1, 1, 1, day + 10), trade(2, 1, 1, day + 20), trade(3, 1, 1, day * 2)];
+        let table = gas(&records);
+        assert_eq!(column(&table, 0), ["2022-01-08", "2074-01-15"]);
+        assert_eq!(column(&table, 1), ["2", "1"]);
+    }
+}
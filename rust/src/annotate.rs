@@ -0,0 +1,102 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, Log, H256, U256};
+use ethers::utils::keccak256;
+
+use crate::chain;
+use crate::mev;
+use crate::receipts;
+use crate::state::StateStore;
+
+/// Explains what `tx_hash` did from its receipt logs, cross-referenced with the bot's
+/// persisted positions and MEV reports.
+pub async fn run(
+    provider: &Provider<Http>,
+    tx_hash: H256,
+    state: &StateStore,
+    mev_report_file: Option<&Path>,
+) -> Result<()> {
+    let profile = chain::profile();
+    let tx = provider
+        .get_transaction(tx_hash)
+        .await?
+        .ok_or_else(|| anyhow!("transaction {:?} not found", tx_hash))?;
+
+    println!("Transaction {}", profile.tx_url(tx_hash));
+    println!("  from {}", profile.address_url(tx.from));
+    if let Some(to) = tx.to {
+        println!("  to   {}", profile.address_url(to));
+    }
+    if !tx.value.is_zero() {
+        println!("  sent {}", profile.format_native(tx.value));
+    }
+
+    let Some(receipt) = provider.get_transaction_receipt(tx_hash).await? else {
+        println!("  still pending (nonce {})", tx.nonce);
+        return Ok(());
+    };
+    let status = if receipt.status == Some(1u64.into()) { "succeeded" } else { "reverted" };
+    println!(
+        "  {} in block {}, gas {}",
+        status,
+        receipt.block_number.unwrap_or_default(),
+        profile.format_native(receipts::gas_cost(&receipt))
+    );
+
+    if tx.to == Some(tx.from) && tx.value.is_zero() && tx.input.as_ref().is_empty() {
+        println!("  0-value self-transfer: nonce {} replaced by repair", tx.nonce);
+    }
+    for log in &receipt.logs {
+        if let Some(line) = describe_log(log, tx.from) {
+            println!("  {}", line);
+        }
+    }
+
+    for position in state.open_positions()? {
+        if position.buy_tx == Some(tx_hash) {
+            println!(
+                "  entry of the open position in {} ({} tranches sold, held {}s)",
+                profile.address_url(position.token),
+                position.filled_tranches,
+                position.held_for().as_secs()
+            );
+        }
+    }
+    if let Some(path) = mev_report_file {
+        if let Some(report) = mev::find_report(path, tx_hash)? {
+            println!(
+                "  MEV: position {} in block, {} front-runners, sandwiched by {:?}, {} bps vs quote",
+                report.position,
+                report.front_runners.len(),
+                report.sandwiched_by,
+                report.cost_bps
+            );
+        }
+    }
+    Ok(())
+}
+
+/// One line for token transfers and approvals involving `wallet`.
+fn describe_log(log: &Log, wallet: Address) -> Option<String> {
+    let transfer = H256::from(keccak256("Transfer(address,address,uint256)"));
+    let approval = H256::from(keccak256("Approval(address,address,uint256)"));
+    if log.topics.len() != 3 || log.data.len() < 32 {
+        return None;
+    }
+    let first = Address::from(log.topics[1]);
+    let second = Address::from(log.topics[2]);
+    let amount = ethers::utils::format_units(U256::from_big_endian(&log.data[..32]), 18).ok()?;
+    let token = chain::profile().address_url(log.address);
+
+    if log.topics[0] == transfer && second == wallet {
+        Some(format!("received {} of {}", amount, token))
+    } else if log.topics[0] == transfer && first == wallet {
+        Some(format!("sent {} of {} to {:?}", amount, token, second))
+    } else if log.topics[0] == approval && first == wallet {
+        Some(format!("approved {:?} to spend {} of {}", second, amount, token))
+    } else {
+        None
+    }
+}
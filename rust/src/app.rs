@@ -0,0 +1,363 @@
+use std::collections::HashSet;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, U256};
+use tokio::time::Duration;
+use tracing::warn;
+
+use crate::accounting::AccountingConfig;
+use crate::age::AgeConfig;
+use crate::approvals::ApprovalConfig;
+use crate::audit::AuditLog;
+use crate::caches::RetentionConfig;
+use crate::chain;
+use crate::chaos::ChaosConfig;
+use crate::cli::Cli;
+use crate::config::{ConfigFile, Profile, Target, TradeParams};
+use crate::control::{ControlConfig, Controls, ParamLimits};
+use crate::execstats::{AnomalyWatch, ExecLog};
+use crate::explore::ExploreConfig;
+use crate::failover::FailoverConfig;
+use crate::fleet::FleetConfig;
+use crate::gas_budget::GasBudget;
+use crate::gas_strategy::GasStrategy;
+use crate::impact::ImpactConfig;
+use crate::ledger::Ledger;
+use crate::lockdown::LockList;
+use crate::notify::Notifier;
+use crate::orders::OrderBook;
+use crate::pinning::PinPolicy;
+use crate::price_feed::PriceFeed;
+use crate::recovery::{AdoptionPolicy, RecoveryMode};
+use crate::reorg::ReorgConfig;
+use crate::reentry::ReentryConfig;
+use crate::risk::RiskLimits;
+use crate::rpc_pool::RpcPool;
+use crate::safety::SafetyConfig;
+use crate::settle::SettleConfig;
+use crate::shutdown::Shutdown;
+use crate::signals::{SignalConfig, Signals};
+use crate::signer;
+use crate::sink::{self, SinkConfig};
+use crate::sniper::SniperConfig;
+use crate::start::{ClockCheck, StartAt};
+use crate::state::{self, StateStore};
+use crate::traces::TraceConfig;
+use crate::tx_manager::RetryPolicy;
+use crate::utilization::UtilizationConfig;
+use crate::warmer::WarmerConfig;
+
+/// Everything a run is configured with, from the config file, env vars and CLI flags.
+pub struct AppConfig {
+    pub rpc_url: String,
+    pub private_key: String,
+    pub targets: Vec<Target>,
+    pub defaults: TradeParams,
+    pub recipient: Option<Address>,
+    pub bonding_curve: Option<Address>,
+    pub age: Option<AgeConfig>,
+    pub price_feed: Option<Arc<PriceFeed>>,
+    pub deadline_secs_from_now: u64,
+    pub exit_check_interval_secs: u64,
+    pub pin_policy: Option<PinPolicy>,
+    pub max_entry_wait_blocks: u64,
+    pub start_at: Option<StartAt>,
+    pub clock_check: ClockCheck,
+    pub sentry_dsn: Option<String>,
+    pub mev_report_file: Option<PathBuf>,
+    pub gas_budget: Option<GasBudget>,
+    /// Native that balance-based sizing leaves in the wallet for gas, from
+    /// `GAS_RESERVE_MON`.
+    pub gas_reserve: U256,
+    pub risk: Option<RiskLimits>,
+    pub sniper: SniperConfig,
+    pub settle: SettleConfig,
+    pub utilization: UtilizationConfig,
+    pub retention: RetentionConfig,
+    pub approvals: ApprovalConfig,
+    pub state: Arc<StateStore>,
+    pub failover: Option<FailoverConfig>,
+    pub fleet: Option<FleetConfig>,
+    pub safety: SafetyConfig,
+    pub retry_policy: RetryPolicy,
+    pub chaos: Option<ChaosConfig>,
+    /// How round-trip buys and sells are priced, from `GAS_STRATEGY`.
+    pub gas_strategy: GasStrategy,
+    pub rpc_pool: RpcPool,
+    pub dry_run: bool,
+    pub ledger: Ledger,
+    pub accounting: Option<AccountingConfig>,
+    pub sink: Option<SinkConfig>,
+    pub protocol_refresh_secs: u64,
+    pub protocol_max_age: Option<Duration>,
+    pub audit: Option<AuditLog>,
+    pub traces: Option<TraceConfig>,
+    pub notifier: Notifier,
+    pub recovery: RecoveryMode,
+    pub reorg: Option<ReorgConfig>,
+    pub adoption: AdoptionPolicy,
+    pub snapshot_interval_secs: u64,
+    pub orders: OrderBook,
+    pub exec_log: ExecLog,
+    pub exec_auto_tune: bool,
+    pub exec_anomaly: Option<AnomalyWatch>,
+    pub warmer: Option<WarmerConfig>,
+    pub blocklist: HashSet<Address>,
+    pub control: Option<ControlConfig>,
+    pub controls: Arc<Controls>,
+    pub shutdown: Shutdown,
+    pub signals: Option<Arc<Signals>>,
+    pub explore: Option<ExploreConfig>,
+    pub impact: Option<ImpactConfig>,
+    pub reentry: Option<ReentryConfig>,
+    pub locks: LockList,
+}
+
+impl AppConfig {
+    /// Layers built-in defaults, the config file, env vars and CLI flags, each
+    /// overriding the one before. Env trade settings apply to every token.
+    pub fn load(cli: &Cli) -> Result<Self> {
+        let rpc_url = env::var("RPC_URL").context("RPC_URL missing")?;
+        let private_key = signer::private_key()?;
+
+        let file = ConfigFile::load(cli.config.as_deref())?;
+        let env_profile = Profile::from_env()?;
+        let defaults = resolve_defaults(&file, &env_profile)?;
+        let env_token = env::var("TOKEN_ADDRESS")
+            .ok()
+            .map(|v| v.parse().context("invalid token"))
+            .transpose()?;
+        let targets = match cli.token.or(env_token) {
+            Some(token) => vec![file.target(token, &env_profile)?],
+            None => file
+                .tokens
+                .iter()
+                .map(|section| file.target(section.address, &env_profile))
+                .collect::<Result<_>>()?,
+        };
+
+        if let Some(target) = targets.iter().find(|target| file.is_blocked(target.token)) {
+            return Err(anyhow!("token {:?} is on the blocklist", target.token));
+        }
+        let blocklist = file.blocked.iter().map(|blocked| blocked.address).collect();
+        let history_file = env::var("PARAMS_HISTORY_FILE").ok().map(PathBuf::from);
+        let controls = Controls::new(targets.iter().map(|target| target.token))
+            .with_tuning(ParamLimits::from_env()?, history_file)?;
+        let controls = Arc::new(controls);
+        let control = ControlConfig::from_env()?;
+        let signals = if file.peers.is_empty() {
+            None
+        } else if control.is_none() {
+            return Err(anyhow!("[[peer]] signal sharing needs CONTROL_ADDR to receive signals"));
+        } else {
+            let signals = Signals::new(SignalConfig::from_env()?, file.peers.clone())?;
+            Some(Arc::new(signals))
+        };
+
+        let recipient = env::var("RECIPIENT_ADDRESS")
+            .ok()
+            .and_then(|value| value.parse().ok());
+
+        let bonding_curve = env::var("BONDING_CURVE_ADDRESS")
+            .ok()
+            .map(|v| v.parse().context("invalid BONDING_CURVE_ADDRESS"))
+            .transpose()?;
+        let age = AgeConfig::from_env()?;
+        if age.is_some() && bonding_curve.is_none() {
+            return Err(anyhow!("token age gating indexes curve events; set BONDING_CURVE_ADDRESS"));
+        }
+
+        let deadline_secs_from_now = env::var("DEADLINE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(600);
+
+        let exit_check_interval_secs = env::var("EXIT_CHECK_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10);
+
+        let pin_policy = match env::var("TOKEN_PIN_POLICY") {
+            Ok(value) if value.eq_ignore_ascii_case("off") => None,
+            Ok(value) => Some(value.parse().context("invalid TOKEN_PIN_POLICY")?),
+            Err(_) => Some(PinPolicy::Alert),
+        };
+
+        let max_entry_wait_blocks = env::var("MAX_ENTRY_WAIT_BLOCKS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let start_block = env::var("START_AT_BLOCK")
+            .ok()
+            .map(|v| v.parse().context("invalid START_AT_BLOCK"))
+            .transpose()?;
+        let start_time = env::var("START_AT")
+            .ok()
+            .map(|v| v.parse::<StartAt>().context("invalid START_AT"))
+            .transpose()?;
+        let start_at = match (start_block, start_time) {
+            (Some(_), Some(_)) => {
+                return Err(anyhow!("set only one of START_AT_BLOCK and START_AT"))
+            }
+            (Some(block), None) => Some(StartAt::Block(block)),
+            (None, start_time) => start_time,
+        };
+
+        let clock_check = ClockCheck {
+            server: env::var("NTP_SERVER").unwrap_or_else(|_| "pool.ntp.org:123".into()),
+            max_drift_ms: env::var("MAX_CLOCK_DRIFT_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(250),
+        };
+
+        let mev_report_file = env::var("MEV_REPORT_FILE").ok().map(PathBuf::from);
+
+        let gas_budget = env::var("MAX_DAILY_GAS_MON")
+            .ok()
+            .map(|v| chain::profile().parse_native(&v).context("invalid MAX_DAILY_GAS_MON"))
+            .transpose()?
+            .map(|cap| {
+                let path = env::var("GAS_BUDGET_FILE").unwrap_or_else(|_| "gas_budget.json".into());
+                GasBudget::new(PathBuf::from(path), cap)
+            });
+
+        let gas_reserve = chain::profile()
+            .parse_native(&env::var("GAS_RESERVE_MON").unwrap_or_else(|_| "0.1".into()))
+            .context("invalid GAS_RESERVE_MON")?;
+
+        let sentry_dsn = env::var("SENTRY_DSN").ok().filter(|v| !v.is_empty());
+        let rpc_pool = RpcPool::from_env(&rpc_url);
+        let price_feed = PriceFeed::from_env(&rpc_url, bonding_curve)?;
+        let state_file = state::state_file();
+        let audit = env::var("AUDIT_LOG_FILE")
+            .ok()
+            .map(|path| AuditLog::open(PathBuf::from(path), &private_key))
+            .transpose()?;
+
+        Ok(Self {
+            rpc_url,
+            private_key,
+            targets,
+            defaults,
+            recipient,
+            bonding_curve,
+            age,
+            price_feed,
+            deadline_secs_from_now,
+            exit_check_interval_secs,
+            pin_policy,
+            max_entry_wait_blocks,
+            start_at,
+            clock_check,
+            sentry_dsn,
+            mev_report_file,
+            gas_budget,
+            gas_reserve,
+            risk: RiskLimits::from_env()?,
+            sniper: SniperConfig::from_env()?,
+            settle: SettleConfig::from_env()?,
+            utilization: UtilizationConfig::from_env()?,
+            retention: RetentionConfig::from_env(),
+            approvals: ApprovalConfig::from_env(),
+            failover: FailoverConfig::from_env(&state_file)?,
+            fleet: FleetConfig::from_env(&state_file, cli.instance.as_deref())?,
+            state: Arc::new(StateStore::new(state_file)),
+            safety: SafetyConfig::from_env()?,
+            retry_policy: RetryPolicy::from_env()?,
+            chaos: ChaosConfig::from_env()?,
+            gas_strategy: env::var("GAS_STRATEGY")
+                .ok()
+                .map(|v| v.parse().context("invalid GAS_STRATEGY"))
+                .transpose()?
+                .unwrap_or(GasStrategy::Normal),
+            rpc_pool,
+            dry_run: cli.dry_run,
+            accounting: AccountingConfig::from_env()?,
+            sink: SinkConfig::from_env()?,
+            ledger: Ledger::from_env(),
+            protocol_refresh_secs: env::var("PROTOCOL_REFRESH_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+            protocol_max_age: env::var("PROTOCOL_MAX_AGE_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs),
+            audit,
+            traces: TraceConfig::from_env()?,
+            notifier: Notifier::from_env()?,
+            recovery: RecoveryMode::from_env()?,
+            reorg: ReorgConfig::from_env()?,
+            adoption: AdoptionPolicy::from_env()?,
+            snapshot_interval_secs: env::var("SNAPSHOT_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            orders: OrderBook::new(PathBuf::from(
+                env::var("LIMIT_ORDERS_FILE").unwrap_or_else(|_| "orders.json".into()),
+            )),
+            exec_log: ExecLog::from_env(),
+            exec_auto_tune: env::var("EXEC_AUTO_TUNE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(false),
+            exec_anomaly: AnomalyWatch::from_env()?,
+            warmer: WarmerConfig::from_env()?,
+            blocklist,
+            control,
+            controls,
+            shutdown: Shutdown::new(cli.exit_on_shutdown),
+            signals,
+            explore: ExploreConfig::from_env()?,
+            impact: ImpactConfig::from_env()?,
+            reentry: ReentryConfig::from_env()?,
+            locks: LockList::from_env(),
+        })
+    }
+
+    /// The default trade params alone, for commands that run without a wallet or RPC.
+    pub fn load_defaults(cli: &Cli) -> Result<TradeParams> {
+        let file = ConfigFile::load(cli.config.as_deref())?;
+        resolve_defaults(&file, &Profile::from_env()?)
+    }
+
+    pub fn params_for(&self, token: Address) -> &TradeParams {
+        self.targets
+            .iter()
+            .find(|target| target.token == token)
+            .map(|target| &target.params)
+            .unwrap_or(&self.defaults)
+    }
+
+    /// Appends a decision to the signed audit log, if one is configured.
+    pub fn audit(&self, event: &str, details: serde_json::Value) {
+        sink::event(event, &details);
+        if let Some(audit) = &self.audit {
+            if let Err(err) = audit.record(event, details) {
+                warn!("Failed to write audit entry: {:#}", err);
+            }
+        }
+    }
+
+    pub fn deadline_u256(&self) -> U256 {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        U256::from(now + self.deadline_secs_from_now)
+    }
+}
+
+fn resolve_defaults(file: &ConfigFile, env_profile: &Profile) -> Result<TradeParams> {
+    file.defaults
+        .clone()
+        .overlay(env_profile)
+        .resolve()
+        .context("invalid trade settings")
+}
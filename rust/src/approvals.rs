@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::RwLock;
+
+use anyhow::{Context, Result};
+use ethers::types::{Address, H256, U256};
+use tracing::info;
+
+use crate::engine::ExecutionClient;
+use crate::tx_manager;
+
+/// Known router allowances by (token, router, wallet), so a sell with enough allowance
+/// left makes no `allowance` call.
+static ALLOWANCES: RwLock<BTreeMap<(Address, Address, Address), U256>> =
+    RwLock::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApprovalConfig {
+    /// Approve `U256::MAX` instead of the amount being sold, so later sells through the
+    /// same router need no approval of their own.
+    pub infinite: bool,
+    /// Approve the sell router right after a snipe's buy, so the exit needs only the sell.
+    pub ahead: bool,
+}
+
+impl ApprovalConfig {
+    /// `APPROVE_INFINITE` and `APPROVE_AHEAD`, both off by default.
+    pub fn from_env() -> Self {
+        let flag = |name: &str| {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(false)
+        };
+        Self {
+            infinite: flag("APPROVE_INFINITE"),
+            ahead: flag("APPROVE_AHEAD"),
+        }
+    }
+}
+
+fn cached(token: Address, router: Address, owner: Address) -> Option<U256> {
+    ALLOWANCES.read().ok()?.get(&(token, router, owner)).copied()
+}
+
+fn cache(token: Address, router: Address, owner: Address, allowance: U256) {
+    if let Ok(mut allowances) = ALLOWANCES.write() {
+        allowances.insert((token, router, owner), allowance);
+    }
+}
+
+/// Makes sure `router` may spend `amount` of the owner's `token`, approving only when the
+/// cached or current allowance falls short. Returns the approval, if one was sent.
+pub async fn ensure(
+    config: &ApprovalConfig,
+    client: &impl ExecutionClient,
+    token: Address,
+    owner: Address,
+    router: Address,
+    amount: U256,
+) -> Result<Option<H256>> {
+    if cached(token, router, owner).is_some_and(|allowance| allowance >= amount) {
+        return Ok(None);
+    }
+    let allowance = client
+        .allowance(token, owner, router)
+        .await
+        .context("failed to fetch router allowance")?;
+    if allowance >= amount {
+        cache(token, router, owner, allowance);
+        return Ok(None);
+    }
+
+    let approved = if config.infinite { U256::MAX } else { amount };
+    info!("Approving router {} for {}", router, token);
+    let lock = tx_manager::send_lock(owner);
+    let _guard = lock.lock().await;
+    let approve_tx = client
+        .approve(token, router, approved)
+        .await
+        .context("router approval failed")?;
+    info!("Approve submitted: {:?}", approve_tx);
+    cache(token, router, owner, approved);
+    Ok(Some(approve_tx))
+}
+
+/// Approves the router a sell of `amount` would go through now, ahead of the exit.
+pub async fn prepare(
+    config: &ApprovalConfig,
+    client: &impl ExecutionClient,
+    token: Address,
+    owner: Address,
+    amount: U256,
+) -> Result<Option<H256>> {
+    let (router, _) = client
+        .quote(token, amount, false)
+        .await
+        .context("failed to resolve the sell router")?;
+    ensure(config, client, token, owner, router, amount).await
+}
+
+/// Takes a sold `amount` off the cached allowance. Infinite approvals aren't spent down.
+pub fn spent(token: Address, router: Address, owner: Address, amount: U256) {
+    if let Ok(mut allowances) = ALLOWANCES.write() {
+        if let Some(allowance) = allowances.get_mut(&(token, router, owner)) {
+            if *allowance != U256::MAX {
+                *allowance = allowance.saturating_sub(amount);
+            }
+        }
+    }
+}
+
+/// Drops the cached allowance, so the next sell reads it from the chain again.
+pub fn forget(token: Address, router: Address, owner: Address) {
+    if let Ok(mut allowances) = ALLOWANCES.write() {
+        allowances.remove(&(token, router, owner));
+    }
+}
@@ -0,0 +1,201 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, Signature, H256};
+use ethers::utils::keccak256;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::file_lock;
+use crate::state;
+
+/// The signed part of an audit entry; `prev_hash` chains it to the entry before.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AuditBody {
+    seq: u64,
+    at: u64,
+    signer: Address,
+    event: String,
+    details: Value,
+    prev_hash: H256,
+}
+
+impl AuditBody {
+    fn hash(&self) -> Result<H256> {
+        Ok(H256::from(keccak256(serde_json::to_vec(self)?)))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AuditEntry {
+    #[serde(flatten)]
+    body: AuditBody,
+    hash: H256,
+    signature: String,
+}
+
+/// Append-only JSONL record of trading decisions. Each entry carries the hash of
+/// the previous one and a signature by the bot's key over its own hash, so edits,
+/// deletions and reordering are all detectable.
+pub struct AuditLog {
+    path: PathBuf,
+    wallet: LocalWallet,
+}
+
+impl AuditLog {
+    /// Appends to `path`, continuing the chain from its last entry.
+    pub fn open(path: PathBuf, private_key: &str) -> Result<Self> {
+        let wallet: LocalWallet = private_key.parse().context("invalid PRIVATE_KEY")?;
+        read_entries(&path)?;
+        Ok(Self { path, wallet })
+    }
+
+    /// Appends an entry under the log's file lock, chained to whatever entry is last by
+    /// then, so instances sharing the file extend one chain instead of forking it.
+    pub fn record(&self, event: &str, details: Value) -> Result<()> {
+        let _lock = file_lock::exclusive(&self.path)?;
+        let head = match read_entries(&self.path)?.last() {
+            Some(entry) => (entry.body.seq + 1, entry.hash),
+            None => (0, H256::zero()),
+        };
+        let body = AuditBody {
+            seq: head.0,
+            at: state::unix_now(),
+            signer: self.wallet.address(),
+            event: event.to_string(),
+            details,
+            prev_hash: head.1,
+        };
+        let hash = body.hash()?;
+        let signature = self.wallet.sign_hash(hash).context("failed to sign audit entry")?;
+        let entry = AuditEntry {
+            body,
+            hash,
+            signature: signature.to_string(),
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
+
+/// Checks every entry's hash, chain link and signature by `signer`, failing at the first
+/// break. Returns how many entries there are.
+pub fn verify(path: &Path, signer: Address) -> Result<usize> {
+    let entries = read_entries(path)?;
+    let mut prev_hash = H256::zero();
+    for (seq, entry) in entries.iter().enumerate() {
+        let at = || format!("entry {} of {}", seq, path.display());
+        if entry.body.seq != seq as u64 {
+            return Err(anyhow!("{}: sequence {} out of order", at(), entry.body.seq));
+        }
+        if entry.body.prev_hash != prev_hash {
+            return Err(anyhow!("{}: does not chain to the previous entry", at()));
+        }
+        if entry.body.hash()? != entry.hash {
+            return Err(anyhow!("{}: contents do not match its hash", at()));
+        }
+        let signature: Signature = entry
+            .signature
+            .parse()
+            .with_context(|| format!("{}: malformed signature", at()))?;
+        let recovered = signature
+            .recover(entry.hash)
+            .with_context(|| format!("{}: unrecoverable signature", at()))?;
+        if recovered != signer || entry.body.signer != signer {
+            return Err(anyhow!("{}: signed by {:?}, not {:?}", at(), recovered, signer));
+        }
+        prev_hash = entry.hash;
+    }
+    Ok(entries.len())
+}
+
+fn read_entries(path: &Path) -> Result<Vec<AuditEntry>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(
+            serde_json::from_str(&line)
+                .with_context(|| format!("corrupt audit log {}", path.display()))?,
+        );
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key(byte: &str) -> String {
+        format!("0x{}", byte.repeat(32))
+    }
+
+    fn log_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("nadfun-audit-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn signer_of(private_key: &str) -> Address {
+        private_key.parse::<LocalWallet>().unwrap().address()
+    }
+
+    #[test]
+    fn appends_chain_across_handles_and_verify() {
+        let path = log_path("chain");
+        let first = AuditLog::open(path.clone(), &key("01")).unwrap();
+        let second = AuditLog::open(path.clone(), &key("01")).unwrap();
+        first.record("buy", json!({ "amount": 1 })).unwrap();
+        second.record("sell", json!({ "amount": 2 })).unwrap();
+        first.record("buy", json!({ "amount": 3 })).unwrap();
+
+        assert_eq!(verify(&path, signer_of(&key("01"))).unwrap(), 3);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn edited_entries_fail_verification() {
+        let path = log_path("tamper");
+        let log = AuditLog::open(path.clone(), &key("01")).unwrap();
+        log.record("buy", json!({ "amount": 1 })).unwrap();
+        log.record("sell", json!({ "amount": 2 })).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, contents.replacen("\"amount\":2", "\"amount\":20", 1)).unwrap();
+        let err = verify(&path, signer_of(&key("01"))).unwrap_err();
+        assert!(err.to_string().contains("entry 1"));
+        assert!(err.to_string().contains("do not match its hash"));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn a_log_re_signed_by_another_key_fails_verification() {
+        let path = log_path("signer");
+        // Self-consistent, but written with a key other than the bot's.
+        let forged = AuditLog::open(path.clone(), &key("02")).unwrap();
+        forged.record("buy", json!({ "amount": 1 })).unwrap();
+
+        assert!(verify(&path, signer_of(&key("02"))).is_ok());
+        let err = verify(&path, signer_of(&key("01"))).unwrap_err();
+        assert!(err.to_string().contains("signed by"));
+        std::fs::remove_file(&path).ok();
+    }
+}
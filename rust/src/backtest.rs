@@ -0,0 +1,712 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use ethers::contract::{parse_log, EthEvent};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, Filter, I256, U256};
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+use tracing::info;
+
+use crate::chain;
+use crate::cli::Cli;
+use crate::config::TradeParams;
+use crate::curve::{CurveBuyFilter, CurveCreateFilter, CurveSellFilter};
+use crate::exit_strategy::{ExitRules, ExitStrategy, Position};
+use crate::ledger::signed_native;
+use crate::rpc_pool::RpcPool;
+use crate::snapshot::SnapshotDiff;
+use crate::sniper::{Launch, SniperConfig};
+use crate::app::AppConfig;
+
+#[derive(Debug, Args)]
+pub struct BacktestArgs {
+    /// First block to replay, fetched from the bonding curve's logs.
+    #[arg(long)]
+    pub from_block: Option<u64>,
+
+    /// Last block to replay; defaults to the chain head.
+    #[arg(long)]
+    pub to_block: Option<u64>,
+
+    /// Replay a capture written by `--save` instead of fetching logs.
+    #[arg(long, conflicts_with_all = ["from_block", "to_block"])]
+    pub capture: Option<PathBuf>,
+
+    /// Write the fetched events to this capture file.
+    #[arg(long)]
+    pub save: Option<PathBuf>,
+
+    /// Take profit levels to try, in percent; 0 turns the rule off. Defaults to
+    /// TAKE_PROFIT_PCT.
+    #[arg(long, value_delimiter = ',')]
+    pub take_profit: Vec<f64>,
+
+    /// Stop loss levels to try, in percent; 0 turns the rule off. Defaults to
+    /// STOP_LOSS_PCT.
+    #[arg(long, value_delimiter = ',')]
+    pub stop_loss: Vec<f64>,
+
+    /// Trailing stops to try, in percent; 0 turns the rule off. Defaults to
+    /// TRAILING_STOP_PCT.
+    #[arg(long, value_delimiter = ',')]
+    pub trailing_stop: Vec<f64>,
+
+    /// Maximum holds to try, in seconds. Defaults to SETTLEMENT_WAIT_SECS.
+    #[arg(long, value_delimiter = ',')]
+    pub max_hold_secs: Vec<u64>,
+
+    /// Curve fee charged on each simulated fill.
+    #[arg(long, default_value_t = 100)]
+    pub fee_bps: u64,
+
+    /// Blocks per `eth_getLogs` request.
+    #[arg(long, default_value_t = 2_000)]
+    pub chunk_blocks: u64,
+
+    /// Also print every simulated trade of the best configuration.
+    #[arg(long)]
+    pub trades: bool,
+}
+
+/// One bonding curve log, as replayed and as stored in a capture file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Event {
+    Launch {
+        block: u64,
+        token: Address,
+        creator: Address,
+        name: String,
+        symbol: String,
+        virtual_mon: U256,
+        virtual_token: U256,
+    },
+    Trade {
+        block: u64,
+        token: Address,
+        buy: bool,
+        amount_in: U256,
+        amount_out: U256,
+    },
+}
+
+impl Event {
+    fn block(&self) -> u64 {
+        match self {
+            Event::Launch { block, .. } | Event::Trade { block, .. } => *block,
+        }
+    }
+}
+
+/// Virtual reserves of one curve, moved by every replayed trade and by the simulated
+/// fills themselves.
+#[derive(Debug, Clone, Copy)]
+struct Reserves {
+    mon: U256,
+    tokens: U256,
+    /// MON bought into the curve, what the sniper's minimum liquidity filter reads.
+    real_mon: U256,
+}
+
+impl Reserves {
+    fn apply(&mut self, buy: bool, amount_in: U256, amount_out: U256) {
+        if buy {
+            self.mon += amount_in;
+            self.real_mon += amount_in;
+            self.tokens = self.tokens.saturating_sub(amount_out);
+        } else {
+            self.tokens += amount_in;
+            self.mon = self.mon.saturating_sub(amount_out);
+            self.real_mon = self.real_mon.saturating_sub(amount_out);
+        }
+    }
+
+    fn buy_quote(&self, amount_in: U256, fee_bps: u64) -> U256 {
+        let net = amount_in - amount_in * U256::from(fee_bps) / U256::from(10_000u64);
+        if (self.mon + net).is_zero() {
+            return U256::zero();
+        }
+        self.tokens * net / (self.mon + net)
+    }
+
+    fn sell_quote(&self, amount: U256, fee_bps: u64) -> U256 {
+        if (self.tokens + amount).is_zero() {
+            return U256::zero();
+        }
+        let gross = self.mon * amount / (self.tokens + amount);
+        gross - gross * U256::from(fee_bps) / U256::from(10_000u64)
+    }
+}
+
+/// The exit rules of one configuration under test.
+#[derive(Debug, Clone, Copy)]
+struct Strategy {
+    take_profit_pct: Option<f64>,
+    stop_loss_pct: Option<f64>,
+    trailing_stop_pct: Option<f64>,
+    max_hold: Duration,
+}
+
+impl Strategy {
+    fn label(&self) -> String {
+        let pct = |value: Option<f64>| value.map_or("-".to_string(), |pct| format!("{pct}%"));
+        format!(
+            "tp {} sl {} trail {} hold {}s",
+            pct(self.take_profit_pct),
+            pct(self.stop_loss_pct),
+            pct(self.trailing_stop_pct),
+            self.max_hold.as_secs()
+        )
+    }
+
+    fn rules(&self) -> Result<ExitRules> {
+        ExitRules::new(
+            self.take_profit_pct,
+            self.stop_loss_pct,
+            self.trailing_stop_pct,
+            self.max_hold,
+        )
+    }
+}
+
+struct Held {
+    symbol: String,
+    entry_block: u64,
+    cost: U256,
+    amount: U256,
+    peak_value: U256,
+}
+
+struct SimTrade {
+    token: Address,
+    symbol: String,
+    entry_block: u64,
+    exit_block: u64,
+    cost: U256,
+    proceeds: U256,
+    reason: String,
+}
+
+impl SimTrade {
+    fn pnl(&self) -> I256 {
+        I256::from_raw(self.proceeds) - I256::from_raw(self.cost)
+    }
+}
+
+/// Replays recorded launches and curve trades through the sniper's filters and each
+/// combination of exit rules, filling simulated buys and sells against the replayed
+/// reserves, and prints the hypothetical PnL of every configuration. Fills ignore gas
+/// and other bots' reaction to our own trades, so results are optimistic.
+/// The parts of the config a backtest reads. It needs no wallet, and only fetching
+/// logs needs `RPC_URL`.
+pub struct BacktestConfig {
+    defaults: TradeParams,
+    sniper: SniperConfig,
+    bonding_curve: Option<Address>,
+    rpc_url: Option<String>,
+}
+
+impl BacktestConfig {
+    pub fn load(cli: &Cli) -> Result<Self> {
+        Ok(Self {
+            defaults: AppConfig::load_defaults(cli)?,
+            sniper: SniperConfig::from_env()?,
+            bonding_curve: env::var("BONDING_CURVE_ADDRESS")
+                .ok()
+                .map(|v| v.parse().context("invalid BONDING_CURVE_ADDRESS"))
+                .transpose()?,
+            rpc_url: env::var("RPC_URL").ok(),
+        })
+    }
+}
+
+pub async fn run(cfg: &BacktestConfig, args: &BacktestArgs) -> Result<()> {
+    let events = match &args.capture {
+        Some(path) => load_capture(path)?,
+        None => fetch(cfg, args).await?,
+    };
+    if let Some(path) = &args.save {
+        save_capture(path, &events)?;
+        println!("Saved {} events to {}", events.len(), path.display());
+    }
+    let launches = events.iter().filter(|e| matches!(e, Event::Launch { .. })).count();
+    println!(
+        "Replaying {} launches and {} trades",
+        launches,
+        events.len() - launches
+    );
+
+    let strategies = strategies(cfg, args);
+    let mut results = Vec::new();
+    for strategy in strategies {
+        let rules = strategy
+            .rules()
+            .with_context(|| format!("invalid configuration {}", strategy.label()))?;
+        let trades = simulate(&events, &cfg.sniper, &rules, cfg.defaults.amount_in, args.fee_bps);
+        results.push((strategy, trades));
+    }
+    results.sort_by_key(|(_, trades)| {
+        std::cmp::Reverse(trades.iter().map(SimTrade::pnl).fold(I256::zero(), |a, b| a + b))
+    });
+
+    let profile = chain::profile();
+    println!(
+        "{:<44}  {:>6}  {:>6}  {:>16}  {:>15}  {:>9}",
+        "configuration", "trades", "wins", "in", "pnl", "pnl %"
+    );
+    for (strategy, trades) in &results {
+        let cost = trades.iter().fold(U256::zero(), |total, trade| total + trade.cost);
+        let pnl = trades.iter().map(SimTrade::pnl).fold(I256::zero(), |a, b| a + b);
+        let wins = trades.iter().filter(|trade| trade.proceeds > trade.cost).count();
+        let pct = if cost.is_zero() {
+            0.0
+        } else {
+            pnl.as_i128() as f64 / cost.as_u128() as f64 * 100.0
+        };
+        println!(
+            "{:<44}  {:>6}  {:>6}  {:>16}  {:>15}  {:>+8.2}%",
+            strategy.label(),
+            trades.len(),
+            wins,
+            profile.format_native(cost),
+            signed_native(pnl),
+            pct
+        );
+    }
+
+    if let (true, Some((strategy, trades))) = (args.trades, results.first()) {
+        println!("\nTrades of {}", strategy.label());
+        for trade in trades {
+            println!(
+                "{:?} {:<10} blocks {}-{}  {:>15}  {}",
+                trade.token,
+                trade.symbol,
+                trade.entry_block,
+                trade.exit_block,
+                signed_native(trade.pnl()),
+                trade.reason
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Every combination of the levels passed for each rule, with unset rules taken from
+/// the environment's defaults.
+fn strategies(cfg: &BacktestConfig, args: &BacktestArgs) -> Vec<Strategy> {
+    let defaults = &cfg.defaults.profile;
+    let levels = |passed: &[f64], default: Option<f64>| -> Vec<Option<f64>> {
+        if passed.is_empty() {
+            return vec![default];
+        }
+        passed.iter().map(|pct| (*pct > 0.0).then_some(*pct)).collect()
+    };
+    let holds = if args.max_hold_secs.is_empty() {
+        vec![defaults.max_hold_secs.unwrap_or(30)]
+    } else {
+        args.max_hold_secs.clone()
+    };
+
+    let mut strategies = Vec::new();
+    for take_profit_pct in levels(&args.take_profit, defaults.take_profit_pct) {
+        for stop_loss_pct in levels(&args.stop_loss, defaults.stop_loss_pct) {
+            for trailing_stop_pct in levels(&args.trailing_stop, defaults.trailing_stop_pct) {
+                for secs in &holds {
+                    strategies.push(Strategy {
+                        take_profit_pct,
+                        stop_loss_pct,
+                        trailing_stop_pct,
+                        max_hold: Duration::from_secs(*secs),
+                    });
+                }
+            }
+        }
+    }
+    strategies
+}
+
+/// Runs one configuration over the events. A launch that passes the sniper's metadata
+/// filters is bought at the reserves left after its launch block, as the live sniper
+/// lands no earlier than the next block; exits are checked on every later event.
+fn simulate(
+    events: &[Event],
+    sniper: &SniperConfig,
+    rules: &ExitRules,
+    amount_in: U256,
+    fee_bps: u64,
+) -> Vec<SimTrade> {
+    let block_time = chain::profile().block_time;
+    let mut reserves: HashMap<Address, Reserves> = HashMap::new();
+    let mut pending: Vec<(Address, String, u64)> = Vec::new();
+    let mut open: HashMap<Address, Held> = HashMap::new();
+    let mut closed = Vec::new();
+    let mut last_block = 0;
+
+    for event in events {
+        let block = event.block();
+        last_block = block;
+
+        let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut pending)
+            .into_iter()
+            .partition(|(_, _, launched)| *launched < block);
+        pending = waiting;
+        for (token, symbol, _) in due {
+            let Some(curve) = reserves.get_mut(&token) else {
+                continue;
+            };
+            if sniper.min_initial_liquidity.is_some_and(|min| curve.real_mon < min)
+                || open.len() >= sniper.max_concurrent
+            {
+                continue;
+            }
+            let amount = curve.buy_quote(amount_in, fee_bps);
+            if amount.is_zero() {
+                continue;
+            }
+            curve.apply(true, amount_in, amount);
+            open.insert(
+                token,
+                Held {
+                    symbol,
+                    entry_block: block,
+                    cost: amount_in,
+                    amount,
+                    peak_value: U256::zero(),
+                },
+            );
+        }
+
+        match event {
+            Event::Launch {
+                token,
+                creator,
+                name,
+                symbol,
+                virtual_mon,
+                virtual_token,
+                ..
+            } => {
+                reserves.insert(
+                    *token,
+                    Reserves {
+                        mon: *virtual_mon,
+                        tokens: *virtual_token,
+                        real_mon: U256::zero(),
+                    },
+                );
+                let launch = Launch {
+                    token: *token,
+                    creator: *creator,
+                    name: name.clone(),
+                    symbol: symbol.clone(),
+                    block: Some(block),
+                };
+                if sniper.screen_metadata(&launch).is_none() {
+                    pending.push((*token, symbol.clone(), block));
+                }
+            }
+            Event::Trade {
+                token,
+                buy,
+                amount_in,
+                amount_out,
+                ..
+            } => {
+                if let Some(curve) = reserves.get_mut(token) {
+                    curve.apply(*buy, *amount_in, *amount_out);
+                }
+            }
+        }
+
+        let exits: Vec<(Address, String)> = open
+            .iter_mut()
+            .filter_map(|(token, held)| {
+                let curve = reserves.get(token)?;
+                let position = position(held, curve, block, block_time, fee_bps);
+                held.peak_value = position.peak_value;
+                rules.should_exit(&position).map(|exit| (*token, exit.reason))
+            })
+            .collect();
+        for (token, reason) in exits {
+            if let (Some(held), Some(curve)) = (open.remove(&token), reserves.get_mut(&token)) {
+                closed.push(close(token, held, curve, block, fee_bps, reason));
+            }
+        }
+    }
+
+    for (token, held) in open.drain() {
+        if let Some(curve) = reserves.get_mut(&token) {
+            let reason = "still open at the end of the replay".to_string();
+            closed.push(close(token, held, curve, last_block, fee_bps, reason));
+        }
+    }
+    closed.sort_by_key(|trade| trade.entry_block);
+    closed
+}
+
+fn position(
+    held: &Held,
+    curve: &Reserves,
+    block: u64,
+    block_time: Duration,
+    fee_bps: u64,
+) -> Position {
+    let value = curve.sell_quote(held.amount, fee_bps);
+    let blocks = block.saturating_sub(held.entry_block);
+    Position {
+        cost: held.cost,
+        amount: held.amount,
+        value,
+        peak_value: held.peak_value.max(value),
+        held_for: Duration::from_secs_f64(block_time.as_secs_f64() * blocks as f64),
+        snapshot: SnapshotDiff::default(),
+    }
+}
+
+fn close(
+    token: Address,
+    held: Held,
+    curve: &mut Reserves,
+    block: u64,
+    fee_bps: u64,
+    reason: String,
+) -> SimTrade {
+    let proceeds = curve.sell_quote(held.amount, fee_bps);
+    curve.apply(false, held.amount, proceeds);
+    SimTrade {
+        token,
+        symbol: held.symbol,
+        entry_block: held.entry_block,
+        exit_block: block,
+        cost: held.cost,
+        proceeds,
+        reason,
+    }
+}
+
+/// Fetches the curve's launch, buy and sell logs over the block range, in chain order.
+async fn fetch(cfg: &BacktestConfig, args: &BacktestArgs) -> Result<Vec<Event>> {
+    let curve = cfg
+        .bonding_curve
+        .ok_or_else(|| anyhow!("BONDING_CURVE_ADDRESS is required to fetch history"))?;
+    let from = args
+        .from_block
+        .ok_or_else(|| anyhow!("pass --from-block, or --capture to replay a saved capture"))?;
+    let rpc_url = cfg
+        .rpc_url
+        .as_deref()
+        .ok_or_else(|| anyhow!("RPC_URL is required to fetch history"))?;
+    let pool = RpcPool::from_env(rpc_url);
+    let rpc_url = if pool.has_fallbacks() {
+        pool.best(None).await?
+    } else {
+        rpc_url.to_string()
+    };
+    let provider = Provider::<Http>::try_from(rpc_url.as_str()).context("invalid RPC_URL")?;
+    let to = match args.to_block {
+        Some(to) => to,
+        None => provider.get_block_number().await?.as_u64(),
+    };
+
+    let create = CurveCreateFilter::signature();
+    let buy = CurveBuyFilter::signature();
+    let sell = CurveSellFilter::signature();
+    let mut events = Vec::new();
+    let mut start = from;
+    while start <= to {
+        let end = (start + args.chunk_blocks.max(1) - 1).min(to);
+        let filter = Filter::new()
+            .address(curve)
+            .from_block(start)
+            .to_block(end)
+            .topic0(vec![create, buy, sell]);
+        let mut logs = provider
+            .get_logs(&filter)
+            .await
+            .with_context(|| format!("failed to fetch logs for blocks {}-{}", start, end))?;
+        logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+        for log in logs {
+            let (Some(topic), Some(block)) = (log.topics.first().copied(), log.block_number)
+            else {
+                continue;
+            };
+            let block = block.as_u64();
+            let event = if topic == create {
+                let event: CurveCreateFilter = parse_log(log)?;
+                Event::Launch {
+                    block,
+                    token: event.token,
+                    creator: event.creator,
+                    name: event.name,
+                    symbol: event.symbol,
+                    virtual_mon: event.virtual_mon,
+                    virtual_token: event.virtual_token,
+                }
+            } else if topic == buy {
+                let event: CurveBuyFilter = parse_log(log)?;
+                Event::Trade {
+                    block,
+                    token: event.token,
+                    buy: true,
+                    amount_in: event.amount_in,
+                    amount_out: event.amount_out,
+                }
+            } else {
+                let event: CurveSellFilter = parse_log(log)?;
+                Event::Trade {
+                    block,
+                    token: event.token,
+                    buy: false,
+                    amount_in: event.amount_in,
+                    amount_out: event.amount_out,
+                }
+            };
+            events.push(event);
+        }
+        info!("Fetched blocks {}-{} of {}: {} events", start, end, to, events.len());
+        start = end + 1;
+    }
+    Ok(events)
+}
+
+fn load_capture(path: &Path) -> Result<Vec<Event>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read capture {}", path.display()))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("{}:{}: invalid event", path.display(), index + 1))
+        })
+        .collect()
+}
+
+fn save_capture(path: &Path, events: &[Event]) -> Result<()> {
+    let mut contents = String::new();
+    for event in events {
+        contents.push_str(&serde_json::to_string(event)?);
+        contents.push('\n');
+    }
+    fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sniper(min_liquidity: Option<u64>) -> SniperConfig {
+        SniperConfig {
+            ws_url: None,
+            creators: Vec::new(),
+            name_regex: None,
+            symbol_regex: None,
+            min_initial_liquidity: min_liquidity.map(U256::from),
+            max_concurrent: 5,
+            entry_delay_blocks: 0,
+            degraded_filters: false,
+            race: None,
+            reputation: None,
+            edge: None,
+            scoring: None,
+            tuning: None,
+            calendar: None,
+        }
+    }
+
+    fn launch(block: u64, token: u8) -> Event {
+        Event::Launch {
+            block,
+            token: Address::repeat_byte(token),
+            creator: Address::repeat_byte(9),
+            name: "Test".into(),
+            symbol: "TST".into(),
+            virtual_mon: U256::from(1_000u64),
+            virtual_token: U256::from(1_000_000u64),
+        }
+    }
+
+    fn trade(block: u64, token: u8, buy: bool, amount_in: u64, amount_out: u64) -> Event {
+        Event::Trade {
+            block,
+            token: Address::repeat_byte(token),
+            buy,
+            amount_in: U256::from(amount_in),
+            amount_out: U256::from(amount_out),
+        }
+    }
+
+    #[test]
+    fn quotes_follow_the_constant_product_after_the_fee() {
+        let reserves = Reserves {
+            mon: U256::from(900u64),
+            tokens: U256::from(10_000u64),
+            real_mon: U256::zero(),
+        };
+        assert_eq!(reserves.buy_quote(U256::from(100u64), 0), U256::from(1_000u64));
+        assert_eq!(reserves.buy_quote(U256::from(100u64), 100), U256::from(990u64));
+        assert_eq!(reserves.sell_quote(U256::from(10_000u64), 0), U256::from(450u64));
+        assert_eq!(reserves.sell_quote(U256::from(10_000u64), 100), U256::from(446u64));
+
+        let mut moved = reserves;
+        moved.apply(true, U256::from(100u64), U256::from(1_000u64));
+        let state = |r: Reserves| (r.mon.as_u64(), r.tokens.as_u64(), r.real_mon.as_u64());
+        assert_eq!(state(moved), (1_000, 9_000, 100));
+        moved.apply(false, U256::from(1_000u64), U256::from(200u64));
+        assert_eq!(state(moved), (800, 10_000, 0));
+    }
+
+    #[test]
+    fn a_launch_is_bought_after_its_block_and_sold_on_the_exit_rules() {
+        let events = [
+            launch(10, 1),
+            trade(10, 1, true, 500, 300_000),
+            trade(11, 1, true, 10, 2_000),
+            trade(12, 1, true, 5_000, 100_000),
+        ];
+        let rules = ExitRules::new(Some(50.0), None, None, Duration::from_secs(3_600)).unwrap();
+        let trades = simulate(&events, &sniper(None), &rules, U256::from(100u64), 0);
+        assert_eq!(trades.len(), 1);
+        let trade = &trades[0];
+        // Bought at block 11, after the launch block's own buy moved the curve.
+        assert_eq!((trade.entry_block, trade.exit_block), (11, 12));
+        assert!(trade.pnl() > I256::zero());
+        assert!(trade.reason.contains("take profit"), "{}", trade.reason);
+    }
+
+    #[test]
+    fn launches_below_the_liquidity_filter_are_skipped_and_open_ones_close_at_the_end() {
+        let events = [
+            launch(10, 1),
+            trade(10, 1, true, 50, 40_000),
+            launch(10, 2),
+            trade(10, 2, true, 500, 300_000),
+            trade(11, 2, false, 10, 5),
+        ];
+        let rules = ExitRules::new(None, None, None, Duration::from_secs(3_600)).unwrap();
+        let trades = simulate(&events, &sniper(Some(100)), &rules, U256::from(100u64), 0);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].token, Address::repeat_byte(2));
+        assert_eq!(trades[0].reason, "still open at the end of the replay");
+    }
+
+    #[test]
+    fn captures_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("nadfun-backtest-{}.jsonl", std::process::id()));
+        let events = [launch(10, 1), trade(11, 1, false, 10, 5)];
+        save_capture(&path, &events).unwrap();
+        let loaded = load_capture(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[1].block(), 11);
+        assert!(matches!(loaded[0], Event::Launch { symbol: ref s, .. } if s == "TST"));
+    }
+}
@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::Mutex;
+
+use tokio::time::Duration;
+
+static SIZES: Mutex<BTreeMap<&'static str, usize>> = Mutex::new(BTreeMap::new());
+
+/// How long per-token state of inactive tokens is kept in a long-running instance.
+pub struct RetentionConfig {
+    /// Tokens finished this long ago leave the runtime watchlist and the sniper's set of
+    /// seen launches.
+    pub idle_ttl: Duration,
+    pub prune_interval: Duration,
+}
+
+impl RetentionConfig {
+    pub fn from_env() -> Self {
+        let secs = |name: &str, default: u64| -> u64 {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            idle_ttl: Duration::from_secs(secs("CACHE_IDLE_TTL_SECS", 3_600)),
+            prune_interval: Duration::from_secs(secs("CACHE_PRUNE_SECS", 60).max(1)),
+        }
+    }
+}
+
+/// Records how many entries `cache` holds, for `/caches`.
+pub fn observe(cache: &'static str, entries: usize) {
+    if let Ok(mut sizes) = SIZES.lock() {
+        sizes.insert(cache, entries);
+    }
+}
+
+/// The last observed size of every cache.
+pub fn sizes() -> BTreeMap<&'static str, usize> {
+    SIZES.lock().map(|sizes| sizes.clone()).unwrap_or_default()
+}
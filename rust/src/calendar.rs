@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::DateTime;
+use ethers::types::{Address, U256};
+use regex::Regex;
+use serde::Deserialize;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::app::AppConfig;
+use crate::chain;
+use crate::engine::ExecutionClient;
+use crate::notify::Event;
+use crate::sniper::Launch;
+use crate::state;
+
+/// How often the calendar looks for windows coming up.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct CalendarFile {
+    #[serde(default)]
+    launch: Vec<EntryFile>,
+}
+
+#[derive(Deserialize)]
+struct EntryFile {
+    label: String,
+    creator: Option<Address>,
+    name_regex: Option<String>,
+    symbol_regex: Option<String>,
+    /// RFC 3339, e.g. `2026-10-15T18:00:00Z`.
+    from: String,
+    to: String,
+}
+
+/// An announced launch: who or what to expect, and when.
+struct Expected {
+    label: String,
+    creator: Option<Address>,
+    name_regex: Option<Regex>,
+    symbol_regex: Option<Regex>,
+    from: u64,
+    to: u64,
+}
+
+impl Expected {
+    fn matches(&self, launch: &Launch) -> bool {
+        self.creator.is_none_or(|creator| creator == launch.creator)
+            && self.name_regex.as_ref().is_none_or(|regex| regex.is_match(&launch.name))
+            && self.symbol_regex.as_ref().is_none_or(|regex| regex.is_match(&launch.symbol))
+    }
+}
+
+/// Announced upcoming launches. Ahead of each window the sniper checks the wallet can
+/// fund its snipes and keeps its connections warm; inside it, a matching launch is
+/// sniped past the creator, name and symbol filters and without the entry delay.
+pub struct LaunchCalendar {
+    entries: Vec<Expected>,
+    /// How long before a window opens to get ready for it.
+    lead: Duration,
+}
+
+impl LaunchCalendar {
+    /// Enabled by `LAUNCH_CALENDAR_FILE`, a TOML file of `[[launch]]` entries, each with a
+    /// `label`, `from` and `to` and any of `creator`, `name_regex` and `symbol_regex`.
+    /// `CALENDAR_LEAD_SECS` defaults to 300.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(path) = env::var("LAUNCH_CALENDAR_FILE").ok() else {
+            return Ok(None);
+        };
+        let lead = env::var("CALENDAR_LEAD_SECS")
+            .ok()
+            .map(|v| v.parse().context("invalid CALENDAR_LEAD_SECS"))
+            .transpose()?
+            .unwrap_or(300);
+        let contents =
+            fs::read_to_string(&path).with_context(|| format!("failed to read {}", path))?;
+        let calendar = Self::parse(&contents, Duration::from_secs(lead))
+            .with_context(|| format!("invalid launch calendar {}", path))?;
+        Ok(Some(calendar))
+    }
+
+    fn parse(contents: &str, lead: Duration) -> Result<Self> {
+        let file: CalendarFile = toml::from_str(contents)?;
+        let time = |label: &str, value: &str| -> Result<u64> {
+            let at = DateTime::parse_from_rfc3339(value)
+                .with_context(|| format!("{}: invalid time {:?}", label, value))?;
+            Ok(at.timestamp().max(0) as u64)
+        };
+        let regex = |label: &str, pattern: Option<String>| -> Result<Option<Regex>> {
+            pattern
+                .map(|p| Regex::new(&p).with_context(|| format!("{}: invalid regex", label)))
+                .transpose()
+        };
+        let entries = file
+            .launch
+            .into_iter()
+            .map(|entry| {
+                let (from, to) = (
+                    time(&entry.label, &entry.from)?,
+                    time(&entry.label, &entry.to)?,
+                );
+                if to < from {
+                    return Err(anyhow!("{}: the window ends before it starts", entry.label));
+                }
+                Ok(Expected {
+                    creator: entry.creator,
+                    name_regex: regex(&entry.label, entry.name_regex)?,
+                    symbol_regex: regex(&entry.label, entry.symbol_regex)?,
+                    from,
+                    to,
+                    label: entry.label,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { entries, lead })
+    }
+
+    /// The label of the entry `launch` was announced as, if its window is open at `now`.
+    pub fn expected(&self, launch: &Launch, now: u64) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| (entry.from..=entry.to).contains(&now) && entry.matches(launch))
+            .map(|entry| entry.label.as_str())
+    }
+
+    /// Entries whose window, counting its lead, is open at `now`.
+    fn upcoming(&self, now: u64) -> impl Iterator<Item = &Expected> {
+        let lead = self.lead.as_secs();
+        self.entries
+            .iter()
+            .filter(move |entry| (entry.from.saturating_sub(lead)..=entry.to).contains(&now))
+    }
+}
+
+/// Gets ready for each window as its lead starts: alerts if the wallet can't fund
+/// `max_concurrent` snipes and the gas reserve, then keeps the RPC connections busy
+/// each block until the window closes. Runs until the sniper stops.
+pub async fn prepare(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    calendar: Option<&LaunchCalendar>,
+    max_concurrent: usize,
+) {
+    let Some(calendar) = calendar else {
+        return std::future::pending().await;
+    };
+    let mut prepared = HashSet::new();
+    loop {
+        let now = state::unix_now();
+        let mut active = false;
+        for entry in calendar.upcoming(now) {
+            active = true;
+            if prepared.insert(entry.label.clone()) {
+                info!(
+                    "Raising alertness for {}: expected between {} and {}",
+                    entry.label, entry.from, entry.to
+                );
+                let needed = cfg.defaults.amount_in * U256::from(max_concurrent) + cfg.gas_reserve;
+                check_funding(cfg, client, &entry.label, needed).await;
+            }
+        }
+        // Keeps the connections to the node open and its answers cached.
+        let pause = if active {
+            if let Err(err) = client.gas_price().await {
+                warn!("Calendar warm-up call failed: {:#}", err);
+            }
+            chain::profile().block_time
+        } else {
+            CHECK_INTERVAL
+        };
+        tokio::time::sleep(pause).await;
+    }
+}
+
+async fn check_funding(cfg: &AppConfig, client: &impl ExecutionClient, label: &str, needed: U256) {
+    let wallet = client.wallet();
+    match client.native_balance(wallet, None).await {
+        Ok(balance) if balance < needed => {
+            let profile = chain::profile();
+            let message = format!(
+                "Fund {:?} before {}: it holds {} of the {} its snipes need",
+                wallet,
+                label,
+                profile.format_native(balance),
+                profile.format_native(needed)
+            );
+            warn!("{}", message);
+            cfg.notifier.send(Event::Error, message);
+        }
+        Ok(_) => info!("{:?} is funded for {}", wallet, label),
+        Err(err) => warn!("Funding check for {} failed: {:#}", label, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CALENDAR: &str = r#"
+        [[launch]]
+        label = "frog drop"
+        creator = "0x1111111111111111111111111111111111111111"
+        symbol_regex = "^FROG$"
+        from = "2026-10-15T18:00:00Z"
+        to = "2026-10-15T19:00:00Z"
+    "#;
+
+    fn launch(creator: u8, symbol: &str) -> Launch {
+        Launch {
+            token: Address::repeat_byte(0xaa),
+            creator: Address::repeat_byte(creator),
+            name: "Frog".into(),
+            symbol: symbol.into(),
+            block: None,
+        }
+    }
+
+    #[test]
+    fn only_matching_launches_inside_the_window_are_expected() {
+        let calendar = LaunchCalendar::parse(CALENDAR, Duration::from_secs(300)).unwrap();
+        let opens = 1_792_087_200;
+        assert_eq!(
+            calendar.expected(&launch(0x11, "FROG"), opens + 60),
+            Some("frog drop")
+        );
+        assert_eq!(calendar.expected(&launch(0x11, "FROG"), opens - 60), None);
+        assert_eq!(calendar.expected(&launch(0x11, "TOAD"), opens + 60), None);
+        assert_eq!(calendar.expected(&launch(0x22, "FROG"), opens + 60), None);
+    }
+
+    #[test]
+    fn windows_come_up_their_lead_early() {
+        let calendar = LaunchCalendar::parse(CALENDAR, Duration::from_secs(300)).unwrap();
+        let opens = 1_792_087_200;
+        assert_eq!(calendar.upcoming(opens - 301).count(), 0);
+        assert_eq!(calendar.upcoming(opens - 300).count(), 1);
+        assert_eq!(calendar.upcoming(opens + 3_600).count(), 1);
+        assert_eq!(calendar.upcoming(opens + 3_601).count(), 0);
+    }
+
+    #[test]
+    fn a_window_ending_before_it_starts_is_rejected() {
+        let inverted = CALENDAR.replace("T19:00", "T17:00");
+        assert!(LaunchCalendar::parse(&inverted, Duration::ZERO).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use std::env;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, H256, U256};
+use tokio::time::Duration;
+
+static ACTIVE: OnceLock<ChainProfile> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub struct ChainProfile {
+    pub name: String,
+    pub chain_id: u64,
+    pub native_symbol: String,
+    pub native_decimals: u32,
+    pub explorer_url: String,
+    pub block_time: Duration,
+}
+
+impl ChainProfile {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let (chain_id, explorer_url, block_time_ms) = match name {
+            "monad" => (143, "https://monadscan.com", 400),
+            "monad-testnet" => (10143, "https://testnet.monadexplorer.com", 400),
+            _ => return None,
+        };
+        Some(Self {
+            name: name.to_string(),
+            chain_id,
+            native_symbol: "MON".into(),
+            native_decimals: 18,
+            explorer_url: explorer_url.into(),
+            block_time: Duration::from_millis(block_time_ms),
+        })
+    }
+
+    /// Selects a built-in profile with `CHAIN` (default `monad`), with per-field env overrides.
+    pub fn from_env() -> Result<Self> {
+        let name = env::var("CHAIN").unwrap_or_else(|_| "monad".into());
+        let mut profile = Self::builtin(&name)
+            .ok_or_else(|| anyhow!("unknown CHAIN `{name}` (expected monad or monad-testnet)"))?;
+
+        if let Ok(value) = env::var("CHAIN_ID") {
+            profile.chain_id = value.parse().context("invalid CHAIN_ID")?;
+        }
+        if let Ok(value) = env::var("NATIVE_SYMBOL") {
+            profile.native_symbol = value;
+        }
+        if let Ok(value) = env::var("NATIVE_DECIMALS") {
+            profile.native_decimals = value.parse().context("invalid NATIVE_DECIMALS")?;
+        }
+        if let Ok(value) = env::var("EXPLORER_URL") {
+            profile.explorer_url = value.trim_end_matches('/').to_string();
+        }
+        if let Ok(value) = env::var("BLOCK_TIME_MS") {
+            profile.block_time = Duration::from_millis(value.parse().context("invalid BLOCK_TIME_MS")?);
+        }
+        Ok(profile)
+    }
+
+    pub fn tx_url(&self, tx_hash: H256) -> String {
+        format!("{}/tx/{:?}", self.explorer_url, tx_hash)
+    }
+
+    pub fn address_url(&self, address: Address) -> String {
+        format!("{}/address/{:?}", self.explorer_url, address)
+    }
+
+    pub fn format_native(&self, value: U256) -> String {
+        let amount = ethers::utils::format_units(value, self.native_decimals).unwrap_or_default();
+        format!("{} {}", amount, self.native_symbol)
+    }
+
+    /// `value` in whole native units, for arithmetic where precision doesn't matter.
+    pub fn native_f64(&self, value: U256) -> f64 {
+        ethers::utils::format_units(value, self.native_decimals)
+            .ok()
+            .and_then(|amount| amount.parse().ok())
+            .unwrap_or(0.0)
+    }
+
+    pub fn parse_native(&self, value: &str) -> Result<U256> {
+        Ok(ethers::utils::parse_units(value, self.native_decimals)?.into())
+    }
+
+    /// Interval for polling block height, a fraction of the block time.
+    pub fn block_poll_interval(&self) -> Duration {
+        (self.block_time / 4).max(Duration::from_millis(10))
+    }
+}
+
+/// Installs the profile used by the rest of the process. Call once at startup.
+pub fn init(profile: ChainProfile) {
+    let _ = ACTIVE.set(profile);
+}
+
+/// The active chain profile, falling back to the default Monad profile if `init` wasn't called.
+pub fn profile() -> &'static ChainProfile {
+    ACTIVE.get_or_init(|| ChainProfile::builtin("monad").expect("built-in monad profile"))
+}
@@ -0,0 +1,241 @@
+//! Failure injection, for rehearsing the bot's retries, reconciliation and alerts with
+//! small sizes before trusting it with real ones.
+
+use std::collections::HashSet;
+use std::env;
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, TransactionReceipt, H256, U256};
+use tracing::warn;
+
+use crate::tx_manager::TxChain;
+
+/// Injects RPC timeouts into quotes and broadcasts, reverts into mined receipts and
+/// reorgs into receipts looked up again and into the reorg watch's blocks, each at its
+/// own rate in percent.
+///
+/// An injected revert leaves a trade that did fill looking failed, for recovery to find
+/// the tokens at the next start; an injected reorg only shows with `TX_CONFIRMATIONS`
+/// above 1, as that is when a receipt is looked up again.
+#[derive(Debug, Clone)]
+pub struct ChaosConfig {
+    pub timeout_pct: f64,
+    pub revert_pct: f64,
+    pub reorg_pct: f64,
+}
+
+impl ChaosConfig {
+    /// Enabled by `CHAOS_MODE=1`, with `CHAOS_TIMEOUT_PCT`, `CHAOS_REVERT_PCT` and
+    /// `CHAOS_REORG_PCT` defaulting to 10, 5 and 5.
+    pub fn from_env() -> Result<Option<Self>> {
+        let enabled = env::var("CHAOS_MODE")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+        let pct = |name: &str, default: f64| -> Result<f64> {
+            let pct = env::var(name)
+                .ok()
+                .map(|v| v.parse::<f64>().with_context(|| format!("invalid {name}")))
+                .transpose()?
+                .unwrap_or(default);
+            if !(0.0..=100.0).contains(&pct) {
+                return Err(anyhow!("{name} must be between 0 and 100"));
+            }
+            Ok(pct)
+        };
+        Ok(Some(Self {
+            timeout_pct: pct("CHAOS_TIMEOUT_PCT", 10.0)?,
+            revert_pct: pct("CHAOS_REVERT_PCT", 5.0)?,
+            reorg_pct: pct("CHAOS_REORG_PCT", 5.0)?,
+        }))
+    }
+
+    fn roll(pct: f64) -> bool {
+        rand::random::<f64>() * 100.0 < pct
+    }
+
+    /// Fails `call` with a timeout `timeout_pct` of the time, before it reaches the node.
+    pub fn rpc(&self, call: &str) -> Result<()> {
+        if Self::roll(self.timeout_pct) {
+            warn!("Chaos: injecting an RPC timeout into {}", call);
+            return Err(anyhow!("chaos: {} request timed out", call));
+        }
+        Ok(())
+    }
+
+    /// Whether to report a reorg of the block just followed, `reorg_pct` of the time.
+    pub fn reorg(&self) -> bool {
+        let reorg = Self::roll(self.reorg_pct);
+        if reorg {
+            warn!("Chaos: injecting a reorg notification");
+        }
+        reorg
+    }
+}
+
+/// A [`TxChain`] that passes every call through to `inner`, injecting failures when
+/// chaos mode is on. Each transaction is rolled for once, so repeated lookups agree.
+pub struct ChaosChain<'a, C> {
+    inner: &'a C,
+    chaos: Option<&'a ChaosConfig>,
+    /// Receipts already looked up, and those reported reverted or reorged.
+    seen: Mutex<HashSet<H256>>,
+    reverted: Mutex<HashSet<H256>>,
+    reorged: Mutex<HashSet<H256>>,
+}
+
+impl<'a, C: TxChain> ChaosChain<'a, C> {
+    pub fn new(inner: &'a C, chaos: Option<&'a ChaosConfig>) -> Self {
+        Self {
+            inner,
+            chaos,
+            seen: Mutex::default(),
+            reverted: Mutex::default(),
+            reorged: Mutex::default(),
+        }
+    }
+
+    /// Called before each broadcast, so an injected timeout never leaves the nonce used.
+    pub fn broadcast(&self, label: &str) -> Result<()> {
+        match self.chaos {
+            Some(chaos) => chaos.rpc(&format!("{label} broadcast")),
+            None => Ok(()),
+        }
+    }
+
+    fn inject(&self, mut receipt: TransactionReceipt) -> TransactionReceipt {
+        let Some(chaos) = self.chaos else {
+            return receipt;
+        };
+        let tx_hash = receipt.transaction_hash;
+        let first_lookup = lock(&self.seen).insert(tx_hash);
+        if first_lookup && receipt.status == Some(1u64.into()) {
+            if ChaosConfig::roll(chaos.revert_pct) {
+                warn!("Chaos: reporting {:?} as reverted", tx_hash);
+                lock(&self.reverted).insert(tx_hash);
+            } else if ChaosConfig::roll(chaos.reorg_pct) {
+                warn!("Chaos: reorging {:?} out of its block", tx_hash);
+                lock(&self.reorged).insert(tx_hash);
+            }
+        }
+        if lock(&self.reverted).contains(&tx_hash) {
+            receipt.status = Some(0u64.into());
+        } else if !first_lookup && lock(&self.reorged).contains(&tx_hash) {
+            receipt.block_hash = Some(H256::random());
+        }
+        receipt
+    }
+}
+
+impl<C: TxChain> TxChain for ChaosChain<'_, C> {
+    fn address(&self) -> Address {
+        self.inner.address()
+    }
+
+    async fn nonce(&self, pending: bool) -> Result<U256> {
+        self.inner.nonce(pending).await
+    }
+
+    async fn gas_price(&self) -> Result<U256> {
+        self.inner.gas_price().await
+    }
+
+    async fn block_number(&self) -> Result<u64> {
+        self.inner.block_number().await
+    }
+
+    async fn transaction_receipt(&self, tx_hash: H256) -> Result<Option<TransactionReceipt>> {
+        let receipt = self.inner.transaction_receipt(tx_hash).await?;
+        Ok(receipt.map(|receipt| self.inject(receipt)))
+    }
+
+    async fn offered_fee(&self, tx_hash: H256) -> Result<Option<U256>> {
+        self.inner.offered_fee(tx_hash).await
+    }
+
+    async fn send_cancel(&self, nonce: U256, gas_price: U256) -> Result<H256> {
+        self.inner.send_cancel(nonce, gas_price).await
+    }
+
+    async fn revert_reason(&self, receipt: &TransactionReceipt) -> Option<String> {
+        if lock(&self.reverted).contains(&receipt.transaction_hash) {
+            return Some("injected by chaos mode".into());
+        }
+        self.inner.revert_reason(receipt).await
+    }
+}
+
+fn lock(set: &Mutex<HashSet<H256>>) -> MutexGuard<'_, HashSet<H256>> {
+    set.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry;
+    use crate::testing::MinedChain;
+    use crate::tx_manager::{is_revert, RetryPolicy, TimeInForce, TxManager};
+    use tokio::time::Duration;
+
+    fn chaos(timeout_pct: f64, revert_pct: f64, reorg_pct: f64) -> ChaosConfig {
+        ChaosConfig {
+            timeout_pct,
+            revert_pct,
+            reorg_pct,
+        }
+    }
+
+    fn policy(confirmations: u64) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            confirm_timeout: Duration::from_secs(1),
+            bump_pct: 130,
+            confirmations,
+            buy_tif: TimeInForce::Retry,
+            sell_tif: TimeInForce::Retry,
+        }
+    }
+
+    async fn submit(chain: &ChaosChain<'_, MinedChain>, policy: &RetryPolicy) -> Result<()> {
+        let txs = TxManager::new(chain, policy);
+        txs.submit("buy", TimeInForce::Retry, |_nonce| async move {
+            chain.broadcast("buy")?;
+            Ok(H256::repeat_byte(0xab))
+        })
+        .await
+        .map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn without_chaos_every_call_passes_through() {
+        let inner = MinedChain::default();
+        let chain = ChaosChain::new(&inner, None);
+        submit(&chain, &policy(2)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn injected_timeouts_are_retried_until_the_attempts_run_out() {
+        let inner = MinedChain::default();
+        let chaos = chaos(100.0, 0.0, 0.0);
+        let chain = ChaosChain::new(&inner, Some(&chaos));
+        let err = submit(&chain, &policy(1)).await.unwrap_err();
+        assert_eq!(telemetry::classify(&err), "timeout");
+    }
+
+    #[tokio::test]
+    async fn injected_reverts_and_reorgs_fail_the_submission() {
+        let inner = MinedChain::default();
+        let reverts = chaos(0.0, 100.0, 0.0);
+        let err = submit(&ChaosChain::new(&inner, Some(&reverts)), &policy(1)).await.unwrap_err();
+        assert!(is_revert(&err));
+        assert!(format!("{:#}", err).contains("injected by chaos mode"));
+
+        let reorgs = chaos(0.0, 0.0, 100.0);
+        let err = submit(&ChaosChain::new(&inner, Some(&reorgs)), &policy(2)).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("reorged out of block"));
+    }
+}
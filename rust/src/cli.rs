@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use ethers::types::{Address, H256};
+
+use crate::analytics::AnalyticsArgs;
+use crate::backtest::BacktestArgs;
+use crate::dataset::DatasetArgs;
+use crate::depth::DepthArgs;
+use crate::export::ExportArgs;
+use crate::ledger::ReportArgs;
+use crate::lists::ListArgs;
+use crate::lockdown::LockdownArgs;
+use crate::orders::OrderArgs;
+use crate::plan::PlanArgs;
+use crate::repair::RepairArgs;
+use crate::risk::RiskArgs;
+use crate::self_update::UpdateArgs;
+use crate::tuning::TuningArgs;
+
+#[derive(Debug, Parser)]
+#[command(name = "nadfun_trading_bot", version, about = "Rust Trading Bot for Nad.fun")]
+pub struct Cli {
+    /// Instance name; loads `.env.<instance>` on top of the base `.env`.
+    #[arg(long, env = "BOT_INSTANCE")]
+    pub instance: Option<String>,
+
+    /// Base env file shared by all instances.
+    #[arg(long, default_value = ".env")]
+    pub env_file: PathBuf,
+
+    /// Config file with per-token profiles; `config.toml` is used if present.
+    #[arg(long, env = "BOT_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Trade only this token, overriding TOKEN_ADDRESS and the config watchlist.
+    #[arg(long)]
+    pub token: Option<Address>,
+
+    /// Quote, simulate and run the safety checks, but send no transactions.
+    #[arg(long, env = "DRY_RUN")]
+    pub dry_run: bool,
+
+    /// On SIGINT or SIGTERM, sell every open position before exiting instead of leaving
+    /// them for the next start.
+    #[arg(long, env = "EXIT_ON_SHUTDOWN")]
+    pub exit_on_shutdown: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Watch for new nad.fun launches and snipe the ones matching the filters.
+    Sniper,
+    /// Mirror the nad.fun buys of the COPY_WALLETS addresses at a fraction of their size.
+    Copy,
+    /// Buy TOKEN_ADDRESS on a recurring schedule, then exit the accumulated position.
+    Dca,
+    /// Hold TOKEN_ADDRESS only while the net buy flow into its curve is positive.
+    Flow,
+    /// Trade the watchlist while taking quote, buy, sell and other commands from a prompt.
+    Repl,
+    /// Discover nothing; only trade the orders posted to the control API's /orders.
+    Exec,
+    /// Quote both sides of a token at a ladder of sizes.
+    Depth(DepthArgs),
+    /// Explain what the bot did in a transaction.
+    Annotate {
+        tx_hash: H256,
+    },
+    /// Size positions, gas reserve and runway for a bankroll before trading.
+    Plan(PlanArgs),
+    /// Import or export the watchlist and blocklist as CSV or JSON.
+    Lists(ListArgs),
+    /// Manage resting limit orders, or watch and fill them.
+    Order(OrderArgs),
+    /// Realized PnL per token over a date range, from the trade ledger.
+    Report(ReportArgs),
+    /// Replay recorded launches and trades through the sniper filters and exit rules.
+    Backtest(BacktestArgs),
+    /// Write the bonding curve's launches, swaps and holder flows to partitioned Parquet.
+    Export(ExportArgs),
+    /// Label past launches with their features at entry and their outcome, to train a
+    /// SCORER_MODEL on.
+    Features(DatasetArgs),
+    /// Per-token slippage, revert rate and inclusion delay, from the execution log.
+    Execution,
+    /// Canned queries over the trade ledger and execution log, as a table or CSV.
+    Analytics(AnalyticsArgs),
+    /// Show or reset the spending limits' circuit breaker.
+    Risk(RiskArgs),
+    /// Show or revert the sniper thresholds tuned from realized outcomes.
+    Tuning(TuningArgs),
+    /// Check the hashes, chain and signatures of a signed audit log.
+    VerifyAudit {
+        /// Defaults to AUDIT_LOG_FILE.
+        path: Option<PathBuf>,
+        /// Address every entry must be signed by; defaults to AUDIT_SIGNER.
+        #[arg(long)]
+        signer: Option<Address>,
+    },
+    /// Clear stuck transactions, nonce gaps and dangling approvals from the wallet.
+    Repair(RepairArgs),
+    /// Stop trading from a possibly compromised wallet and sweep its funds to safety.
+    Lockdown(LockdownArgs),
+    /// Run the end-of-day settlement now: flatten or roll positions, sweep the day's
+    /// profit, reconcile, report and rotate logs. SETTLE_AT runs it daily instead.
+    Settle,
+    /// Replace the binary with the newest release from SELF_UPDATE_FEED, once its
+    /// signature by SELF_UPDATE_SIGNER and its digest check out.
+    SelfUpdate(UpdateArgs),
+}
+
+impl Cli {
+    /// Loads env files so that process env beats the instance overlay, which beats the base file.
+    pub fn load_env(&self) -> Result<()> {
+        if let Some(instance) = &self.instance {
+            let overlay = overlay_path(&self.env_file, instance);
+            dotenvy::from_path(&overlay)
+                .with_context(|| format!("failed to load instance overlay {}", overlay.display()))?;
+            println!("Loaded instance overlay {}", overlay.display());
+        }
+        dotenvy::from_path(&self.env_file).ok();
+        Ok(())
+    }
+}
+
+fn overlay_path(base: &Path, instance: &str) -> PathBuf {
+    let mut name = base
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_else(|| ".env".into());
+    name.push(".");
+    name.push(instance);
+    base.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlay_sits_next_to_the_base_env_file() {
+        assert_eq!(overlay_path(Path::new(".env"), "eu"), PathBuf::from(".env.eu"));
+        assert_eq!(
+            overlay_path(Path::new("/etc/bot/prod.env"), "a"),
+            PathBuf::from("/etc/bot/prod.env.a")
+        );
+        assert_eq!(overlay_path(Path::new("/"), "a"), PathBuf::from("/.env.a"));
+    }
+}
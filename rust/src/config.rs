@@ -0,0 +1,380 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+use tracing::info;
+
+use crate::chain;
+use crate::exit_strategy::{self, CreatorDump, ExitRules, ReserveDrop, Tranche};
+use crate::sizing::Sizing;
+use crate::venue::VenueKind;
+
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// `config.toml`: shared `[defaults]` plus one `[[token]]` section per watchlist entry.
+///
+/// ```toml
+/// [defaults]
+/// amount_in_mon = "0.1"
+/// slippage_bps = 100
+///
+/// [[token]]
+/// address = "0x..."
+/// amount_in_mon = "0.25"
+/// take_profit_pct = 40
+/// stop_loss_pct = 15
+/// trailing_stop_pct = 10
+/// exit_tranches = "50@30,25@60"
+/// exit_creator_dump_pct = 50
+///
+/// [[blocked]]
+/// address = "0x..."
+/// reason = "honeypot"
+///
+/// [[peer]]
+/// name = "alice"
+/// url = "https://alice.example:8787"
+/// weight = 0.6
+/// token = "..."
+/// inbound_token = "..."
+/// inbound_secret = "..."
+/// max_signals_per_min = 30
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub defaults: Profile,
+    #[serde(default, rename = "token")]
+    pub tokens: Vec<TokenProfile>,
+    /// Tokens never bought, whatever mode finds them.
+    #[serde(default)]
+    pub blocked: Vec<BlockedToken>,
+    /// Other bot instances that signals are shared with.
+    #[serde(default, rename = "peer", skip_serializing_if = "Vec::is_empty")]
+    pub peers: Vec<Peer>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenProfile {
+    pub address: Address,
+    #[serde(flatten)]
+    pub profile: Profile,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockedToken {
+    pub address: Address,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// A cooperating bot instance reached through its control API, or a third-party
+/// provider that only sends signals and has no `url`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Peer {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// How far this peer's signals are trusted, from 0 (ignored) to 1.
+    pub weight: f64,
+    /// Bearer token sent with signals to this peer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Bearer token this peer sends with its signals, which identifies it.
+    pub inbound_token: String,
+    /// HMAC-SHA256 key the signals sent to this peer are signed with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// HMAC-SHA256 key this peer's signals must be signed with. Required to take its signals.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inbound_secret: Option<String>,
+    /// Signals taken from this peer per minute; unlimited when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_signals_per_min: Option<u32>,
+}
+
+/// Per-trade settings from one config layer; unset fields fall through to the layer below.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_in_mon: Option<String>,
+    /// `fixed` (the default), `balance:PCT` or `risk:MON`, evaluated at trade time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_sizing: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slippage_bps: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub take_profit_pct: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_loss_pct: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_stop_pct: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_hold_secs: Option<u64>,
+    /// Only emergency exits sell a position younger than this.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_hold_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_tranches: Option<String>,
+    /// Exit when the curve's MON reserve drops this much between token snapshots.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_reserve_drop_pct: Option<f64>,
+    /// Exit when the creator's token balance drops this much between token snapshots.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_creator_dump_pct: Option<f64>,
+    /// `nadfun` (the default) or `uniswap_v2`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub venue: Option<String>,
+}
+
+/// Resolved settings for trading one token.
+pub struct TradeParams {
+    pub amount_in: U256,
+    pub sizing: Sizing,
+    pub slippage_bps: u64,
+    pub exit_rules: ExitRules,
+    pub stop_loss_pct: Option<f64>,
+    pub tranches: Vec<Tranche>,
+    /// Whether any exit rule needs token snapshots.
+    pub snapshot_exits: bool,
+    pub venue: VenueKind,
+    /// The merged layers these were resolved from, for applying runtime overrides.
+    pub profile: Profile,
+}
+
+pub struct Target {
+    pub token: Address,
+    pub params: TradeParams,
+}
+
+impl ConfigFile {
+    /// Reads `path`, or `config.toml` if it exists when no path is given.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Path::new(DEFAULT_CONFIG_FILE),
+            None => return Ok(Self::default()),
+        };
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        let file: Self = toml::from_str(&contents)
+            .with_context(|| format!("invalid config file {}", path.display()))?;
+        info!(
+            "Loaded {} with {} token profiles",
+            path.display(),
+            file.tokens.len()
+        );
+        Ok(file)
+    }
+
+    /// Writes the file back as TOML. Comments in the original are not kept.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = toml::to_string_pretty(self).context("failed to serialize config")?;
+        fs::write(path, contents)
+            .with_context(|| format!("failed to write config file {}", path.display()))
+    }
+
+    pub fn is_blocked(&self, token: Address) -> bool {
+        self.blocked.iter().any(|blocked| blocked.address == token)
+    }
+
+    /// Settings for `token`: its `[[token]]` section over `[defaults]`, with `env` on top.
+    pub fn target(&self, token: Address, env: &Profile) -> Result<Target> {
+        let mut profile = self.defaults.clone();
+        if let Some(section) = self.tokens.iter().find(|t| t.address == token) {
+            profile = profile.overlay(&section.profile);
+        }
+        let params = profile
+            .overlay(env)
+            .resolve()
+            .with_context(|| format!("invalid settings for token {:?}", token))?;
+        Ok(Target { token, params })
+    }
+}
+
+impl Profile {
+    pub fn from_env() -> Result<Self> {
+        let pct = |name: &str| -> Result<Option<f64>> {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {name}")))
+                .transpose()
+        };
+        Ok(Self {
+            amount_in_mon: env::var("AMOUNT_IN_MON").ok(),
+            amount_sizing: env::var("AMOUNT_SIZING").ok(),
+            slippage_bps: env::var("SLIPPAGE_BPS").ok().and_then(|v| v.parse().ok()),
+            take_profit_pct: pct("TAKE_PROFIT_PCT")?,
+            stop_loss_pct: pct("STOP_LOSS_PCT")?,
+            trailing_stop_pct: pct("TRAILING_STOP_PCT")?,
+            max_hold_secs: env::var("SETTLEMENT_WAIT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            min_hold_secs: env::var("MIN_HOLD_SECS")
+                .ok()
+                .map(|v| v.parse().context("invalid MIN_HOLD_SECS"))
+                .transpose()?,
+            exit_tranches: env::var("EXIT_TRANCHES").ok(),
+            exit_reserve_drop_pct: pct("EXIT_RESERVE_DROP_PCT")?,
+            exit_creator_dump_pct: pct("EXIT_CREATOR_DUMP_PCT")?,
+            venue: env::var("VENUE").ok(),
+        })
+    }
+
+    /// Fields set in `top` win over this layer's.
+    pub fn overlay(self, top: &Profile) -> Self {
+        let top = top.clone();
+        Self {
+            amount_in_mon: top.amount_in_mon.or(self.amount_in_mon),
+            amount_sizing: top.amount_sizing.or(self.amount_sizing),
+            slippage_bps: top.slippage_bps.or(self.slippage_bps),
+            take_profit_pct: top.take_profit_pct.or(self.take_profit_pct),
+            stop_loss_pct: top.stop_loss_pct.or(self.stop_loss_pct),
+            trailing_stop_pct: top.trailing_stop_pct.or(self.trailing_stop_pct),
+            max_hold_secs: top.max_hold_secs.or(self.max_hold_secs),
+            min_hold_secs: top.min_hold_secs.or(self.min_hold_secs),
+            exit_tranches: top.exit_tranches.or(self.exit_tranches),
+            exit_reserve_drop_pct: top.exit_reserve_drop_pct.or(self.exit_reserve_drop_pct),
+            exit_creator_dump_pct: top.exit_creator_dump_pct.or(self.exit_creator_dump_pct),
+            venue: top.venue.or(self.venue),
+        }
+    }
+
+    pub fn resolve(self) -> Result<TradeParams> {
+        let profile = self.clone();
+        let amount_in = chain::profile()
+            .parse_native(self.amount_in_mon.as_deref().unwrap_or("0.1"))
+            .context("invalid amount_in_mon")?;
+        let sizing = match &self.amount_sizing {
+            Some(value) => value.parse().context("invalid amount_sizing")?,
+            None => Sizing::Fixed,
+        };
+        if matches!(sizing, Sizing::Risk(_)) && self.stop_loss_pct.is_none() {
+            return Err(anyhow!("risk sizing needs stop_loss_pct"));
+        }
+        if self.slippage_bps.is_some_and(|bps| bps >= 10_000) {
+            return Err(anyhow!("slippage_bps must be below 10000"));
+        }
+        let max_hold_secs = self.max_hold_secs.unwrap_or(30);
+        let min_hold_secs = self.min_hold_secs.unwrap_or(0);
+        if min_hold_secs > max_hold_secs {
+            return Err(anyhow!("min_hold_secs must not exceed max_hold_secs"));
+        }
+        let mut exit_rules = ExitRules::new(
+            self.take_profit_pct,
+            self.stop_loss_pct,
+            self.trailing_stop_pct,
+            Duration::from_secs(max_hold_secs),
+        )?
+        .holding_for(Duration::from_secs(min_hold_secs));
+        if let Some(pct) = self.exit_reserve_drop_pct {
+            exit_rules = exit_rules.with(ReserveDrop { pct });
+        }
+        if let Some(pct) = self.exit_creator_dump_pct {
+            exit_rules = exit_rules.with(CreatorDump { pct });
+        }
+        let venue: VenueKind = match &self.venue {
+            Some(value) => value.parse().context("invalid venue")?,
+            None => VenueKind::NadFun,
+        };
+        // Curve snapshots only exist for nad.fun tokens.
+        if venue != VenueKind::NadFun
+            && (self.exit_reserve_drop_pct.is_some() || self.exit_creator_dump_pct.is_some())
+        {
+            return Err(anyhow!("snapshot exits need the nadfun venue"));
+        }
+        let tranches = self
+            .exit_tranches
+            .as_deref()
+            .map(exit_strategy::parse_tranches)
+            .transpose()
+            .context("invalid exit_tranches")?
+            .unwrap_or_default();
+        Ok(TradeParams {
+            amount_in,
+            sizing,
+            slippage_bps: self.slippage_bps.unwrap_or(100), // 1%
+            exit_rules,
+            stop_loss_pct: self.stop_loss_pct,
+            tranches,
+            snapshot_exits: self.exit_reserve_drop_pct.is_some()
+                || self.exit_creator_dump_pct.is_some(),
+            venue,
+            profile,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_fills_in_the_defaults() {
+        let params = Profile::default().resolve().unwrap();
+        assert_eq!(params.amount_in, U256::exp10(17));
+        assert_eq!(params.sizing, Sizing::Fixed);
+        assert_eq!(params.slippage_bps, 100);
+        assert!(params.tranches.is_empty());
+        assert!(!params.snapshot_exits);
+    }
+
+    #[test]
+    fn resolve_takes_the_top_layer_first() {
+        let file = Profile {
+            amount_in_mon: Some("2".into()),
+            slippage_bps: Some(300),
+            ..Profile::default()
+        };
+        let env = Profile {
+            slippage_bps: Some(50),
+            exit_reserve_drop_pct: Some(20.0),
+            ..Profile::default()
+        };
+        let params = file.overlay(&env).resolve().unwrap();
+        assert_eq!(params.amount_in, U256::exp10(18) * 2);
+        assert_eq!(params.slippage_bps, 50);
+        assert!(params.snapshot_exits);
+    }
+
+    #[test]
+    fn resolve_rejects_invalid_settings() {
+        let risk_without_stop = Profile {
+            amount_sizing: Some("risk:0.5".into()),
+            ..Profile::default()
+        };
+        assert!(risk_without_stop.resolve().is_err());
+        let bad_amount = Profile {
+            amount_in_mon: Some("lots".into()),
+            ..Profile::default()
+        };
+        assert!(bad_amount.resolve().is_err());
+        let bad_stop = Profile {
+            stop_loss_pct: Some(0.0),
+            ..Profile::default()
+        };
+        assert!(bad_stop.resolve().is_err());
+        let full_slippage = Profile {
+            slippage_bps: Some(10_000),
+            ..Profile::default()
+        };
+        assert!(full_slippage.resolve().is_err());
+        let hold_past_max = Profile {
+            max_hold_secs: Some(60),
+            min_hold_secs: Some(61),
+            ..Profile::default()
+        };
+        assert!(hold_past_max.resolve().is_err());
+        let curve_exit_off_curve = Profile {
+            venue: Some("uniswap_v2".into()),
+            exit_reserve_drop_pct: Some(30.0),
+            ..Profile::default()
+        };
+        assert!(curve_exit_off_curve.resolve().is_err());
+    }
+}
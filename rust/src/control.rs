@@ -0,0 +1,968 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use anyhow::{anyhow, Context, Result};
+use axum::body::Bytes;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use subtle::ConstantTimeEq;
+use tokio::sync::{mpsc, oneshot, Mutex as AsyncMutex, MutexGuard};
+use tracing::{info, warn};
+
+use crate::caches;
+use crate::chain;
+use crate::config::{Profile, TradeParams};
+use crate::execstats::Side;
+use crate::exit_strategy::{Exit, ExitKind, ExitRules, ExitStrategy, Position};
+use crate::latency::{self, StageSummary};
+use crate::ledger::{Ledger, TradeRecord};
+use crate::logging;
+use crate::race::{self, SignerStats};
+use crate::signals::{ProviderReport, Rejection, Signals, SIGNATURE_HEADER};
+use crate::simulate::{Simulation, SimulationRequest};
+use crate::state::{self, StateStore};
+use crate::stats;
+
+/// Settings that can be changed while the bot runs; unset fields keep the configured value.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Overrides {
+    /// Buy size for new fixed-size entries, in MON.
+    #[serde(default)]
+    pub amount_in_mon: Option<String>,
+    #[serde(default)]
+    pub slippage_bps: Option<u64>,
+    #[serde(default)]
+    pub take_profit_pct: Option<f64>,
+    #[serde(default)]
+    pub stop_loss_pct: Option<f64>,
+    #[serde(default)]
+    pub trailing_stop_pct: Option<f64>,
+    #[serde(default)]
+    pub max_hold_secs: Option<u64>,
+}
+
+/// The overridable settings and their units, in the order the schema lists them.
+const TUNABLE: [(&str, &str); 6] = [
+    ("amount_in_mon", "MON"),
+    ("slippage_bps", "bps"),
+    ("take_profit_pct", "%"),
+    ("stop_loss_pct", "%"),
+    ("trailing_stop_pct", "%"),
+    ("max_hold_secs", "s"),
+];
+
+impl Overrides {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn as_profile(&self) -> Profile {
+        Profile {
+            amount_in_mon: self.amount_in_mon.clone(),
+            slippage_bps: self.slippage_bps,
+            take_profit_pct: self.take_profit_pct,
+            stop_loss_pct: self.stop_loss_pct,
+            trailing_stop_pct: self.trailing_stop_pct,
+            max_hold_secs: self.max_hold_secs,
+            ..Profile::default()
+        }
+    }
+
+    /// Each of [`TUNABLE`]'s values, for checking against the guardrails.
+    fn values(&self) -> Result<[Option<f64>; 6]> {
+        let amount = self
+            .amount_in_mon
+            .as_deref()
+            .map(|v| v.parse::<f64>().context("invalid amount_in_mon"))
+            .transpose()?;
+        Ok([
+            amount,
+            self.slippage_bps.map(|v| v as f64),
+            self.take_profit_pct,
+            self.stop_loss_pct,
+            self.trailing_stop_pct,
+            self.max_hold_secs.map(|v| v as f64),
+        ])
+    }
+}
+
+/// Bounds on the overrides that are accepted, from `PARAM_LIMITS`, e.g.
+/// `slippage_bps=10..500,stop_loss_pct=2..30`. Settings without bounds take any value
+/// the trade params accept.
+#[derive(Debug, Default, Clone)]
+pub struct ParamLimits(BTreeMap<&'static str, (f64, f64)>);
+
+impl ParamLimits {
+    pub fn from_env() -> Result<Self> {
+        match env::var("PARAM_LIMITS") {
+            Ok(value) => value.parse().context("invalid PARAM_LIMITS"),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<(f64, f64)> {
+        self.0.get(name).copied()
+    }
+
+    fn check(&self, overrides: &Overrides) -> Result<()> {
+        for ((name, _), value) in TUNABLE.iter().zip(overrides.values()?) {
+            if let (Some(value), Some((min, max))) = (value, self.get(name)) {
+                if value < min || value > max {
+                    return Err(anyhow!("{name} {value} is outside its {min}..{max} guardrail"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ParamLimits {
+    type Err = anyhow::Error;
+
+    /// Comma-separated `name=min..max` bounds.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut limits = BTreeMap::new();
+        for bound in s.split(',').map(str::trim).filter(|bound| !bound.is_empty()) {
+            let (name, range) = bound
+                .split_once('=')
+                .ok_or_else(|| anyhow!("`{bound}` should be name=min..max"))?;
+            let name = TUNABLE
+                .iter()
+                .map(|(tunable, _)| *tunable)
+                .find(|tunable| *tunable == name.trim())
+                .ok_or_else(|| anyhow!("`{}` is not a tunable setting", name.trim()))?;
+            let (min, max) = range
+                .split_once("..")
+                .ok_or_else(|| anyhow!("`{bound}` should be name=min..max"))?;
+            let min: f64 =
+                min.trim().parse().with_context(|| format!("invalid minimum in `{bound}`"))?;
+            let max: f64 =
+                max.trim().parse().with_context(|| format!("invalid maximum in `{bound}`"))?;
+            if min > max {
+                return Err(anyhow!("`{bound}` has its minimum above its maximum"));
+            }
+            limits.insert(name, (min, max));
+        }
+        Ok(Self(limits))
+    }
+}
+
+/// One tunable setting, as `/params/schema` describes it.
+#[derive(Debug, Serialize)]
+struct ParamSpec {
+    name: &'static str,
+    unit: &'static str,
+    min: Option<f64>,
+    max: Option<f64>,
+    /// The override in effect, if any.
+    value: Option<f64>,
+}
+
+/// An accepted change to the overrides.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParamChange {
+    pub at: u64,
+    /// `api` or `repl`.
+    pub source: String,
+    pub before: Overrides,
+    pub after: Overrides,
+}
+
+/// The most recent override changes, appended to `PARAMS_HISTORY_FILE` when set so
+/// they outlive a restart.
+#[derive(Default)]
+struct ParamHistory {
+    recent: VecDeque<ParamChange>,
+    file: Option<PathBuf>,
+}
+
+const HISTORY_LEN: usize = 100;
+
+impl ParamHistory {
+    fn open(file: Option<PathBuf>) -> Result<Self> {
+        let mut recent = VecDeque::new();
+        if let Some(path) = file.as_ref().filter(|path| path.exists()) {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                let change = serde_json::from_str(line)
+                    .with_context(|| format!("invalid entry in {}", path.display()))?;
+                recent.push_back(change);
+                if recent.len() > HISTORY_LEN {
+                    recent.pop_front();
+                }
+            }
+        }
+        Ok(Self { recent, file })
+    }
+
+    fn push(&mut self, change: ParamChange) {
+        if let Some(path) = &self.file {
+            let written = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", json!(change)));
+            if let Err(err) = written {
+                warn!("Failed to append to {}: {}", path.display(), err);
+            }
+        }
+        self.recent.push_back(change);
+        if self.recent.len() > HISTORY_LEN {
+            self.recent.pop_front();
+        }
+    }
+}
+
+/// A buy posted to `/orders`, traded through the normal round trip.
+#[derive(Debug, Clone, Copy)]
+pub struct ExecOrder {
+    pub token: Address,
+    /// Buy size instead of the token's `amount_in`.
+    pub amount_in: Option<U256>,
+    /// Slippage instead of the token's configured value.
+    pub slippage_bps: Option<u64>,
+}
+
+/// Runtime switches shared between the trading tasks and the control API.
+pub struct Controls {
+    paused: AtomicBool,
+    force_sells: Mutex<HashSet<Address>>,
+    overrides: RwLock<Overrides>,
+    /// Bumped on every override change so cached exit rules know to rebuild.
+    version: AtomicU64,
+    limits: ParamLimits,
+    history: Mutex<ParamHistory>,
+    watchlist: RwLock<BTreeSet<Address>>,
+    added: mpsc::UnboundedSender<Address>,
+    /// Held by the task trading the watchlist for as long as it runs.
+    added_rx: AsyncMutex<mpsc::UnboundedReceiver<Address>>,
+    orders: mpsc::UnboundedSender<ExecOrder>,
+    /// Held by the execution-only mode for as long as it runs.
+    orders_rx: AsyncMutex<mpsc::UnboundedReceiver<ExecOrder>>,
+    simulations: mpsc::UnboundedSender<SimulationRequest>,
+    /// Held by the task answering simulations while a trading mode runs.
+    simulations_rx: AsyncMutex<mpsc::UnboundedReceiver<SimulationRequest>>,
+}
+
+impl Controls {
+    pub fn new(watchlist: impl IntoIterator<Item = Address>) -> Self {
+        let (added, added_rx) = mpsc::unbounded_channel();
+        let (orders, orders_rx) = mpsc::unbounded_channel();
+        let (simulations, simulations_rx) = mpsc::unbounded_channel();
+        Self {
+            paused: AtomicBool::new(false),
+            force_sells: Mutex::default(),
+            overrides: RwLock::default(),
+            version: AtomicU64::new(0),
+            limits: ParamLimits::default(),
+            history: Mutex::default(),
+            watchlist: RwLock::new(watchlist.into_iter().collect()),
+            added,
+            added_rx: AsyncMutex::new(added_rx),
+            orders,
+            orders_rx: AsyncMutex::new(orders_rx),
+            simulations,
+            simulations_rx: AsyncMutex::new(simulations_rx),
+        }
+    }
+
+    /// Bounds the overrides by `limits` and keeps their history, in `history_file` too
+    /// when given.
+    pub fn with_tuning(self, limits: ParamLimits, history_file: Option<PathBuf>) -> Result<Self> {
+        Ok(Self {
+            limits,
+            history: Mutex::new(ParamHistory::open(history_file)?),
+            ..self
+        })
+    }
+
+    /// Whether new entries are on hold.
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn overrides(&self) -> Overrides {
+        self.overrides.read().map(|o| o.clone()).unwrap_or_default()
+    }
+
+    /// Slippage for a new entry: the override if set, else `configured`.
+    pub fn slippage_bps(&self, configured: u64) -> u64 {
+        self.overrides().slippage_bps.unwrap_or(configured)
+    }
+
+    /// Size of a new fixed-size entry: the override if set, else `configured`.
+    pub fn amount_in(&self, configured: U256) -> U256 {
+        self.overrides()
+            .amount_in_mon
+            .and_then(|mon| chain::profile().parse_native(&mon).ok())
+            .unwrap_or(configured)
+    }
+
+    /// The accepted override changes, oldest first.
+    pub fn history(&self) -> Vec<ParamChange> {
+        self.history
+            .lock()
+            .map(|history| history.recent.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn schema(&self) -> Vec<ParamSpec> {
+        let values = self.overrides().values().unwrap_or_default();
+        TUNABLE
+            .iter()
+            .zip(values)
+            .map(|((name, unit), value)| {
+                let limits = self.limits.get(name);
+                ParamSpec {
+                    name,
+                    unit,
+                    min: limits.map(|(min, _)| min),
+                    max: limits.map(|(_, max)| max),
+                    value,
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_watched(&self, token: Address) -> bool {
+        self.watchlist.read().is_ok_and(|list| list.contains(&token))
+    }
+
+    /// Drops `token` from the watchlist. False if it wasn't watched.
+    pub fn unwatch(&self, token: Address) -> bool {
+        self.watchlist.write().is_ok_and(|mut list| list.remove(&token))
+    }
+
+    pub fn watchlist_len(&self) -> usize {
+        self.watchlist.read().map(|list| list.len()).unwrap_or(0)
+    }
+
+    /// Tokens added through the API, for the task that trades the watchlist.
+    pub async fn added(&self) -> MutexGuard<'_, mpsc::UnboundedReceiver<Address>> {
+        self.added_rx.lock().await
+    }
+
+    /// Adds `token` to the watchlist and hands it to the task trading it. False if it
+    /// was already watched.
+    pub fn watch(&self, token: Address) -> Result<bool> {
+        if !self.trades_watchlist() {
+            return Err(anyhow!("this mode does not trade the watchlist"));
+        }
+        let mut list = self.watchlist.write().map_err(|_| anyhow!("watchlist lock poisoned"))?;
+        if !list.insert(token) {
+            return Ok(false);
+        }
+        self.added
+            .send(token)
+            .map_err(|_| anyhow!("the watchlist task has stopped"))?;
+        Ok(true)
+    }
+
+    /// Whether a task is currently picking up added tokens.
+    fn trades_watchlist(&self) -> bool {
+        self.added_rx.try_lock().is_err()
+    }
+
+    /// Orders posted through the API, for the execution-only mode.
+    pub async fn orders(&self) -> MutexGuard<'_, mpsc::UnboundedReceiver<ExecOrder>> {
+        self.orders_rx.lock().await
+    }
+
+    fn submit(&self, order: ExecOrder) -> Result<()> {
+        if self.orders_rx.try_lock().is_ok() {
+            return Err(anyhow!("orders are only taken in exec mode"));
+        }
+        self.orders
+            .send(order)
+            .map_err(|_| anyhow!("the order task has stopped"))
+    }
+
+    /// Simulations requested through the API.
+    pub async fn simulations(&self) -> MutexGuard<'_, mpsc::UnboundedReceiver<SimulationRequest>> {
+        self.simulations_rx.lock().await
+    }
+
+    async fn simulate(&self, token: Address, side: Side, amount: Option<U256>) -> Result<Simulation> {
+        if self.simulations_rx.try_lock().is_ok() {
+            return Err(anyhow!("simulations are only answered while a trading mode runs"));
+        }
+        let (reply, answer) = oneshot::channel();
+        let request = SimulationRequest {
+            token,
+            side,
+            amount,
+            reply,
+        };
+        self.simulations
+            .send(request)
+            .map_err(|_| anyhow!("the simulation task has stopped"))?;
+        answer
+            .await
+            .map_err(|_| anyhow!("the simulation task dropped the request"))?
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Replaces the runtime overrides, if they are within the guardrails and resolve
+    /// against the default profile, and records the change as made through `source`.
+    pub fn set_overrides(&self, overrides: Overrides, source: &str) -> Result<()> {
+        self.limits.check(&overrides)?;
+        Profile::default().overlay(&overrides.as_profile()).resolve()?;
+        let mut current = self.overrides.write().map_err(|_| anyhow!("overrides lock poisoned"))?;
+        let before = std::mem::replace(&mut *current, overrides.clone());
+        self.version.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut history) = self.history.lock() {
+            history.push(ParamChange {
+                at: state::unix_now(),
+                source: source.to_string(),
+                before,
+                after: overrides,
+            });
+        }
+        Ok(())
+    }
+
+    /// Queues a sell of `token` for its next exit check, for the control API and the
+    /// settlement.
+    pub fn force_sell(&self, token: Address) {
+        if let Ok(mut tokens) = self.force_sells.lock() {
+            tokens.insert(token);
+        }
+    }
+
+    /// Consumes a pending force-sell request for `token`.
+    fn take_force_sell(&self, token: Address) -> bool {
+        self.force_sells.lock().is_ok_and(|mut tokens| tokens.remove(&token))
+    }
+}
+
+/// A token's exit rules with the runtime TP/SL overrides applied on top and force-sell
+/// requests honoured. Rules are rebuilt from the token's profile whenever the overrides
+/// change.
+pub struct LiveRules<'a> {
+    params: &'a TradeParams,
+    controls: &'a Controls,
+    token: Address,
+    cached: Mutex<Option<(u64, ExitRules)>>,
+}
+
+impl<'a> LiveRules<'a> {
+    pub fn new(params: &'a TradeParams, controls: &'a Controls, token: Address) -> Self {
+        Self {
+            params,
+            controls,
+            token,
+            cached: Mutex::new(None),
+        }
+    }
+}
+
+impl ExitStrategy for LiveRules<'_> {
+    fn should_exit(&self, position: &Position) -> Option<Exit> {
+        if self.controls.take_force_sell(self.token) {
+            let reason = "force-sell requested";
+            return Some(Exit::new(ExitKind::ForceSell, reason));
+        }
+        let overrides = self.controls.overrides();
+        if overrides.is_empty() {
+            return self.params.exit_rules.should_exit(position);
+        }
+
+        let version = self.controls.version.load(Ordering::Relaxed);
+        let mut cached = self.cached.lock().ok()?;
+        if cached.as_ref().map(|(built, _)| *built) != Some(version) {
+            match self.params.profile.clone().overlay(&overrides.as_profile()).resolve() {
+                Ok(params) => *cached = Some((version, params.exit_rules)),
+                Err(err) => {
+                    warn!("Runtime overrides ignored for {:?}: {:#}", self.token, err);
+                    return self.params.exit_rules.should_exit(position);
+                }
+            }
+        }
+        cached.as_ref()?.1.should_exit(position)
+    }
+
+    fn may_sell(&self, position: &Position) -> bool {
+        self.params.exit_rules.may_sell(position)
+    }
+}
+
+pub struct ControlConfig {
+    pub addr: SocketAddr,
+    /// Required as a bearer token on every request when set.
+    pub token: Option<String>,
+}
+
+impl ControlConfig {
+    /// Enabled by `CONTROL_ADDR`, e.g. `127.0.0.1:8787`, with `CONTROL_TOKEN` for auth.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(addr) = env::var("CONTROL_ADDR").ok().filter(|v| !v.is_empty()) else {
+            return Ok(None);
+        };
+        let addr: SocketAddr = addr.parse().context("invalid CONTROL_ADDR")?;
+        let token = env::var("CONTROL_TOKEN").ok().filter(|v| !v.is_empty());
+        if token.is_none() && !addr.ip().is_loopback() {
+            return Err(anyhow!("CONTROL_TOKEN is required when CONTROL_ADDR is not loopback"));
+        }
+        Ok(Some(Self { addr, token }))
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    controls: Arc<Controls>,
+    state: Arc<StateStore>,
+    ledger: Arc<Ledger>,
+    signals: Option<Arc<Signals>>,
+    token: Option<Arc<String>>,
+}
+
+/// Binds the control API and serves it in the background. `/signals` takes peer
+/// signals and authenticates with each peer's own token and signature key rather than
+/// `CONTROL_TOKEN`. `/stats/*` aggregate `ledger` read-only, for dashboards.
+pub async fn serve(
+    config: &ControlConfig,
+    controls: Arc<Controls>,
+    state: Arc<StateStore>,
+    ledger: Ledger,
+    signals: Option<Arc<Signals>>,
+) -> Result<()> {
+    let api = ApiState {
+        controls,
+        state,
+        ledger: Arc::new(ledger),
+        signals,
+        token: config.token.clone().map(Arc::new),
+    };
+    let app = Router::new()
+        .route("/positions", get(positions))
+        .route("/positions/:token/sell", post(force_sell))
+        .route("/orders", post(place_order))
+        .route("/simulate", post(simulate_trade))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/params", get(get_params).put(put_params))
+        .route("/params/schema", get(params_schema))
+        .route("/params/history", get(params_history))
+        .route("/watchlist", get(get_watchlist).post(add_watch))
+        .route("/watchlist/:token", axum::routing::delete(remove_watch))
+        .route("/latency", get(latency_summary))
+        .route("/signers", get(signer_stats))
+        .route("/caches", get(cache_sizes))
+        .route("/stats/daily", get(daily_stats))
+        .route("/stats/pnl", get(pnl_curve))
+        .route("/stats/strategies", get(strategy_stats))
+        .route("/stats/tokens", get(token_stats))
+        .route("/log-filter", get(get_log_filter).put(put_log_filter))
+        .route("/signals/providers", get(signal_providers))
+        .route("/signals/providers/:name/enable", post(enable_provider))
+        .layer(middleware::from_fn_with_state(api.clone(), authorize))
+        .route("/signals", post(receive_signal))
+        .with_state(api);
+
+    let listener = tokio::net::TcpListener::bind(config.addr)
+        .await
+        .with_context(|| format!("failed to bind CONTROL_ADDR {}", config.addr))?;
+    info!("Control API listening on {}", config.addr);
+    tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, app).await {
+            warn!("Control API stopped: {:#}", err);
+        }
+    });
+    Ok(())
+}
+
+async fn authorize(State(api): State<ApiState>, request: Request, next: Next) -> Response {
+    if let Some(token) = &api.token {
+        let expected = format!("Bearer {}", token);
+        let given = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .map(|value| value.as_bytes())
+            .unwrap_or_default();
+        // Compared in constant time so response timing doesn't leak the token.
+        if !bool::from(given.ct_eq(expected.as_bytes())) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+async fn positions(State(api): State<ApiState>) -> Response {
+    match api.state.open_positions() {
+        Ok(positions) => Json(positions).into_response(),
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", err)),
+    }
+}
+
+async fn force_sell(State(api): State<ApiState>, Path(token): Path<Address>) -> Response {
+    let open = match api.state.open_positions() {
+        Ok(positions) => positions.iter().any(|position| position.token == token),
+        Err(err) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", err)),
+    };
+    if !open {
+        return error(StatusCode::NOT_FOUND, format!("no open position in {:?}", token));
+    }
+    api.controls.force_sell(token);
+    info!("Force-sell of {:?} requested through the control API", token);
+    let body = json!({ "token": token, "status": "selling at the next exit check" });
+    (StatusCode::ACCEPTED, Json(body)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OrderSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Deserialize)]
+struct OrderRequest {
+    token: Address,
+    side: OrderSide,
+    amount_mon: Option<String>,
+    slippage_bps: Option<u64>,
+}
+
+/// A buy opens a position held on the token's exit rules; a sell force-sells an open one.
+async fn place_order(State(api): State<ApiState>, Json(request): Json<OrderRequest>) -> Response {
+    let token = request.token;
+    if let OrderSide::Sell = request.side {
+        return force_sell(State(api), Path(token)).await;
+    }
+    let amount_in = request
+        .amount_mon
+        .as_deref()
+        .map(|value| chain::profile().parse_native(value))
+        .transpose();
+    let amount_in = match amount_in {
+        Ok(amount_in) => amount_in,
+        Err(err) => {
+            return error(StatusCode::BAD_REQUEST, format!("invalid amount_mon: {:#}", err))
+        }
+    };
+    if request.slippage_bps.is_some_and(|bps| bps >= 10_000) {
+        return error(StatusCode::BAD_REQUEST, "slippage_bps must be below 10000");
+    }
+    let order = ExecOrder {
+        token,
+        amount_in,
+        slippage_bps: request.slippage_bps,
+    };
+    if let Err(err) = api.controls.submit(order) {
+        return error(StatusCode::SERVICE_UNAVAILABLE, format!("{:#}", err));
+    }
+    info!("Buy order for {:?} received through the control API", token);
+    (StatusCode::ACCEPTED, Json(json!({ "token": token, "status": "queued" }))).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct SimulateRequest {
+    token: Address,
+    side: Side,
+    /// MON for a buy, tokens for a sell.
+    amount: Option<String>,
+}
+
+/// What the bot would do with a trade right now, without sending anything.
+async fn simulate_trade(
+    State(api): State<ApiState>,
+    Json(request): Json<SimulateRequest>,
+) -> Response {
+    let amount = request
+        .amount
+        .as_deref()
+        .map(|value| chain::profile().parse_native(value))
+        .transpose();
+    let amount = match amount {
+        Ok(amount) => amount,
+        Err(err) => return error(StatusCode::BAD_REQUEST, format!("invalid amount: {:#}", err)),
+    };
+    if api.controls.simulations_rx.try_lock().is_ok() {
+        return error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "simulations are only answered while a trading mode runs",
+        );
+    }
+    match api.controls.simulate(request.token, request.side, amount).await {
+        Ok(simulation) => Json(simulation).into_response(),
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", err)),
+    }
+}
+
+async fn pause(State(api): State<ApiState>) -> Json<serde_json::Value> {
+    api.controls.set_paused(true);
+    info!("New entries paused through the control API");
+    Json(json!({ "paused": true }))
+}
+
+async fn resume(State(api): State<ApiState>) -> Json<serde_json::Value> {
+    api.controls.set_paused(false);
+    info!("New entries resumed through the control API");
+    Json(json!({ "paused": false }))
+}
+
+/// Hot-path stage timings since start, in microseconds.
+async fn latency_summary() -> Json<Vec<StageSummary>> {
+    Json(latency::summary())
+}
+
+/// Race buy signatures per signer since start.
+async fn signer_stats() -> Json<Vec<SignerStats>> {
+    Json(race::signer_stats())
+}
+
+async fn cache_sizes() -> Json<BTreeMap<&'static str, usize>> {
+    Json(caches::sizes())
+}
+
+#[derive(Debug, Deserialize)]
+struct LogFilter {
+    filter: String,
+}
+
+/// Answers with `aggregate` over the ledger's round trips.
+fn ledger_stats<T: Serialize>(
+    api: &ApiState,
+    aggregate: impl Fn(&[TradeRecord]) -> T,
+) -> Response {
+    match api.ledger.load() {
+        Ok(records) => Json(aggregate(&records)).into_response(),
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", err)),
+    }
+}
+
+async fn daily_stats(State(api): State<ApiState>) -> Response {
+    ledger_stats(&api, stats::daily)
+}
+
+async fn pnl_curve(State(api): State<ApiState>) -> Response {
+    ledger_stats(&api, stats::pnl_curve)
+}
+
+async fn strategy_stats(State(api): State<ApiState>) -> Response {
+    ledger_stats(&api, stats::by_strategy)
+}
+
+#[derive(Debug, Deserialize)]
+struct TopTokens {
+    /// 10 by default.
+    limit: Option<usize>,
+}
+
+async fn token_stats(State(api): State<ApiState>, Query(query): Query<TopTokens>) -> Response {
+    ledger_stats(&api, |records| {
+        stats::top_tokens(records, query.limit.unwrap_or(10))
+    })
+}
+
+async fn get_log_filter() -> Response {
+    match logging::filter() {
+        Ok(filter) => Json(json!({ "filter": filter })).into_response(),
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", err)),
+    }
+}
+
+/// Swaps the tracing filter, e.g. to debug one module during a live campaign.
+async fn put_log_filter(Json(request): Json<LogFilter>) -> Response {
+    if let Err(err) = logging::set_filter(&request.filter) {
+        return error(StatusCode::BAD_REQUEST, format!("{:#}", err));
+    }
+    info!("Log filter set to {:?} through the control API", request.filter);
+    Json(json!({ "filter": request.filter })).into_response()
+}
+
+async fn get_params(State(api): State<ApiState>) -> Json<Overrides> {
+    Json(api.controls.overrides())
+}
+
+/// Replaces the overrides; fields left out or null go back to the configured value.
+async fn put_params(State(api): State<ApiState>, Json(overrides): Json<Overrides>) -> Response {
+    if let Err(err) = api.controls.set_overrides(overrides.clone(), "api") {
+        return error(StatusCode::BAD_REQUEST, format!("{:#}", err));
+    }
+    info!("Runtime overrides set through the control API: {:?}", overrides);
+    Json(overrides).into_response()
+}
+
+/// The tunable settings with their units, guardrails and current overrides.
+async fn params_schema(State(api): State<ApiState>) -> Json<Vec<ParamSpec>> {
+    Json(api.controls.schema())
+}
+
+async fn params_history(State(api): State<ApiState>) -> Json<Vec<ParamChange>> {
+    Json(api.controls.history())
+}
+
+async fn get_watchlist(State(api): State<ApiState>) -> Json<Vec<Address>> {
+    let list = api.controls.watchlist.read().map(|list| list.iter().copied().collect());
+    Json(list.unwrap_or_default())
+}
+
+#[derive(Deserialize)]
+struct WatchRequest {
+    token: Address,
+}
+
+async fn add_watch(State(api): State<ApiState>, Json(request): Json<WatchRequest>) -> Response {
+    match api.controls.watch(request.token) {
+        Ok(true) => {
+            info!("{:?} added to the watchlist through the control API", request.token);
+            (StatusCode::CREATED, Json(json!({ "token": request.token }))).into_response()
+        }
+        Ok(false) => error(StatusCode::CONFLICT, format!("{:?} is already watched", request.token)),
+        Err(err) => error(StatusCode::SERVICE_UNAVAILABLE, format!("{:#}", err)),
+    }
+}
+
+/// Takes a signal from a peer or provider, authenticated by its bearer token and an
+/// HMAC-SHA256 of the body under its `inbound_secret` in `X-Signature`.
+async fn receive_signal(State(api): State<ApiState>, headers: HeaderMap, body: Bytes) -> Response {
+    let Some(signals) = &api.signals else {
+        return error(StatusCode::NOT_FOUND, "signal sharing is off");
+    };
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let (peer, signal) = match signals.admit(bearer, signature, &body) {
+        Ok(admitted) => admitted,
+        Err(Rejection::Unauthorized) => return StatusCode::UNAUTHORIZED.into_response(),
+        Err(Rejection::RateLimited) => {
+            return error(StatusCode::TOO_MANY_REQUESTS, "signal rate limit reached")
+        }
+        Err(Rejection::Replayed) => return error(StatusCode::CONFLICT, "signal already received"),
+        Err(Rejection::Disabled(reason)) => {
+            return error(StatusCode::FORBIDDEN, format!("provider disabled: {}", reason))
+        }
+        Err(Rejection::Invalid(message)) => return error(StatusCode::BAD_REQUEST, message),
+    };
+    let token = signal.token;
+    match signals.receive(peer, signal) {
+        Ok(true) => match api.controls.watch(token) {
+            Ok(true) => info!("Trading {:?} on trusted peer signals", token),
+            Ok(false) => {}
+            Err(err) => info!("Trusted peer signals for {:?} not traded: {:#}", token, err),
+        },
+        Ok(false) => {}
+        Err(err) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", err)),
+    }
+    StatusCode::ACCEPTED.into_response()
+}
+
+/// Each signal sender's record of backed trades, and whether it was disabled.
+async fn signal_providers(State(api): State<ApiState>) -> Response {
+    match &api.signals {
+        Some(signals) => Json::<Vec<ProviderReport>>(signals.providers()).into_response(),
+        None => error(StatusCode::NOT_FOUND, "signal sharing is off"),
+    }
+}
+
+/// Takes signals from a disabled provider again, starting its record over.
+async fn enable_provider(State(api): State<ApiState>, Path(name): Path<String>) -> Response {
+    let Some(signals) = &api.signals else {
+        return error(StatusCode::NOT_FOUND, "signal sharing is off");
+    };
+    match signals.enable(&name) {
+        Ok(true) => {
+            info!("Signal provider {} re-enabled through the control API", name);
+            Json(json!({ "name": name, "enabled": true })).into_response()
+        }
+        Ok(false) => error(StatusCode::NOT_FOUND, format!("no provider named {}", name)),
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", err)),
+    }
+}
+
+/// Stops the token from being bought; an open position is left to its exit rules.
+async fn remove_watch(State(api): State<ApiState>, Path(token): Path<Address>) -> Response {
+    if !api.controls.unwatch(token) {
+        return error(StatusCode::NOT_FOUND, format!("{:?} is not watched", token));
+    }
+    info!("{:?} removed from the watchlist through the control API", token);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_reject_full_slippage() {
+        let controls = Controls::new([]);
+        let overrides = |bps| Overrides {
+            slippage_bps: Some(bps),
+            ..Overrides::default()
+        };
+        controls.set_overrides(overrides(9_999), "test").unwrap();
+        assert!(controls.set_overrides(overrides(10_000), "test").is_err());
+        assert_eq!(controls.slippage_bps(100), 9_999);
+    }
+
+    #[test]
+    fn guardrails_bound_the_overrides_and_changes_are_kept() {
+        let limits: ParamLimits = "slippage_bps=10..500, amount_in_mon=0.01..1".parse().unwrap();
+        let controls = Controls::new([]).with_tuning(limits, None).unwrap();
+        let wide = Overrides {
+            slippage_bps: Some(800),
+            ..Overrides::default()
+        };
+        assert!(controls.set_overrides(wide, "test").is_err());
+        let large = Overrides {
+            amount_in_mon: Some("2".into()),
+            ..Overrides::default()
+        };
+        assert!(controls.set_overrides(large, "test").is_err());
+
+        let tuned = Overrides {
+            amount_in_mon: Some("0.5".into()),
+            slippage_bps: Some(200),
+            ..Overrides::default()
+        };
+        controls.set_overrides(tuned.clone(), "test").unwrap();
+        assert_eq!(controls.amount_in(U256::one()), U256::exp10(17) * 5);
+        let history = controls.history();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].before, Overrides::default());
+        assert_eq!(history[0].after, tuned);
+
+        let schema = controls.schema();
+        assert_eq!(schema[1].name, "slippage_bps");
+        assert_eq!(
+            (schema[1].min, schema[1].max, schema[1].value),
+            (Some(10.0), Some(500.0), Some(200.0))
+        );
+    }
+
+    #[test]
+    fn limits_reject_unknown_settings_and_bad_ranges() {
+        assert!("gas=1..2".parse::<ParamLimits>().is_err());
+        assert!("stop_loss_pct=30..2".parse::<ParamLimits>().is_err());
+        assert!("stop_loss_pct=2".parse::<ParamLimits>().is_err());
+        assert!("".parse::<ParamLimits>().unwrap().0.is_empty());
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::HashSet;
+use std::env;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::providers::{Http, Middleware, Provider, Ws};
+use ethers::types::{Address, Transaction, TransactionReceipt, H256, U256};
+use ethers::utils::keccak256;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::chain;
+use crate::engine::{ExecutionClient, RpcClient};
+use crate::notify::Event;
+use crate::app::AppConfig;
+use crate::trading::{round_trip, EntryHints};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Where alpha wallet buys are picked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopySource {
+    /// Mined blocks; the token is read off the Transfer logs of the buy.
+    Confirmed,
+    /// The WS pending-transaction feed; the token is taken from the calldata and
+    /// confirmed with a quote, so the copy can land in the same block.
+    Pending,
+}
+
+impl FromStr for CopySource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "confirmed" => Ok(Self::Confirmed),
+            "pending" => Ok(Self::Pending),
+            other => Err(anyhow!("unknown COPY_SOURCE {:?}; expected confirmed or pending", other)),
+        }
+    }
+}
+
+pub struct CopyConfig {
+    pub wallets: Vec<Address>,
+    /// Only transactions sent to these routers count as buys; any destination if empty.
+    pub routers: Vec<Address>,
+    /// Share of the alpha wallet's buy to mirror.
+    pub ratio: f64,
+    pub max_per_trade: U256,
+    pub max_concurrent: usize,
+    pub source: CopySource,
+}
+
+impl CopyConfig {
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            wallets: address_list("COPY_WALLETS")?,
+            routers: address_list("COPY_ROUTERS")?,
+            ratio: env::var("COPY_RATIO")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0.1),
+            max_per_trade: chain::profile()
+                .parse_native(&env::var("COPY_MAX_MON").unwrap_or_else(|_| "0.5".into()))
+                .context("invalid COPY_MAX_MON")?,
+            max_concurrent: env::var("MAX_CONCURRENT_COPIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            source: env::var("COPY_SOURCE")
+                .ok()
+                .map(|v| v.parse())
+                .transpose()?
+                .unwrap_or(CopySource::Confirmed),
+        })
+    }
+
+    /// `ratio` of the alpha buy, capped at `max_per_trade`.
+    fn size(&self, alpha_value: U256) -> U256 {
+        let scaled = U256::from((alpha_value.as_u128() as f64 * self.ratio) as u128);
+        scaled.min(self.max_per_trade)
+    }
+}
+
+fn address_list(var: &str) -> Result<Vec<Address>> {
+    env::var(var)
+        .ok()
+        .map(|list| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse().with_context(|| format!("invalid address in {}", var)))
+                .collect::<Result<Vec<Address>>>()
+        })
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// A buy seen from one of the followed wallets, with the addresses that may be its token.
+#[derive(Debug, Clone)]
+struct AlphaBuy {
+    wallet: Address,
+    tx: H256,
+    value: U256,
+    candidates: Vec<Address>,
+}
+
+/// Follows the `COPY_WALLETS` addresses and runs a sized-down round trip for every
+/// nad.fun buy they make, exiting on the token's normal exit rules.
+pub async fn run(
+    cfg: &AppConfig,
+    copy: &CopyConfig,
+    client: &RpcClient,
+) -> Result<()> {
+    if copy.wallets.is_empty() {
+        return Err(anyhow!("COPY_WALLETS is required for copy mode"));
+    }
+
+    let (buy_tx, mut buys) = mpsc::unbounded_channel();
+    match copy.source {
+        CopySource::Confirmed => {
+            tokio::spawn(watch_blocks(
+                client.provider().clone(),
+                copy.wallets.clone(),
+                copy.routers.clone(),
+                buy_tx,
+            ));
+        }
+        CopySource::Pending => {
+            let ws_url = cfg
+                .sniper
+                .ws_url
+                .clone()
+                .ok_or_else(|| anyhow!("WS_URL is required for COPY_SOURCE=pending"))?;
+            tokio::spawn(watch_mempool(
+                ws_url,
+                copy.wallets.clone(),
+                copy.routers.clone(),
+                buy_tx,
+            ));
+        }
+    }
+    info!("Copying buys from {} wallets", copy.wallets.len());
+
+    let mut copying = HashSet::new();
+    let mut in_flight = FuturesUnordered::new();
+    loop {
+        if cfg.shutdown.requested() && in_flight.is_empty() {
+            return Ok(());
+        }
+        tokio::select! {
+            buy = buys.recv(), if !cfg.shutdown.requested() => {
+                let buy: AlphaBuy = buy.ok_or_else(|| anyhow!("copy watcher stopped"))?;
+                let Some(token) = resolve_token(client, &buy).await else {
+                    info!("Skipping {:?} from {:?}: no tradable token found", buy.tx, buy.wallet);
+                    continue;
+                };
+                if copying.contains(&token) {
+                    info!("Skipping {:?}: already copying it", token);
+                    continue;
+                }
+                if in_flight.len() >= copy.max_concurrent {
+                    info!("Skipping {:?}: {} copies already in flight", token, in_flight.len());
+                    continue;
+                }
+                let amount = copy.size(buy.value);
+                if amount.is_zero() {
+                    continue;
+                }
+
+                let profile = chain::profile();
+                info!(
+                    "{:?} bought {} with {} in {}; copying with {}",
+                    buy.wallet,
+                    profile.address_url(token),
+                    profile.format_native(buy.value),
+                    profile.tx_url(buy.tx),
+                    profile.format_native(amount)
+                );
+                cfg.notifier.send(
+                    Event::Copy,
+                    format!(
+                        "Copying {:?} buying {}: {} in",
+                        buy.wallet,
+                        profile.address_url(token),
+                        profile.format_native(amount)
+                    ),
+                );
+                copying.insert(token);
+                in_flight.push(mirror(cfg, client, token, amount));
+            }
+            Some(token) = in_flight.next(), if !in_flight.is_empty() => {
+                copying.remove(&token);
+            }
+            _ = cfg.shutdown.wait(), if !cfg.shutdown.requested() => {}
+        }
+    }
+}
+
+async fn mirror(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    token: Address,
+    amount: U256,
+) -> Address {
+    let hints = EntryHints {
+        amount_in: Some(amount),
+        strategy: Some("copy"),
+        ..EntryHints::default()
+    };
+    if let Err(err) = round_trip(cfg, cfg.params_for(token), client, token, hints).await {
+        warn!("Copy of {:?} failed: {:#}", token, err);
+        cfg.notifier.send(Event::Error, format!("Copy of {:?} failed: {:#}", token, err));
+    }
+    token
+}
+
+/// The first candidate the SDK can quote a buy for.
+async fn resolve_token(client: &impl ExecutionClient, buy: &AlphaBuy) -> Option<Address> {
+    for &candidate in &buy.candidates {
+        if client.quote(candidate, buy.value, true).await.is_ok() {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+fn is_alpha_buy(tx: &Transaction, wallets: &[Address], routers: &[Address]) -> bool {
+    wallets.contains(&tx.from)
+        && !tx.value.is_zero()
+        && tx
+            .to
+            .is_some_and(|to| routers.is_empty() || routers.contains(&to))
+}
+
+/// Tokens transferred to `wallet` in the transaction.
+fn received_tokens(receipt: &TransactionReceipt, wallet: Address) -> Vec<Address> {
+    let transfer = H256::from(keccak256("Transfer(address,address,uint256)"));
+    let mut tokens = Vec::new();
+    for log in &receipt.logs {
+        if log.topics.len() == 3
+            && log.topics[0] == transfer
+            && Address::from(log.topics[2]) == wallet
+            && !tokens.contains(&log.address)
+        {
+            tokens.push(log.address);
+        }
+    }
+    tokens
+}
+
+/// Address-shaped arguments in the calldata, in order, other than the sender.
+fn calldata_addresses(tx: &Transaction) -> Vec<Address> {
+    let Some(args) = tx.input.get(4..) else {
+        return Vec::new();
+    };
+    let mut addresses = Vec::new();
+    for word in args.chunks_exact(32) {
+        if word[..12].iter().any(|b| *b != 0) || word[12..].iter().all(|b| *b == 0) {
+            continue;
+        }
+        let address = Address::from_slice(&word[12..]);
+        if address != tx.from && !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+    addresses
+}
+
+/// Scans each new block for successful buys by the followed wallets.
+async fn watch_blocks(
+    provider: Provider<Http>,
+    wallets: Vec<Address>,
+    routers: Vec<Address>,
+    buys: mpsc::UnboundedSender<AlphaBuy>,
+) {
+    let mut next = None;
+    while !buys.is_closed() {
+        if let Err(err) = scan_blocks(&provider, &mut next, &wallets, &routers, &buys).await {
+            warn!("Copy block scan failed: {:#}", err);
+        }
+        tokio::time::sleep(chain::profile().block_poll_interval()).await;
+    }
+}
+
+async fn scan_blocks(
+    provider: &Provider<Http>,
+    next: &mut Option<u64>,
+    wallets: &[Address],
+    routers: &[Address],
+    buys: &mpsc::UnboundedSender<AlphaBuy>,
+) -> Result<()> {
+    let head = provider.get_block_number().await?.as_u64();
+    for number in next.unwrap_or(head)..=head {
+        let block = provider
+            .get_block_with_txs(number)
+            .await?
+            .ok_or_else(|| anyhow!("block {} not found", number))?;
+        for tx in &block.transactions {
+            if !is_alpha_buy(tx, wallets, routers) {
+                continue;
+            }
+            let Some(receipt) = provider.get_transaction_receipt(tx.hash).await? else {
+                continue;
+            };
+            if receipt.status != Some(1u64.into()) {
+                continue;
+            }
+            let candidates = received_tokens(&receipt, tx.from);
+            if candidates.is_empty() {
+                continue;
+            }
+            let _ = buys.send(AlphaBuy {
+                wallet: tx.from,
+                tx: tx.hash,
+                value: tx.value,
+                candidates,
+            });
+        }
+        *next = Some(number + 1);
+    }
+    Ok(())
+}
+
+/// Watches pending transactions for buys by the followed wallets, reconnecting
+/// whenever the socket drops.
+async fn watch_mempool(
+    ws_url: String,
+    wallets: Vec<Address>,
+    routers: Vec<Address>,
+    buys: mpsc::UnboundedSender<AlphaBuy>,
+) {
+    loop {
+        match subscribe_pending(&ws_url, &wallets, &routers, &buys).await {
+            Ok(()) => info!("Pending transaction subscription ended, reconnecting"),
+            Err(err) => warn!("Pending transaction subscription failed: {:#}, reconnecting", err),
+        }
+        if buys.is_closed() {
+            return;
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn subscribe_pending(
+    ws_url: &str,
+    wallets: &[Address],
+    routers: &[Address],
+    buys: &mpsc::UnboundedSender<AlphaBuy>,
+) -> Result<()> {
+    let ws = Provider::<Ws>::connect(ws_url)
+        .await
+        .context("failed to connect WS_URL")?;
+    let mut stream = ws.subscribe_pending_txs().await?;
+    info!("Watching pending transactions on {}", ws_url);
+
+    while let Some(hash) = stream.next().await {
+        let Ok(Some(tx)) = ws.get_transaction(hash).await else {
+            continue;
+        };
+        if !is_alpha_buy(&tx, wallets, routers) {
+            continue;
+        }
+        let buy = AlphaBuy {
+            wallet: tx.from,
+            tx: tx.hash,
+            value: tx.value,
+            candidates: calldata_addresses(&tx),
+        };
+        if buys.send(buy).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{Bytes, Log};
+
+    fn word(address: Address) -> Vec<u8> {
+        let mut word = vec![0u8; 12];
+        word.extend_from_slice(address.as_bytes());
+        word
+    }
+
+    fn alpha_tx(from: Address, to: Address, value: u64) -> Transaction {
+        Transaction {
+            from,
+            to: Some(to),
+            value: U256::from(value),
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn calldata_yields_address_arguments_except_the_sender() {
+        let sender = Address::repeat_byte(1);
+        let token = Address::repeat_byte(2);
+        let mut input = vec![0xde, 0xad, 0xbe, 0xef];
+        input.extend(word(sender));
+        input.extend(word(token));
+        // An amount with high bytes set and an all-zero word are not addresses.
+        input.extend([0xffu8; 32]);
+        input.extend([0u8; 32]);
+        input.extend(word(token));
+        let tx = Transaction {
+            from: sender,
+            input: Bytes::from(input),
+            ..Transaction::default()
+        };
+        assert_eq!(calldata_addresses(&tx), vec![token]);
+
+        let short = Transaction {
+            input: Bytes::from(vec![0xde, 0xad]),
+            ..Transaction::default()
+        };
+        assert!(calldata_addresses(&short).is_empty());
+    }
+
+    #[test]
+    fn received_tokens_reads_transfers_to_the_wallet() {
+        let wallet = Address::repeat_byte(1);
+        let transfer = H256::from(keccak256("Transfer(address,address,uint256)"));
+        let log = |token: Address, to: Address| Log {
+            address: token,
+            topics: vec![transfer, H256::zero(), H256::from(to)],
+            ..Log::default()
+        };
+        let receipt = TransactionReceipt {
+            logs: vec![
+                log(Address::repeat_byte(2), wallet),
+                log(Address::repeat_byte(3), Address::repeat_byte(9)),
+                log(Address::repeat_byte(2), wallet),
+            ],
+            ..TransactionReceipt::default()
+        };
+        assert_eq!(received_tokens(&receipt, wallet), vec![Address::repeat_byte(2)]);
+    }
+
+    #[test]
+    fn alpha_buys_need_value_and_a_known_router() {
+        let wallet = Address::repeat_byte(1);
+        let router = Address::repeat_byte(5);
+        let wallets = [wallet];
+        assert!(is_alpha_buy(&alpha_tx(wallet, router, 1), &wallets, &[]));
+        assert!(is_alpha_buy(&alpha_tx(wallet, router, 1), &wallets, &[router]));
+        assert!(!is_alpha_buy(&alpha_tx(wallet, router, 1), &wallets, &[wallet]));
+        assert!(!is_alpha_buy(&alpha_tx(wallet, router, 0), &wallets, &[]));
+        assert!(!is_alpha_buy(&alpha_tx(router, router, 1), &wallets, &[]));
+    }
+
+    #[test]
+    fn copies_are_scaled_and_capped() {
+        let copy = CopyConfig {
+            wallets: Vec::new(),
+            routers: Vec::new(),
+            ratio: 0.1,
+            max_per_trade: U256::from(500u64),
+            max_concurrent: 1,
+            source: CopySource::Confirmed,
+        };
+        assert_eq!(copy.size(U256::from(1_000u64)), U256::from(100u64));
+        assert_eq!(copy.size(U256::from(100_000u64)), U256::from(500u64));
+        assert_eq!("Pending".parse::<CopySource>().unwrap(), CopySource::Pending);
+        assert!("mempool".parse::<CopySource>().is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use ethers::contract::abigen;
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, U256};
+use tokio::time::Instant;
+
+abigen!(
+    BondingCurve,
+    r#"[
+        function curves(address token) external view returns (uint256 realMonReserve, uint256 realTokenReserve, uint256 virtualMonReserve, uint256 virtualTokenReserve, uint256 k, uint256 targetTokenAmount, uint256 initVirtualMonReserve, uint256 initVirtualTokenReserve)
+        function isGraduated(address token) external view returns (bool)
+        function config() external view returns (uint256 virtualMonReserve, uint256 virtualTokenReserve, uint256 targetTokenAmount)
+        function feeConfig() external view returns (uint256 deployFeeAmount, uint256 graduateFeeAmount, uint24 protocolFee)
+        event CurveCreate(address indexed creator, address indexed token, address indexed pool, string name, string symbol, string tokenURI, uint256 virtualMon, uint256 virtualToken, uint256 targetTokenAmount)
+        event CurveBuy(address indexed sender, address indexed token, uint256 amountIn, uint256 amountOut)
+        event CurveSell(address indexed sender, address indexed token, uint256 amountIn, uint256 amountOut)
+    ]"#
+);
+
+#[derive(Debug, Clone)]
+pub struct CurveState {
+    pub real_mon_reserve: U256,
+    pub virtual_mon_reserve: U256,
+    pub virtual_token_reserve: U256,
+    pub k: U256,
+    pub target_token_amount: U256,
+    pub init_virtual_mon_reserve: U256,
+    pub init_virtual_token_reserve: U256,
+    pub graduated: bool,
+}
+
+impl CurveState {
+    /// Share of the sellable curve supply already bought, in percent.
+    pub fn progress_pct(&self) -> f64 {
+        if self.graduated {
+            return 100.0;
+        }
+        let total = self
+            .init_virtual_token_reserve
+            .saturating_sub(self.target_token_amount);
+        if total.is_zero() {
+            return 100.0;
+        }
+        let sold = self
+            .init_virtual_token_reserve
+            .saturating_sub(self.virtual_token_reserve)
+            .min(total);
+        (sold * U256::from(10_000u64) / total).as_u64() as f64 / 100.0
+    }
+
+    /// MON that still has to flow into the curve before it graduates.
+    pub fn mon_to_graduation(&self) -> U256 {
+        if self.graduated || self.target_token_amount.is_zero() {
+            return U256::zero();
+        }
+        (self.k / self.target_token_amount).saturating_sub(self.virtual_mon_reserve)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CurveProgress {
+    pub state: CurveState,
+    pub pct: f64,
+    pub mon_to_graduation: U256,
+    pub pct_per_min: Option<f64>,
+}
+
+pub struct CurveTracker {
+    curve: BondingCurve<Provider<Http>>,
+    last: Option<(Address, Instant, f64)>,
+}
+
+impl CurveTracker {
+    pub fn new(rpc_url: &str, curve_address: Address) -> Result<Self> {
+        let provider = Provider::<Http>::try_from(rpc_url).context("invalid RPC_URL")?;
+        Ok(Self {
+            curve: BondingCurve::new(curve_address, Arc::new(provider)),
+            last: None,
+        })
+    }
+
+    pub fn address(&self) -> Address {
+        self.curve.address()
+    }
+
+    pub async fn state(&self, token: Address) -> Result<CurveState> {
+        let (
+            real_mon_reserve,
+            _real_token_reserve,
+            virtual_mon_reserve,
+            virtual_token_reserve,
+            k,
+            target_token_amount,
+            init_virtual_mon_reserve,
+            init_virtual_token_reserve,
+        ) = self
+            .curve
+            .curves(token)
+            .call()
+            .await
+            .context("failed to read curve state")?;
+        let graduated = self
+            .curve
+            .is_graduated(token)
+            .call()
+            .await
+            .context("failed to read graduation status")?;
+
+        Ok(CurveState {
+            real_mon_reserve,
+            virtual_mon_reserve,
+            virtual_token_reserve,
+            k,
+            target_token_amount,
+            init_virtual_mon_reserve,
+            init_virtual_token_reserve,
+            graduated,
+        })
+    }
+
+    /// Reads the curve and derives progress rate from the previous observation of the same token.
+    pub async fn observe(&mut self, token: Address) -> Result<CurveProgress> {
+        let state = self.state(token).await?;
+        let pct = state.progress_pct();
+        let now = Instant::now();
+
+        let pct_per_min = match self.last {
+            Some((last_token, at, last_pct)) if last_token == token => {
+                let minutes = now.duration_since(at).as_secs_f64() / 60.0;
+                (minutes > 0.0).then(|| (pct - last_pct) / minutes)
+            }
+            _ => None,
+        };
+        self.last = Some((token, now, pct));
+
+        Ok(CurveProgress {
+            mon_to_graduation: state.mon_to_graduation(),
+            state,
+            pct,
+            pct_per_min,
+        })
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+
+use crate::chain;
+use crate::export::{self, Columns, LaunchRow, SwapRow};
+use crate::scoring::{Features, FEATURE_NAMES};
+
+#[derive(Debug, Args)]
+pub struct DatasetArgs {
+    /// First block to take launches from.
+    #[arg(long)]
+    pub from_block: u64,
+
+    /// Last block to take launches from; defaults to the newest block whose launches
+    /// have lived out the horizon.
+    #[arg(long)]
+    pub to_block: Option<u64>,
+
+    /// Blocks after the launch that the features are taken at, as the sniper's
+    /// SNIPE_ENTRY_DELAY_BLOCKS.
+    #[arg(long, default_value_t = 0)]
+    pub entry_blocks: u64,
+
+    /// Minutes after the launch that the outcome is labelled at.
+    #[arg(long, default_value_t = 30.0)]
+    pub horizon_mins: f64,
+
+    /// Fall from the peak price within the horizon, in percent, that labels a rug.
+    #[arg(long, default_value_t = 80.0)]
+    pub rug_drop_pct: f64,
+
+    /// Creator score for creators with no labelled launch yet, as CREATOR_NEW_SCORE.
+    #[arg(long, default_value_t = 0.5)]
+    pub new_creator_score: f64,
+
+    /// Output file; Parquet if it ends in `.parquet`, CSV otherwise.
+    #[arg(long, default_value = "features.csv")]
+    pub out: PathBuf,
+
+    /// Blocks per `eth_getLogs` request.
+    #[arg(long, default_value_t = 2_000)]
+    pub chunk_blocks: u64,
+}
+
+/// What became of a launch by the end of the horizon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Outcome {
+    graduated: bool,
+    rugged: bool,
+    /// Curve price change from the entry block to the end of the horizon.
+    price_change_pct: f64,
+}
+
+struct Example {
+    token: Address,
+    creator: Address,
+    block: u64,
+    features: Features,
+    outcome: Outcome,
+}
+
+fn to_f64(value: U256) -> f64 {
+    value.to_string().parse().unwrap_or(0.0)
+}
+
+/// The launch's features at `entry`, as [`Features::collect`] would have read them
+/// then: real MON is approximated by MON bought in less MON sold out.
+fn features_at(launch: &LaunchRow, swaps: &[&SwapRow], entry: u64) -> Features {
+    let mut mon = 0.0f64;
+    let mut buyers = HashSet::new();
+    let mut buys = 0u64;
+    for swap in swaps.iter().filter(|swap| swap.block <= entry) {
+        if swap.buy {
+            mon += chain::profile().native_f64(swap.amount_in);
+            buyers.insert(swap.trader);
+            buys += 1;
+        } else {
+            mon -= chain::profile().native_f64(swap.amount_out);
+        }
+    }
+    Features {
+        liquidity_mon: mon.max(0.0),
+        name_len: launch.name.chars().count() as f64,
+        symbol_len: launch.symbol.chars().count() as f64,
+        buyers: buyers.len() as f64,
+        buys: buys as f64,
+        blocks_since_launch: (entry - launch.block) as f64,
+        ..Features::default()
+    }
+}
+
+/// Replays the curve's virtual reserves from the launch through the horizon: graduated
+/// once the virtual token reserve reaches the target, rugged once the price falls
+/// `rug_drop_pct` from its running peak.
+fn outcome(
+    launch: &LaunchRow,
+    swaps: &[&SwapRow],
+    entry: u64,
+    end: u64,
+    rug_drop_pct: f64,
+) -> Outcome {
+    let mut mon = to_f64(launch.virtual_mon);
+    let mut tokens = to_f64(launch.virtual_token);
+    let target = to_f64(launch.target_token_amount);
+    let price = |mon: f64, tokens: f64| if tokens > 0.0 { mon / tokens } else { 0.0 };
+    let mut entry_price = price(mon, tokens);
+    let mut peak = entry_price;
+    let mut drawdown = 0.0f64;
+    let mut graduated = false;
+    for swap in swaps.iter().filter(|swap| swap.block <= end) {
+        if swap.buy {
+            mon += to_f64(swap.amount_in);
+            tokens -= to_f64(swap.amount_out);
+        } else {
+            tokens += to_f64(swap.amount_in);
+            mon -= to_f64(swap.amount_out);
+        }
+        graduated |= target > 0.0 && tokens <= target;
+        let now = price(mon, tokens);
+        if swap.block <= entry {
+            entry_price = now;
+        }
+        peak = peak.max(now);
+        if peak > 0.0 {
+            drawdown = drawdown.max((peak - now) / peak * 100.0);
+        }
+    }
+    let last = price(mon, tokens);
+    Outcome {
+        graduated,
+        rugged: drawdown >= rug_drop_pct,
+        price_change_pct: if entry_price > 0.0 {
+            (last / entry_price - 1.0) * 100.0
+        } else {
+            0.0
+        },
+    }
+}
+
+/// Labels every launch in the range whose horizon has passed. Creator features count only
+/// the creator's earlier launches in the range, and score only the ones already labelled
+/// when this one launched, so no example sees its own future.
+fn label(
+    launches: &[LaunchRow],
+    swaps: &[SwapRow],
+    args: &DatasetArgs,
+    horizon_blocks: u64,
+    last_launch_block: u64,
+) -> Vec<Example> {
+    let mut by_token: HashMap<Address, Vec<&SwapRow>> = HashMap::new();
+    for swap in swaps {
+        by_token.entry(swap.token).or_default().push(swap);
+    }
+    // Per creator: the block each earlier launch's label became known, and whether it rugged.
+    let mut history: HashMap<Address, Vec<(u64, bool)>> = HashMap::new();
+    let mut examples = Vec::new();
+    for launch in launches
+        .iter()
+        .filter(|launch| launch.block <= last_launch_block)
+    {
+        let swaps = by_token
+            .get(&launch.token)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let entry = launch.block + args.entry_blocks;
+        let end = launch.block + horizon_blocks;
+        let outcome = outcome(launch, swaps, entry, end, args.rug_drop_pct);
+
+        let earlier = history.entry(launch.creator).or_default();
+        let known: Vec<bool> = earlier
+            .iter()
+            .filter(|(labelled_at, _)| *labelled_at <= launch.block)
+            .map(|(_, rugged)| *rugged)
+            .collect();
+        let mut features = features_at(launch, swaps, entry);
+        features.creator_launches = earlier.len() as f64;
+        features.creator_score = if known.is_empty() {
+            args.new_creator_score
+        } else {
+            1.0 - known.iter().filter(|rugged| **rugged).count() as f64 / known.len() as f64
+        };
+        earlier.push((end, outcome.rugged));
+
+        examples.push(Example {
+            token: launch.token,
+            creator: launch.creator,
+            block: launch.block,
+            features,
+            outcome,
+        });
+    }
+    examples
+}
+
+fn write_csv(path: &Path, examples: &[Example]) -> Result<()> {
+    let mut writer = csv::Writer::from_path(path)
+        .with_context(|| format!("failed to create {}", path.display()))?;
+    let mut header = vec!["token", "creator", "block"];
+    header.extend(FEATURE_NAMES);
+    header.extend(["graduated", "rugged", "price_change_pct"]);
+    writer.write_record(&header)?;
+    for example in examples {
+        let mut row = vec![
+            format!("{:?}", example.token),
+            format!("{:?}", example.creator),
+            example.block.to_string(),
+        ];
+        row.extend(example.features.values().iter().map(f64::to_string));
+        row.push(u8::from(example.outcome.graduated).to_string());
+        row.push(u8::from(example.outcome.rugged).to_string());
+        row.push(example.outcome.price_change_pct.to_string());
+        writer.write_record(&row)?;
+    }
+    writer
+        .flush()
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn write_parquet(path: &Path, examples: &[Example]) -> Result<()> {
+    let mut columns = Columns::new("features", examples.len())
+        .text("token", examples.iter().map(|e| format!("{:?}", e.token)))
+        .text(
+            "creator",
+            examples.iter().map(|e| format!("{:?}", e.creator)),
+        )
+        .int("block", examples.iter().map(|e| e.block));
+    for (index, name) in FEATURE_NAMES.iter().enumerate() {
+        columns = columns.float(name, examples.iter().map(|e| e.features.values()[index]));
+    }
+    columns
+        .int(
+            "graduated",
+            examples.iter().map(|e| u64::from(e.outcome.graduated)),
+        )
+        .int(
+            "rugged",
+            examples.iter().map(|e| u64::from(e.outcome.rugged)),
+        )
+        .float(
+            "price_change_pct",
+            examples.iter().map(|e| e.outcome.price_change_pct),
+        )
+        .write(path)
+}
+
+/// Builds a labelled dataset from the curve's logs for training a scorer outside the
+/// bot: each launch's features at entry, in the scorer's feature order, and whether it
+/// graduated or rugged within the horizon and how its price moved.
+pub async fn run(args: &DatasetArgs) -> Result<()> {
+    let (provider, curve) = export::curve_history().await?;
+    let block_secs = chain::profile().block_time.as_secs_f64().max(0.001);
+    let horizon_blocks = ((args.horizon_mins * 60.0 / block_secs) as u64).max(args.entry_blocks);
+    let head = provider.get_block_number().await?.as_u64();
+    let latest = head.saturating_sub(horizon_blocks);
+    let last_launch_block = args.to_block.map_or(latest, |to| to.min(latest));
+    if last_launch_block < args.from_block {
+        println!(
+            "No launch since block {} has lived out the horizon yet",
+            args.from_block
+        );
+        return Ok(());
+    }
+
+    let rows = export::fetch(
+        &provider,
+        curve,
+        args.from_block,
+        last_launch_block + horizon_blocks,
+        args.chunk_blocks.max(1),
+    )
+    .await?;
+    let examples = label(
+        &rows.launches,
+        &rows.swaps,
+        args,
+        horizon_blocks,
+        last_launch_block,
+    );
+    if let Some(dir) = args.out.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    if args.out.extension().is_some_and(|ext| ext == "parquet") {
+        write_parquet(&args.out, &examples)?;
+    } else {
+        write_csv(&args.out, &examples)?;
+    }
+    let rugged = examples.iter().filter(|e| e.outcome.rugged).count();
+    let graduated = examples.iter().filter(|e| e.outcome.graduated).count();
+    println!(
+        "Wrote {} launches ({} graduated, {} rugged) to {}",
+        examples.len(),
+        graduated,
+        rugged,
+        args.out.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::H256;
+
+    fn args() -> DatasetArgs {
+        DatasetArgs {
+            from_block: 0,
+            to_block: None,
+            entry_blocks: 1,
+            horizon_mins: 30.0,
+            rug_drop_pct: 80.0,
+            new_creator_score: 0.5,
+            out: PathBuf::from("features.csv"),
+            chunk_blocks: 2_000,
+        }
+    }
+
+    fn launch(token: u8, creator: u8, block: u64) -> LaunchRow {
+        LaunchRow {
+            block,
+            log_index: 0,
+            tx_hash: H256::zero(),
+            token: Address::repeat_byte(token),
+            creator: Address::repeat_byte(creator),
+            pool: Address::zero(),
+            name: "Test".into(),
+            symbol: "TST".into(),
+            virtual_mon: U256::from(100),
+            virtual_token: U256::from(1_000),
+            target_token_amount: U256::from(200),
+        }
+    }
+
+    fn swap(token: u8, block: u64, buy: bool, amount_in: u64, amount_out: u64) -> SwapRow {
+        SwapRow {
+            block,
+            log_index: 0,
+            tx_hash: H256::zero(),
+            token: Address::repeat_byte(token),
+            trader: Address::repeat_byte(block as u8),
+            buy,
+            amount_in: U256::from(amount_in),
+            amount_out: U256::from(amount_out),
+        }
+    }
+
+    #[test]
+    fn buying_the_curve_down_to_its_target_graduates() {
+        let launch = launch(1, 9, 10);
+        let swaps = [swap(1, 11, true, 100, 500), swap(1, 12, true, 300, 400)];
+        let swaps: Vec<&SwapRow> = swaps.iter().collect();
+        let outcome = outcome(&launch, &swaps, 11, 100, 80.0);
+        assert!(outcome.graduated && !outcome.rugged);
+        // 200 MON / 500 tokens at entry, 500 MON / 100 tokens at the end.
+        assert!((outcome.price_change_pct - 1_150.0).abs() < 1e-9);
+
+        let features = features_at(&launch, &swaps, 11);
+        assert_eq!((features.buys, features.buyers, features.blocks_since_launch), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn a_dump_from_the_peak_is_a_rug() {
+        let launch = launch(1, 9, 10);
+        let swaps = [swap(1, 11, true, 100, 500), swap(1, 12, false, 480, 195)];
+        let swaps: Vec<&SwapRow> = swaps.iter().collect();
+        let outcome = outcome(&launch, &swaps, 11, 100, 80.0);
+        assert!(outcome.rugged && !outcome.graduated);
+    }
+
+    #[test]
+    fn creator_scores_use_only_launches_labelled_before_the_next_one() {
+        let launches = [launch(1, 9, 10), launch(2, 9, 50), launch(3, 9, 1_020)];
+        let swaps = [swap(1, 11, true, 100, 500), swap(1, 12, false, 480, 195)];
+        let examples = label(&launches, &swaps, &args(), 1_000, 5_000);
+        let scores: Vec<(f64, f64)> = examples
+            .iter()
+            .map(|e| (e.features.creator_launches, e.features.creator_score))
+            .collect();
+        // The first launch's rug is known from block 1010, the second's outcome from 1050.
+        assert_eq!(scores, [(0.0, 0.5), (1.0, 0.5), (2.0, 0.0)]);
+        assert!(examples[0].outcome.rugged);
+    }
+}
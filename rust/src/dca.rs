@@ -0,0 +1,194 @@
+use std::env;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{TimeZone, Utc};
+use ethers::types::U256;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::chain;
+use crate::config::Target;
+use crate::engine::ExecutionClient;
+use crate::notify::Event;
+use crate::state::unix_now;
+use crate::app::AppConfig;
+use crate::trading::{hold_position, round_trip, EntryHints};
+
+/// When the recurring buys happen.
+pub enum Schedule {
+    /// Every interval from the first buy.
+    Every(Duration),
+    /// On the ticks of a cron expression with a seconds field, e.g. `0 0 */4 * * *`.
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    /// The first buy time at or after `from` of a schedule started at `start`.
+    fn next(&self, start: u64, from: u64) -> Option<u64> {
+        match self {
+            Self::Every(interval) => {
+                let interval = interval.as_secs().max(1);
+                Some(start + from.saturating_sub(start).div_ceil(interval) * interval)
+            }
+            Self::Cron(schedule) => {
+                let from = Utc.timestamp_opt(from as i64 - 1, 0).single()?;
+                schedule.after(&from).next().map(|at| at.timestamp() as u64)
+            }
+        }
+    }
+}
+
+/// Fixed-size buys of one token on a schedule, accumulated into a single position that
+/// the normal exit engine takes over after the last buy.
+pub struct DcaConfig {
+    /// Size of each buy; the token's `amount_in_mon` when unset.
+    pub amount_in: Option<U256>,
+    pub schedule: Schedule,
+    /// How long after the first buy the schedule keeps buying.
+    pub duration: Duration,
+}
+
+impl DcaConfig {
+    pub fn from_env() -> Result<Self> {
+        let schedule = match (env::var("DCA_CRON").ok(), env::var("DCA_INTERVAL_SECS").ok()) {
+            (Some(expr), None) => Schedule::Cron(Box::new(
+                cron::Schedule::from_str(&expr)
+                    .map_err(|err| anyhow!("invalid DCA_CRON {:?}: {}", expr, err))?,
+            )),
+            (None, Some(secs)) => {
+                let secs: u64 = secs.parse().context("invalid DCA_INTERVAL_SECS")?;
+                if secs == 0 {
+                    return Err(anyhow!("DCA_INTERVAL_SECS must be positive"));
+                }
+                Schedule::Every(Duration::from_secs(secs))
+            }
+            (Some(_), Some(_)) => {
+                return Err(anyhow!("set DCA_CRON or DCA_INTERVAL_SECS, not both"))
+            }
+            (None, None) => return Err(anyhow!("DCA mode needs DCA_INTERVAL_SECS or DCA_CRON")),
+        };
+        let duration: u64 = env::var("DCA_DURATION_SECS")
+            .context("DCA mode needs DCA_DURATION_SECS")?
+            .parse()
+            .context("invalid DCA_DURATION_SECS")?;
+        Ok(Self {
+            amount_in: env::var("DCA_AMOUNT_MON")
+                .ok()
+                .map(|v| chain::profile().parse_native(&v).context("invalid DCA_AMOUNT_MON"))
+                .transpose()?,
+            schedule,
+            duration: Duration::from_secs(duration),
+        })
+    }
+}
+
+/// Buys `target` on the schedule, then holds the accumulated position until its exit
+/// rules fire. A restart picks the schedule back up from the position's first buy.
+pub async fn run(
+    cfg: &AppConfig,
+    dca: &DcaConfig,
+    client: &impl ExecutionClient,
+    target: &Target,
+) -> Result<()> {
+    let token = target.token;
+    let wallet = cfg.recipient.unwrap_or_else(|| client.wallet());
+    let open = |cfg: &AppConfig| -> Result<_> {
+        Ok(cfg
+            .state
+            .open_positions()?
+            .into_iter()
+            .find(|position| position.token == token && position.wallet == wallet))
+    };
+    let start = match open(cfg)? {
+        Some(position) if position.accumulating => {
+            info!(
+                "Continuing the DCA schedule into {:?} started {}s ago",
+                token,
+                position.held_for().as_secs()
+            );
+            position.opened_at
+        }
+        Some(_) => {
+            return Err(anyhow!("{:?} already has an open position outside a DCA schedule", token))
+        }
+        None => unix_now(),
+    };
+    let end = start + dca.duration.as_secs();
+    let amount_in = dca.amount_in.unwrap_or(target.params.amount_in);
+    let profile = chain::profile();
+    info!(
+        "DCA into {}: {} per buy until {}",
+        profile.address_url(token),
+        profile.format_native(amount_in),
+        Utc.timestamp_opt(end as i64, 0).single().unwrap_or_default().to_rfc3339()
+    );
+
+    let mut from = unix_now();
+    while let Some(at) = dca.schedule.next(start, from).filter(|at| *at < end) {
+        let wait = Duration::from_secs(at.saturating_sub(unix_now()));
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = cfg.shutdown.wait() => {}
+        }
+        if cfg.shutdown.requested() {
+            if cfg.shutdown.exit_positions {
+                break;
+            }
+            info!("Shutting down; the DCA schedule carries on from here on the next start");
+            return Ok(());
+        }
+
+        let hints = EntryHints {
+            amount_in: Some(amount_in),
+            accumulate: true,
+            strategy: Some("dca"),
+            ..EntryHints::default()
+        };
+        if let Err(err) = round_trip(cfg, &target.params, client, token, hints).await {
+            warn!("DCA buy of {:?} failed, waiting for the next one: {:#}", token, err);
+            cfg.notifier.send(Event::Error, format!("DCA buy of {:?} failed: {:#}", token, err));
+        }
+        // Slots missed while a slow buy was in flight are skipped, not caught up on.
+        from = (at + 1).max(unix_now());
+    }
+
+    let Some(mut position) = open(cfg)? else {
+        if cfg.dry_run {
+            return Ok(());
+        }
+        return Err(anyhow!("the DCA schedule for {:?} ended without a filled buy", token));
+    };
+    position.accumulating = false;
+    cfg.state.open(position.clone())?;
+    info!(
+        "DCA schedule done: {} spent on {:?}, handing the position to the exit rules",
+        profile.format_native(position.amount_in),
+        token
+    );
+    hold_position(cfg, client, &position).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_count_from_the_first_buy() {
+        let every = Schedule::Every(Duration::from_secs(60));
+        assert_eq!(every.next(1_000, 1_000), Some(1_000));
+        assert_eq!(every.next(1_000, 1_001), Some(1_060));
+        assert_eq!(every.next(1_000, 1_060), Some(1_060));
+        // A restart after several missed slots resumes on the next one.
+        assert_eq!(every.next(1_000, 1_250), Some(1_300));
+    }
+
+    #[test]
+    fn cron_ticks_include_the_current_second() {
+        let cron = Schedule::Cron(Box::new(cron::Schedule::from_str("0 0 * * * *").unwrap()));
+        // 2023-11-14T22:00:00Z and the hour after it.
+        assert_eq!(cron.next(0, 1_700_000_000 - 800), Some(1_699_999_200));
+        assert_eq!(cron.next(0, 1_699_999_200), Some(1_699_999_200));
+        assert_eq!(cron.next(0, 1_699_999_201), Some(1_700_002_800));
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use ethers::types::{Address, U256};
+
+use crate::chain;
+use crate::nadfun::Trade;
+
+#[derive(Debug, Args)]
+pub struct DepthArgs {
+    /// Token to quote.
+    pub token: Address,
+
+    /// Buy sizes to quote, in native units.
+    #[arg(long, value_delimiter = ',', default_value = "0.1,0.5,1,5")]
+    pub sizes: Vec<String>,
+}
+
+/// Quotes a buy at each size and the sell of what it would return, printing the effective
+/// price per level and the round-trip cost.
+pub async fn run(trade: &Trade, args: &DepthArgs) -> Result<()> {
+    let profile = chain::profile();
+    let mut best_price = None;
+
+    println!("Depth for {}", profile.address_url(args.token));
+    println!(
+        "{:>12}  {:>22}  {:>16}  {:>9}  {:>12}  {:>10}",
+        "size", "tokens out", "price", "impact", "exit", "round trip"
+    );
+    for size in &args.sizes {
+        let amount_in = profile
+            .parse_native(size)
+            .with_context(|| format!("invalid size {:?}", size))?;
+        let (router, tokens_out) = trade
+            .get_amount_out(args.token, amount_in, true)
+            .await
+            .with_context(|| format!("buy quote for {} failed", size))?;
+        if tokens_out.is_zero() {
+            println!("{:>12}  no liquidity via {:?}", size, router);
+            continue;
+        }
+        let (_, exit) = trade
+            .get_amount_out(args.token, tokens_out, false)
+            .await
+            .with_context(|| format!("sell quote for {} failed", size))?;
+
+        let price = amount_in.as_u128() as f64 / tokens_out.as_u128() as f64;
+        let best = *best_price.get_or_insert(price);
+        let round_trip_bps =
+            amount_in.saturating_sub(exit) * U256::from(10_000u64) / amount_in;
+        println!(
+            "{:>12}  {:>22}  {:>16.10}  {:>8.2}%  {:>12}  {:>7} bps",
+            size,
+            ethers::utils::format_units(tokens_out, 18)?,
+            price,
+            (price / best - 1.0) * 100.0,
+            profile.format_native(exit),
+            round_trip_bps
+        );
+    }
+    Ok(())
+}
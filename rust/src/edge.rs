@@ -0,0 +1,147 @@
+use std::env;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::U256;
+use tracing::info;
+
+use crate::protocol;
+use crate::reputation::ReputationConfig;
+use crate::sniper::Launch;
+
+/// Gas a round trip is costed at: the buy, the sell's approval and the sell.
+const ROUND_TRIP_GAS: u64 = 600_000;
+
+/// The expected edge a snipe needs: the mean return of comparable launches over the rug
+/// window, from the creator index, less the curve fee both ways and the round trip's
+/// gas. Comparable launches are the creator's own once they have
+/// `EDGE_MIN_SAMPLES` evaluated, else the latest `EDGE_RECENT_LAUNCHES` by anyone.
+pub struct EdgeConfig {
+    pub min_edge_pct: f64,
+    min_samples: usize,
+    recent_launches: usize,
+}
+
+impl EdgeConfig {
+    /// Enabled by `SNIPER_MIN_EDGE_PCT`, which sniper mode requires.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(min_edge) = env::var("SNIPER_MIN_EDGE_PCT").ok() else {
+            return Ok(None);
+        };
+        let count = |name: &str, default: usize| -> Result<usize> {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {name}")))
+                .transpose()
+                .map(|count| count.unwrap_or(default).max(1))
+        };
+        Ok(Some(Self {
+            min_edge_pct: min_edge.parse().context("invalid SNIPER_MIN_EDGE_PCT")?,
+            min_samples: count("EDGE_MIN_SAMPLES", 3)?,
+            recent_launches: count("EDGE_RECENT_LAUNCHES", 200)?,
+        }))
+    }
+
+    /// The expected return in percent and what it was taken from, if there is enough
+    /// history for one.
+    fn expected_return(&self, creator: &[f64], recent: &[f64]) -> Option<(f64, &'static str)> {
+        let mean = |returns: &[f64]| returns.iter().sum::<f64>() / returns.len() as f64;
+        if creator.len() >= self.min_samples {
+            Some((mean(creator), "the creator's launches"))
+        } else if recent.len() >= self.min_samples {
+            Some((mean(recent), "recent launches"))
+        } else {
+            None
+        }
+    }
+
+    /// The reason to skip `launch`, if its expected edge on `amount_in` is under
+    /// `min_edge_pct` or can't be estimated.
+    pub async fn screen(
+        &self,
+        provider: &Provider<Http>,
+        reputation: &ReputationConfig,
+        launch: &Launch,
+        amount_in: U256,
+    ) -> Option<String> {
+        let Some((creator, recent)) = reputation.returns(launch, self.recent_launches) else {
+            return Some("launch history unavailable".into());
+        };
+        let Some((expected, basis)) = self.expected_return(&creator, &recent) else {
+            return Some(format!(
+                "fewer than {} evaluated launches to estimate an edge from",
+                self.min_samples
+            ));
+        };
+        let costs = match costs(provider, amount_in).await {
+            Ok(costs) => costs,
+            Err(err) => return Some(format!("round trip costs unavailable: {:#}", err)),
+        };
+        let edge = expected - costs;
+        info!(
+            "{:?} expects {:+.1}% from {}, less {:.1}% costs: edge {:+.1}%",
+            launch.token, expected, basis, costs, edge
+        );
+        (edge < self.min_edge_pct).then(|| {
+            format!(
+                "expected edge {:+.1}% below SNIPER_MIN_EDGE_PCT {:+.1}%",
+                edge, self.min_edge_pct
+            )
+        })
+    }
+}
+
+async fn costs(provider: &Provider<Http>, amount_in: U256) -> Result<f64> {
+    let fee_bps = protocol::current()
+        .map(|params| params.fee_bps())
+        .ok_or_else(|| anyhow!("protocol fee not read yet"))?;
+    let gas_price = provider.get_gas_price().await?;
+    Ok(costs_pct(fee_bps, gas_price, amount_in))
+}
+
+/// Round trip costs as a percentage of `amount_in`: the fee on the buy and the sell, and
+/// [`ROUND_TRIP_GAS`] at `gas_price`.
+fn costs_pct(fee_bps: u64, gas_price: U256, amount_in: U256) -> f64 {
+    let to_f64 = |value: U256| value.to_string().parse::<f64>().unwrap_or(f64::MAX);
+    let gas = to_f64(gas_price * U256::from(ROUND_TRIP_GAS));
+    let gas_pct = if amount_in.is_zero() {
+        f64::INFINITY
+    } else {
+        gas / to_f64(amount_in) * 100.0
+    };
+    2.0 * fee_bps as f64 / 100.0 + gas_pct
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge() -> EdgeConfig {
+        EdgeConfig {
+            min_edge_pct: 5.0,
+            min_samples: 3,
+            recent_launches: 200,
+        }
+    }
+
+    #[test]
+    fn the_creator_history_is_used_once_it_is_deep_enough() {
+        let recent = [10.0, 20.0, 30.0];
+        assert_eq!(edge().expected_return(&[-50.0], &recent), Some((20.0, "recent launches")));
+        let creator = [-10.0, -20.0, -30.0];
+        assert_eq!(
+            edge().expected_return(&creator, &recent),
+            Some((-20.0, "the creator's launches"))
+        );
+        assert_eq!(edge().expected_return(&[], &[1.0, 2.0]), None);
+    }
+
+    #[test]
+    fn costs_are_both_fees_and_the_round_trip_gas() {
+        // 1% each way, and 600k gas at 50 gwei on 1 MON is 3%.
+        let gas_price = U256::from(50_000_000_000u64);
+        let one_mon = U256::exp10(18);
+        assert!((costs_pct(100, gas_price, one_mon) - 5.0).abs() < 1e-9);
+        assert!(costs_pct(100, gas_price, U256::zero()).is_infinite());
+    }
+}
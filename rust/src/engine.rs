@@ -0,0 +1,467 @@
+use std::future::Future;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, BlockNumber, Bytes, TransactionReceipt, H256, U256};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{info_span, warn, Instrument};
+
+use crate::app::AppConfig;
+use crate::chain::{self, ChainProfile};
+use crate::chaos::{ChaosChain, ChaosConfig};
+use crate::cli::Cli;
+use crate::config::TradeParams;
+use crate::control::ExecOrder;
+use crate::latency;
+use crate::gas_strategy::GasStrategy;
+use crate::nadfun::{BuyParams, GasEstimationParams, SellParams, TokenHelper, Trade, TxOptions};
+use crate::receipts;
+use crate::recovery;
+use crate::start_services;
+use crate::state::OpenPosition;
+use crate::trading::{hold_position, resume_positions, round_trip, run_strategy, EntryHints};
+use crate::tx_manager::{RetryPolicy, SignerChain, TimeInForce, TxChain, TxManager};
+use crate::uniswap_v2::UniswapV2;
+use crate::venue::{AnyVenue, Venue, VenueKind, VenueMap};
+
+/// Decides what the engine buys. Each order gets the same round trip as a command-line
+/// entry: safety checks, the risk limits, the buy, the token's exit rules and the sell.
+pub trait Strategy: Send {
+    /// The next order to run, or `None` once the strategy has nothing more to trade. The
+    /// engine drops the future when shutdown is requested first, so it must be cancel safe.
+    fn next_order(&mut self) -> impl Future<Output = Option<ExecOrder>> + Send + '_;
+}
+
+/// Orders sent down a channel, as the control API's `/orders` does in exec mode.
+impl Strategy for mpsc::UnboundedReceiver<ExecOrder> {
+    fn next_order(&mut self) -> impl Future<Output = Option<ExecOrder>> + Send + '_ {
+        self.recv()
+    }
+}
+
+/// The chain calls a round trip makes: quotes, balances, approvals, the buys and sells
+/// themselves and their receipts. [`RpcClient`] sends them over `RPC_URL`; the engine
+/// trades through any other implementation the same way.
+pub trait ExecutionClient: Sync {
+    /// The wallet trades are sent from.
+    fn wallet(&self) -> Address;
+
+    /// Native balance of `owner`, at `block` or the latest one.
+    fn native_balance(
+        &self,
+        owner: Address,
+        block: Option<u64>,
+    ) -> impl Future<Output = Result<U256>> + Send;
+
+    fn token_balance(
+        &self,
+        token: Address,
+        owner: Address,
+    ) -> impl Future<Output = Result<U256>> + Send;
+
+    fn allowance(
+        &self,
+        token: Address,
+        owner: Address,
+        spender: Address,
+    ) -> impl Future<Output = Result<U256>> + Send;
+
+    /// Sends an approval of `amount` to `spender`, returning its transaction.
+    fn approve(
+        &self,
+        token: Address,
+        spender: Address,
+        amount: U256,
+    ) -> impl Future<Output = Result<H256>> + Send;
+
+    fn gas_price(&self) -> impl Future<Output = Result<U256>> + Send;
+
+    fn block_number(&self) -> impl Future<Output = Result<u64>> + Send;
+
+    fn code(&self, address: Address) -> impl Future<Output = Result<Bytes>> + Send;
+
+    /// The router a trade of `amount` would go through and what it would return.
+    fn quote(
+        &self,
+        token: Address,
+        amount: U256,
+        is_buy: bool,
+    ) -> impl Future<Output = Result<(Address, U256)>> + Send;
+
+    fn estimate_gas(
+        &self,
+        router: Address,
+        params: GasEstimationParams,
+    ) -> impl Future<Output = Result<U256>> + Send;
+
+    /// Waits for `tx_hash` to be mined.
+    fn receipt(&self, tx_hash: H256) -> impl Future<Output = Result<TransactionReceipt>> + Send;
+
+    /// Sends a buy through `router` and returns its receipt once it is mined and final,
+    /// retrying or cancelling it as `tif` allows. [`TxManager`] does that around a send.
+    fn buy(
+        &self,
+        router: Address,
+        params: BuyParams,
+        tif: TimeInForce,
+    ) -> impl Future<Output = Result<TransactionReceipt>> + Send;
+
+    /// Like [`buy`](Self::buy), for a sell.
+    fn sell(
+        &self,
+        router: Address,
+        params: SellParams,
+        tif: TimeInForce,
+    ) -> impl Future<Output = Result<TransactionReceipt>> + Send;
+}
+
+/// The bot's trading machinery, configured from the environment and config file like the
+/// command line, for embedding it in another program. Trades go through `C`.
+pub struct Engine<C = RpcClient> {
+    cfg: AppConfig,
+    client: C,
+}
+
+impl Engine {
+    /// Loads the env files, chain profile and trading config `cli` points at, and connects
+    /// to `RPC_URL` with the configured wallet. Logging is left to the caller.
+    pub async fn connect(cli: &Cli) -> Result<Self> {
+        let cfg = load_config(cli)?;
+        let client = RpcClient::open(&cfg).await?;
+        Ok(Self { cfg, client })
+    }
+}
+
+impl<C: ExecutionClient> Engine<C> {
+    /// Loads the config like [`Engine::connect`], but trades through `client`.
+    pub fn with_client(cli: &Cli, client: C) -> Result<Self> {
+        Ok(Self {
+            cfg: load_config(cli)?,
+            client,
+        })
+    }
+
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    /// Starts the background tasks the configuration enables, such as the protocol watch,
+    /// price feed and control API. Call once, before trading.
+    pub async fn start(&self) -> Result<()> {
+        start_services(&self.cfg).await
+    }
+
+    /// The trade params of `token`: its config file profile, or the defaults.
+    pub fn params_for(&self, token: Address) -> &TradeParams {
+        self.cfg.params_for(token)
+    }
+
+    /// Buys `order.token`, holds it under its exit rules and sells it.
+    pub async fn round_trip(&self, order: ExecOrder) -> Result<()> {
+        let hints = EntryHints {
+            amount_in: order.amount_in,
+            slippage_bps: order.slippage_bps,
+            strategy: Some("exec"),
+            ..EntryHints::default()
+        };
+        let params = self.cfg.params_for(order.token);
+        round_trip(&self.cfg, params, &self.client, order.token, hints).await
+    }
+
+    /// Runs every order `strategy` places until it runs out of orders or `stop` is called,
+    /// then waits for the trades in flight to finish.
+    pub async fn run(&self, strategy: &mut impl Strategy) -> Result<()> {
+        run_strategy(&self.cfg, &self.client, strategy).await
+    }
+
+    /// Open positions, and the exits of the ones being held.
+    pub fn positions(&self) -> PositionManager<'_, C> {
+        PositionManager {
+            cfg: &self.cfg,
+            client: &self.client,
+        }
+    }
+
+    /// Stops taking new entries; positions already held still see their exit through.
+    pub fn stop(&self) {
+        self.cfg.shutdown.request();
+    }
+}
+
+fn load_config(cli: &Cli) -> Result<AppConfig> {
+    cli.load_env()?;
+    chain::init(ChainProfile::from_env()?);
+    AppConfig::load(cli)
+}
+
+/// Extra gas over a trade's estimate, in percent.
+const GAS_LIMIT_HEADROOM_PCT: u64 = 120;
+
+/// Sends trades over `RPC_URL` from the configured wallet, through each token's venue and
+/// the transaction manager's retries, priced by `GAS_STRATEGY`.
+pub struct RpcClient {
+    provider: Provider<Http>,
+    trade: Trade,
+    uniswap_v2: Option<UniswapV2>,
+    venues: VenueMap,
+    token_helper: TokenHelper,
+    signer: SignerChain,
+    retry_policy: RetryPolicy,
+    gas_strategy: GasStrategy,
+    chaos: Option<ChaosConfig>,
+}
+
+impl RpcClient {
+    /// Connects and checks that the RPC serves the chain the active profile expects.
+    pub(crate) async fn open(cfg: &AppConfig) -> Result<Self> {
+        let provider =
+            Provider::<Http>::try_from(cfg.rpc_url.as_str()).context("invalid RPC_URL")?;
+        let trade = Trade::new(cfg.rpc_url.clone(), cfg.private_key.clone())
+            .await
+            .context("failed to initialize Trade client")?;
+        let token_helper = TokenHelper::new(cfg.rpc_url.clone(), cfg.private_key.clone())
+            .await
+            .context("failed to initialize token helper")?;
+
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let profile = chain::profile();
+        if chain_id != profile.chain_id {
+            return Err(anyhow!(
+                "RPC_URL serves chain {} but the {} profile expects {}",
+                chain_id,
+                profile.name,
+                profile.chain_id
+            ));
+        }
+        let signer = SignerChain::new(&provider, &cfg.private_key)?;
+        let venues = VenueMap::new(
+            cfg.defaults.venue,
+            cfg.targets.iter().map(|target| (target.token, target.params.venue)),
+        );
+        let uniswap_v2 = UniswapV2::from_env(&provider, &cfg.private_key).await?;
+        if venues.uses(VenueKind::UniswapV2) && uniswap_v2.is_none() {
+            return Err(anyhow!("the uniswap_v2 venue needs UNISWAP_V2_ROUTER"));
+        }
+        if let Some(chaos) = &cfg.chaos {
+            warn!(
+                "Chaos mode is on: injecting {}% RPC timeouts, {}% reverts and {}% reorgs",
+                chaos.timeout_pct, chaos.revert_pct, chaos.reorg_pct
+            );
+        }
+        Ok(Self {
+            provider,
+            trade,
+            uniswap_v2,
+            venues,
+            token_helper,
+            signer,
+            retry_policy: cfg.retry_policy.clone(),
+            gas_strategy: cfg.gas_strategy,
+            chaos: cfg.chaos.clone(),
+        })
+    }
+
+    pub fn provider(&self) -> &Provider<Http> {
+        &self.provider
+    }
+
+    pub fn trade(&self) -> &Trade {
+        &self.trade
+    }
+
+    /// Sends `value` of native from the wallet to `to` through the transaction manager,
+    /// so it takes its nonce in turn with the trades in flight.
+    pub async fn transfer(&self, to: Address, value: U256) -> Result<TransactionReceipt> {
+        let txs = TxManager::new(&self.signer, &self.retry_policy);
+        txs.submit("transfer", TimeInForce::Retry, |nonce| async move {
+            let gas_price = self.signer.gas_price().await?;
+            self.signer.send_value(to, value, nonce, gas_price).await
+        })
+        .await
+    }
+
+    /// The venue `token` trades on.
+    fn venue(&self, token: Address) -> Result<AnyVenue<'_>> {
+        match self.venues.get(token) {
+            VenueKind::NadFun => Ok(AnyVenue::NadFun(&self.trade)),
+            VenueKind::UniswapV2 => self
+                .uniswap_v2
+                .as_ref()
+                .map(AnyVenue::UniswapV2)
+                .ok_or_else(|| anyhow!("the uniswap_v2 venue needs UNISWAP_V2_ROUTER")),
+        }
+    }
+
+    /// The gas limit a trade is sent with: its estimate, with headroom for the price
+    /// moving before it is mined. A revert the estimate runs into fails it here.
+    async fn gas_limit(
+        &self,
+        venue: &AnyVenue<'_>,
+        router: Address,
+        params: GasEstimationParams,
+    ) -> Result<U256> {
+        let estimate = venue.estimate_gas(router, params).await?;
+        Ok(estimate * GAS_LIMIT_HEADROOM_PCT / 100)
+    }
+}
+
+impl ExecutionClient for RpcClient {
+    fn wallet(&self) -> Address {
+        self.trade.wallet_address()
+    }
+
+    async fn native_balance(&self, owner: Address, block: Option<u64>) -> Result<U256> {
+        let block = block.map(|block| BlockNumber::Number(block.into()).into());
+        Ok(self.provider.get_balance(owner, block).await?)
+    }
+
+    async fn token_balance(&self, token: Address, owner: Address) -> Result<U256> {
+        self.token_helper.balance_of(token, owner).await
+    }
+
+    async fn allowance(&self, token: Address, owner: Address, spender: Address) -> Result<U256> {
+        self.token_helper.allowance(token, owner, spender).await
+    }
+
+    async fn approve(&self, token: Address, spender: Address, amount: U256) -> Result<H256> {
+        Ok(self.token_helper.approve(token, spender, amount).await?.tx_hash)
+    }
+
+    async fn gas_price(&self) -> Result<U256> {
+        Ok(self.provider.get_gas_price().await?)
+    }
+
+    async fn block_number(&self) -> Result<u64> {
+        Ok(self.provider.get_block_number().await?.as_u64())
+    }
+
+    async fn code(&self, address: Address) -> Result<Bytes> {
+        Ok(self.provider.get_code(address, None).await?)
+    }
+
+    async fn quote(&self, token: Address, amount: U256, is_buy: bool) -> Result<(Address, U256)> {
+        if let Some(chaos) = &self.chaos {
+            chaos.rpc("quote")?;
+        }
+        self.venue(token)?.quote(token, amount, is_buy).await
+    }
+
+    async fn estimate_gas(&self, router: Address, params: GasEstimationParams) -> Result<U256> {
+        let token = match &params {
+            GasEstimationParams::Buy { token, .. } | GasEstimationParams::Sell { token, .. } => {
+                *token
+            }
+        };
+        self.venue(token)?.estimate_gas(router, params).await
+    }
+
+    async fn receipt(&self, tx_hash: H256) -> Result<TransactionReceipt> {
+        receipts::wait_for_receipt(&self.provider, tx_hash).await
+    }
+
+    async fn buy(
+        &self,
+        router: Address,
+        params: BuyParams,
+        tif: TimeInForce,
+    ) -> Result<TransactionReceipt> {
+        let estimate = GasEstimationParams::Buy {
+            token: params.token,
+            amount_in: params.amount_in,
+            amount_out_min: params.amount_out_min,
+            to: params.recipient,
+            deadline: params.deadline,
+        };
+        let venue = &self.venue(params.token)?;
+        let gas_limit =
+            self.gas_limit(venue, router, estimate).await.context("buy gas estimate failed")?;
+        let chain = ChaosChain::new(&self.signer, self.chaos.as_ref());
+        let txs = TxManager::new(&chain, &self.retry_policy);
+        let (chain, params) = (&chain, &params);
+        txs.submit("buy", tif, |nonce| async move {
+            let fees = self.gas_strategy.fees(&self.provider).await?;
+            let tx = TxOptions {
+                nonce,
+                gas_limit,
+                fees,
+            };
+            chain.broadcast("buy")?;
+            let send_started = Instant::now();
+            let tx_hash = venue
+                .buy(router, params.clone(), tx)
+                .instrument(info_span!("broadcast"))
+                .await
+                .context("buy transaction failed")?;
+            latency::record("submit", send_started.elapsed());
+            Ok(tx_hash)
+        })
+        .await
+    }
+
+    async fn sell(
+        &self,
+        router: Address,
+        params: SellParams,
+        tif: TimeInForce,
+    ) -> Result<TransactionReceipt> {
+        let estimate = GasEstimationParams::Sell {
+            token: params.token,
+            amount_in: params.amount_in,
+            amount_out_min: params.amount_out_min,
+            to: params.recipient,
+            deadline: params.deadline,
+        };
+        let venue = &self.venue(params.token)?;
+        let gas_limit =
+            self.gas_limit(venue, router, estimate).await.context("sell gas estimate failed")?;
+        let chain = ChaosChain::new(&self.signer, self.chaos.as_ref());
+        let txs = TxManager::new(&chain, &self.retry_policy);
+        let (chain, params) = (&chain, &params);
+        txs.submit("sell", tif, |nonce| async move {
+            let fees = self.gas_strategy.fees(&self.provider).await?;
+            let tx = TxOptions {
+                nonce,
+                gas_limit,
+                fees,
+            };
+            chain.broadcast("sell")?;
+            venue
+                .sell(router, params.clone(), tx)
+                .instrument(info_span!("broadcast"))
+                .await
+                .context("sell transaction failed")
+        })
+        .await
+    }
+}
+
+/// The positions in the state file, shared with every process pointed at it.
+pub struct PositionManager<'a, C> {
+    cfg: &'a AppConfig,
+    client: &'a C,
+}
+
+impl<C: ExecutionClient> PositionManager<'_, C> {
+    pub fn open(&self) -> Result<Vec<OpenPosition>> {
+        self.cfg.state.open_positions()
+    }
+
+    /// Holds `position` under its token's exit rules until it is sold.
+    pub async fn hold(&self, position: &OpenPosition) -> Result<()> {
+        hold_position(self.cfg, self.client, position).await
+    }
+
+    /// Sells the position in `token` at its next exit check.
+    pub fn sell(&self, token: Address) {
+        self.cfg.controls.force_sell(token);
+    }
+
+    /// Reconciles the state file with the wallet under `RECOVERY`, then holds the
+    /// positions a previous run left open until they are sold.
+    pub async fn resume(&self) -> Result<()> {
+        let wallet = self.cfg.recipient.unwrap_or_else(|| self.client.wallet());
+        recovery::reconcile(self.cfg, self.client, wallet).await?;
+        resume_positions(self.cfg, self.client).await
+    }
+}
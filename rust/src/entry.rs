@@ -0,0 +1,116 @@
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, U256};
+use tracing::{info, warn};
+
+use crate::trading::apply_slippage;
+use crate::chain;
+use crate::engine::ExecutionClient;
+use crate::nadfun::GasEstimationParams;
+use crate::warmer;
+
+const PROBE_DIVISOR: u64 = 10;
+
+pub struct EntryRequest {
+    pub token: Address,
+    pub amount_in: U256,
+    pub recipient: Address,
+    pub deadline: U256,
+    pub slippage_bps: u64,
+}
+
+pub struct Entry {
+    pub router: Address,
+    pub quoted_out: U256,
+    pub amount_out_min: U256,
+    pub buy_gas: U256,
+}
+
+/// Re-quotes and simulates the buy once per block until it would succeed,
+/// so launch-block anti-bot restrictions don't cost a reverted transaction.
+/// A fresh warm quote for the same size is used as is.
+pub async fn first_allowed_entry(
+    client: &impl ExecutionClient,
+    req: &EntryRequest,
+    max_wait_blocks: u64,
+) -> Result<Entry> {
+    if let Some(entry) = warmer::entry(req.token, req.amount_in, req.slippage_bps) {
+        return Ok(entry);
+    }
+    let start_block = client.block_number().await?;
+    let mut block = start_block;
+
+    loop {
+        let last_error = match simulate_buy(client, req, req.amount_in).await {
+            Ok(entry) => {
+                if block > start_block {
+                    info!(
+                        "Buy allowed at block {} after waiting {} blocks",
+                        block,
+                        block - start_block
+                    );
+                }
+                return Ok(entry);
+            }
+            Err(err) => err,
+        };
+
+        let probe = req.amount_in / U256::from(PROBE_DIVISOR);
+        let restriction = if !probe.is_zero() && simulate_buy(client, req, probe).await.is_ok() {
+            "max buy limit"
+        } else {
+            "trading not enabled"
+        };
+
+        if block - start_block >= max_wait_blocks {
+            return Err(last_error.context(format!(
+                "buy simulation still failing after {max_wait_blocks} blocks ({restriction})"
+            )));
+        }
+
+        warn!("Buy simulation failed at block {block} ({restriction}), retrying next block");
+        while client.block_number().await? <= block {
+            tokio::time::sleep(chain::profile().block_poll_interval()).await;
+        }
+        block = client.block_number().await?;
+    }
+}
+
+async fn simulate_buy(
+    client: &impl ExecutionClient,
+    req: &EntryRequest,
+    amount_in: U256,
+) -> Result<Entry> {
+    let (router, quoted_out) = client
+        .quote(req.token, amount_in, true)
+        .await
+        .context("failed to query quote")?;
+    if quoted_out.is_zero() {
+        return Err(anyhow!("quote returned zero tokens"));
+    }
+
+    let amount_out_min = apply_slippage(quoted_out, req.slippage_bps);
+    let buy_gas = client
+        .estimate_gas(
+            router,
+            GasEstimationParams::Buy {
+                token: req.token,
+                amount_in,
+                amount_out_min,
+                to: req.recipient,
+                deadline: req.deadline,
+            },
+        )
+        .await
+        .context("failed to estimate buy gas")?;
+
+    if amount_in == req.amount_in {
+        info!("Estimated buy gas: {}", buy_gas);
+    }
+
+    Ok(Entry {
+        router,
+        quoted_out,
+        amount_out_min,
+        buy_gas,
+    })
+}
@@ -0,0 +1,471 @@
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+
+use crate::state;
+
+/// Buys considered when tuning a token's slippage.
+const TUNE_WINDOW: usize = 20;
+/// Fewer buys than this and the configured slippage is used as is.
+const TUNE_MIN_SAMPLES: usize = 5;
+/// Above this revert rate the configured slippage is used as is.
+const TUNE_MAX_REVERT_RATE: f64 = 0.2;
+/// Headroom added to the observed slippage.
+const TUNE_MARGIN_BPS: u64 = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// One submitted buy or sell: what was quoted, what arrived and how long inclusion took.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecRecord {
+    pub token: Address,
+    pub side: Side,
+    pub at: u64,
+    /// Tokens for buys, native for sells.
+    pub quoted: U256,
+    /// Same unit as `quoted`; zero when the transaction reverted.
+    pub received: U256,
+    pub reverted: bool,
+    pub inclusion_ms: u64,
+}
+
+impl ExecRecord {
+    pub fn filled(
+        token: Address,
+        side: Side,
+        quoted: U256,
+        received: U256,
+        inclusion: Duration,
+    ) -> Self {
+        Self {
+            token,
+            side,
+            at: state::unix_now(),
+            quoted,
+            received,
+            reverted: false,
+            inclusion_ms: inclusion.as_millis() as u64,
+        }
+    }
+
+    pub fn reverted(token: Address, side: Side, quoted: U256, inclusion: Duration) -> Self {
+        Self {
+            reverted: true,
+            ..Self::filled(token, side, quoted, U256::zero(), inclusion)
+        }
+    }
+
+    /// Shortfall against the quote in bps; negative when the fill beat it.
+    pub fn slippage_bps(&self) -> Option<f64> {
+        if self.reverted || self.quoted.is_zero() {
+            return None;
+        }
+        let quoted = self.quoted.as_u128() as f64;
+        Some((quoted - self.received.as_u128() as f64) / quoted * 10_000.0)
+    }
+}
+
+/// Append-only JSONL log of buy and sell executions.
+pub struct ExecLog {
+    path: PathBuf,
+}
+
+impl ExecLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The log at `EXECUTION_LOG_FILE`, `execution.jsonl` by default.
+    pub fn from_env() -> Self {
+        Self::new(PathBuf::from(
+            env::var("EXECUTION_LOG_FILE").unwrap_or_else(|_| "execution.jsonl".into()),
+        ))
+    }
+
+    pub fn record(&self, record: &ExecRecord) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    pub fn load(&self) -> Result<Vec<ExecRecord>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut records = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            records.push(
+                serde_json::from_str(&line)
+                    .with_context(|| format!("corrupt execution log {}", self.path.display()))?,
+            );
+        }
+        Ok(records)
+    }
+
+    /// Buy slippage for `token` sized to its recent fills: the 90th percentile of the
+    /// last buys plus headroom, never above `configured`. Falls back to `configured`
+    /// when there is too little history or too many of the buys reverted.
+    pub fn tuned_slippage_bps(&self, token: Address, configured: u64) -> Result<u64> {
+        let buys: Vec<ExecRecord> = self
+            .load()?
+            .into_iter()
+            .filter(|record| record.token == token && record.side == Side::Buy)
+            .collect();
+        let recent = &buys[buys.len().saturating_sub(TUNE_WINDOW)..];
+        if recent.len() < TUNE_MIN_SAMPLES {
+            return Ok(configured);
+        }
+        let reverts = recent.iter().filter(|record| record.reverted).count();
+        if reverts as f64 / recent.len() as f64 > TUNE_MAX_REVERT_RATE {
+            return Ok(configured);
+        }
+
+        let mut slippage: Vec<f64> = recent
+            .iter()
+            .filter_map(ExecRecord::slippage_bps)
+            .map(|bps| bps.max(0.0))
+            .collect();
+        if slippage.is_empty() {
+            return Ok(configured);
+        }
+        slippage.sort_by(f64::total_cmp);
+        let p90 = slippage[(slippage.len() * 9 / 10).min(slippage.len() - 1)];
+        Ok((p90.ceil() as u64 + TUNE_MARGIN_BPS).min(configured))
+    }
+}
+
+/// Revert rate, mean slippage and median inclusion delay of a run of executions.
+struct Metrics {
+    revert_rate: f64,
+    slippage_bps: Option<f64>,
+    inclusion_ms: f64,
+}
+
+impl Metrics {
+    fn of(records: &[ExecRecord]) -> Self {
+        let reverts = records.iter().filter(|record| record.reverted).count();
+        let slippage: Vec<f64> = records.iter().filter_map(ExecRecord::slippage_bps).collect();
+        let mut inclusion: Vec<u64> = records.iter().map(|record| record.inclusion_ms).collect();
+        inclusion.sort_unstable();
+        Self {
+            revert_rate: reverts as f64 / records.len().max(1) as f64,
+            slippage_bps: (!slippage.is_empty())
+                .then(|| slippage.iter().sum::<f64>() / slippage.len() as f64),
+            inclusion_ms: inclusion.get(inclusion.len() / 2).copied().unwrap_or(0) as f64,
+        }
+    }
+}
+
+/// Alerts when the latest `EXEC_ANOMALY_WINDOW` executions drift from the ones before
+/// them: a revert rate `EXEC_ANOMALY_REVERT_PCT` points higher, mean slippage
+/// `EXEC_ANOMALY_SLIPPAGE_BPS` worse, or a median inclusion delay
+/// `EXEC_ANOMALY_INCLUSION_FACTOR` times longer. Those point at a degraded RPC, gas set
+/// too low or a searcher sandwiching the bot. Each drift alerts once, and again only
+/// after it has recovered.
+pub struct AnomalyWatch {
+    window: usize,
+    /// Executions before the window that make up the baseline.
+    baseline: usize,
+    revert_pct: f64,
+    slippage_bps: f64,
+    inclusion_factor: f64,
+    alerting: Mutex<HashSet<&'static str>>,
+}
+
+impl AnomalyWatch {
+    /// Enabled by `EXEC_ANOMALY_WINDOW`; the baseline is `EXEC_ANOMALY_BASELINE`
+    /// executions, 100 by default.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(window) = env::var("EXEC_ANOMALY_WINDOW").ok() else {
+            return Ok(None);
+        };
+        let number = |name: &str, default: f64| -> Result<f64> {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {}", name)))
+                .transpose()
+                .map(|v| v.unwrap_or(default))
+        };
+        Ok(Some(Self {
+            window: window.parse::<usize>().context("invalid EXEC_ANOMALY_WINDOW")?.max(1),
+            baseline: (number("EXEC_ANOMALY_BASELINE", 100.0)? as usize).max(1),
+            revert_pct: number("EXEC_ANOMALY_REVERT_PCT", 20.0)?,
+            slippage_bps: number("EXEC_ANOMALY_SLIPPAGE_BPS", 100.0)?,
+            inclusion_factor: number("EXEC_ANOMALY_INCLUSION_FACTOR", 2.0)?,
+            alerting: Mutex::default(),
+        }))
+    }
+
+    /// Drifts that `records`, oldest first, newly show. Nothing is judged until the log
+    /// holds a full window and at least as many executions again for the baseline.
+    pub fn check(&self, records: &[ExecRecord]) -> Vec<String> {
+        if records.len() < self.window * 2 {
+            return Vec::new();
+        }
+        let split = records.len() - self.window;
+        let before = &records[split.saturating_sub(self.baseline)..split];
+        let (baseline, recent) = (Metrics::of(before), Metrics::of(&records[split..]));
+
+        let mut drifts = Vec::new();
+        if (recent.revert_rate - baseline.revert_rate) * 100.0 >= self.revert_pct {
+            drifts.push((
+                "reverts",
+                format!(
+                    "revert rate {:.0}% over the last {} executions, against {:.0}% before",
+                    recent.revert_rate * 100.0,
+                    self.window,
+                    baseline.revert_rate * 100.0
+                ),
+            ));
+        }
+        if let (Some(recent_bps), Some(baseline_bps)) =
+            (recent.slippage_bps, baseline.slippage_bps)
+        {
+            if recent_bps - baseline_bps >= self.slippage_bps {
+                drifts.push((
+                    "slippage",
+                    format!(
+                        "slippage {:.0} bps over the last {} executions, against {:.0} bps before",
+                        recent_bps, self.window, baseline_bps
+                    ),
+                ));
+            }
+        }
+        if baseline.inclusion_ms > 0.0
+            && recent.inclusion_ms >= baseline.inclusion_ms * self.inclusion_factor
+        {
+            drifts.push((
+                "inclusion",
+                format!(
+                    "median inclusion {:.0} ms over the last {} executions, \
+                     against {:.0} ms before",
+                    recent.inclusion_ms, self.window, baseline.inclusion_ms
+                ),
+            ));
+        }
+
+        let Ok(mut alerting) = self.alerting.lock() else {
+            return Vec::new();
+        };
+        alerting.retain(|metric| drifts.iter().any(|(drifted, _)| drifted == metric));
+        drifts
+            .into_iter()
+            .filter(|(metric, _)| alerting.insert(metric))
+            .map(|(_, message)| format!("Execution quality drifted: {}", message))
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct Quality {
+    buys: usize,
+    sells: usize,
+    reverts: usize,
+    slippage_bps: Vec<f64>,
+    inclusion_ms: Vec<u64>,
+    quoted: f64,
+    received: f64,
+}
+
+impl Quality {
+    fn add(&mut self, record: &ExecRecord) {
+        match record.side {
+            Side::Buy => self.buys += 1,
+            Side::Sell => self.sells += 1,
+        }
+        if record.reverted {
+            self.reverts += 1;
+        }
+        self.inclusion_ms.push(record.inclusion_ms);
+        if let Some(bps) = record.slippage_bps() {
+            self.slippage_bps.push(bps);
+            self.quoted += record.quoted.as_u128() as f64;
+            self.received += record.received.as_u128() as f64;
+        }
+    }
+
+    fn row(&self, label: &str) -> String {
+        let trades = self.buys + self.sells;
+        let avg = |values: &[f64]| values.iter().sum::<f64>() / values.len().max(1) as f64;
+        let inclusion: Vec<f64> = self.inclusion_ms.iter().map(|ms| *ms as f64).collect();
+        let realized = if self.quoted > 0.0 {
+            self.received / self.quoted * 100.0
+        } else {
+            0.0
+        };
+        format!(
+            "{:<44}  {:>5}  {:>5}  {:>8.1}%  {:>9.1}  {:>11.0}  {:>9.2}%",
+            label,
+            self.buys,
+            self.sells,
+            self.reverts as f64 / trades.max(1) as f64 * 100.0,
+            avg(&self.slippage_bps),
+            avg(&inclusion),
+            realized
+        )
+    }
+}
+
+/// Prints execution quality per token and overall from the execution log.
+pub fn run(log: &ExecLog) -> Result<()> {
+    let records = log.load()?;
+    if records.is_empty() {
+        println!("No executions in {}", log.path.display());
+        return Ok(());
+    }
+
+    let mut by_token: BTreeMap<Address, Quality> = BTreeMap::new();
+    let mut total = Quality::default();
+    for record in &records {
+        by_token.entry(record.token).or_default().add(record);
+        total.add(record);
+    }
+
+    println!(
+        "{:<44}  {:>5}  {:>5}  {:>9}  {:>9}  {:>11}  {:>10}",
+        "token", "buys", "sells", "reverts", "slip bps", "incl. ms", "got/quote"
+    );
+    for (token, quality) in &by_token {
+        println!("{}", quality.row(&format!("{:?}", token)));
+    }
+    println!("{}", total.row("total"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(name: &str) -> ExecLog {
+        let path = std::env::temp_dir().join(format!(
+            "nadfun-execstats-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        ExecLog::new(path)
+    }
+
+    fn buy(token: Address, received: u64) -> ExecRecord {
+        let quoted = U256::from(10_000u64);
+        ExecRecord::filled(token, Side::Buy, quoted, U256::from(received), Duration::ZERO)
+    }
+
+    fn watch() -> AnomalyWatch {
+        AnomalyWatch {
+            window: 5,
+            baseline: 20,
+            revert_pct: 20.0,
+            slippage_bps: 100.0,
+            inclusion_factor: 2.0,
+            alerting: Mutex::default(),
+        }
+    }
+
+    fn timed(received: u64, inclusion_ms: u64) -> ExecRecord {
+        let quoted = U256::from(10_000u64);
+        let inclusion = Duration::from_millis(inclusion_ms);
+        ExecRecord::filled(Address::zero(), Side::Buy, quoted, U256::from(received), inclusion)
+    }
+
+    #[test]
+    fn a_revert_spike_alerts_once_until_it_recovers() {
+        let watch = watch();
+        let mut records: Vec<ExecRecord> = (0..20).map(|_| timed(9_990, 400)).collect();
+        assert!(watch.check(&records).is_empty());
+
+        let reverted =
+            ExecRecord::reverted(Address::zero(), Side::Buy, U256::one(), Duration::ZERO);
+        records.extend([reverted.clone(), reverted]);
+        let alerts = watch.check(&records);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].contains("revert rate 40%"));
+        assert!(watch.check(&records).is_empty());
+
+        records.extend((0..5).map(|_| timed(9_990, 400)));
+        assert!(watch.check(&records).is_empty());
+        assert!(watch.alerting.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn slow_inclusion_and_worse_slippage_are_drifts_too() {
+        let watch = watch();
+        let mut records: Vec<ExecRecord> = (0..20).map(|_| timed(9_990, 400)).collect();
+        records.extend((0..5).map(|_| timed(9_700, 1_000)));
+        let alerts = watch.check(&records);
+        assert_eq!(alerts.len(), 2);
+        assert!(alerts[0].contains("slippage 300 bps"));
+        assert!(alerts[1].contains("median inclusion 1000 ms"));
+    }
+
+    #[test]
+    fn short_logs_are_not_judged() {
+        let records: Vec<ExecRecord> = (0..9).map(|_| timed(0, 5_000)).collect();
+        assert!(watch().check(&records).is_empty());
+    }
+
+    #[test]
+    fn slippage_is_the_shortfall_against_the_quote() {
+        let token = Address::repeat_byte(1);
+        assert_eq!(buy(token, 9_900).slippage_bps(), Some(100.0));
+        assert_eq!(buy(token, 10_100).slippage_bps(), Some(-100.0));
+        let reverted = ExecRecord::reverted(token, Side::Buy, U256::from(1u64), Duration::ZERO);
+        assert_eq!(reverted.slippage_bps(), None);
+    }
+
+    #[test]
+    fn tuning_needs_history_and_few_reverts() {
+        let token = Address::repeat_byte(1);
+        let log = log("history");
+        for _ in 0..TUNE_MIN_SAMPLES - 1 {
+            log.record(&buy(token, 9_990)).unwrap();
+        }
+        assert_eq!(log.tuned_slippage_bps(token, 300).unwrap(), 300);
+
+        for _ in 0..2 {
+            let reverted = ExecRecord::reverted(token, Side::Buy, U256::one(), Duration::ZERO);
+            log.record(&reverted).unwrap();
+        }
+        // Two reverts in six buys is above the 20% limit.
+        assert_eq!(log.tuned_slippage_bps(token, 300).unwrap(), 300);
+        let _ = std::fs::remove_file(&log.path);
+    }
+
+    #[test]
+    fn tuning_takes_the_p90_plus_margin_capped_at_the_configured_value() {
+        let token = Address::repeat_byte(1);
+        let log = log("tune");
+        // Nine fills at 10 bps and one at 100 bps; another token is ignored.
+        for _ in 0..9 {
+            log.record(&buy(token, 9_990)).unwrap();
+        }
+        log.record(&buy(token, 9_900)).unwrap();
+        log.record(&buy(Address::repeat_byte(2), 5_000)).unwrap();
+        assert_eq!(log.tuned_slippage_bps(token, 300).unwrap(), 100 + TUNE_MARGIN_BPS);
+        assert_eq!(log.tuned_slippage_bps(token, 50).unwrap(), 50);
+        let _ = std::fs::remove_file(&log.path);
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, U256};
+use tokio::time::Duration;
+use tracing::info;
+
+use crate::chain;
+use crate::engine::ExecutionClient;
+use crate::exit_strategy::{Exit, ExitStrategy, Position, Tranche};
+use crate::pinning::PinWatch;
+use crate::price_feed::PriceWatch;
+use crate::snapshot::SnapshotWatch;
+use crate::state::OpenPosition;
+
+/// Quotes selling `amount` of `token` against current state, returning the native amount out.
+pub async fn simulate_exit(
+    client: &impl ExecutionClient,
+    token: Address,
+    amount: U256,
+) -> Result<U256> {
+    let (_, amount_out) = client
+        .quote(token, amount, false)
+        .await
+        .context("exit quote failed")?;
+    if amount_out.is_zero() {
+        return Err(anyhow!("exit quote returned zero"));
+    }
+    Ok(amount_out)
+}
+
+/// What else to watch while holding, besides the exit quote.
+#[derive(Default)]
+pub struct Watches<'a> {
+    pub pin: Option<&'a PinWatch>,
+    pub snapshots: Option<&'a mut SnapshotWatch>,
+    pub prices: Option<&'a PriceWatch>,
+}
+
+pub enum ExitDecision {
+    /// The next tranche's profit target was reached.
+    Tranche(Tranche),
+    /// The exit rules fired; sell everything that is left.
+    Full(Exit),
+}
+
+/// Holds `position`, re-quoting its exit (and re-checking the token pin and taking
+/// token snapshots, if any) every `interval` until `tranche` is reached once `strategy`
+/// allows selling, or `strategy` decides to sell. The peak quote is kept in `peak_value`,
+/// so the caller carries it from one tranche to the next; it restarts on resume. With a
+/// fresh price from the shared feed the exit is quoted from it instead of over RPC.
+///
+/// Returns the error if the exit stops simulating cleanly or the pin demands an
+/// exit, so the caller can sell while it still can.
+pub async fn hold(
+    client: &impl ExecutionClient,
+    position: &OpenPosition,
+    strategy: &dyn ExitStrategy,
+    tranche: Option<Tranche>,
+    interval: Duration,
+    peak_value: &mut U256,
+    watches: Watches<'_>,
+) -> Result<ExitDecision> {
+    let Watches {
+        pin,
+        mut snapshots,
+        prices,
+    } = watches;
+
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let fed = prices.and_then(|prices| prices.sell_quote(position.quoted_out));
+        let value = match fed.filter(|value| !value.is_zero()) {
+            Some(value) => value,
+            None => simulate_exit(client, position.token, position.quoted_out).await?,
+        };
+        *peak_value = (*peak_value).max(value);
+        let diff = match snapshots.as_mut() {
+            Some(watch) => watch.poll().await,
+            None => Default::default(),
+        };
+        let snapshot = Position {
+            cost: position.amount_in,
+            amount: position.quoted_out,
+            value,
+            peak_value: *peak_value,
+            held_for: position.held_for(),
+            snapshot: diff,
+        };
+        info!(
+            "Exit simulation: {} ({:+.2}%)",
+            chain::profile().format_native(snapshot.value),
+            snapshot.pnl_pct()
+        );
+
+        if let Some(pin) = pin {
+            pin.check().await?;
+        }
+        if let Some(exit) = strategy.should_exit(&snapshot) {
+            return Ok(ExitDecision::Full(exit));
+        }
+        let reached = |tranche: &Tranche| {
+            tranche.reached(&snapshot) && strategy.may_sell(&snapshot)
+        };
+        if let Some(tranche) = tranche.filter(reached) {
+            return Ok(ExitDecision::Tranche(tranche));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::H256;
+
+    use crate::exit_strategy::{ExitKind, TrailingStop};
+    use crate::testing::ScriptedClient;
+
+    #[tokio::test]
+    async fn the_trailing_peak_survives_a_tranche() {
+        let position = OpenPosition::new(
+            Address::repeat_byte(0x33),
+            Address::repeat_byte(0x11),
+            H256::zero(),
+            Address::repeat_byte(0x22),
+            U256::from(100u64),
+            U256::from(1_000u64),
+        );
+        // The tranche sells at the peak; the drop after it is 20% below the peak but
+        // would be the first quote of a fresh hold.
+        let client = ScriptedClient::with_sell_quotes([120, 150, 120]);
+        let stop = TrailingStop { pct: 15.0 };
+        let tranche = Tranche {
+            at_profit_pct: 40.0,
+            share_pct: 50,
+        };
+        let mut peak = U256::zero();
+
+        let first = hold(
+            &client,
+            &position,
+            &stop,
+            Some(tranche),
+            Duration::ZERO,
+            &mut peak,
+            Watches::default(),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(first, ExitDecision::Tranche(_)));
+        assert_eq!(peak, U256::from(150u64));
+
+        let second = hold(
+            &client,
+            &position,
+            &stop,
+            None,
+            Duration::ZERO,
+            &mut peak,
+            Watches::default(),
+        )
+        .await
+        .unwrap();
+        match second {
+            ExitDecision::Full(exit) => assert_eq!(exit.kind, ExitKind::TrailingStop),
+            ExitDecision::Tranche(_) => panic!("no tranche was given"),
+        }
+    }
+}
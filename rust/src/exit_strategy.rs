@@ -0,0 +1,425 @@
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use ethers::types::U256;
+use tokio::time::Duration;
+
+use crate::chain;
+use crate::snapshot::SnapshotDiff;
+
+/// What a held position looks like at one check of the exit loop.
+pub struct Position {
+    /// Native amount spent on entry.
+    pub cost: U256,
+    /// Tokens held.
+    pub amount: U256,
+    /// Native amount a full exit would return right now.
+    pub value: U256,
+    /// Highest `value` seen while holding.
+    pub peak_value: U256,
+    pub held_for: Duration,
+    /// Set on checks where a new token snapshot was taken.
+    pub snapshot: SnapshotDiff,
+}
+
+impl Position {
+    /// Unrealized profit or loss in percent of cost.
+    pub fn pnl_pct(&self) -> f64 {
+        if self.cost.is_zero() {
+            return 0.0;
+        }
+        let cost = self.cost.as_u128() as f64;
+        let value = self.value.as_u128() as f64;
+        (value - cost) / cost * 100.0
+    }
+
+    /// Native per whole token a full exit would realize right now.
+    pub fn price(&self) -> Option<f64> {
+        if self.amount.is_zero() {
+            return None;
+        }
+        Some(self.value.as_u128() as f64 / self.amount.as_u128() as f64)
+    }
+}
+
+/// Which rule an exit came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitKind {
+    TakeProfit,
+    StopLoss,
+    TrailingStop,
+    MaxHold,
+    ReserveDrop,
+    CreatorDump,
+    LimitSell,
+    ForceSell,
+    Shutdown,
+}
+
+impl ExitKind {
+    /// Exits that cut a loss, escape a rug or were asked for, which a minimum hold never
+    /// delays.
+    pub fn is_emergency(self) -> bool {
+        matches!(
+            self,
+            Self::StopLoss
+                | Self::ReserveDrop
+                | Self::CreatorDump
+                | Self::ForceSell
+                | Self::Shutdown
+        )
+    }
+}
+
+/// Why a position is being sold: the rule that fired, and how it fired.
+#[derive(Debug, Clone)]
+pub struct Exit {
+    pub kind: ExitKind,
+    pub reason: String,
+}
+
+impl Exit {
+    pub fn new(kind: ExitKind, reason: impl Into<String>) -> Self {
+        Self {
+            kind,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.reason)
+    }
+}
+
+/// A rule deciding when a held position should be sold.
+pub trait ExitStrategy: Send + Sync {
+    /// Returns why to exit now, or `None` to keep holding.
+    fn should_exit(&self, position: &Position) -> Option<Exit>;
+
+    /// Whether sells other than emergencies, take-profit tranches included, are allowed
+    /// yet.
+    fn may_sell(&self, _position: &Position) -> bool {
+        true
+    }
+}
+
+pub struct TakeProfit {
+    pub pct: f64,
+}
+
+impl ExitStrategy for TakeProfit {
+    fn should_exit(&self, position: &Position) -> Option<Exit> {
+        let pnl = position.pnl_pct();
+        (pnl >= self.pct).then(|| {
+            let reason = format!("take profit hit at {:+.2}% (target +{}%)", pnl, self.pct);
+            Exit::new(ExitKind::TakeProfit, reason)
+        })
+    }
+}
+
+pub struct StopLoss {
+    pub pct: f64,
+}
+
+impl ExitStrategy for StopLoss {
+    fn should_exit(&self, position: &Position) -> Option<Exit> {
+        let pnl = position.pnl_pct();
+        (pnl <= -self.pct).then(|| {
+            let reason = format!("stop loss hit at {:+.2}% (limit -{}%)", pnl, self.pct);
+            Exit::new(ExitKind::StopLoss, reason)
+        })
+    }
+}
+
+pub struct TrailingStop {
+    pub pct: f64,
+}
+
+impl ExitStrategy for TrailingStop {
+    fn should_exit(&self, position: &Position) -> Option<Exit> {
+        if position.peak_value.is_zero() {
+            return None;
+        }
+        let peak = position.peak_value.as_u128() as f64;
+        let drawdown = (peak - position.value.as_u128() as f64) / peak * 100.0;
+        (drawdown >= self.pct).then(|| {
+            let reason = format!(
+                "trailing stop hit, {:.2}% below peak of {}",
+                drawdown,
+                chain::profile().format_native(position.peak_value)
+            );
+            Exit::new(ExitKind::TrailingStop, reason)
+        })
+    }
+}
+
+pub struct MaxHold {
+    pub duration: Duration,
+}
+
+impl ExitStrategy for MaxHold {
+    fn should_exit(&self, position: &Position) -> Option<Exit> {
+        (position.held_for >= self.duration).then(|| {
+            let reason = format!("max hold of {}s reached", self.duration.as_secs());
+            Exit::new(ExitKind::MaxHold, reason)
+        })
+    }
+}
+
+/// Exits when the bonding curve's MON reserve falls by `pct` between two snapshots.
+pub struct ReserveDrop {
+    pub pct: f64,
+}
+
+impl ExitStrategy for ReserveDrop {
+    fn should_exit(&self, position: &Position) -> Option<Exit> {
+        let drop = position.snapshot.reserve_drop_pct?;
+        (drop >= self.pct).then(|| {
+            let reason = format!("curve reserve fell {:.1}% since the last snapshot", drop);
+            Exit::new(ExitKind::ReserveDrop, reason)
+        })
+    }
+}
+
+/// Exits when the creator's token balance falls by `pct` between two snapshots.
+pub struct CreatorDump {
+    pub pct: f64,
+}
+
+impl ExitStrategy for CreatorDump {
+    fn should_exit(&self, position: &Position) -> Option<Exit> {
+        let drop = position.snapshot.creator_drop_pct?;
+        (drop >= self.pct).then(|| {
+            let reason = format!("creator balance fell {:.1}% since the last snapshot", drop);
+            Exit::new(ExitKind::CreatorDump, reason)
+        })
+    }
+}
+
+/// Exits on the first rule that fires, holding back all but emergencies until the
+/// position is `min_hold` old so a fast rule can't churn it away within a few blocks.
+#[derive(Default)]
+pub struct ExitRules {
+    rules: Vec<Box<dyn ExitStrategy>>,
+    min_hold: Duration,
+}
+
+impl ExitRules {
+    /// Take profit, stop loss and trailing stop are optional; the position is always
+    /// sold after `max_hold` at the latest.
+    pub fn new(
+        take_profit_pct: Option<f64>,
+        stop_loss_pct: Option<f64>,
+        trailing_stop_pct: Option<f64>,
+        max_hold: Duration,
+    ) -> Result<Self> {
+        let mut rules = Self::default();
+        if let Some(pct) = take_profit_pct {
+            if pct <= 0.0 {
+                return Err(anyhow!("take profit must be positive"));
+            }
+            rules = rules.with(TakeProfit { pct });
+        }
+        if let Some(pct) = stop_loss_pct {
+            if pct <= 0.0 {
+                return Err(anyhow!("stop loss must be positive"));
+            }
+            rules = rules.with(StopLoss { pct });
+        }
+        if let Some(pct) = trailing_stop_pct {
+            if pct <= 0.0 || pct >= 100.0 {
+                return Err(anyhow!("trailing stop must be between 0 and 100"));
+            }
+            rules = rules.with(TrailingStop { pct });
+        }
+        Ok(rules.with(MaxHold { duration: max_hold }))
+    }
+
+    pub fn with(mut self, rule: impl ExitStrategy + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    pub fn holding_for(mut self, min_hold: Duration) -> Self {
+        self.min_hold = min_hold;
+        self
+    }
+}
+
+impl ExitStrategy for ExitRules {
+    fn should_exit(&self, position: &Position) -> Option<Exit> {
+        let may_sell = self.may_sell(position);
+        self.rules
+            .iter()
+            .filter_map(|rule| rule.should_exit(position))
+            .find(|exit| may_sell || exit.kind.is_emergency())
+    }
+
+    fn may_sell(&self, position: &Position) -> bool {
+        position.held_for >= self.min_hold
+    }
+}
+
+/// A partial exit: sell `share_pct` of the original position once profit reaches `at_profit_pct`.
+#[derive(Debug, Clone, Copy)]
+pub struct Tranche {
+    pub share_pct: u64,
+    pub at_profit_pct: f64,
+}
+
+impl Tranche {
+    pub fn reached(&self, position: &Position) -> bool {
+        position.pnl_pct() >= self.at_profit_pct
+    }
+}
+
+/// Parses `50@30,25@60` as "sell 50% at +30%, then 25% at +60%". Whatever the tranches
+/// leave is sold when the exit rules fire.
+pub fn parse_tranches(spec: &str) -> Result<Vec<Tranche>> {
+    let mut tranches = spec
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let (share, at) = part
+                .split_once('@')
+                .ok_or_else(|| anyhow!("expected share@profit, got {:?}", part))?;
+            let tranche = Tranche {
+                share_pct: share.trim().trim_end_matches('%').parse()?,
+                at_profit_pct: at.trim().trim_start_matches('+').trim_end_matches('%').parse()?,
+            };
+            if tranche.share_pct == 0 || tranche.at_profit_pct <= 0.0 {
+                return Err(anyhow!("tranche {:?} must have a positive share and profit", part));
+            }
+            Ok(tranche)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if tranches.iter().map(|t| t.share_pct).sum::<u64>() > 100 {
+        return Err(anyhow!("tranche shares add up to more than 100%"));
+    }
+    tranches.sort_by(|a, b| a.at_profit_pct.total_cmp(&b.at_profit_pct));
+    Ok(tranches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(value: u64, peak_value: u64) -> Position {
+        Position {
+            cost: U256::from(100u64),
+            amount: U256::from(1_000u64),
+            value: U256::from(value),
+            peak_value: U256::from(peak_value),
+            held_for: Duration::from_secs(60),
+            snapshot: SnapshotDiff::default(),
+        }
+    }
+
+    fn kind(rule: &impl ExitStrategy, position: &Position) -> Option<ExitKind> {
+        rule.should_exit(position).map(|exit| exit.kind)
+    }
+
+    #[test]
+    fn take_profit_fires_at_its_target() {
+        let rule = TakeProfit { pct: 50.0 };
+        assert_eq!(kind(&rule, &position(149, 149)), None);
+        assert_eq!(kind(&rule, &position(150, 150)), Some(ExitKind::TakeProfit));
+    }
+
+    #[test]
+    fn stop_loss_fires_at_its_limit() {
+        let rule = StopLoss { pct: 20.0 };
+        assert_eq!(kind(&rule, &position(81, 100)), None);
+        assert_eq!(kind(&rule, &position(80, 100)), Some(ExitKind::StopLoss));
+        assert_eq!(kind(&rule, &position(200, 200)), None);
+    }
+
+    #[test]
+    fn trailing_stop_measures_from_the_peak() {
+        let rule = TrailingStop { pct: 25.0 };
+        // In profit, but 25% off the peak.
+        assert_eq!(kind(&rule, &position(150, 200)), Some(ExitKind::TrailingStop));
+        assert_eq!(kind(&rule, &position(151, 200)), None);
+        assert_eq!(kind(&rule, &position(50, 0)), None);
+    }
+
+    #[test]
+    fn max_hold_fires_once_held_long_enough() {
+        let held = position(100, 100);
+        let rule = MaxHold {
+            duration: Duration::from_secs(60),
+        };
+        assert_eq!(kind(&rule, &held), Some(ExitKind::MaxHold));
+        let rule = MaxHold {
+            duration: Duration::from_secs(61),
+        };
+        assert_eq!(kind(&rule, &held), None);
+    }
+
+    #[test]
+    fn snapshot_rules_fire_only_on_a_snapshot_drop() {
+        let reserve = ReserveDrop { pct: 30.0 };
+        let creator = CreatorDump { pct: 50.0 };
+        let mut held = position(100, 100);
+        assert_eq!(kind(&reserve, &held), None);
+        assert_eq!(kind(&creator, &held), None);
+
+        held.snapshot = SnapshotDiff {
+            reserve_drop_pct: Some(30.0),
+            creator_drop_pct: Some(49.9),
+        };
+        assert_eq!(kind(&reserve, &held), Some(ExitKind::ReserveDrop));
+        assert_eq!(kind(&creator, &held), None);
+        held.snapshot.creator_drop_pct = Some(80.0);
+        assert_eq!(kind(&creator, &held), Some(ExitKind::CreatorDump));
+    }
+
+    #[test]
+    fn rules_report_the_first_that_fires() {
+        let rules = ExitRules::new(Some(50.0), Some(20.0), None, Duration::from_secs(3600))
+            .unwrap();
+        assert_eq!(kind(&rules, &position(100, 100)), None);
+        let exit = rules.should_exit(&position(70, 100)).unwrap();
+        assert_eq!(exit.kind, ExitKind::StopLoss);
+        assert!(exit.to_string().starts_with("stop loss hit at -30.00%"));
+        assert!(ExitRules::new(None, None, Some(100.0), Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn a_minimum_hold_only_lets_emergencies_through() {
+        let rules = ExitRules::new(Some(50.0), Some(20.0), Some(10.0), Duration::from_secs(600))
+            .unwrap()
+            .with(ReserveDrop { pct: 30.0 })
+            .holding_for(Duration::from_secs(120));
+        let mut young = position(200, 200);
+        assert!(!rules.may_sell(&young));
+        assert_eq!(kind(&rules, &young), None);
+        young.value = U256::from(70u64);
+        assert_eq!(kind(&rules, &young), Some(ExitKind::StopLoss));
+
+        // The trailing stop fires first but waits; the reserve drop behind it doesn't.
+        let mut rugged = position(150, 200);
+        rugged.snapshot.reserve_drop_pct = Some(40.0);
+        assert_eq!(kind(&rules, &rugged), Some(ExitKind::ReserveDrop));
+
+        let mut old = position(200, 200);
+        old.held_for = Duration::from_secs(120);
+        assert!(rules.may_sell(&old));
+        assert_eq!(kind(&rules, &old), Some(ExitKind::TakeProfit));
+    }
+
+    #[test]
+    fn tranches_parse_sorted_by_profit() {
+        let tranches = parse_tranches("25@+60%, 50%@30").unwrap();
+        let parsed: Vec<_> = tranches.iter().map(|t| (t.share_pct, t.at_profit_pct)).collect();
+        assert_eq!(parsed, vec![(50, 30.0), (25, 60.0)]);
+        assert!(parse_tranches("").unwrap().is_empty());
+        assert!(parse_tranches("50").is_err());
+        assert!(parse_tranches("0@30").is_err());
+        assert!(parse_tranches("60@30,50@60").is_err());
+    }
+}
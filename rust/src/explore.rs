@@ -0,0 +1,81 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+
+use crate::chain;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Spend {
+    spent: U256,
+}
+
+/// Small trades on launches that miss a filter threshold by less than `margin_pct`,
+/// tagged in the ledger by the filter they missed so `report` shows whether it is too
+/// strict. Their total size is capped by a budget that persists across runs.
+pub struct ExploreConfig {
+    pub amount_in: U256,
+    pub margin_pct: f64,
+    cap: U256,
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl ExploreConfig {
+    /// Enabled by `EXPLORE_BUDGET_MON`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(cap) = env::var("EXPLORE_BUDGET_MON").ok() else {
+            return Ok(None);
+        };
+        let profile = chain::profile();
+        let cap = profile.parse_native(&cap).context("invalid EXPLORE_BUDGET_MON")?;
+        let amount_in = profile
+            .parse_native(&env::var("EXPLORE_AMOUNT_MON").unwrap_or_else(|_| "0.01".into()))
+            .context("invalid EXPLORE_AMOUNT_MON")?;
+        let margin_pct: f64 = env::var("EXPLORE_MARGIN_PCT")
+            .ok()
+            .map(|v| v.parse().context("invalid EXPLORE_MARGIN_PCT"))
+            .transpose()?
+            .unwrap_or(25.0);
+        if !(0.0..100.0).contains(&margin_pct) {
+            return Err(anyhow!("EXPLORE_MARGIN_PCT must be between 0 and 100"));
+        }
+        Ok(Some(Self {
+            amount_in,
+            margin_pct,
+            cap,
+            path: PathBuf::from(
+                env::var("EXPLORE_BUDGET_FILE").unwrap_or_else(|_| "explore_budget.json".into()),
+            ),
+            lock: Mutex::new(()),
+        }))
+    }
+
+    /// The lowest value within `margin_pct` of a `min` threshold.
+    pub fn lower_bound(&self, min: U256) -> U256 {
+        let keep_bps = ((100.0 - self.margin_pct) * 100.0) as u64;
+        min * U256::from(keep_bps) / U256::from(10_000u64)
+    }
+
+    /// Takes one trade's `amount_in` out of the budget, or returns false if it is spent.
+    pub fn reserve(&self) -> Result<bool> {
+        let _guard = self.lock.lock().map_err(|_| anyhow!("explore budget lock poisoned"))?;
+        let mut spend: Spend = match fs::read_to_string(&self.path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("corrupt explore budget {}", self.path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Spend::default(),
+            Err(err) => return Err(err.into()),
+        };
+        if spend.spent + self.amount_in > self.cap {
+            return Ok(false);
+        }
+        spend.spent += self.amount_in;
+        fs::write(&self.path, serde_json::to_string_pretty(&spend)?)
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        Ok(true)
+    }
+}
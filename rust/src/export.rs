@@ -0,0 +1,563 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use ethers::contract::{parse_log, EthEvent};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, Filter, H256, U256};
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::curve::{CurveBuyFilter, CurveCreateFilter, CurveSellFilter};
+use crate::rpc_pool::RpcPool;
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Directory the tables are written under, one subdirectory per table.
+    #[arg(long, default_value = "exports")]
+    pub out: PathBuf,
+
+    /// First block to export; defaults to the block after the last export into `--out`.
+    #[arg(long)]
+    pub from_block: Option<u64>,
+
+    /// Last block to export; defaults to the chain head.
+    #[arg(long)]
+    pub to_block: Option<u64>,
+
+    /// Blocks per `block_start=` partition directory.
+    #[arg(long, default_value_t = 100_000)]
+    pub partition_blocks: u64,
+
+    /// Blocks per `eth_getLogs` request.
+    #[arg(long, default_value_t = 2_000)]
+    pub chunk_blocks: u64,
+}
+
+/// Where the exports into a directory have reached, saved as `cursor.json` in it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cursor {
+    exported_to: Option<u64>,
+}
+
+pub struct LaunchRow {
+    pub block: u64,
+    pub log_index: u64,
+    pub tx_hash: H256,
+    pub token: Address,
+    pub creator: Address,
+    pub pool: Address,
+    pub name: String,
+    pub symbol: String,
+    pub virtual_mon: U256,
+    pub virtual_token: U256,
+    pub target_token_amount: U256,
+}
+
+pub struct SwapRow {
+    pub block: u64,
+    pub log_index: u64,
+    pub tx_hash: H256,
+    pub token: Address,
+    pub trader: Address,
+    pub buy: bool,
+    pub amount_in: U256,
+    pub amount_out: U256,
+}
+
+/// Tokens one wallet took from and returned to one curve over the exported range.
+/// Transfers between wallets aren't in the curve's logs, so this is curve flow rather
+/// than a balance.
+#[derive(Default)]
+struct HolderRow {
+    bought: U256,
+    sold: U256,
+    buys: u64,
+    sells: u64,
+    first_block: u64,
+    last_block: u64,
+}
+
+/// The curve's logs over one block range.
+#[derive(Default)]
+pub struct Rows {
+    pub launches: Vec<LaunchRow>,
+    pub swaps: Vec<SwapRow>,
+}
+
+/// One Parquet column; amounts are decimal strings since they overflow every integer
+/// type Parquet has.
+enum Values {
+    Int64(Vec<i64>),
+    Double(Vec<f64>),
+    Text(Vec<ByteArray>),
+}
+
+/// A table's columns, in schema order.
+pub struct Columns {
+    table: &'static str,
+    pub rows: usize,
+    columns: Vec<(&'static str, Values)>,
+}
+
+impl Columns {
+    pub fn new(table: &'static str, rows: usize) -> Self {
+        Self {
+            table,
+            rows,
+            columns: Vec::new(),
+        }
+    }
+
+    pub fn int(mut self, name: &'static str, values: impl Iterator<Item = u64>) -> Self {
+        let values = values
+            .map(|value| value.min(i64::MAX as u64) as i64)
+            .collect();
+        self.columns.push((name, Values::Int64(values)));
+        self
+    }
+
+    pub fn float(mut self, name: &'static str, values: impl Iterator<Item = f64>) -> Self {
+        self.columns.push((name, Values::Double(values.collect())));
+        self
+    }
+
+    pub fn text(mut self, name: &'static str, values: impl Iterator<Item = String>) -> Self {
+        let values = values
+            .map(|value| ByteArray::from(value.into_bytes()))
+            .collect();
+        self.columns.push((name, Values::Text(values)));
+        self
+    }
+
+    fn schema(&self) -> String {
+        let fields: String = self
+            .columns
+            .iter()
+            .map(|(name, values)| match values {
+                Values::Int64(_) => format!("REQUIRED INT64 {name}; "),
+                Values::Double(_) => format!("REQUIRED DOUBLE {name}; "),
+                Values::Text(_) => format!("REQUIRED BYTE_ARRAY {name} (UTF8); "),
+            })
+            .collect();
+        format!("message {} {{ {}}}", self.table, fields)
+    }
+
+    /// Writes the table as one Parquet file, through a temporary file so readers never
+    /// see half of it.
+    pub fn write(self, path: &Path) -> Result<()> {
+        let schema = Arc::new(parse_message_type(&self.schema())?);
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let tmp = path.with_extension("parquet.tmp");
+        let file =
+            File::create(&tmp).with_context(|| format!("failed to create {}", tmp.display()))?;
+        let mut writer = SerializedFileWriter::new(file, schema, Arc::new(props))?;
+        let mut row_group = writer.next_row_group()?;
+        let mut columns = self.columns.iter();
+        while let Some(mut column) = row_group.next_column()? {
+            match columns.next().map(|(_, values)| values) {
+                Some(Values::Int64(values)) => {
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(values, None, None)?;
+                }
+                Some(Values::Double(values)) => {
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(values, None, None)?;
+                }
+                Some(Values::Text(values)) => {
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(values, None, None)?;
+                }
+                None => return Err(anyhow!("{} schema has more columns than data", self.table)),
+            }
+            column.close()?;
+        }
+        row_group.close()?;
+        writer.close()?;
+        fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))
+    }
+}
+
+impl Rows {
+    /// Curve flow per token and wallet, from the swaps.
+    fn holders(&self) -> BTreeMap<(Address, Address), HolderRow> {
+        let mut holders: BTreeMap<(Address, Address), HolderRow> = BTreeMap::new();
+        for swap in &self.swaps {
+            let holder = holders
+                .entry((swap.token, swap.trader))
+                .or_insert_with(|| HolderRow {
+                    first_block: swap.block,
+                    ..HolderRow::default()
+                });
+            if swap.buy {
+                holder.bought = holder.bought.saturating_add(swap.amount_out);
+                holder.buys += 1;
+            } else {
+                holder.sold = holder.sold.saturating_add(swap.amount_in);
+                holder.sells += 1;
+            }
+            holder.first_block = holder.first_block.min(swap.block);
+            holder.last_block = holder.last_block.max(swap.block);
+        }
+        holders
+    }
+
+    fn tables(&self) -> [Columns; 3] {
+        let launches = &self.launches;
+        let swaps = &self.swaps;
+        let holders = self.holders();
+        [
+            Columns::new("launches", launches.len())
+                .int("block", launches.iter().map(|l| l.block))
+                .int("log_index", launches.iter().map(|l| l.log_index))
+                .text(
+                    "tx_hash",
+                    launches.iter().map(|l| format!("{:?}", l.tx_hash)),
+                )
+                .text("token", launches.iter().map(|l| format!("{:?}", l.token)))
+                .text(
+                    "creator",
+                    launches.iter().map(|l| format!("{:?}", l.creator)),
+                )
+                .text("pool", launches.iter().map(|l| format!("{:?}", l.pool)))
+                .text("name", launches.iter().map(|l| l.name.clone()))
+                .text("symbol", launches.iter().map(|l| l.symbol.clone()))
+                .text(
+                    "virtual_mon",
+                    launches.iter().map(|l| l.virtual_mon.to_string()),
+                )
+                .text(
+                    "virtual_token",
+                    launches.iter().map(|l| l.virtual_token.to_string()),
+                )
+                .text(
+                    "target_token_amount",
+                    launches.iter().map(|l| l.target_token_amount.to_string()),
+                ),
+            Columns::new("swaps", swaps.len())
+                .int("block", swaps.iter().map(|s| s.block))
+                .int("log_index", swaps.iter().map(|s| s.log_index))
+                .text("tx_hash", swaps.iter().map(|s| format!("{:?}", s.tx_hash)))
+                .text("token", swaps.iter().map(|s| format!("{:?}", s.token)))
+                .text("trader", swaps.iter().map(|s| format!("{:?}", s.trader)))
+                .text(
+                    "side",
+                    swaps
+                        .iter()
+                        .map(|s| if s.buy { "buy" } else { "sell" }.to_string()),
+                )
+                .text("amount_in", swaps.iter().map(|s| s.amount_in.to_string()))
+                .text("amount_out", swaps.iter().map(|s| s.amount_out.to_string())),
+            Columns::new("holders", holders.len())
+                .text(
+                    "token",
+                    holders.keys().map(|(token, _)| format!("{token:?}")),
+                )
+                .text(
+                    "holder",
+                    holders.keys().map(|(_, holder)| format!("{holder:?}")),
+                )
+                .text("bought", holders.values().map(|h| h.bought.to_string()))
+                .text("sold", holders.values().map(|h| h.sold.to_string()))
+                .int("buys", holders.values().map(|h| h.buys))
+                .int("sells", holders.values().map(|h| h.sells))
+                .int("first_block", holders.values().map(|h| h.first_block))
+                .int("last_block", holders.values().map(|h| h.last_block)),
+        ]
+    }
+}
+
+/// `<out>/<table>/block_start=<partition>/blocks_<from>_<to>.parquet`, hive-style so
+/// DuckDB and pyarrow pick the partition column up from the path.
+fn file_path(out: &Path, table: &str, partition: u64, from: u64, to: u64) -> PathBuf {
+    out.join(table)
+        .join(format!("block_start={partition}"))
+        .join(format!("blocks_{from}_{to}.parquet"))
+}
+
+fn load_cursor(path: &Path) -> Result<Cursor> {
+    match fs::read_to_string(path) {
+        Ok(json) => {
+            serde_json::from_str(&json).with_context(|| format!("invalid {}", path.display()))
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Cursor::default()),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+fn save_cursor(path: &Path, cursor: &Cursor) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string(cursor)?)
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))
+}
+
+/// Writes the bonding curve's launches, swaps and per-wallet curve flow over a block
+/// range to Parquet, one file per table and partition touched. Each run picks up where
+/// the last one into the same directory stopped, so a cron job keeps the export current.
+pub async fn run(args: &ExportArgs) -> Result<()> {
+    let (provider, curve) = curve_history().await?;
+
+    let cursor_path = args.out.join("cursor.json");
+    let cursor = load_cursor(&cursor_path)?;
+    let from = match (args.from_block, cursor.exported_to) {
+        (Some(from), _) => from,
+        (None, Some(exported_to)) => exported_to + 1,
+        (None, None) => {
+            return Err(anyhow!(
+                "pass --from-block for the first export into {}",
+                args.out.display()
+            ))
+        }
+    };
+    let to = match args.to_block {
+        Some(to) => to,
+        None => provider.get_block_number().await?.as_u64(),
+    };
+    if from > to {
+        println!(
+            "Nothing to export: {} is already at block {}",
+            args.out.display(),
+            to
+        );
+        return Ok(());
+    }
+
+    let span = args.partition_blocks.max(1);
+    let mut start = from;
+    while start <= to {
+        let partition = start / span * span;
+        let end = (partition + span - 1).min(to);
+        let rows = fetch(&provider, curve, start, end, args.chunk_blocks.max(1)).await?;
+        for table in rows.tables() {
+            if table.rows == 0 {
+                continue;
+            }
+            let path = file_path(&args.out, table.table, partition, start, end);
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("failed to create {}", dir.display()))?;
+            }
+            table.write(&path)?;
+        }
+        // A re-export of earlier blocks doesn't move the cursor back.
+        let exported_to = cursor.exported_to.map_or(end, |done| done.max(end));
+        save_cursor(
+            &cursor_path,
+            &Cursor {
+                exported_to: Some(exported_to),
+            },
+        )?;
+        println!(
+            "Exported blocks {}-{}: {} launches, {} swaps",
+            start,
+            end,
+            rows.launches.len(),
+            rows.swaps.len()
+        );
+        start = end + 1;
+    }
+    Ok(())
+}
+
+/// A provider and the bonding curve address from `RPC_URL` and `BONDING_CURVE_ADDRESS`,
+/// for commands that read the curve's history.
+pub async fn curve_history() -> Result<(Provider<Http>, Address)> {
+    let curve = env::var("BONDING_CURVE_ADDRESS")
+        .context("BONDING_CURVE_ADDRESS is required to read the curve's history")?
+        .parse()
+        .context("invalid BONDING_CURVE_ADDRESS")?;
+    let rpc_url = env::var("RPC_URL").context("RPC_URL is required to read the curve's history")?;
+    let pool = RpcPool::from_env(&rpc_url);
+    let rpc_url = if pool.has_fallbacks() {
+        pool.best(None).await?
+    } else {
+        rpc_url
+    };
+    let provider = Provider::<Http>::try_from(rpc_url.as_str()).context("invalid RPC_URL")?;
+    Ok((provider, curve))
+}
+
+/// Fetches the curve's launch, buy and sell logs over the block range, in chain order.
+pub async fn fetch(
+    provider: &Provider<Http>,
+    curve: Address,
+    from: u64,
+    to: u64,
+    chunk_blocks: u64,
+) -> Result<Rows> {
+    let create = CurveCreateFilter::signature();
+    let buy = CurveBuyFilter::signature();
+    let sell = CurveSellFilter::signature();
+    let mut rows = Rows::default();
+    let mut start = from;
+    while start <= to {
+        let end = (start + chunk_blocks - 1).min(to);
+        let filter = Filter::new()
+            .address(curve)
+            .from_block(start)
+            .to_block(end)
+            .topic0(vec![create, buy, sell]);
+        let mut logs = provider
+            .get_logs(&filter)
+            .await
+            .with_context(|| format!("failed to fetch logs for blocks {}-{}", start, end))?;
+        logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+        for log in logs {
+            let (Some(topic), Some(block)) = (log.topics.first().copied(), log.block_number) else {
+                continue;
+            };
+            let block = block.as_u64();
+            let log_index = log.log_index.map_or(0, |index| index.as_u64());
+            let tx_hash = log.transaction_hash.unwrap_or_default();
+            if topic == create {
+                let event: CurveCreateFilter = parse_log(log)?;
+                rows.launches.push(LaunchRow {
+                    block,
+                    log_index,
+                    tx_hash,
+                    token: event.token,
+                    creator: event.creator,
+                    pool: event.pool,
+                    name: event.name,
+                    symbol: event.symbol,
+                    virtual_mon: event.virtual_mon,
+                    virtual_token: event.virtual_token,
+                    target_token_amount: event.target_token_amount,
+                });
+            } else {
+                let (buy, trader, token, amount_in, amount_out) = if topic == buy {
+                    let event: CurveBuyFilter = parse_log(log)?;
+                    (
+                        true,
+                        event.sender,
+                        event.token,
+                        event.amount_in,
+                        event.amount_out,
+                    )
+                } else {
+                    let event: CurveSellFilter = parse_log(log)?;
+                    (
+                        false,
+                        event.sender,
+                        event.token,
+                        event.amount_in,
+                        event.amount_out,
+                    )
+                };
+                rows.swaps.push(SwapRow {
+                    block,
+                    log_index,
+                    tx_hash,
+                    token,
+                    trader,
+                    buy,
+                    amount_in,
+                    amount_out,
+                });
+            }
+        }
+        info!("Fetched blocks {}-{} of {}", start, end, to);
+        start = end + 1;
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    fn swap(block: u64, trader: Address, buy: bool, amount_in: u64, amount_out: u64) -> SwapRow {
+        SwapRow {
+            block,
+            log_index: 0,
+            tx_hash: H256::zero(),
+            token: Address::repeat_byte(0x70),
+            trader,
+            buy,
+            amount_in: U256::from(amount_in),
+            amount_out: U256::from(amount_out),
+        }
+    }
+
+    #[test]
+    fn holders_sum_each_wallets_curve_flow() {
+        let alice = Address::repeat_byte(0xa1);
+        let bob = Address::repeat_byte(0xb0);
+        let rows = Rows {
+            launches: Vec::new(),
+            swaps: vec![
+                swap(10, alice, true, 5, 500),
+                swap(12, bob, true, 1, 90),
+                swap(15, alice, false, 200, 2),
+            ],
+        };
+        let holders = rows.holders();
+        let alice = &holders[&(Address::repeat_byte(0x70), alice)];
+        assert_eq!(
+            (alice.bought, alice.sold),
+            (U256::from(500), U256::from(200))
+        );
+        assert_eq!(
+            (alice.buys, alice.sells, alice.first_block, alice.last_block),
+            (1, 1, 10, 15)
+        );
+        assert_eq!(holders.len(), 2);
+    }
+
+    #[test]
+    fn tables_round_trip_through_parquet() {
+        let dir = std::env::temp_dir().join(format!("nadfun-export-{}", std::process::id()));
+        let rows = Rows {
+            launches: Vec::new(),
+            swaps: vec![swap(7, Address::repeat_byte(0xa1), true, 5, 500)],
+        };
+        let [launches, swaps, _] = rows.tables();
+        assert_eq!(launches.rows, 0);
+        let path = file_path(&dir, swaps.table, 0, 7, 9);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        swaps.write(&path).unwrap();
+        assert!(path.ends_with("swaps/block_start=0/blocks_7_9.parquet"));
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 1);
+        let row = reader.get_row_iter(None).unwrap().next().unwrap().unwrap();
+        assert_eq!(row.get_long(0).unwrap(), 7);
+        assert_eq!(row.get_string(5).unwrap(), "buy");
+        assert_eq!(row.get_string(7).unwrap(), "500");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn the_cursor_starts_empty_and_survives_a_save() {
+        let path =
+            std::env::temp_dir().join(format!("nadfun-export-cursor-{}.json", std::process::id()));
+        assert_eq!(load_cursor(&path).unwrap().exported_to, None);
+        save_cursor(
+            &path,
+            &Cursor {
+                exported_to: Some(42),
+            },
+        )
+        .unwrap();
+        assert_eq!(load_cursor(&path).unwrap().exported_to, Some(42));
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,215 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+use tracing::{error, info};
+
+use crate::app::AppConfig;
+use crate::file_lock;
+use crate::notify::Event;
+use crate::state;
+
+/// Who is trading, as last written to the lease file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Lease {
+    holder: String,
+    renewed_at: u64,
+}
+
+/// A primary and a standby sharing the position store: whichever holds the lease next
+/// to it trades and renews it, and the other takes over once it has gone unrenewed for
+/// `FAILOVER_WINDOW_SECS`, resuming the open positions and orders from the store like
+/// any restart. Both hosts need the same `STATE_FILE`, on a shared volume.
+#[derive(Debug, Clone)]
+pub struct FailoverConfig {
+    pub instance: String,
+    pub standby: bool,
+    pub window: Duration,
+    path: PathBuf,
+}
+
+impl FailoverConfig {
+    /// Enabled by `FAILOVER_ROLE`, `primary` or `standby`. `FAILOVER_INSTANCE` names
+    /// this host in the lease and `FAILOVER_LEASE_FILE` defaults to the state file's
+    /// `.lease`.
+    pub fn from_env(state_file: &Path) -> Result<Option<Self>> {
+        let Some(role) = env::var("FAILOVER_ROLE").ok() else {
+            return Ok(None);
+        };
+        let standby = match role.trim().to_ascii_lowercase().as_str() {
+            "primary" => false,
+            "standby" => true,
+            other => {
+                return Err(anyhow!(
+                    "unknown FAILOVER_ROLE `{other}` (expected primary or standby)"
+                ))
+            }
+        };
+        let window = env::var("FAILOVER_WINDOW_SECS")
+            .ok()
+            .map(|v| v.parse().context("invalid FAILOVER_WINDOW_SECS"))
+            .transpose()?
+            .unwrap_or(30);
+        if window == 0 {
+            return Err(anyhow!("FAILOVER_WINDOW_SECS must be positive"));
+        }
+        let instance = env::var("FAILOVER_INSTANCE").unwrap_or_else(|_| {
+            format!(
+                "{}-{}",
+                role.trim().to_ascii_lowercase(),
+                std::process::id()
+            )
+        });
+        Ok(Some(Self {
+            instance,
+            standby,
+            window: Duration::from_secs(window),
+            path: env::var("FAILOVER_LEASE_FILE")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| state_file.with_extension("lease")),
+        }))
+    }
+
+    /// Takes or renews the lease unless someone else renewed it within the window, in
+    /// which case their name is returned. A standby counts a missing lease as renewed
+    /// at `started`, so a primary starting alongside it gets the first window.
+    fn claim(&self, now: u64, started: u64) -> Result<Option<String>> {
+        let _lock = file_lock::exclusive(&self.path)?;
+        let lease = match fs::read_to_string(&self.path) {
+            Ok(json) => Some(
+                serde_json::from_str::<Lease>(&json)
+                    .with_context(|| format!("corrupt lease file {}", self.path.display()))?,
+            ),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        let (holder, renewed_at) = match &lease {
+            Some(lease) => (lease.holder.as_str(), lease.renewed_at),
+            None if self.standby => ("the primary", started),
+            None => (self.instance.as_str(), 0),
+        };
+        if holder != self.instance && now.saturating_sub(renewed_at) < self.window.as_secs() {
+            return Ok(Some(holder.to_string()));
+        }
+        let lease = Lease {
+            holder: self.instance.clone(),
+            renewed_at: now,
+        };
+        let tmp = self.path.with_extension("lease.tmp");
+        fs::write(&tmp, serde_json::to_string(&lease)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        Ok(None)
+    }
+
+    fn renew_every(&self) -> Duration {
+        self.window / 3
+    }
+
+    /// Waits until this instance holds the lease. `false` if a shutdown came first.
+    pub async fn acquire(&self, cfg: &AppConfig) -> Result<bool> {
+        let started = state::unix_now();
+        let mut waiting_on = None;
+        loop {
+            match self.claim(state::unix_now(), started)? {
+                None => break,
+                Some(holder) => {
+                    if waiting_on.as_ref() != Some(&holder) {
+                        info!(
+                            "Standing by: {} holds the lease {}",
+                            holder,
+                            self.path.display()
+                        );
+                        waiting_on = Some(holder);
+                    }
+                }
+            }
+            tokio::select! {
+                () = tokio::time::sleep(self.renew_every()) => {}
+                () = cfg.shutdown.wait() => return Ok(false),
+            }
+        }
+        if waiting_on.is_some() {
+            let message = format!("{} took over trading", self.instance);
+            info!("{}", message);
+            cfg.notifier.send(Event::Error, message);
+        } else {
+            info!("{} holds the lease {}", self.instance, self.path.display());
+        }
+        Ok(true)
+    }
+
+    /// Renews the lease while trading. If another instance has taken it over, shuts down
+    /// gracefully, so only the trades already in flight finish here.
+    pub async fn hold(&self, cfg: &AppConfig) {
+        loop {
+            tokio::time::sleep(self.renew_every()).await;
+            match self.claim(state::unix_now(), 0) {
+                Ok(None) => {}
+                Ok(Some(holder)) => {
+                    let message =
+                        format!("{} lost the lease to {}; stopping", self.instance, holder);
+                    error!("{}", message);
+                    cfg.notifier.send(Event::Error, message);
+                    cfg.shutdown.request();
+                    break;
+                }
+                Err(err) => error!("Failed to renew the lease: {:#}", err),
+            }
+        }
+        std::future::pending::<()>().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(name: &str, standby: bool, path: &Path) -> FailoverConfig {
+        FailoverConfig {
+            instance: name.into(),
+            standby,
+            window: Duration::from_secs(30),
+            path: path.to_path_buf(),
+        }
+    }
+
+    fn lease_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "nadfun-failover-{}-{}.lease",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn the_standby_takes_over_once_the_primary_stops_renewing() {
+        let path = lease_path("takeover");
+        let primary = instance("a", false, &path);
+        let standby = instance("b", true, &path);
+        assert_eq!(primary.claim(1_000, 1_000).unwrap(), None);
+        assert_eq!(standby.claim(1_010, 1_000).unwrap(), Some("a".into()));
+        assert_eq!(primary.claim(1_020, 0).unwrap(), None);
+        assert_eq!(standby.claim(1_049, 1_000).unwrap(), Some("a".into()));
+        assert_eq!(standby.claim(1_050, 1_000).unwrap(), None);
+        // The primary coming back finds the standby trading and waits in turn.
+        assert_eq!(primary.claim(1_055, 1_055).unwrap(), Some("b".into()));
+    }
+
+    #[test]
+    fn a_standby_gives_a_primary_starting_with_it_the_first_window() {
+        let path = lease_path("start");
+        let standby = instance("b", true, &path);
+        assert_eq!(
+            standby.claim(1_000, 1_000).unwrap(),
+            Some("the primary".into())
+        );
+        assert_eq!(standby.claim(1_030, 1_000).unwrap(), None);
+    }
+}
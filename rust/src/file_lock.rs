@@ -0,0 +1,79 @@
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tokio::time::{sleep, Duration};
+
+/// Longest pause between attempts while [`exclusive_async`] waits for the lock.
+const MAX_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Takes an exclusive lock on the `.lock` file next to `path`, blocking until every other
+/// process holding it lets go. Released when the returned file is dropped. Called on a
+/// multi-threaded runtime, a contended wait hands the worker's other tasks off first.
+pub fn exclusive(path: &Path) -> Result<File> {
+    let (file, lock_path) = open(path)?;
+    if try_lock(&file, &lock_path)? {
+        return Ok(file);
+    }
+    let wait = || file.lock_exclusive();
+    let locked = match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+        Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(wait),
+        _ => wait(),
+    };
+    locked.with_context(|| format!("failed to lock {}", lock_path.display()))?;
+    Ok(file)
+}
+
+/// [`exclusive`] for async code: retries with a growing pause instead of blocking the
+/// runtime while another process holds the lock.
+pub async fn exclusive_async(path: &Path) -> Result<File> {
+    let (file, lock_path) = open(path)?;
+    let mut backoff = Duration::from_millis(1);
+    while !try_lock(&file, &lock_path)? {
+        sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+    Ok(file)
+}
+
+fn open(path: &Path) -> Result<(File, PathBuf)> {
+    let lock_path = path.with_extension("lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("failed to open {}", lock_path.display()))?;
+    Ok((file, lock_path))
+}
+
+/// Whether the lock was taken; `false` when someone else holds it.
+fn try_lock(file: &File, lock_path: &Path) -> Result<bool> {
+    match file.try_lock_exclusive() {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == fs2::lock_contended_error().kind() => Ok(false),
+        Err(err) => Err(err).with_context(|| format!("failed to lock {}", lock_path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn async_waiters_get_the_lock_once_it_is_released() {
+        let path = std::env::temp_dir().join(format!("nadfun-flock-{}.json", std::process::id()));
+        let held = exclusive(&path).unwrap();
+        let waiter = tokio::spawn({
+            let path = path.clone();
+            async move { exclusive_async(&path).await.map(|_| ()) }
+        });
+        sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+        drop(held);
+        waiter.await.unwrap().unwrap();
+        let _ = std::fs::remove_file(path.with_extension("lock"));
+    }
+}
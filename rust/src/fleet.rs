@@ -0,0 +1,302 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, I256};
+use serde::Serialize;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::app::AppConfig;
+use crate::chain;
+use crate::cli::Command;
+use crate::control::Controls;
+use crate::ledger::{Ledger, TradeRecord};
+use crate::state::{self, StateStore};
+
+/// Registers the instance with a fleet coordinator and reports a heartbeat with its
+/// version, mode and summary stats, so operators running many instances see their
+/// health in one place. The coordinator counts an instance as down when its heartbeats
+/// stop; a clean exit reports `stopped` first.
+#[derive(Debug, Clone)]
+pub struct FleetConfig {
+    /// Stable across restarts: `FLEET_INSTANCE_ID`, or generated once and kept in
+    /// `FLEET_ID_FILE`.
+    pub id: String,
+    /// The `--instance` overlay the process was started with.
+    name: Option<String>,
+    url: String,
+    token: Option<String>,
+    interval: Duration,
+}
+
+impl FleetConfig {
+    /// Enabled by `FLEET_COORDINATOR_URL`, which heartbeats are POSTed to every
+    /// `FLEET_HEARTBEAT_SECS` (30), with `FLEET_TOKEN` as a bearer token when set.
+    /// `FLEET_ID_FILE` defaults to the state file's `.instance`.
+    pub fn from_env(state_file: &Path, name: Option<&str>) -> Result<Option<Self>> {
+        let Some(url) = env::var("FLEET_COORDINATOR_URL")
+            .ok()
+            .filter(|v| !v.is_empty())
+        else {
+            return Ok(None);
+        };
+        let secs: u64 = env::var("FLEET_HEARTBEAT_SECS")
+            .ok()
+            .map(|v| v.parse().context("invalid FLEET_HEARTBEAT_SECS"))
+            .transpose()?
+            .unwrap_or(30);
+        let id = match env::var("FLEET_INSTANCE_ID").ok().filter(|v| !v.is_empty()) {
+            Some(id) => id,
+            None => instance_id(
+                &env::var("FLEET_ID_FILE")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|_| state_file.with_extension("instance")),
+            )?,
+        };
+        Ok(Some(Self {
+            id,
+            name: name.map(String::from),
+            url,
+            token: env::var("FLEET_TOKEN").ok().filter(|v| !v.is_empty()),
+            interval: Duration::from_secs(secs.max(1)),
+        }))
+    }
+}
+
+/// The id kept in `path`, generated and written on first use.
+fn instance_id(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(id) if !id.trim().is_empty() => return Ok(id.trim().to_string()),
+        Ok(_) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+    let id = format!("nadfun-{:016x}", rand::random::<u64>());
+    fs::write(path, &id).with_context(|| format!("failed to write {}", path.display()))?;
+    info!("Generated instance id {} into {}", id, path.display());
+    Ok(id)
+}
+
+/// The mode a command runs the bot in, as reported to the coordinator.
+pub fn mode(command: Option<&Command>) -> &'static str {
+    match command {
+        None => "watchlist",
+        Some(Command::Sniper) => "sniper",
+        Some(Command::Copy) => "copy",
+        Some(Command::Dca) => "dca",
+        Some(Command::Flow) => "flow",
+        Some(Command::Repl) => "repl",
+        Some(Command::Exec) => "exec",
+        Some(Command::Order(_)) => "orders",
+        Some(_) => "command",
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct Summary {
+    open_positions: usize,
+    trades_today: usize,
+    pnl_today: f64,
+    trades_total: usize,
+    pnl_total: f64,
+}
+
+/// Round trips closed since `day_start`, and all of them, from the ledger.
+fn summary(records: &[TradeRecord], open_positions: usize, day_start: u64) -> Summary {
+    let native = |pnl: I256| {
+        let amount = chain::profile().native_f64(pnl.unsigned_abs());
+        if pnl.is_negative() {
+            -amount
+        } else {
+            amount
+        }
+    };
+    let mut summary = Summary {
+        open_positions,
+        trades_total: records.len(),
+        ..Summary::default()
+    };
+    let (mut today, mut total) = (I256::zero(), I256::zero());
+    for record in records {
+        total += record.pnl();
+        if record.closed_at >= day_start {
+            summary.trades_today += 1;
+            today += record.pnl();
+        }
+    }
+    summary.pnl_today = native(today);
+    summary.pnl_total = native(total);
+    summary
+}
+
+#[derive(Debug, Serialize)]
+struct Heartbeat<'a> {
+    id: &'a str,
+    name: Option<&'a str>,
+    version: &'static str,
+    mode: &'static str,
+    status: &'static str,
+    chain_id: u64,
+    wallet: Address,
+    started_at: u64,
+    at: u64,
+    paused: bool,
+    summary: Summary,
+}
+
+/// What a heartbeat reports on, taken from the config so it can outlive it.
+struct Reporter {
+    config: FleetConfig,
+    client: reqwest::Client,
+    mode: &'static str,
+    wallet: Address,
+    started_at: u64,
+    state: Arc<StateStore>,
+    ledger: Ledger,
+    controls: Arc<Controls>,
+}
+
+impl Reporter {
+    fn new(config: &FleetConfig, cfg: &AppConfig, mode: &'static str) -> Self {
+        let wallet = cfg.recipient.unwrap_or_else(|| {
+            cfg.private_key
+                .parse::<LocalWallet>()
+                .map(|wallet| wallet.address())
+                .unwrap_or_default()
+        });
+        Self {
+            config: config.clone(),
+            client: reqwest::Client::new(),
+            mode,
+            wallet,
+            started_at: state::unix_now(),
+            state: cfg.state.clone(),
+            ledger: Ledger::new(cfg.ledger.path().to_path_buf()),
+            controls: cfg.controls.clone(),
+        }
+    }
+
+    async fn send(&self, status: &'static str) -> Result<()> {
+        let now = state::unix_now();
+        let summary = summary(
+            &self.ledger.load()?,
+            self.state.open_positions()?.len(),
+            now - now % 86_400,
+        );
+        let heartbeat = Heartbeat {
+            id: &self.config.id,
+            name: self.config.name.as_deref(),
+            version: env!("CARGO_PKG_VERSION"),
+            mode: self.mode,
+            status,
+            chain_id: chain::profile().chain_id,
+            wallet: self.wallet,
+            started_at: self.started_at,
+            at: now,
+            paused: self.controls.paused(),
+            summary,
+        };
+        let mut request = self.client.post(&self.config.url).json(&heartbeat);
+        if let Some(token) = &self.config.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await.context("coordinator unreachable")?;
+        if !response.status().is_success() {
+            return Err(anyhow!("coordinator answered {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Reports a heartbeat every `FLEET_HEARTBEAT_SECS` in the background. A failed one is
+/// logged once until heartbeats go through again.
+pub fn start(config: &FleetConfig, cfg: &AppConfig, mode: &'static str) {
+    let reporter = Reporter::new(config, cfg, mode);
+    info!(
+        "Reporting to the fleet coordinator as {}",
+        reporter.config.id
+    );
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(reporter.config.interval);
+        let mut failing = false;
+        loop {
+            ticker.tick().await;
+            match reporter.send("running").await {
+                Ok(()) if failing => {
+                    info!("Fleet heartbeats are going through again");
+                    failing = false;
+                }
+                Ok(()) => {}
+                Err(err) if !failing => {
+                    warn!("Fleet heartbeat failed: {:#}", err);
+                    failing = true;
+                }
+                Err(_) => {}
+            }
+        }
+    });
+}
+
+/// Tells the coordinator the instance stopped on purpose rather than went silent.
+pub async fn stopped(config: &FleetConfig, cfg: &AppConfig, mode: &'static str) {
+    if let Err(err) = Reporter::new(config, cfg, mode).send("stopped").await {
+        warn!(
+            "Failed to report the stop to the fleet coordinator: {:#}",
+            err
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U256;
+
+    #[test]
+    fn the_instance_id_is_generated_once_and_kept() {
+        let path =
+            std::env::temp_dir().join(format!("nadfun-fleet-{}.instance", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let id = instance_id(&path).unwrap();
+        assert!(id.starts_with("nadfun-"));
+        assert_eq!(instance_id(&path).unwrap(), id);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn the_summary_splits_today_from_the_total() {
+        let mon = U256::exp10(18);
+        let trade = |proceeds: U256, closed_at: u64| TradeRecord {
+            token: Address::repeat_byte(1),
+            wallet: Address::repeat_byte(2),
+            buy_tx: None,
+            opened_at: closed_at - 60,
+            closed_at,
+            amount_in: mon,
+            proceeds,
+            gas_spent: U256::zero(),
+            exploration: None,
+            strategy: None,
+        };
+        let records = [
+            trade(mon * 3, 1_000),
+            trade(mon / 2, 90_000),
+            trade(mon * 2, 90_100),
+        ];
+        let summary = summary(&records, 4, 86_400);
+        assert_eq!(
+            summary,
+            Summary {
+                open_positions: 4,
+                trades_today: 2,
+                pnl_today: 0.5,
+                trades_total: 3,
+                pnl_total: 2.5,
+            }
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::VecDeque;
+use std::env;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, I256, U256};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::app::AppConfig;
+use crate::chain;
+use crate::config::Target;
+use crate::engine::ExecutionClient;
+use crate::notify::Event;
+use crate::price_feed::CurveTrade;
+use crate::state::OpenPosition;
+use crate::trading::{round_trip, EntryHints};
+
+/// How often the window is re-evaluated when no trades come in.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Follows a token's buy pressure: holds a long position only while the net native flow
+/// into its curve over the last `window` is positive, entering once it is above
+/// `min_net` and force-selling as soon as it turns negative. The token's exit rules
+/// still apply while holding.
+pub struct FlowConfig {
+    pub window: Duration,
+    pub min_net: U256,
+}
+
+impl FlowConfig {
+    /// `FLOW_WINDOW_SECS` defaults to 60 and `FLOW_MIN_NET_MON` to 0.
+    pub fn from_env() -> Result<Self> {
+        let window: u64 = env::var("FLOW_WINDOW_SECS")
+            .ok()
+            .map(|v| v.parse().context("invalid FLOW_WINDOW_SECS"))
+            .transpose()?
+            .unwrap_or(60);
+        if window == 0 {
+            return Err(anyhow!("FLOW_WINDOW_SECS must be positive"));
+        }
+        Ok(Self {
+            window: Duration::from_secs(window),
+            min_net: chain::profile()
+                .parse_native(&env::var("FLOW_MIN_NET_MON").unwrap_or_else(|_| "0".into()))
+                .context("invalid FLOW_MIN_NET_MON")?,
+        })
+    }
+}
+
+/// Native flow through one token's curve over a rolling window: MON paid in by buys less
+/// MON paid out to sells.
+struct FlowWindow {
+    window: Duration,
+    trades: VecDeque<(Instant, bool, U256)>,
+    bought: U256,
+    sold: U256,
+}
+
+impl FlowWindow {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            trades: VecDeque::new(),
+            bought: U256::zero(),
+            sold: U256::zero(),
+        }
+    }
+
+    fn push(&mut self, at: Instant, buy: bool, mon: U256) {
+        if buy {
+            self.bought += mon;
+        } else {
+            self.sold += mon;
+        }
+        self.trades.push_back((at, buy, mon));
+    }
+
+    /// Drops the trades that fell out of the window by `now`.
+    fn expire(&mut self, now: Instant) {
+        while let Some(&(at, buy, mon)) = self.trades.front() {
+            if now.saturating_duration_since(at) <= self.window {
+                break;
+            }
+            if buy {
+                self.bought -= mon;
+            } else {
+                self.sold -= mon;
+            }
+            self.trades.pop_front();
+        }
+    }
+
+    fn net(&self) -> I256 {
+        I256::from_raw(self.bought) - I256::from_raw(self.sold)
+    }
+}
+
+/// Counts `trade` into `window` if it is in `token`.
+fn record(
+    window: &mut FlowWindow,
+    trade: Result<CurveTrade, RecvError>,
+    token: Address,
+) -> Result<()> {
+    match trade {
+        Ok(trade) if trade.token == token => {
+            window.push(Instant::now(), trade.buy, trade.mon);
+            Ok(())
+        }
+        Ok(_) => Ok(()),
+        Err(RecvError::Lagged(missed)) => {
+            warn!(
+                "Flow window missed {} curve trades; the net flow is off",
+                missed
+            );
+            Ok(())
+        }
+        Err(RecvError::Closed) => Err(anyhow!("the curve trade stream stopped")),
+    }
+}
+
+/// Runs flow-following on `target` until shutdown. Positions are entered through the
+/// normal round trip, which sells on shutdown as usual.
+pub async fn run(
+    cfg: &AppConfig,
+    flow: &FlowConfig,
+    client: &impl ExecutionClient,
+    target: &Target,
+) -> Result<()> {
+    let feed = cfg
+        .price_feed
+        .as_ref()
+        .ok_or_else(|| anyhow!("flow mode reads curve trades from PRICE_FEED=true"))?;
+    let token = target.token;
+    let wallet = cfg.recipient.unwrap_or_else(|| client.wallet());
+    let mut trades = feed.trades();
+    let mut window = FlowWindow::new(flow.window);
+    // A window that hasn't filled yet would read a burst as a trend.
+    let mut entry_from = Instant::now() + flow.window;
+    let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+    let profile = chain::profile();
+    info!(
+        "Following buy pressure on {} over {}s windows",
+        profile.address_url(token),
+        flow.window.as_secs()
+    );
+
+    loop {
+        tokio::select! {
+            trade = trades.recv() => record(&mut window, trade, token)?,
+            _ = ticker.tick() => {}
+            _ = cfg.shutdown.wait() => return Ok(()),
+        }
+        window.expire(Instant::now());
+        if Instant::now() < entry_from || window.net() <= I256::from_raw(flow.min_net) {
+            continue;
+        }
+
+        info!(
+            "Net flow into {:?} is {}, entering",
+            token,
+            signed(window.net())
+        );
+        let hints = EntryHints {
+            strategy: Some("flow"),
+            ..EntryHints::default()
+        };
+        let trip = round_trip(cfg, &target.params, client, token, hints);
+        tokio::pin!(trip);
+        let mut selling = false;
+        let result = loop {
+            tokio::select! {
+                result = &mut trip => break result,
+                trade = trades.recv() => record(&mut window, trade, token)?,
+                _ = ticker.tick() => {}
+            }
+            window.expire(Instant::now());
+            if selling {
+                continue;
+            }
+            let Some(position) = holding(cfg, token, wallet)? else {
+                continue;
+            };
+            // The curve sees the router, not the wallet, so the bot's own buy is taken
+            // back out while it is in the window.
+            let own = if position.held_for() <= flow.window {
+                I256::from_raw(position.amount_in)
+            } else {
+                I256::zero()
+            };
+            let net = window.net() - own;
+            if !net.is_negative() {
+                continue;
+            }
+            let reason = format!("net flow turned to {}", signed(net));
+            info!("Exiting {:?}: {}", token, reason);
+            cfg.notifier
+                .send(Event::Exit, format!("{:?}: {}", token, reason));
+            cfg.audit("flow_exit", json!({ "token": token, "reason": reason }));
+            cfg.controls.force_sell(token);
+            selling = true;
+        };
+        if let Err(err) = result {
+            warn!("Flow trade in {:?} failed: {:#}", token, err);
+            cfg.notifier.send(
+                Event::Error,
+                format!("Flow trade in {:?} failed: {:#}", token, err),
+            );
+            // A refused entry would otherwise be retried on every tick.
+            entry_from = Instant::now() + flow.window;
+        }
+        if cfg.shutdown.requested() {
+            return Ok(());
+        }
+    }
+}
+
+/// `wallet`'s position in `token` once the buy filled, so a force-sell has something to
+/// sell and isn't left pending for the next trip.
+fn holding(cfg: &AppConfig, token: Address, wallet: Address) -> Result<Option<OpenPosition>> {
+    Ok(cfg
+        .state
+        .open_positions()?
+        .into_iter()
+        .find(|position| position.token == token && position.wallet == wallet))
+}
+
+fn signed(net: I256) -> String {
+    let sign = if net.is_negative() { "-" } else { "+" };
+    format!(
+        "{}{}",
+        sign,
+        chain::profile().format_native(net.unsigned_abs())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(token: u8, buy: bool, mon: u64) -> Result<CurveTrade, RecvError> {
+        Ok(CurveTrade {
+            token: Address::repeat_byte(token),
+            buy,
+            mon: U256::from(mon),
+        })
+    }
+
+    #[test]
+    fn the_net_flow_only_counts_the_window() {
+        let mut window = FlowWindow::new(Duration::from_secs(60));
+        let start = Instant::now();
+        window.push(start, true, U256::from(500u64));
+        window.push(start + Duration::from_secs(30), false, U256::from(200u64));
+        window.push(start + Duration::from_secs(50), false, U256::from(100u64));
+        window.expire(start + Duration::from_secs(60));
+        assert_eq!(window.net(), I256::from(200));
+
+        // The early buy falls out and the flow turns negative.
+        window.expire(start + Duration::from_secs(61));
+        assert_eq!(window.net(), I256::from(-300));
+        window.expire(start + Duration::from_secs(200));
+        assert!(window.net().is_zero());
+        assert!(window.trades.is_empty());
+    }
+
+    #[test]
+    fn only_the_followed_tokens_trades_count() {
+        let mut window = FlowWindow::new(Duration::from_secs(60));
+        let token = Address::repeat_byte(1);
+        record(&mut window, trade(1, true, 100), token).unwrap();
+        record(&mut window, trade(3, false, 50), token).unwrap();
+        record(&mut window, trade(1, false, 30), token).unwrap();
+        record(&mut window, Err(RecvError::Lagged(4)), token).unwrap();
+        assert_eq!(window.net(), I256::from(70));
+        assert!(record(&mut window, Err(RecvError::Closed), token).is_err());
+    }
+}
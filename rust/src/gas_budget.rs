@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+
+use crate::chain;
+use crate::file_lock;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DailySpend {
+    date: String,
+    spent: U256,
+}
+
+/// Daily gas spend cap shared by every process pointed at the same file, which they
+/// update under a lock on its `.lock` sibling. Once reached, entries are refused until the next UTC day; exits are always allowed.
+pub struct GasBudget {
+    path: PathBuf,
+    cap: U256,
+}
+
+impl GasBudget {
+    pub fn new(path: PathBuf, cap: U256) -> Self {
+        Self { path, cap }
+    }
+
+    pub fn cap(&self) -> U256 {
+        self.cap
+    }
+
+    pub fn spent_today(&self) -> Result<U256> {
+        Ok(self.load()?.spent)
+    }
+
+    pub fn ensure_entry_allowed(&self) -> Result<()> {
+        let spent = self.spent_today()?;
+        if spent >= self.cap {
+            return Err(anyhow!(
+                "daily gas budget exhausted ({} of {} spent); only exits are allowed until tomorrow",
+                chain::profile().format_native(spent),
+                chain::profile().format_native(self.cap)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Adds `cost` to today's spend and returns the new total.
+    pub async fn record(&self, cost: U256) -> Result<U256> {
+        let _lock = file_lock::exclusive_async(&self.path).await?;
+        let mut spend = self.load()?;
+        spend.spent += cost;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&spend)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to replace {}", self.path.display()))?;
+        Ok(spend.spent)
+    }
+
+    fn load(&self) -> Result<DailySpend> {
+        let today = chrono::Utc::now().date_naive().to_string();
+        let spend = match fs::read_to_string(&self.path) {
+            Ok(json) => serde_json::from_str::<DailySpend>(&json)
+                .with_context(|| format!("corrupt gas budget file {}", self.path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => DailySpend::default(),
+            Err(err) => return Err(err.into()),
+        };
+
+        if spend.date == today {
+            Ok(spend)
+        } else {
+            Ok(DailySpend {
+                date: today,
+                spent: U256::zero(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(name: &str, cap: u64) -> GasBudget {
+        let dir = std::env::temp_dir().join(format!("nadfun-gas-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("gas_budget.json");
+        fs::remove_file(&path).ok();
+        GasBudget::new(path, U256::from(cap))
+    }
+
+    #[tokio::test]
+    async fn spend_accumulates_until_the_cap() {
+        let budget = budget("cap", 100);
+        assert!(budget.ensure_entry_allowed().is_ok());
+        assert_eq!(budget.record(U256::from(60u64)).await.unwrap(), U256::from(60u64));
+        assert!(budget.ensure_entry_allowed().is_ok());
+        assert_eq!(budget.record(U256::from(40u64)).await.unwrap(), U256::from(100u64));
+        assert!(budget.ensure_entry_allowed().is_err());
+    }
+
+    #[tokio::test]
+    async fn spend_rolls_over_at_the_utc_day() {
+        let budget = budget("rollover", 100);
+        let yesterday = DailySpend {
+            date: "2000-01-01".into(),
+            spent: U256::from(500u64),
+        };
+        fs::write(&budget.path, serde_json::to_string(&yesterday).unwrap()).unwrap();
+        assert_eq!(budget.spent_today().unwrap(), U256::zero());
+        assert!(budget.ensure_entry_allowed().is_ok());
+        assert_eq!(budget.record(U256::from(7u64)).await.unwrap(), U256::from(7u64));
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{BlockNumber, U256};
+use ethers::utils::{format_units, parse_units};
+
+/// How the EIP-1559 fees of a transaction the bot signs itself are priced from the
+/// latest base fee and the node's suggested priority fee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasStrategy {
+    /// The suggested priority fee, with room for the base fee to double.
+    Normal,
+    /// `Normal`'s fees, in percent.
+    Aggressive(u64),
+    /// Everything up to a max fee of the cap, in wei, goes to the priority fee.
+    SnipeMax(U256),
+}
+
+/// The fee fields of one transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fees {
+    pub max_fee: U256,
+    pub priority_fee: U256,
+}
+
+impl GasStrategy {
+    /// Reads the base fee of the latest block and the suggested priority fee.
+    pub async fn fees(&self, provider: &Provider<Http>) -> Result<Fees> {
+        let block = provider
+            .get_block(BlockNumber::Latest)
+            .await?
+            .ok_or_else(|| anyhow!("RPC returned no latest block"))?;
+        let base_fee = block
+            .base_fee_per_gas
+            .ok_or_else(|| anyhow!("latest block has no base fee"))?;
+        let tip: U256 = provider
+            .request("eth_maxPriorityFeePerGas", ())
+            .await
+            .context("failed to fetch the suggested priority fee")?;
+        self.price(base_fee, tip)
+    }
+
+    fn price(&self, base_fee: U256, tip: U256) -> Result<Fees> {
+        let normal = Fees {
+            max_fee: base_fee * 2 + tip,
+            priority_fee: tip,
+        };
+        match *self {
+            Self::Normal => Ok(normal),
+            Self::Aggressive(pct) => Ok(Fees {
+                max_fee: normal.max_fee * pct / 100,
+                priority_fee: normal.priority_fee * pct / 100,
+            }),
+            Self::SnipeMax(cap) => {
+                if cap <= base_fee {
+                    return Err(anyhow!(
+                        "gas cap of {} gwei is not above the base fee of {} gwei",
+                        format_units(cap, "gwei")?,
+                        format_units(base_fee, "gwei")?
+                    ));
+                }
+                Ok(Fees {
+                    max_fee: cap,
+                    priority_fee: cap - base_fee,
+                })
+            }
+        }
+    }
+}
+
+impl FromStr for GasStrategy {
+    type Err = anyhow::Error;
+
+    /// `normal`, `aggressive:<pct>` or `max:<gwei>`.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_ascii_lowercase();
+        let (kind, arg) = s.split_once(':').unwrap_or((s.as_str(), ""));
+        match kind {
+            "normal" => Ok(Self::Normal),
+            "aggressive" => Ok(Self::Aggressive(
+                arg.parse()
+                    .with_context(|| format!("gas strategy `{s}` needs a percent after `:`"))?,
+            )),
+            "max" => Ok(Self::SnipeMax(
+                parse_units(arg, "gwei")
+                    .with_context(|| format!("gas strategy `{s}` needs a gwei cap after `:`"))?
+                    .into(),
+            )),
+            other => Err(anyhow!(
+                "unknown gas strategy `{other}` (expected normal, aggressive:<pct> or max:<gwei>)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for GasStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Normal => write!(f, "normal"),
+            Self::Aggressive(pct) => write!(f, "aggressive:{pct}"),
+            Self::SnipeMax(cap) => {
+                write!(f, "max:{}", format_units(*cap, "gwei").map_err(|_| fmt::Error)?)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gwei(n: u64) -> U256 {
+        U256::from(n) * U256::exp10(9)
+    }
+
+    #[test]
+    fn strategies_parse_and_display() {
+        for s in ["normal", "aggressive:150", "max:250.000000000"] {
+            assert_eq!(s.parse::<GasStrategy>().unwrap().to_string(), s);
+        }
+        assert_eq!("MAX:250".parse::<GasStrategy>().unwrap(), GasStrategy::SnipeMax(gwei(250)));
+        assert!("aggressive".parse::<GasStrategy>().is_err());
+        assert!("fast".parse::<GasStrategy>().is_err());
+    }
+
+    #[test]
+    fn fees_scale_with_the_strategy() {
+        let normal = GasStrategy::Normal.price(gwei(50), gwei(2)).unwrap();
+        assert_eq!(normal, Fees { max_fee: gwei(102), priority_fee: gwei(2) });
+
+        let aggressive = GasStrategy::Aggressive(150).price(gwei(50), gwei(2)).unwrap();
+        assert_eq!(aggressive, Fees { max_fee: gwei(153), priority_fee: gwei(3) });
+
+        let max = GasStrategy::SnipeMax(gwei(300)).price(gwei(50), gwei(2)).unwrap();
+        assert_eq!(max, Fees { max_fee: gwei(300), priority_fee: gwei(250) });
+        assert!(GasStrategy::SnipeMax(gwei(50)).price(gwei(50), gwei(2)).is_err());
+    }
+}
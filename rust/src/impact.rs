@@ -0,0 +1,153 @@
+use std::env;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, U256};
+use tracing::{info, warn};
+
+use crate::trading::apply_slippage;
+use crate::curve::CurveState;
+
+/// Buy minimums from the bonding curve instead of a flat discount: the order's own
+/// price impact is priced in from the reserves, so only `tolerance_bps` of movement by
+/// others is allowed on top. Tight enough to stop sandwiches on small orders without
+/// failing large ones on thin curves.
+pub struct ImpactConfig {
+    pub tolerance_bps: u64,
+    pub max_impact_bps: Option<u64>,
+    /// Refuse entries over `max_impact_bps` instead of only warning.
+    pub abort: bool,
+}
+
+impl ImpactConfig {
+    /// Enabled by `SLIPPAGE_TOLERANCE_BPS`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(tolerance) = env::var("SLIPPAGE_TOLERANCE_BPS").ok() else {
+            return Ok(None);
+        };
+        let tolerance_bps: u64 = tolerance.parse().context("invalid SLIPPAGE_TOLERANCE_BPS")?;
+        if tolerance_bps >= 10_000 {
+            return Err(anyhow!("SLIPPAGE_TOLERANCE_BPS must be below 10000"));
+        }
+        let abort = match env::var("PRICE_IMPACT_ACTION") {
+            Ok(value) if value.eq_ignore_ascii_case("warn") => false,
+            Ok(value) if value.eq_ignore_ascii_case("abort") => true,
+            Ok(value) => return Err(anyhow!("unknown PRICE_IMPACT_ACTION {:?}", value)),
+            Err(_) => true,
+        };
+        Ok(Some(Self {
+            tolerance_bps,
+            max_impact_bps: env::var("MAX_PRICE_IMPACT_BPS")
+                .ok()
+                .map(|v| v.parse().context("invalid MAX_PRICE_IMPACT_BPS"))
+                .transpose()?,
+            abort,
+        }))
+    }
+
+    /// The minimum tokens out of buying `token` with `amount_in`, or None once the
+    /// curve has graduated and the flat slippage applies.
+    pub fn buy_min_out(
+        &self,
+        token: Address,
+        state: &CurveState,
+        amount_in: U256,
+        quoted_out: U256,
+    ) -> Result<Option<U256>> {
+        if state.graduated || state.virtual_mon_reserve.is_zero() {
+            return Ok(None);
+        }
+        let impact_bps = buy_impact_bps(state, amount_in);
+        if let Some(max) = self.max_impact_bps.filter(|max| impact_bps > *max) {
+            if self.abort {
+                return Err(anyhow!(
+                    "price impact {} bps exceeds MAX_PRICE_IMPACT_BPS {}",
+                    impact_bps,
+                    max
+                ));
+            }
+            warn!("Buy of {:?} moves the price {} bps, above {} bps", token, impact_bps, max);
+        }
+        // Never allow more than the router quoted, whatever the curve math says.
+        let expected = buy_out(state, amount_in).min(quoted_out);
+        let min_out = apply_slippage(expected, self.tolerance_bps);
+        info!(
+            "Price impact {} bps; minimum set {} bps under the curve's expected fill",
+            impact_bps, self.tolerance_bps
+        );
+        Ok(Some(min_out))
+    }
+}
+
+/// Tokens out of a buy on the virtual-reserve constant product, before fees.
+fn buy_out(state: &CurveState, amount_in: U256) -> U256 {
+    state.virtual_token_reserve * amount_in / (state.virtual_mon_reserve + amount_in)
+}
+
+/// How much worse than spot the buy fills: `amount_in / (mon_reserve + amount_in)`.
+pub fn buy_impact_bps(state: &CurveState, amount_in: U256) -> u64 {
+    (amount_in * U256::from(10_000u64) / (state.virtual_mon_reserve + amount_in)).as_u64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve(mon: u64, tokens: u64) -> CurveState {
+        CurveState {
+            real_mon_reserve: U256::zero(),
+            virtual_mon_reserve: U256::from(mon),
+            virtual_token_reserve: U256::from(tokens),
+            k: U256::from(mon) * U256::from(tokens),
+            target_token_amount: U256::zero(),
+            init_virtual_mon_reserve: U256::from(mon),
+            init_virtual_token_reserve: U256::from(tokens),
+            graduated: false,
+        }
+    }
+
+    fn config(max_impact_bps: Option<u64>, abort: bool) -> ImpactConfig {
+        ImpactConfig {
+            tolerance_bps: 100,
+            max_impact_bps,
+            abort,
+        }
+    }
+
+    #[test]
+    fn impact_and_fill_follow_the_constant_product() {
+        let state = curve(9_000, 1_000_000);
+        // 1000 in against 9000 virtual MON: a tenth of the pool after the trade.
+        assert_eq!(buy_impact_bps(&state, U256::from(1_000u64)), 1_000);
+        assert_eq!(buy_out(&state, U256::from(1_000u64)), U256::from(100_000u64));
+    }
+
+    #[test]
+    fn minimum_is_the_tolerance_under_the_lower_of_curve_and_quote() {
+        let state = curve(9_000, 1_000_000);
+        let token = Address::zero();
+        let amount = U256::from(1_000u64);
+        let min = config(None, true).buy_min_out(token, &state, amount, U256::from(200_000u64));
+        assert_eq!(min.unwrap(), Some(U256::from(99_000u64)));
+        let min = config(None, true).buy_min_out(token, &state, amount, U256::from(50_000u64));
+        assert_eq!(min.unwrap(), Some(U256::from(49_500u64)));
+    }
+
+    #[test]
+    fn impact_over_the_limit_aborts_or_warns() {
+        let state = curve(9_000, 1_000_000);
+        let token = Address::zero();
+        let amount = U256::from(1_000u64);
+        let quoted = U256::from(100_000u64);
+        assert!(config(Some(500), true).buy_min_out(token, &state, amount, quoted).is_err());
+        let min = config(Some(500), false).buy_min_out(token, &state, amount, quoted);
+        assert_eq!(min.unwrap(), Some(U256::from(99_000u64)));
+    }
+
+    #[test]
+    fn graduated_curves_keep_the_flat_slippage() {
+        let mut state = curve(9_000, 1_000_000);
+        state.graduated = true;
+        let min = config(None, true).buy_min_out(Address::zero(), &state, U256::one(), U256::one());
+        assert_eq!(min.unwrap(), None);
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tokio::time::Duration;
+use tracing::info;
+
+/// Buckets per power of two; values are kept to within about 6% of their true size.
+const SUB_BUCKETS: u64 = 16;
+const BUCKETS: usize = 1024;
+
+static STAGES: Mutex<BTreeMap<&'static str, Histogram>> = Mutex::new(BTreeMap::new());
+
+/// Microsecond timings of one hot-path stage in log-linear buckets, so recording is a
+/// constant-time increment and memory stays fixed however many samples come in.
+struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    max_us: u64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            counts: vec![0; BUCKETS],
+            total: 0,
+            max_us: 0,
+        }
+    }
+
+    fn record(&mut self, us: u64) {
+        self.counts[bucket(us)] += 1;
+        self.total += 1;
+        self.max_us = self.max_us.max(us);
+    }
+
+    /// The bucket floor at or below which `pct` percent of the samples fall.
+    fn percentile(&self, pct: f64) -> u64 {
+        let rank = ((pct / 100.0) * self.total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return floor(index).min(self.max_us);
+            }
+        }
+        self.max_us
+    }
+}
+
+fn bucket(us: u64) -> usize {
+    if us < SUB_BUCKETS {
+        return us as usize;
+    }
+    let exp = 63 - us.leading_zeros() as u64;
+    let mantissa = us >> (exp - 4);
+    (SUB_BUCKETS * (exp - 3) + (mantissa - SUB_BUCKETS)) as usize
+}
+
+fn floor(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let exp = index / SUB_BUCKETS + 3;
+    (index % SUB_BUCKETS + SUB_BUCKETS) << (exp - 4)
+}
+
+/// Adds one timing of `stage`.
+pub fn record(stage: &'static str, elapsed: Duration) {
+    let us = elapsed.as_micros().min(u64::MAX as u128) as u64;
+    if let Ok(mut stages) = STAGES.lock() {
+        stages.entry(stage).or_insert_with(Histogram::new).record(us);
+    }
+}
+
+/// Percentiles of one stage, in microseconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct StageSummary {
+    pub stage: &'static str,
+    pub count: u64,
+    pub p50_us: u64,
+    pub p90_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+}
+
+pub fn summary() -> Vec<StageSummary> {
+    let Ok(stages) = STAGES.lock() else {
+        return Vec::new();
+    };
+    stages
+        .iter()
+        .map(|(stage, histogram)| StageSummary {
+            stage,
+            count: histogram.total,
+            p50_us: histogram.percentile(50.0),
+            p90_us: histogram.percentile(90.0),
+            p99_us: histogram.percentile(99.0),
+            max_us: histogram.max_us,
+        })
+        .collect()
+}
+
+/// Logs every stage's percentiles.
+pub fn report() {
+    for stage in summary() {
+        info!(
+            "Latency {}: n={} p50 {}us p90 {}us p99 {}us max {}us",
+            stage.stage, stage.count, stage.p50_us, stage.p90_us, stage.p99_us, stage.max_us
+        );
+    }
+}
+
+/// Logs the percentiles every `LATENCY_REPORT_SECS`, when set.
+pub fn start_reporting() {
+    let Some(secs) = env::var("LATENCY_REPORT_SECS").ok().and_then(|v| v.parse::<u64>().ok())
+    else {
+        return;
+    };
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(secs.max(1)));
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            report();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_floor_within_a_sixteenth() {
+        for us in (0..100_000u64).chain([u64::MAX / 3, u64::MAX]) {
+            let index = bucket(us);
+            assert!(index < BUCKETS);
+            let floor = floor(index);
+            assert!(floor <= us, "{} floored to {}", us, floor);
+            assert!(us - floor <= us / SUB_BUCKETS, "{} floored to {}", us, floor);
+        }
+    }
+
+    #[test]
+    fn percentiles_pick_the_ranked_bucket() {
+        let mut histogram = Histogram::new();
+        for us in 1..=100 {
+            histogram.record(us);
+        }
+        assert_eq!(histogram.percentile(50.0), 50);
+        assert_eq!(histogram.percentile(90.0), 88);
+        assert_eq!(histogram.percentile(100.0), 100);
+        assert_eq!(histogram.max_us, 100);
+    }
+
+    #[test]
+    fn summary_reports_recorded_stages() {
+        record("latency-test", Duration::from_micros(5));
+        record("latency-test", Duration::from_micros(7));
+        let stage = summary()
+            .into_iter()
+            .find(|stage| stage.stage == "latency-test")
+            .unwrap();
+        assert_eq!(stage.count, 2);
+        assert_eq!(stage.p50_us, 5);
+        assert_eq!(stage.max_us, 7);
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate};
+use clap::Args;
+use ethers::types::{Address, TransactionReceipt, H256, I256, U256};
+use serde::{Deserialize, Serialize};
+
+use crate::chain;
+use crate::engine::ExecutionClient;
+use crate::receipts;
+use crate::state::{self, OpenPosition};
+
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    /// First day to include (UTC, YYYY-MM-DD).
+    #[arg(long)]
+    pub from: Option<NaiveDate>,
+
+    /// Last day to include (UTC, YYYY-MM-DD).
+    #[arg(long)]
+    pub to: Option<NaiveDate>,
+}
+
+/// A completed round trip: what went in, what the sells returned and the gas it cost.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeRecord {
+    pub token: Address,
+    pub wallet: Address,
+    /// `None` for a position adopted at startup.
+    pub buy_tx: Option<H256>,
+    pub opened_at: u64,
+    pub closed_at: u64,
+    pub amount_in: U256,
+    pub proceeds: U256,
+    pub gas_spent: U256,
+    /// The filter the entry was let past, for exploration trades.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exploration: Option<String>,
+    /// The mode that made the entry, `None` for trades recorded before it was kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+}
+
+impl TradeRecord {
+    pub fn closed(position: &OpenPosition) -> Self {
+        Self {
+            token: position.token,
+            wallet: position.wallet,
+            buy_tx: position.buy_tx,
+            opened_at: position.opened_at,
+            closed_at: state::unix_now(),
+            amount_in: position.amount_in,
+            proceeds: position.proceeds,
+            gas_spent: position.gas_spent,
+            exploration: position.exploration.clone(),
+            strategy: position.strategy.clone(),
+        }
+    }
+
+    /// Realized PnL net of gas.
+    pub fn pnl(&self) -> I256 {
+        I256::from_raw(self.proceeds) - I256::from_raw(self.amount_in) - I256::from_raw(self.gas_spent)
+    }
+
+    fn closed_on(&self) -> Option<NaiveDate> {
+        DateTime::from_timestamp(self.closed_at as i64, 0).map(|at| at.date_naive())
+    }
+}
+
+/// Append-only JSONL log of completed round trips.
+pub struct Ledger {
+    path: PathBuf,
+}
+
+impl Ledger {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The log at `TRADE_LEDGER_FILE`, `trades.jsonl` by default.
+    pub fn from_env() -> Self {
+        Self::new(PathBuf::from(
+            env::var("TRADE_LEDGER_FILE").unwrap_or_else(|_| "trades.jsonl".into()),
+        ))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, record: &TradeRecord) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    pub fn load(&self) -> Result<Vec<TradeRecord>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut records = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            records.push(
+                serde_json::from_str(&line)
+                    .with_context(|| format!("corrupt trade ledger {}", self.path.display()))?,
+            );
+        }
+        Ok(records)
+    }
+}
+
+/// Native the sell paid out to `wallet`: its balance change over the sell's block,
+/// with the sell's own gas added back.
+pub async fn native_received(
+    client: &impl ExecutionClient,
+    receipt: &TransactionReceipt,
+    wallet: Address,
+) -> Result<U256> {
+    let block = receipt
+        .block_number
+        .context("sell receipt has no block number")?
+        .as_u64();
+    let before = client.native_balance(wallet, Some(block.saturating_sub(1))).await?;
+    let after = client.native_balance(wallet, Some(block)).await?;
+    Ok((after + receipts::gas_cost(receipt)).saturating_sub(before))
+}
+
+#[derive(Default)]
+struct Totals {
+    trades: usize,
+    amount_in: U256,
+    proceeds: U256,
+    gas: U256,
+    pnl: I256,
+}
+
+impl Totals {
+    fn add(&mut self, record: &TradeRecord) {
+        self.trades += 1;
+        self.amount_in += record.amount_in;
+        self.proceeds += record.proceeds;
+        self.gas += record.gas_spent;
+        self.pnl += record.pnl();
+    }
+
+    fn row(&self, label: &str) -> String {
+        let profile = chain::profile();
+        let pct = if self.amount_in.is_zero() {
+            0.0
+        } else {
+            self.pnl.as_i128() as f64 / self.amount_in.as_u128() as f64 * 100.0
+        };
+        format!(
+            "{:<44}  {:>6}  {:>14}  {:>14}  {:>12}  {:>15}  {:>+8.2}%",
+            label,
+            self.trades,
+            profile.format_native(self.amount_in),
+            profile.format_native(self.proceeds),
+            profile.format_native(self.gas),
+            signed_native(self.pnl),
+            pct
+        )
+    }
+}
+
+/// Prints realized PnL per token and in total for round trips closed between `--from`
+/// and `--to`.
+pub fn run(ledger: &Ledger, args: &ReportArgs) -> Result<()> {
+    let records: Vec<TradeRecord> = ledger
+        .load()?
+        .into_iter()
+        .filter(|record| {
+            let day = record.closed_on();
+            args.from.is_none_or(|from| day.is_some_and(|day| day >= from))
+                && args.to.is_none_or(|to| day.is_some_and(|day| day <= to))
+        })
+        .collect();
+    if records.is_empty() {
+        println!("No completed round trips in {}", ledger.path.display());
+        return Ok(());
+    }
+
+    let mut by_token: BTreeMap<Address, Totals> = BTreeMap::new();
+    let mut by_exploration: BTreeMap<&str, Totals> = BTreeMap::new();
+    let mut total = Totals::default();
+    for record in &records {
+        by_token.entry(record.token).or_default().add(record);
+        if let Some(filter) = &record.exploration {
+            by_exploration.entry(filter).or_default().add(record);
+        }
+        total.add(record);
+    }
+
+    println!(
+        "{:<44}  {:>6}  {:>14}  {:>14}  {:>12}  {:>15}  {:>9}",
+        "token", "trades", "in", "out", "gas", "pnl", "pnl %"
+    );
+    for (token, totals) in &by_token {
+        println!("{}", totals.row(&format!("{:?}", token)));
+    }
+    println!("{}", total.row("total"));
+    for (filter, totals) in &by_exploration {
+        println!("{}", totals.row(&format!("exploring past {}", filter)));
+    }
+    Ok(())
+}
+
+pub fn signed_native(value: I256) -> String {
+    let sign = if value.is_negative() { "-" } else { "+" };
+    format!("{}{}", sign, chain::profile().format_native(value.unsigned_abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(token: u8, amount_in: u64, proceeds: u64, gas_spent: u64) -> TradeRecord {
+        TradeRecord {
+            token: Address::repeat_byte(token),
+            wallet: Address::repeat_byte(1),
+            buy_tx: Some(H256::repeat_byte(token)),
+            opened_at: 1_700_000_000,
+            closed_at: 1_700_000_600,
+            amount_in: U256::from(amount_in),
+            proceeds: U256::from(proceeds),
+            gas_spent: U256::from(gas_spent),
+            exploration: None,
+            strategy: None,
+        }
+    }
+
+    #[test]
+    fn pnl_is_net_of_gas() {
+        assert_eq!(record(1, 100, 150, 10).pnl(), I256::from(40));
+        assert_eq!(record(1, 100, 80, 10).pnl(), I256::from(-30));
+    }
+
+    #[test]
+    fn records_are_dated_by_their_close() {
+        let date = NaiveDate::from_ymd_opt(2023, 11, 14).unwrap();
+        assert_eq!(record(1, 1, 1, 0).closed_on(), Some(date));
+    }
+
+    #[test]
+    fn totals_sum_each_record() {
+        let mut totals = Totals::default();
+        totals.add(&record(1, 100, 150, 10));
+        totals.add(&record(2, 50, 20, 5));
+        assert_eq!(totals.trades, 2);
+        assert_eq!(totals.amount_in, U256::from(150u64));
+        assert_eq!(totals.proceeds, U256::from(170u64));
+        assert_eq!(totals.gas, U256::from(15u64));
+        assert_eq!(totals.pnl, I256::from(5));
+    }
+
+    #[test]
+    fn signed_native_prefixes_the_sign() {
+        let one = I256::from_raw(chain::profile().parse_native("1.5").unwrap());
+        assert!(signed_native(one).starts_with("+1.5"));
+        assert!(signed_native(-one).starts_with("-1.5"));
+    }
+
+    #[test]
+    fn ledger_appends_and_loads_records() {
+        let path = std::env::temp_dir().join(format!("nadfun-ledger-{}.jsonl", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        let ledger = Ledger::new(path.clone());
+        assert!(ledger.load().unwrap().is_empty());
+
+        ledger.record(&record(1, 100, 150, 10)).unwrap();
+        ledger.record(&record(2, 50, 20, 5)).unwrap();
+        let records = ledger.load().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].token, Address::repeat_byte(2));
+        assert_eq!(records[1].pnl(), I256::from(-35));
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,540 @@
+mod accounting;
+mod analytics;
+mod age;
+mod annotate;
+mod app;
+mod approvals;
+mod audit;
+mod backtest;
+mod caches;
+mod calendar;
+mod chain;
+mod chaos;
+pub mod cli;
+mod config;
+mod control;
+mod copytrade;
+mod curve;
+mod dataset;
+mod dca;
+mod depth;
+mod edge;
+mod engine;
+mod entry;
+mod execstats;
+mod exit_guard;
+mod exit_strategy;
+mod explore;
+mod export;
+mod failover;
+mod file_lock;
+mod fleet;
+mod flow;
+mod gas_budget;
+mod gas_strategy;
+mod impact;
+mod latency;
+mod ledger;
+mod lists;
+mod lockdown;
+mod logging;
+mod mev;
+mod nadfun;
+mod notify;
+mod orders;
+mod pinning;
+mod plan;
+mod price_feed;
+mod protocol;
+mod race;
+mod receipts;
+mod recovery;
+mod reentry;
+mod reorg;
+mod repair;
+mod repl;
+mod reputation;
+mod risk;
+mod routing;
+mod rpc_pool;
+mod safety;
+mod scoring;
+mod self_update;
+mod settle;
+mod shutdown;
+mod signals;
+mod signer;
+mod simulate;
+mod sink;
+mod sizing;
+mod sniper;
+mod snapshot;
+mod start;
+mod state;
+mod stats;
+mod telemetry;
+#[cfg(test)]
+mod testing;
+mod trading;
+mod traces;
+mod tuning;
+mod tx_manager;
+mod uniswap_v2;
+mod utilization;
+mod venue;
+mod warmer;
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use app::AppConfig;
+use backtest::BacktestConfig;
+use chain::ChainProfile;
+use cli::{Cli, Command};
+use copytrade::CopyConfig;
+use dca::DcaConfig;
+use execstats::ExecLog;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::Address;
+use flow::FlowConfig;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use ledger::Ledger;
+use notify::Event;
+use orders::OrderAction;
+use telemetry::ErrorReporter;
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+use trading::{resume_positions, round_trip, run_strategy, EntryHints};
+
+pub use config::TradeParams;
+pub use control::ExecOrder;
+pub use engine::{Engine, ExecutionClient, PositionManager, RpcClient, Strategy};
+pub use nadfun::{BuyParams, GasEstimationParams, SellParams};
+pub use state::OpenPosition;
+pub use tx_manager::{RetryPolicy, TimeInForce, TxChain, TxManager};
+
+/// Runs the bot the way the command line asks.
+pub async fn run(cli: Cli) -> Result<()> {
+    cli.load_env()?;
+    logging::init()?;
+
+    chain::init(ChainProfile::from_env()?);
+
+    if let Some(Command::Repair(args)) = &cli.command {
+        return repair::run(args).await;
+    }
+    if let Some(Command::Lists(args)) = &cli.command {
+        return lists::run(cli.config.as_deref(), args);
+    }
+    if let Some(Command::VerifyAudit { path, signer }) = &cli.command {
+        let path = match path {
+            Some(path) => path.clone(),
+            None => PathBuf::from(
+                env::var("AUDIT_LOG_FILE").context("pass a path or set AUDIT_LOG_FILE")?,
+            ),
+        };
+        // The log names its own signer, so the expected one has to come from outside it.
+        let signer = match signer {
+            Some(signer) => *signer,
+            None => env::var("AUDIT_SIGNER")
+                .context("pass --signer or set AUDIT_SIGNER")?
+                .parse()
+                .context("invalid AUDIT_SIGNER")?,
+        };
+        let count = audit::verify(&path, signer)?;
+        println!("{}: {} entries verified, signed by {:?}", path.display(), count, signer);
+        return Ok(());
+    }
+    // Commands that only read local files or history need no wallet or RPC.
+    if let Some(Command::Report(args)) = &cli.command {
+        return ledger::run(&Ledger::from_env(), args);
+    }
+    if let Some(Command::Execution) = &cli.command {
+        return execstats::run(&ExecLog::from_env());
+    }
+    if let Some(Command::Analytics(args)) = &cli.command {
+        return analytics::run(&Ledger::from_env(), &ExecLog::from_env(), args);
+    }
+    if let Some(Command::Risk(args)) = &cli.command {
+        return risk::run(args);
+    }
+    if let Some(Command::Tuning(args)) = &cli.command {
+        return tuning::run(args);
+    }
+    if let Some(Command::Backtest(args)) = &cli.command {
+        return backtest::run(&BacktestConfig::load(&cli)?, args).await;
+    }
+    if let Some(Command::Export(args)) = &cli.command {
+        return export::run(args).await;
+    }
+    if let Some(Command::Features(args)) = &cli.command {
+        return dataset::run(args).await;
+    }
+    if let Some(Command::SelfUpdate(args)) = &cli.command {
+        return self_update::run(args).await;
+    }
+
+    let mut cfg = AppConfig::load(&cli)?;
+    let reporter = ErrorReporter::init(cfg.sentry_dsn.as_deref());
+    if let Some(Command::Lockdown(args)) = &cli.command {
+        return lockdown::run(&cfg, args).await;
+    }
+    if let Some(Command::Order(args)) = &cli.command {
+        if !matches!(args.action, OrderAction::Watch) {
+            return orders::edit(&cfg, &args.action);
+        }
+    }
+    if cfg.rpc_pool.has_fallbacks() {
+        cfg.rpc_url = cfg.rpc_pool.best(None).await?;
+    }
+    if let Some(Command::Plan(args)) = &cli.command {
+        return plan::run(&cfg, args).await;
+    }
+    start_services(&cfg).await?;
+    let mode = fleet::mode(cli.command.as_ref());
+    if let Some(fleet) = &cfg.fleet {
+        fleet::start(fleet, &cfg, mode);
+    }
+    cfg.shutdown.listen()?;
+    if let Some(failover) = &cfg.failover {
+        if !failover.acquire(&cfg).await? {
+            return Ok(());
+        }
+    }
+
+    // After an RPC failover only open positions are resumed, so a one-shot run that
+    // failed after its buy doesn't buy again. Long-running modes restart fully.
+    let mut failovers = 0;
+    let mut resume_only = false;
+    let result = loop {
+        let result = run_command(&cfg, cli.command.as_ref(), resume_only).await;
+        match &result {
+            Err(err)
+                if cfg.rpc_pool.has_fallbacks()
+                    && failovers < cfg.rpc_pool.max_failovers
+                    && matches!(telemetry::classify(err), "rpc" | "timeout") =>
+            {
+                warn!("RPC {} failed mid-run: {:#}", cfg.rpc_url, err);
+                cfg.rpc_url = cfg.rpc_pool.best(Some(&cfg.rpc_url)).await?;
+                failovers += 1;
+                resume_only = true;
+            }
+            _ => break result,
+        }
+    };
+    if let Some(accounting) = &cfg.accounting {
+        let ledger = Ledger::new(cfg.ledger.path().to_path_buf());
+        if let Err(err) = accounting::deliver(accounting, &reqwest::Client::new(), &ledger).await {
+            warn!("Undelivered trades go to the accounting webhook on the next run: {:#}", err);
+        }
+    }
+    if let Some(fleet) = &cfg.fleet {
+        fleet::stopped(fleet, &cfg, mode).await;
+    }
+    sink::flush().await;
+    latency::report();
+    logging::flush();
+    if cfg.shutdown.requested() {
+        let open = cfg.state.open_positions().map(|positions| positions.len()).unwrap_or(0);
+        info!("Shut down cleanly; {} positions left open in the state file", open);
+    }
+    if let Err(err) = &result {
+        cfg.notifier.send(Event::Error, format!("Bot stopped: {:#}", err));
+        let wallet = cfg
+            .private_key
+            .parse::<LocalWallet>()
+            .map(|wallet| wallet.address())
+            .unwrap_or_default();
+        let token = cfg.targets.first().map(|target| target.token);
+        reporter.capture(err, token.unwrap_or_default(), wallet);
+    }
+    result
+}
+
+/// Starts the background tasks trading relies on: protocol and price watches, the
+/// token warmer, the control API and accounting delivery.
+async fn start_services(cfg: &AppConfig) -> Result<()> {
+    if let Some(curve) = cfg.bonding_curve {
+        let interval = Duration::from_secs(cfg.protocol_refresh_secs);
+        protocol::start_watch(&cfg.rpc_url, curve, interval).await?;
+    }
+    if let Some(warmer) = &cfg.warmer {
+        warmer::start(cfg, warmer).await?;
+    }
+    if let (Some(age), Some(curve)) = (&cfg.age, cfg.bonding_curve) {
+        age::start(&cfg.rpc_url, curve, age)?;
+    }
+    if let Some(feed) = &cfg.price_feed {
+        price_feed::start(feed);
+    }
+    if let Some(control) = &cfg.control {
+        let ledger = Ledger::new(cfg.ledger.path().to_path_buf());
+        let (controls, state) = (cfg.controls.clone(), cfg.state.clone());
+        control::serve(control, controls, state, ledger, cfg.signals.clone()).await?;
+    }
+    if let Some(accounting) = &cfg.accounting {
+        accounting::start(accounting, Ledger::new(cfg.ledger.path().to_path_buf()));
+    }
+    if let Some(sink) = &cfg.sink {
+        sink::start(sink);
+    }
+    latency::start_reporting();
+    Ok(())
+}
+
+async fn run_command(cfg: &AppConfig, command: Option<&Command>, resume_only: bool) -> Result<()> {
+    let client = RpcClient::open(cfg).await?;
+    let trading = async {
+        if cfg.control.is_none() {
+            return run_mode(cfg, &client, command, resume_only).await;
+        }
+        tokio::select! {
+            result = run_mode(cfg, &client, command, resume_only) => result,
+            () = simulate::serve(cfg, &client) => Ok(()),
+        }
+    };
+    let settled = async {
+        if cfg.settle.at.is_none() || matches!(command, Some(Command::Settle)) {
+            return trading.await;
+        }
+        tokio::select! {
+            result = trading => result,
+            () = settle::schedule(cfg, &client) => Ok(()),
+        }
+    };
+    let watched = async {
+        let Some(reorgs) = &cfg.reorg else {
+            return settled.await;
+        };
+        tokio::select! {
+            result = settled => result,
+            () = reorg::watch(cfg, &client, reorgs) => Ok(()),
+        }
+    };
+    let Some(failover) = &cfg.failover else {
+        return watched.await;
+    };
+    tokio::select! {
+        result = watched => result,
+        () = failover.hold(cfg) => Ok(()),
+    }
+}
+
+async fn run_mode(
+    cfg: &AppConfig,
+    client: &RpcClient,
+    command: Option<&Command>,
+    resume_only: bool,
+) -> Result<()> {
+    let (provider, trade) = (client.provider(), client.trade());
+
+    if let Some(Command::Depth(args)) = command {
+        return depth::run(trade, args).await;
+    }
+    if let Some(Command::Annotate { tx_hash }) = command {
+        return annotate::run(provider, *tx_hash, &cfg.state, cfg.mev_report_file.as_deref()).await;
+    }
+    if let Some(Command::Settle) = command {
+        return settle::settle(cfg, client, false).await;
+    }
+
+    let wallet = cfg.recipient.unwrap_or_else(|| client.wallet());
+    recovery::reconcile(cfg, client, wallet).await?;
+    resume_positions(cfg, client).await?;
+    let long_running = matches!(
+        command,
+        Some(Command::Sniper)
+            | Some(Command::Dca)
+            | Some(Command::Flow)
+            | Some(Command::Copy)
+            | Some(Command::Exec)
+            | Some(Command::Repl)
+            | Some(Command::Order(_))
+    ) || (command.is_none() && cfg.control.is_some());
+    if resume_only && !long_running {
+        return Ok(());
+    }
+
+    if let Some(Command::Sniper) = command {
+        return sniper::run(cfg, &cfg.sniper, client).await;
+    }
+    if let Some(Command::Copy) = command {
+        let copy = CopyConfig::from_env()?;
+        return copytrade::run(cfg, &copy, client).await;
+    }
+    if let Some(Command::Order(_)) = command {
+        return orders::watch(cfg, client).await;
+    }
+    if let Some(Command::Exec) = command {
+        return execute_orders(cfg, client).await;
+    }
+    if let Some(Command::Repl) = command {
+        return repl::run(cfg, client, resume_only).await;
+    }
+
+    if command.is_none() && cfg.control.is_some() {
+        return trade_watchlist(cfg, client, resume_only).await;
+    }
+    if cfg.targets.is_empty() {
+        return Err(anyhow!("TOKEN_ADDRESS missing and no [[token]] entries in the config file"));
+    }
+
+    if let Some(Command::Dca) = command {
+        let [target] = cfg.targets.as_slice() else {
+            return Err(anyhow!("DCA mode buys a single token; pass --token"));
+        };
+        let dca = DcaConfig::from_env()?;
+        return dca::run(cfg, &dca, client, target).await;
+    }
+    if let Some(Command::Flow) = command {
+        let [target] = cfg.targets.as_slice() else {
+            return Err(anyhow!("flow mode follows a single token; pass --token"));
+        };
+        let flow = FlowConfig::from_env()?;
+        return flow::run(cfg, &flow, client, target).await;
+    }
+
+    if let Some(start_at) = cfg.start_at {
+        start::wait_for_start(start_at, &cfg.rpc_url, &cfg.clock_check)
+            .await
+            .context("synchronized start failed")?;
+    }
+
+    let results = futures_util::future::join_all(
+        cfg.targets
+            .iter()
+            .map(|target| {
+                let hints = EntryHints::default();
+                round_trip(cfg, &target.params, client, target.token, hints)
+            }),
+    )
+    .await;
+    if cfg.targets.len() == 1 {
+        return results.into_iter().next().unwrap_or(Ok(()));
+    }
+
+    let mut first_err = None;
+    for (target, result) in cfg.targets.iter().zip(results) {
+        if let Err(err) = result {
+            warn!("Round trip for {:?} failed: {:#}", target.token, err);
+            first_err.get_or_insert(err);
+        }
+    }
+    first_err.map_or(Ok(()), Err)
+}
+
+/// The default mode with the control API on: trades the targets like a one-shot run,
+/// then keeps going so tokens added through the API get their round trip too. After a
+/// failover only added tokens are traded, as the targets already had theirs.
+async fn trade_watchlist(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    resume_only: bool,
+) -> Result<()> {
+    let mut added = cfg.controls.added().await;
+    let mut trading = HashSet::new();
+    let mut finished: HashMap<Address, Instant> = HashMap::new();
+    let mut in_flight = FuturesUnordered::new();
+    let mut prune = tokio::time::interval(cfg.retention.prune_interval);
+    if !resume_only {
+        if let Some(start_at) = cfg.start_at {
+            start::wait_for_start(start_at, &cfg.rpc_url, &cfg.clock_check)
+                .await
+                .context("synchronized start failed")?;
+        }
+        for target in &cfg.targets {
+            trading.insert(target.token);
+            in_flight.push(watched_trip(cfg, &target.params, client, target.token));
+        }
+    }
+    info!("Trading the watchlist, {} tokens to start with", in_flight.len());
+
+    loop {
+        if cfg.shutdown.requested() && in_flight.is_empty() {
+            return Ok(());
+        }
+        tokio::select! {
+            token = added.recv(), if !cfg.shutdown.requested() => {
+                let token = token.ok_or_else(|| anyhow!("control API stopped"))?;
+                if cfg.blocklist.contains(&token) {
+                    warn!("Skipping {:?}: on the blocklist", token);
+                    continue;
+                }
+                if !trading.insert(token) {
+                    info!("Skipping {:?}: already trading it", token);
+                    continue;
+                }
+                in_flight.push(watched_trip(cfg, cfg.params_for(token), client, token));
+            }
+            Some(token) = in_flight.next(), if !in_flight.is_empty() => {
+                trading.remove(&token);
+                finished.insert(token, Instant::now());
+            }
+            _ = cfg.shutdown.wait(), if !cfg.shutdown.requested() => {}
+            _ = prune.tick() => {
+                // A finished token is only traded again once it leaves the watchlist and is
+                // added back, so idle ones are dropped rather than kept forever.
+                finished.retain(|token, at| {
+                    if at.elapsed() < cfg.retention.idle_ttl || trading.contains(token) {
+                        return true;
+                    }
+                    if cfg.controls.unwatch(*token) {
+                        info!("{:?} left the watchlist after idling", token);
+                    }
+                    false
+                });
+                caches::observe("watchlist", cfg.controls.watchlist_len());
+            }
+        }
+    }
+}
+
+/// Execution-only mode: no discovery, every buy comes from an external strategy through
+/// the control API's `/orders` and runs the full round trip with its safety checks.
+async fn execute_orders(cfg: &AppConfig, client: &impl ExecutionClient) -> Result<()> {
+    if cfg.control.is_none() {
+        return Err(anyhow!("exec mode takes its orders from the control API; set CONTROL_ADDR"));
+    }
+    let mut orders = cfg.controls.orders().await;
+    info!("Waiting for orders from the control API");
+    run_strategy(cfg, client, &mut *orders).await?;
+    if !cfg.shutdown.requested() {
+        return Err(anyhow!("control API stopped"));
+    }
+    Ok(())
+}
+
+
+async fn watched_trip(
+    cfg: &AppConfig,
+    params: &TradeParams,
+    client: &impl ExecutionClient,
+    token: Address,
+) -> Address {
+    let hints = EntryHints {
+        watchlist: true,
+        strategy: Some("watchlist"),
+        ..EntryHints::default()
+    };
+    loop {
+        if let Err(err) = round_trip(cfg, params, client, token, hints).await {
+            warn!("Round trip for {:?} failed: {:#}", token, err);
+            let message = format!("Round trip for {:?} failed: {:#}", token, err);
+            cfg.notifier.send(Event::Error, message);
+        }
+        let Some(reentry) = &cfg.reentry else {
+            break;
+        };
+        let Some(stop_out) = reentry.take(token) else {
+            break;
+        };
+        info!("Stopped out of {:?}, waiting to re-enter", token);
+        if !reentry.wait(cfg, client, token, &stop_out).await {
+            break;
+        }
+        cfg.notifier.send(Event::Buy, format!("{:?}: re-entering after a stop-out", token));
+    }
+    token
+}
@@ -0,0 +1,314 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Subcommand, ValueEnum};
+use ethers::types::Address;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, BlockedToken, ConfigFile, Profile, TokenProfile};
+
+#[derive(Debug, Args)]
+pub struct ListArgs {
+    #[command(subcommand)]
+    pub action: ListAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ListAction {
+    /// Merge a CSV or JSON file into the config file; rows replace entries with the same address.
+    Import {
+        list: ListKind,
+        path: PathBuf,
+        /// Drop the existing entries instead of merging.
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Write the config file's list as CSV or JSON, picked by the file extension.
+    Export { list: ListKind, path: PathBuf },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ListKind {
+    /// `[[token]]` sections with their per-token overrides.
+    Watchlist,
+    /// `[[blocked]]` tokens.
+    Blocklist,
+}
+
+/// One watchlist row: the address and every per-token override as its own column.
+#[derive(Debug, Serialize, Deserialize)]
+struct WatchRow {
+    address: Address,
+    amount_in_mon: Option<String>,
+    amount_sizing: Option<String>,
+    slippage_bps: Option<u64>,
+    take_profit_pct: Option<f64>,
+    stop_loss_pct: Option<f64>,
+    trailing_stop_pct: Option<f64>,
+    max_hold_secs: Option<u64>,
+    min_hold_secs: Option<u64>,
+    exit_tranches: Option<String>,
+    exit_reserve_drop_pct: Option<f64>,
+    exit_creator_dump_pct: Option<f64>,
+    venue: Option<String>,
+}
+
+impl From<WatchRow> for TokenProfile {
+    fn from(row: WatchRow) -> Self {
+        Self {
+            address: row.address,
+            profile: Profile {
+                amount_in_mon: row.amount_in_mon,
+                amount_sizing: row.amount_sizing,
+                slippage_bps: row.slippage_bps,
+                take_profit_pct: row.take_profit_pct,
+                stop_loss_pct: row.stop_loss_pct,
+                trailing_stop_pct: row.trailing_stop_pct,
+                max_hold_secs: row.max_hold_secs,
+                min_hold_secs: row.min_hold_secs,
+                exit_tranches: row.exit_tranches,
+                exit_reserve_drop_pct: row.exit_reserve_drop_pct,
+                exit_creator_dump_pct: row.exit_creator_dump_pct,
+                venue: row.venue,
+            },
+        }
+    }
+}
+
+impl From<&TokenProfile> for WatchRow {
+    fn from(token: &TokenProfile) -> Self {
+        let profile = token.profile.clone();
+        Self {
+            address: token.address,
+            amount_in_mon: profile.amount_in_mon,
+            amount_sizing: profile.amount_sizing,
+            slippage_bps: profile.slippage_bps,
+            take_profit_pct: profile.take_profit_pct,
+            stop_loss_pct: profile.stop_loss_pct,
+            trailing_stop_pct: profile.trailing_stop_pct,
+            max_hold_secs: profile.max_hold_secs,
+            min_hold_secs: profile.min_hold_secs,
+            exit_tranches: profile.exit_tranches,
+            exit_reserve_drop_pct: profile.exit_reserve_drop_pct,
+            exit_creator_dump_pct: profile.exit_creator_dump_pct,
+            venue: profile.venue,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    fn of(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Ok(Self::Csv),
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Ok(Self::Json),
+            _ => Err(anyhow!("{} must end in .csv or .json", path.display())),
+        }
+    }
+}
+
+/// Imports into or exports from `config_path` (`config.toml` when not given).
+pub fn run(config_path: Option<&Path>, args: &ListArgs) -> Result<()> {
+    let config_path = config_path.unwrap_or(Path::new(config::DEFAULT_CONFIG_FILE));
+    let mut file = if config_path.exists() {
+        ConfigFile::load(Some(config_path))?
+    } else {
+        ConfigFile::default()
+    };
+
+    match &args.action {
+        ListAction::Import { list, path, replace } => {
+            let count = match list {
+                ListKind::Watchlist => {
+                    let rows: Vec<WatchRow> = read_rows(path)?;
+                    let count = rows.len();
+                    let imported: Vec<TokenProfile> = rows.into_iter().map(Into::into).collect();
+                    for token in &imported {
+                        let invalid = || format!("invalid settings for token {:?}", token.address);
+                        let profile = file.defaults.clone().overlay(&token.profile);
+                        profile.resolve().with_context(invalid)?;
+                        if file.is_blocked(token.address) {
+                            return Err(anyhow!("token {:?} is on the blocklist", token.address));
+                        }
+                    }
+                    if *replace {
+                        file.tokens.clear();
+                    }
+                    for token in imported {
+                        file.tokens.retain(|existing| existing.address != token.address);
+                        file.tokens.push(token);
+                    }
+                    count
+                }
+                ListKind::Blocklist => {
+                    let rows: Vec<BlockedToken> = read_rows(path)?;
+                    let count = rows.len();
+                    if *replace {
+                        file.blocked.clear();
+                    }
+                    for row in rows {
+                        if file.tokens.iter().any(|token| token.address == row.address) {
+                            println!("Removing blocked token {:?} from the watchlist", row.address);
+                            file.tokens.retain(|token| token.address != row.address);
+                        }
+                        file.blocked.retain(|existing| existing.address != row.address);
+                        file.blocked.push(row);
+                    }
+                    count
+                }
+            };
+            if config_path.exists() {
+                let backup = config_path.with_extension("toml.bak");
+                fs::copy(config_path, &backup)
+                    .with_context(|| format!("failed to back up {}", config_path.display()))?;
+                println!("Previous config saved to {}", backup.display());
+            }
+            file.save(config_path)?;
+            println!(
+                "Imported {} rows into {}: {} watchlist tokens, {} blocked",
+                count,
+                config_path.display(),
+                file.tokens.len(),
+                file.blocked.len()
+            );
+        }
+        ListAction::Export { list, path } => {
+            let count = match list {
+                ListKind::Watchlist => {
+                    let rows: Vec<WatchRow> = file.tokens.iter().map(Into::into).collect();
+                    write_rows(path, &rows)?;
+                    rows.len()
+                }
+                ListKind::Blocklist => {
+                    write_rows(path, &file.blocked)?;
+                    file.blocked.len()
+                }
+            };
+            println!("Exported {} rows to {}", count, path.display());
+        }
+    }
+    Ok(())
+}
+
+fn read_rows<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Vec<T>> {
+    let invalid = || format!("invalid list file {}", path.display());
+    match Format::of(path)? {
+        Format::Csv => csv::Reader::from_path(path)
+            .with_context(invalid)?
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .with_context(invalid),
+        Format::Json => {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            serde_json::from_str(&contents).with_context(invalid)
+        }
+    }
+}
+
+fn write_rows<T: Serialize>(path: &Path, rows: &[T]) -> Result<()> {
+    let failed = || format!("failed to write {}", path.display());
+    match Format::of(path)? {
+        Format::Csv => {
+            let mut writer = csv::Writer::from_path(path).with_context(failed)?;
+            for row in rows {
+                writer.serialize(row).with_context(failed)?;
+            }
+            writer.flush().with_context(failed)
+        }
+        Format::Json => fs::write(path, serde_json::to_string_pretty(rows)?).with_context(failed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("nadfun-lists-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn format_follows_the_extension() {
+        assert!(matches!(Format::of(Path::new("list.CSV")), Ok(Format::Csv)));
+        assert!(matches!(Format::of(Path::new("list.json")), Ok(Format::Json)));
+        assert!(Format::of(Path::new("list.txt")).is_err());
+        assert!(Format::of(Path::new("list")).is_err());
+    }
+
+    #[test]
+    fn watchlist_csv_leaves_empty_columns_unset() {
+        let path = temp("watch.csv");
+        let token = Address::repeat_byte(1);
+        fs::write(
+            &path,
+            format!(
+                "address,amount_in_mon,slippage_bps,take_profit_pct,stop_loss_pct,\
+                 trailing_stop_pct,max_hold_secs,exit_tranches,exit_reserve_drop_pct,\
+                 exit_creator_dump_pct\n{:?},0.5,150,,,,,50@30,,\n",
+                token
+            ),
+        )
+        .unwrap();
+        let rows: Vec<WatchRow> = read_rows(&path).unwrap();
+        let tokens: Vec<TokenProfile> = rows.into_iter().map(Into::into).collect();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].address, token);
+        assert_eq!(tokens[0].profile.amount_in_mon.as_deref(), Some("0.5"));
+        assert_eq!(tokens[0].profile.slippage_bps, Some(150));
+        assert_eq!(tokens[0].profile.take_profit_pct, None);
+        assert_eq!(tokens[0].profile.exit_tranches.as_deref(), Some("50@30"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn blocklist_round_trips_through_json() {
+        let path = temp("blocked.json");
+        let rows = vec![BlockedToken {
+            address: Address::repeat_byte(2),
+            reason: Some("honeypot".into()),
+        }];
+        write_rows(&path, &rows).unwrap();
+        let read: Vec<BlockedToken> = read_rows(&path).unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].address, rows[0].address);
+        assert_eq!(read[0].reason.as_deref(), Some("honeypot"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn blocking_a_token_removes_it_from_the_watchlist() {
+        let config_path = temp("config.toml");
+        let list_path = temp("import.json");
+        let token = Address::repeat_byte(3);
+        let _ = fs::remove_file(&config_path);
+        let mut file = ConfigFile::default();
+        file.tokens.push(TokenProfile {
+            address: token,
+            profile: Profile::default(),
+        });
+        file.save(&config_path).unwrap();
+        write_rows(&list_path, &[BlockedToken { address: token, reason: None }]).unwrap();
+
+        let args = ListArgs {
+            action: ListAction::Import {
+                list: ListKind::Blocklist,
+                path: list_path.clone(),
+                replace: false,
+            },
+        };
+        run(Some(&config_path), &args).unwrap();
+        let file = ConfigFile::load(Some(&config_path)).unwrap();
+        assert!(file.tokens.is_empty());
+        assert!(file.is_blocked(token));
+        for path in [config_path.clone(), list_path, config_path.with_extension("toml.bak")] {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::{BTreeSet, HashSet};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use ethers::abi::{self, Token};
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, BlockNumber, Bytes, TransactionRequest, U256};
+use ethers::utils::id;
+use serde_json::json;
+
+use crate::chain;
+use crate::nadfun::TokenHelper;
+use crate::app::AppConfig;
+
+#[derive(Debug, Args)]
+pub struct LockdownArgs {
+    /// The wallet suspected to be compromised.
+    pub wallet: Address,
+
+    /// Where its funds go; defaults to EMERGENCY_ADDRESS.
+    #[arg(long)]
+    pub to: Option<Address>,
+
+    /// Gas price for the sweep, in percent of the current gas price.
+    #[arg(long, default_value_t = 300)]
+    pub gas_pct: u64,
+}
+
+/// Wallets no process may trade from, shared through a JSON file so a lockdown reaches
+/// bots that are already running.
+#[derive(Debug, Clone)]
+pub struct LockList {
+    path: PathBuf,
+}
+
+impl LockList {
+    pub fn from_env() -> Self {
+        Self {
+            path: PathBuf::from(
+                env::var("LOCKED_WALLETS_FILE").unwrap_or_else(|_| "locked_wallets.json".into()),
+            ),
+        }
+    }
+
+    pub fn load(&self) -> Result<HashSet<Address>> {
+        match fs::read_to_string(&self.path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("corrupt lock list {}", self.path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HashSet::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn contains(&self, wallet: Address) -> Result<bool> {
+        Ok(self.load()?.contains(&wallet))
+    }
+
+    fn add(&self, wallet: Address) -> Result<()> {
+        let mut locked = self.load()?;
+        if !locked.insert(wallet) {
+            return Ok(());
+        }
+        let locked: BTreeSet<Address> = locked.into_iter().collect();
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&locked)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to replace {}", self.path.display()))
+    }
+}
+
+/// Locks `wallet` so no bot trades from it again, then sweeps every known token and
+/// all of its MON to the emergency address at a raised gas price, and drops its open
+/// positions from the state file.
+pub async fn run(cfg: &AppConfig, args: &LockdownArgs) -> Result<()> {
+    let to = match args.to {
+        Some(to) => to,
+        None => env::var("EMERGENCY_ADDRESS")
+            .context("pass --to or set EMERGENCY_ADDRESS")?
+            .parse()
+            .context("invalid EMERGENCY_ADDRESS")?,
+    };
+    if to == args.wallet {
+        return Err(anyhow!("the emergency address is the wallet being locked"));
+    }
+
+    cfg.locks.add(args.wallet)?;
+    println!(
+        "Wallet {:?} locked in {}; running bots stop using it before their next trade",
+        args.wallet,
+        cfg.locks.path.display()
+    );
+    cfg.audit("lockdown", json!({ "wallet": args.wallet, "to": to }));
+
+    let key = &cfg.private_key;
+    if !key.parse::<LocalWallet>().is_ok_and(|w| w.address() == args.wallet) {
+        return Err(anyhow!("no configured key for {:?}; locked but not swept", args.wallet));
+    }
+
+    let provider = Provider::<Http>::try_from(cfg.rpc_url.as_str()).context("invalid RPC_URL")?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    let wallet = key.parse::<LocalWallet>()?.with_chain_id(chain_id);
+    let client = SignerMiddleware::new(provider, wallet);
+    let token_helper = TokenHelper::new(cfg.rpc_url.clone(), key.clone()).await?;
+    let gas_price = client.get_gas_price().await? * U256::from(args.gas_pct) / U256::from(100u64);
+    let mut nonce = client
+        .get_transaction_count(args.wallet, Some(BlockNumber::Pending.into()))
+        .await?;
+
+    let positions: Vec<_> = cfg
+        .state
+        .open_positions()?
+        .into_iter()
+        .filter(|position| position.wallet == args.wallet)
+        .collect();
+    let mut tokens: BTreeSet<Address> = positions.iter().map(|position| position.token).collect();
+    tokens.extend(cfg.targets.iter().map(|target| target.token));
+
+    let mut reserved_gas = U256::zero();
+    for token in tokens {
+        let balance = token_helper.balance_of(token, args.wallet).await?;
+        if balance.is_zero() {
+            continue;
+        }
+        let mut data = id("transfer(address,uint256)").to_vec();
+        data.extend(abi::encode(&[Token::Address(to), Token::Uint(balance)]));
+        let mut tx = TransactionRequest::new()
+            .to(token)
+            .data(Bytes::from(data))
+            .nonce(nonce)
+            .gas_price(gas_price);
+        let gas = client.estimate_gas(&tx.clone().into(), None).await.unwrap_or(100_000u64.into());
+        tx = tx.gas(gas);
+        match client.send_transaction(tx, None).await {
+            Ok(sent) => {
+                println!("Sweeping {} of {:?}: {:?}", balance, token, sent.tx_hash());
+                reserved_gas += gas * gas_price;
+                nonce += U256::one();
+            }
+            Err(err) => println!("Failed to sweep {:?}: {:#}", token, err),
+        }
+    }
+
+    let profile = chain::profile();
+    let native = client.get_balance(args.wallet, None).await?;
+    let transfer_gas = U256::from(21_000u64) * gas_price;
+    let value = native.saturating_sub(reserved_gas + transfer_gas);
+    if value.is_zero() {
+        println!(
+            "{} balance {} doesn't cover the sweep gas",
+            profile.native_symbol,
+            profile.format_native(native)
+        );
+    } else {
+        let tx = TransactionRequest::new()
+            .to(to)
+            .value(value)
+            .nonce(nonce)
+            .gas(21_000u64)
+            .gas_price(gas_price);
+        let sent = client
+            .send_transaction(tx, None)
+            .await
+            .context("failed to sweep native balance")?;
+        println!("Sweeping {}: {:?}", profile.format_native(value), sent.tx_hash());
+    }
+
+    for position in &positions {
+        cfg.state.close(position.token, position.wallet)?;
+    }
+    println!(
+        "Dropped {} open positions of {:?}; replace its key before trading again",
+        positions.len(),
+        args.wallet
+    );
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use std::env;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, Result};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+
+/// Swaps the installed filter, for changes made while the bot runs.
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Installs the global subscriber. `LOG_FORMAT=json` writes one JSON object per line
+/// with the enclosing trade and position spans; anything else is human-readable text.
+/// `RUST_LOG` filters as usual and defaults to `info`; [`set_filter`] replaces it later.
+/// Built with the `otlp` feature, spans also go to `OTEL_EXPORTER_OTLP_ENDPOINT` when set.
+pub fn init() -> Result<()> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let (filter, handle) = reload::Layer::new(filter);
+    let format = match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => fmt::layer().json().with_current_span(true).with_span_list(true).boxed(),
+        Ok("text") | Err(_) => fmt::layer().boxed(),
+        Ok(other) => return Err(anyhow!("unknown LOG_FORMAT {:?}; expected text or json", other)),
+    };
+    let subscriber = tracing_subscriber::registry().with(filter).with(format);
+    #[cfg(feature = "otlp")]
+    let subscriber = subscriber.with(otlp::layer()?);
+    subscriber
+        .try_init()
+        .map_err(|err| anyhow!("failed to install the log subscriber: {}", err))?;
+    FILTER.set(handle).ok();
+    Ok(())
+}
+
+/// The filter in effect, in `RUST_LOG` syntax.
+pub fn filter() -> Result<String> {
+    handle()?
+        .with_current(|filter| filter.to_string())
+        .map_err(|err| anyhow!("failed to read the log filter: {}", err))
+}
+
+/// Replaces the filter with `directives` in `RUST_LOG` syntax, e.g.
+/// `info,nadfun_trading_bot::gas_budget=debug`.
+pub fn set_filter(directives: &str) -> Result<()> {
+    let filter = EnvFilter::try_new(directives)
+        .map_err(|err| anyhow!("invalid log filter {:?}: {}", directives, err))?;
+    handle()?
+        .reload(filter)
+        .map_err(|err| anyhow!("failed to replace the log filter: {}", err))
+}
+
+/// Sends the spans still buffered for export before the process exits.
+pub fn flush() {
+    #[cfg(feature = "otlp")]
+    otlp::shutdown();
+}
+
+fn handle() -> Result<&'static reload::Handle<EnvFilter, Registry>> {
+    FILTER.get().ok_or_else(|| anyhow!("the log subscriber is not installed"))
+}
+
+/// Short random ID tying together every log line of one trade.
+pub fn trade_id() -> String {
+    format!("{:08x}", rand::random::<u32>())
+}
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use std::env;
+    use std::sync::OnceLock;
+
+    use anyhow::{Context, Result};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::SpanExporter;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use opentelemetry_sdk::{runtime, Resource};
+    use tracing::Subscriber;
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    const SERVICE: &str = "nadfun_trading_bot";
+
+    static PROVIDER: OnceLock<TracerProvider> = OnceLock::new();
+
+    /// Exports spans over OTLP/HTTP in batches, if `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+    /// The exporter reads the endpoint and headers from the standard `OTEL_*` variables.
+    pub fn layer<S>() -> Result<Option<impl Layer<S>>>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        if env::var("OTEL_EXPORTER_OTLP_ENDPOINT").map_or(true, |v| v.is_empty()) {
+            return Ok(None);
+        }
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .build()
+            .context("failed to build the OTLP span exporter")?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_resource(Resource::new([KeyValue::new("service.name", SERVICE)]))
+            .build();
+        let tracer = provider.tracer(SERVICE);
+        PROVIDER.set(provider).ok();
+        Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+    }
+
+    pub fn shutdown() {
+        if let Some(provider) = PROVIDER.get() {
+            if let Err(err) = provider.shutdown() {
+                eprintln!("failed to flush the OTLP spans: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_changes_take_effect_and_bad_ones_are_refused() {
+        init().unwrap();
+        set_filter("warn,nadfun_trading_bot::gas_budget=debug").unwrap();
+        let current = filter().unwrap();
+        assert!(current.contains("nadfun_trading_bot::gas_budget=debug"), "{}", current);
+        assert!(current.contains("warn"), "{}", current);
+
+        assert!(set_filter("info,[unclosed").is_err());
+        assert_eq!(filter().unwrap(), current);
+    }
+
+    #[test]
+    fn trade_ids_are_eight_hex_digits() {
+        let id = trade_id();
+        assert_eq!(id.len(), 8);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+}
@@ -0,0 +1,258 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, Transaction, TransactionReceipt, H256, U256};
+use ethers::utils::keccak256;
+use serde::{Deserialize, Serialize};
+
+use crate::receipts::wait_for_receipt;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MevReport {
+    pub tx_hash: H256,
+    pub token: Address,
+    pub block: u64,
+    pub position: usize,
+    pub front_runners: Vec<Address>,
+    pub sandwiched_by: Option<Address>,
+    pub quoted_out: U256,
+    pub received: U256,
+    pub cost_bps: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct MevStats {
+    pub trades: usize,
+    pub front_run: usize,
+    pub sandwiched: usize,
+    pub avg_cost_bps: f64,
+}
+
+/// Inspects the block that included `tx_hash` for other transactions touching `token`
+/// immediately around ours, and compares the tokens actually received against the quote.
+pub async fn analyze(
+    provider: &Provider<Http>,
+    tx_hash: H256,
+    token: Address,
+    recipient: Address,
+    quoted_out: U256,
+) -> Result<MevReport> {
+    let receipt = wait_for_receipt(provider, tx_hash).await?;
+    let block_number = receipt
+        .block_number
+        .ok_or_else(|| anyhow!("receipt has no block number"))?;
+    let block = provider
+        .get_block_with_txs(block_number)
+        .await?
+        .ok_or_else(|| anyhow!("block {} not found", block_number))?;
+
+    let position = block
+        .transactions
+        .iter()
+        .position(|tx| tx.hash == tx_hash)
+        .ok_or_else(|| anyhow!("transaction missing from its block"))?;
+    let ours = &block.transactions[position];
+
+    let touches_token = |tx: &&Transaction| tx.from != ours.from && calldata_mentions(tx, token);
+    let before: Vec<&Transaction> = block.transactions[..position]
+        .iter()
+        .filter(touches_token)
+        .collect();
+    let after: Vec<&Transaction> = block.transactions[position + 1..]
+        .iter()
+        .filter(touches_token)
+        .collect();
+
+    let sandwiched_by = before
+        .iter()
+        .find(|front| after.iter().any(|back| back.from == front.from))
+        .map(|tx| tx.from);
+
+    let received = received_amount(&receipt, token, recipient);
+    let cost_bps = if quoted_out.is_zero() {
+        0
+    } else {
+        (quoted_out.saturating_sub(received) * U256::from(10_000u64) / quoted_out).as_u64()
+    };
+
+    Ok(MevReport {
+        tx_hash,
+        token,
+        block: block_number.as_u64(),
+        position,
+        front_runners: before.iter().map(|tx| tx.from).collect(),
+        sandwiched_by,
+        quoted_out,
+        received,
+        cost_bps,
+    })
+}
+
+pub fn append_report(path: &Path, report: &MevReport) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(report)?)?;
+    Ok(())
+}
+
+pub fn find_report(path: &Path, tx_hash: H256) -> Result<Option<MevReport>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let report: MevReport = serde_json::from_str(&line)?;
+        if report.tx_hash == tx_hash {
+            return Ok(Some(report));
+        }
+    }
+    Ok(None)
+}
+
+pub fn load_stats(path: &Path) -> Result<MevStats> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut stats = MevStats::default();
+    let mut total_cost = 0u64;
+
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let report: MevReport = serde_json::from_str(&line)?;
+        stats.trades += 1;
+        stats.front_run += usize::from(!report.front_runners.is_empty());
+        stats.sandwiched += usize::from(report.sandwiched_by.is_some());
+        total_cost += report.cost_bps;
+    }
+
+    if stats.trades > 0 {
+        stats.avg_cost_bps = total_cost as f64 / stats.trades as f64;
+    }
+    Ok(stats)
+}
+
+fn calldata_mentions(tx: &Transaction, token: Address) -> bool {
+    let args = tx.input.get(4..).unwrap_or_default();
+    tx.to == Some(token)
+        || args.chunks_exact(32).any(|word| {
+            word[..12].iter().all(|b| *b == 0) && &word[12..] == token.as_bytes()
+        })
+}
+
+/// Tokens transferred to `recipient` in the receipt.
+pub fn received_amount(receipt: &TransactionReceipt, token: Address, recipient: Address) -> U256 {
+    let transfer = H256::from(keccak256("Transfer(address,address,uint256)"));
+    receipt
+        .logs
+        .iter()
+        .filter(|log| log.address == token && log.topics.len() == 3 && log.topics[0] == transfer)
+        .filter(|log| Address::from(log.topics[2]) == recipient)
+        .map(|log| U256::from_big_endian(&log.data))
+        .fold(U256::zero(), |acc, amount| acc + amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::Log;
+
+    fn word(address: Address) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[12..].copy_from_slice(address.as_bytes());
+        word
+    }
+
+    #[test]
+    fn calldata_mentions_the_token_as_target_or_argument() {
+        let token = Address::repeat_byte(7);
+        let direct = Transaction {
+            to: Some(token),
+            ..Default::default()
+        };
+        assert!(calldata_mentions(&direct, token));
+
+        let mut input = vec![0xaa, 0xbb, 0xcc, 0xdd];
+        input.extend_from_slice(&[0u8; 32]);
+        input.extend_from_slice(&word(token));
+        let routed = Transaction {
+            to: Some(Address::repeat_byte(1)),
+            input: input.into(),
+            ..Default::default()
+        };
+        assert!(calldata_mentions(&routed, token));
+
+        let unrelated = Transaction {
+            to: Some(Address::repeat_byte(1)),
+            input: vec![0xaa, 0xbb, 0xcc, 0xdd].into(),
+            ..Default::default()
+        };
+        assert!(!calldata_mentions(&unrelated, token));
+    }
+
+    #[test]
+    fn received_amount_sums_transfers_to_the_recipient() {
+        let token = Address::repeat_byte(7);
+        let recipient = Address::repeat_byte(2);
+        let transfer = H256::from(keccak256("Transfer(address,address,uint256)"));
+        let log = |address: Address, to: Address, amount: u64| {
+            let mut data = [0u8; 32];
+            U256::from(amount).to_big_endian(&mut data);
+            Log {
+                address,
+                topics: vec![transfer, H256::zero(), H256::from(word(to))],
+                data: data.to_vec().into(),
+                ..Default::default()
+            }
+        };
+        let receipt = TransactionReceipt {
+            logs: vec![
+                log(token, recipient, 40),
+                log(token, recipient, 2),
+                log(token, Address::repeat_byte(3), 1_000),
+                log(Address::repeat_byte(9), recipient, 1_000),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(received_amount(&receipt, token, recipient), U256::from(42u64));
+    }
+
+    #[test]
+    fn stats_count_front_runs_sandwiches_and_average_cost() {
+        let path = std::env::temp_dir().join(format!("nadfun-mev-{}.jsonl", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        let report = |front_runners: Vec<Address>, sandwiched_by, cost_bps| MevReport {
+            tx_hash: H256::zero(),
+            token: Address::zero(),
+            block: 1,
+            position: 0,
+            front_runners,
+            sandwiched_by,
+            quoted_out: U256::zero(),
+            received: U256::zero(),
+            cost_bps,
+        };
+        let attacker = Address::repeat_byte(5);
+        append_report(&path, &report(vec![], None, 10)).unwrap();
+        append_report(&path, &report(vec![attacker], Some(attacker), 50)).unwrap();
+        append_report(&path, &report(vec![attacker], None, 30)).unwrap();
+
+        let stats = load_stats(&path).unwrap();
+        assert_eq!(stats.trades, 3);
+        assert_eq!(stats.front_run, 2);
+        assert_eq!(stats.sandwiched, 1);
+        assert_eq!(stats.avg_cost_bps, 30.0);
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,304 @@
+//! The nad.fun SDK behind the ethers types the rest of the bot uses. The SDK is built on
+//! alloy; values are converted at this boundary so nothing else has to know.
+
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, H256, U256};
+use nadfun_sdk::constants::{BONDING_CURVE_ROUTER, DEX_ROUTER};
+use nadfun_sdk::prelude as sdk;
+use nadfun_sdk::{IBondingCurveRouter, IDexRouter};
+
+use crate::gas_strategy::Fees;
+
+fn to_sdk_u256(value: U256) -> sdk::U256 {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    sdk::U256::from_be_bytes(bytes)
+}
+
+fn from_sdk_u256(value: sdk::U256) -> U256 {
+    U256::from_big_endian(&value.to_be_bytes::<32>())
+}
+
+fn to_sdk_address(address: Address) -> sdk::Address {
+    sdk::Address::from(address.0)
+}
+
+fn from_sdk_address(address: sdk::Address) -> Address {
+    Address::from(address.into_array())
+}
+
+fn from_sdk_hash(hash: sdk::B256) -> H256 {
+    H256::from(hash.0)
+}
+
+/// Broadcasts a router call at the nonce and gas of a [`TxOptions`]. The two routers'
+/// bindings are separate types with the same builder methods.
+macro_rules! send {
+    ($call:expr, $tx:expr) => {{
+        let tx: TxOptions = $tx;
+        $call
+            .nonce(tx.nonce.as_u64())
+            .gas(tx.gas_limit.as_u64())
+            .max_fee_per_gas(tx.fees.max_fee.as_u128())
+            .max_priority_fee_per_gas(tx.fees.priority_fee.as_u128())
+            .send()
+            .await?
+    }};
+}
+
+/// The SDK router a trade quoted through `router` has to be sent to.
+fn sdk_router(router: Address) -> Result<sdk::Router> {
+    let router = to_sdk_address(router);
+    if router == DEX_ROUTER.parse::<sdk::Address>()? {
+        Ok(sdk::Router::Dex(router))
+    } else if router == BONDING_CURVE_ROUTER.parse::<sdk::Address>()? {
+        Ok(sdk::Router::BondingCurve(router))
+    } else {
+        Err(anyhow!("unknown nad.fun router {:?}", from_sdk_address(router)))
+    }
+}
+
+/// A buy of `token` paying `amount_in` native.
+#[derive(Debug, Clone)]
+pub struct BuyParams {
+    pub token: Address,
+    pub amount_in: U256,
+    pub amount_out_min: U256,
+    pub recipient: Address,
+    pub deadline: U256,
+}
+
+/// A sell of `amount_in` of `token` for native.
+#[derive(Debug, Clone)]
+pub struct SellParams {
+    pub token: Address,
+    pub amount_in: U256,
+    pub amount_out_min: U256,
+    pub recipient: Address,
+    pub deadline: U256,
+}
+
+/// The call a gas estimate is for.
+#[derive(Debug, Clone)]
+pub enum GasEstimationParams {
+    Buy {
+        token: Address,
+        amount_in: U256,
+        amount_out_min: U256,
+        to: Address,
+        deadline: U256,
+    },
+    Sell {
+        token: Address,
+        amount_in: U256,
+        amount_out_min: U256,
+        to: Address,
+        deadline: U256,
+    },
+}
+
+/// The nonce and gas a buy or sell is sent with.
+#[derive(Debug, Clone, Copy)]
+pub struct TxOptions {
+    pub nonce: U256,
+    pub gas_limit: U256,
+    pub fees: Fees,
+}
+
+/// A mined transaction sent through the SDK.
+#[derive(Debug, Clone)]
+pub struct TxReceipt {
+    pub tx_hash: H256,
+}
+
+/// Quotes and trades through the nad.fun lens and routers.
+pub struct Trade {
+    inner: sdk::Trade,
+}
+
+impl Trade {
+    pub async fn new(rpc_url: String, private_key: String) -> Result<Self> {
+        Ok(Self {
+            inner: sdk::Trade::new(rpc_url, private_key).await?,
+        })
+    }
+
+    pub fn wallet_address(&self) -> Address {
+        from_sdk_address(self.inner.wallet_address())
+    }
+
+    /// The router a trade of `amount_in` would go through and what it would return.
+    pub async fn get_amount_out(
+        &self,
+        token: Address,
+        amount_in: U256,
+        is_buy: bool,
+    ) -> Result<(Address, U256)> {
+        let (router, amount_out) = self
+            .inner
+            .get_amount_out(to_sdk_address(token), to_sdk_u256(amount_in), is_buy)
+            .await?;
+        Ok((from_sdk_address(router.address()), from_sdk_u256(amount_out)))
+    }
+
+    pub async fn estimate_gas(&self, router: &Address, params: GasEstimationParams) -> Result<U256> {
+        let params = match params {
+            GasEstimationParams::Buy {
+                token,
+                amount_in,
+                amount_out_min,
+                to,
+                deadline,
+            } => sdk::GasEstimationParams::Buy {
+                token: to_sdk_address(token),
+                amount_in: to_sdk_u256(amount_in),
+                amount_out_min: to_sdk_u256(amount_out_min),
+                to: to_sdk_address(to),
+                deadline: to_sdk_u256(deadline),
+            },
+            GasEstimationParams::Sell {
+                token,
+                amount_in,
+                amount_out_min,
+                to,
+                deadline,
+            } => sdk::GasEstimationParams::Sell {
+                token: to_sdk_address(token),
+                amount_in: to_sdk_u256(amount_in),
+                amount_out_min: to_sdk_u256(amount_out_min),
+                to: to_sdk_address(to),
+                deadline: to_sdk_u256(deadline),
+            },
+        };
+        let gas = self.inner.estimate_gas(&sdk_router(*router)?, params).await?;
+        Ok(U256::from(gas))
+    }
+
+    /// Signs and broadcasts a buy through `router`, returning its hash without waiting
+    /// for it to be mined. The SDK's own `buy` waits for the receipt with no timeout.
+    ///
+    /// The caller picks the nonce and gas in `tx`, so the provider can't assign a nonce
+    /// behind its back or price the trade at whatever it estimates.
+    pub async fn buy(&self, router: &Address, params: BuyParams, tx: TxOptions) -> Result<H256> {
+        let provider = self.inner.provider().as_ref();
+        let value = to_sdk_u256(params.amount_in);
+        let pending = match sdk_router(*router)? {
+            sdk::Router::BondingCurve(address) => {
+                let params = IBondingCurveRouter::BuyParams {
+                    amountOutMin: to_sdk_u256(params.amount_out_min),
+                    token: to_sdk_address(params.token),
+                    to: to_sdk_address(params.recipient),
+                    deadline: to_sdk_u256(params.deadline),
+                };
+                let contract = IBondingCurveRouter::new(address, provider);
+                send!(contract.buy(params).value(value), tx)
+            }
+            sdk::Router::Dex(address) => {
+                let params = IDexRouter::BuyParams {
+                    amountOutMin: to_sdk_u256(params.amount_out_min),
+                    token: to_sdk_address(params.token),
+                    to: to_sdk_address(params.recipient),
+                    deadline: to_sdk_u256(params.deadline),
+                };
+                let contract = IDexRouter::new(address, provider);
+                send!(contract.buy(params).value(value), tx)
+            }
+        };
+        Ok(from_sdk_hash(*pending.tx_hash()))
+    }
+
+    /// Like [`buy`](Self::buy), for a sell.
+    pub async fn sell(&self, router: &Address, params: SellParams, tx: TxOptions) -> Result<H256> {
+        let provider = self.inner.provider().as_ref();
+        let pending = match sdk_router(*router)? {
+            sdk::Router::BondingCurve(address) => {
+                let params = IBondingCurveRouter::SellParams {
+                    amountIn: to_sdk_u256(params.amount_in),
+                    amountOutMin: to_sdk_u256(params.amount_out_min),
+                    token: to_sdk_address(params.token),
+                    to: to_sdk_address(params.recipient),
+                    deadline: to_sdk_u256(params.deadline),
+                };
+                let contract = IBondingCurveRouter::new(address, provider);
+                send!(contract.sell(params), tx)
+            }
+            sdk::Router::Dex(address) => {
+                let params = IDexRouter::SellParams {
+                    amountIn: to_sdk_u256(params.amount_in),
+                    amountOutMin: to_sdk_u256(params.amount_out_min),
+                    token: to_sdk_address(params.token),
+                    to: to_sdk_address(params.recipient),
+                    deadline: to_sdk_u256(params.deadline),
+                };
+                let contract = IDexRouter::new(address, provider);
+                send!(contract.sell(params), tx)
+            }
+        };
+        Ok(from_sdk_hash(*pending.tx_hash()))
+    }
+}
+
+/// ERC-20 reads and approvals.
+pub struct TokenHelper {
+    inner: sdk::TokenHelper,
+}
+
+impl TokenHelper {
+    pub async fn new(rpc_url: String, private_key: String) -> Result<Self> {
+        Ok(Self {
+            inner: sdk::TokenHelper::new(rpc_url, private_key).await?,
+        })
+    }
+
+    pub async fn balance_of(&self, token: Address, owner: Address) -> Result<U256> {
+        let balance = self
+            .inner
+            .balance_of(to_sdk_address(token), to_sdk_address(owner))
+            .await?;
+        Ok(from_sdk_u256(balance))
+    }
+
+    pub async fn allowance(&self, token: Address, owner: Address, spender: Address) -> Result<U256> {
+        let allowance = self
+            .inner
+            .allowance(
+                to_sdk_address(token),
+                to_sdk_address(owner),
+                to_sdk_address(spender),
+            )
+            .await?;
+        Ok(from_sdk_u256(allowance))
+    }
+
+    /// Sends an approval of `value` to `spender`.
+    pub async fn approve(&self, token: Address, spender: Address, value: U256) -> Result<TxReceipt> {
+        let hash = self
+            .inner
+            .approve(to_sdk_address(token), to_sdk_address(spender), to_sdk_u256(value))
+            .await?;
+        Ok(TxReceipt {
+            tx_hash: from_sdk_hash(hash),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn u256_round_trips() {
+        let value = U256::MAX - U256::from(12_345u64);
+        assert_eq!(from_sdk_u256(to_sdk_u256(value)), value);
+        assert_eq!(to_sdk_u256(U256::from(7u64)), sdk::U256::from(7u64));
+    }
+
+    #[test]
+    fn routers_map_to_their_kind() {
+        let dex: Address = DEX_ROUTER.parse().unwrap();
+        let curve: Address = BONDING_CURVE_ROUTER.parse().unwrap();
+        assert!(matches!(sdk_router(dex).unwrap(), sdk::Router::Dex(_)));
+        assert!(matches!(sdk_router(curve).unwrap(), sdk::Router::BondingCurve(_)));
+        assert!(sdk_router(Address::repeat_byte(1)).is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::json;
+use tokio::time::{Duration, Instant};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Snipe,
+    Copy,
+    Buy,
+    Exit,
+    Sell,
+    Error,
+    /// Scheduled summaries, such as the daily settlement.
+    Report,
+}
+
+impl FromStr for Event {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "snipe" => Ok(Self::Snipe),
+            "copy" => Ok(Self::Copy),
+            "buy" => Ok(Self::Buy),
+            "exit" => Ok(Self::Exit),
+            "sell" => Ok(Self::Sell),
+            "error" => Ok(Self::Error),
+            "report" => Ok(Self::Report),
+            other => Err(anyhow!(
+                "unknown event {:?}; expected snipe, copy, buy, exit, sell, error or report",
+                other
+            )),
+        }
+    }
+}
+
+/// How long a message stays a duplicate, and how many messages a channel sends a minute.
+#[derive(Debug, Clone, Copy)]
+struct AlertPolicy {
+    dedup_window: Duration,
+    /// 0 for no limit.
+    rate_per_min: usize,
+}
+
+/// Alerts shown in full in a burst summary; the rest are only counted.
+const SUMMARY_LINES: usize = 10;
+/// How often held alerts and repeat counts are checked for sending.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// When an identical message was first sent, and how often it has come in since.
+struct Seen {
+    since: Instant,
+    repeats: usize,
+}
+
+/// Deduplication, rate limit and burst state of one channel.
+#[derive(Default)]
+struct Gate {
+    /// Send times within the last minute.
+    sent: VecDeque<Instant>,
+    seen: HashMap<String, Seen>,
+    /// Messages over the rate limit, waiting to go out as one summary.
+    held: Vec<String>,
+}
+
+impl Gate {
+    /// Whether `message` goes out now; a duplicate is only counted and a message over
+    /// the rate limit is held for the next summary.
+    fn admit(&mut self, message: &str, now: Instant, policy: &AlertPolicy) -> bool {
+        if !policy.dedup_window.is_zero() {
+            match self.seen.get_mut(message) {
+                Some(seen) if now.duration_since(seen.since) < policy.dedup_window => {
+                    seen.repeats += 1;
+                    return false;
+                }
+                _ => {
+                    self.seen.insert(message.to_string(), Seen { since: now, repeats: 0 });
+                }
+            }
+        }
+        if !self.take(now, policy) {
+            self.held.push(message.to_string());
+            return false;
+        }
+        true
+    }
+
+    /// The repeat counts of duplicates whose window has closed and a summary of the held
+    /// messages, as far as the rate limit allows.
+    fn flush(&mut self, now: Instant, policy: &AlertPolicy) -> Vec<String> {
+        let mut notes = Vec::new();
+        self.seen.retain(|message, seen| {
+            if now.duration_since(seen.since) < policy.dedup_window {
+                return true;
+            }
+            if seen.repeats > 0 {
+                let window = policy.dedup_window.as_secs();
+                notes.push(format!(
+                    "Repeated {} more times in {}s: {}",
+                    seen.repeats, window, message
+                ));
+            }
+            false
+        });
+        let mut out = Vec::new();
+        for note in notes {
+            if self.held.is_empty() && self.take(now, policy) {
+                out.push(note);
+            } else {
+                self.held.push(note);
+            }
+        }
+        if !self.held.is_empty() && self.take(now, policy) {
+            out.push(summary(std::mem::take(&mut self.held)));
+        }
+        out
+    }
+
+    /// Counts a send against the rate limit, if there is room for it.
+    fn take(&mut self, now: Instant, policy: &AlertPolicy) -> bool {
+        while self
+            .sent
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= Duration::from_secs(60))
+        {
+            self.sent.pop_front();
+        }
+        if policy.rate_per_min > 0 && self.sent.len() >= policy.rate_per_min {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+fn summary(mut held: Vec<String>) -> String {
+    if held.len() == 1 {
+        return held.remove(0);
+    }
+    let mut text = format!("{} alerts held back by the rate limit:", held.len());
+    for message in held.iter().take(SUMMARY_LINES) {
+        text.push_str("\n- ");
+        text.push_str(message);
+    }
+    if held.len() > SUMMARY_LINES {
+        text.push_str(&format!("\n...and {} more", held.len() - SUMMARY_LINES));
+    }
+    text
+}
+
+enum Sink {
+    Telegram { token: String, chat: String },
+    Discord { webhook: String },
+}
+
+/// One chat destination with its own rate limit and queue of failed sends.
+struct Channel {
+    sink: Sink,
+    gate: Mutex<Gate>,
+    pending: Mutex<VecDeque<String>>,
+}
+
+/// Pushes event messages to Telegram and/or a Discord webhook. Sends run in the
+/// background so a slow or failing chat API never holds up a trade; messages that
+/// fail are queued (up to `NOTIFY_QUEUE_MAX`, oldest dropped first) and go out
+/// ahead of the next one. Identical messages within `NOTIFY_DEDUP_SECS` are sent once
+/// and counted, and each channel sends at most `NOTIFY_RATE_PER_MIN` a minute, so an
+/// outage turns into a few summaries rather than a flood.
+#[derive(Clone)]
+pub struct Notifier {
+    client: reqwest::Client,
+    channels: Arc<Vec<Channel>>,
+    events: Vec<Event>,
+    policy: AlertPolicy,
+    queue_max: usize,
+    flushing: Arc<AtomicBool>,
+}
+
+impl Notifier {
+    /// `TELEGRAM_BOT_TOKEN` + `TELEGRAM_CHAT_ID` and/or `DISCORD_WEBHOOK_URL`, limited to
+    /// the comma-separated `NOTIFY_EVENTS` (every event by default).
+    pub fn from_env() -> Result<Self> {
+        let mut sinks = Vec::new();
+        match (env::var("TELEGRAM_BOT_TOKEN"), env::var("TELEGRAM_CHAT_ID")) {
+            (Ok(token), Ok(chat)) => sinks.push(Sink::Telegram { token, chat }),
+            (Ok(_), Err(_)) => {
+                return Err(anyhow!("TELEGRAM_BOT_TOKEN is set without TELEGRAM_CHAT_ID"))
+            }
+            _ => {}
+        }
+        if let Some(webhook) = env::var("DISCORD_WEBHOOK_URL").ok().filter(|v| !v.is_empty()) {
+            sinks.push(Sink::Discord { webhook });
+        }
+        let events = match env::var("NOTIFY_EVENTS") {
+            Ok(list) => list
+                .split(',')
+                .filter(|v| !v.trim().is_empty())
+                .map(|v| v.parse().context("invalid NOTIFY_EVENTS"))
+                .collect::<Result<_>>()?,
+            Err(_) => vec![
+                Event::Snipe,
+                Event::Copy,
+                Event::Buy,
+                Event::Exit,
+                Event::Sell,
+                Event::Error,
+                Event::Report,
+            ],
+        };
+        let number = |name: &str, default: u64| -> Result<u64> {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {}", name)))
+                .transpose()
+                .map(|v| v.unwrap_or(default))
+        };
+        let channels = sinks
+            .into_iter()
+            .map(|sink| Channel {
+                sink,
+                gate: Mutex::default(),
+                pending: Mutex::default(),
+            })
+            .collect();
+        Ok(Self {
+            client: reqwest::Client::new(),
+            channels: Arc::new(channels),
+            events,
+            policy: AlertPolicy {
+                dedup_window: Duration::from_secs(number("NOTIFY_DEDUP_SECS", 300)?),
+                rate_per_min: number("NOTIFY_RATE_PER_MIN", 20)? as usize,
+            },
+            queue_max: env::var("NOTIFY_QUEUE_MAX")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(100),
+            flushing: Arc::default(),
+        })
+    }
+
+    pub fn send(&self, event: Event, message: impl Into<String>) {
+        if self.channels.is_empty() || !self.events.contains(&event) {
+            return;
+        }
+        if !self.flushing.swap(true, Ordering::Relaxed) {
+            tokio::spawn(self.clone().flush_periodically());
+        }
+        let message = message.into();
+        let now = Instant::now();
+        for index in 0..self.channels.len() {
+            let admitted = match self.channels[index].gate.lock() {
+                Ok(mut gate) => gate.admit(&message, now, &self.policy),
+                Err(_) => true,
+            };
+            if admitted {
+                self.spawn_delivery(index, message.clone());
+            }
+        }
+    }
+
+    /// Sends the repeat counts and burst summaries the gates let out.
+    async fn flush_periodically(self) {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let now = Instant::now();
+            for index in 0..self.channels.len() {
+                let messages = match self.channels[index].gate.lock() {
+                    Ok(mut gate) => gate.flush(now, &self.policy),
+                    Err(_) => Vec::new(),
+                };
+                for message in messages {
+                    self.spawn_delivery(index, message);
+                }
+            }
+        }
+    }
+
+    fn spawn_delivery(&self, index: usize, message: String) {
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            let channel = &notifier.channels[index];
+            let mut batch: VecDeque<String> = match channel.pending.lock() {
+                Ok(mut pending) => pending.drain(..).collect(),
+                Err(_) => VecDeque::new(),
+            };
+            batch.push_back(message);
+            while let Some(message) = batch.pop_front() {
+                if let Err(err) = notifier.deliver(&channel.sink, &message).await {
+                    batch.push_front(message);
+                    warn!("Notification failed, {} queued: {:#}", batch.len(), err);
+                    notifier.requeue(channel, batch);
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Puts undelivered messages back in front of anything queued meanwhile.
+    fn requeue(&self, channel: &Channel, mut batch: VecDeque<String>) {
+        let Ok(mut pending) = channel.pending.lock() else {
+            return;
+        };
+        batch.extend(pending.drain(..));
+        while batch.len() > self.queue_max {
+            batch.pop_front();
+        }
+        *pending = batch;
+    }
+
+    async fn deliver(&self, sink: &Sink, message: &str) -> Result<()> {
+        match sink {
+            Sink::Telegram { token, chat } => {
+                self.client
+                    .post(format!("https://api.telegram.org/bot{}/sendMessage", token))
+                    .json(&json!({ "chat_id": chat, "text": message }))
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .context("Telegram sendMessage failed")?;
+            }
+            Sink::Discord { webhook } => {
+                self.client
+                    .post(webhook)
+                    .json(&json!({ "content": message }))
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .context("Discord webhook failed")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: AlertPolicy = AlertPolicy {
+        dedup_window: Duration::from_secs(300),
+        rate_per_min: 2,
+    };
+
+    #[test]
+    fn duplicates_are_counted_and_reported_once_their_window_closes() {
+        let mut gate = Gate::default();
+        let start = Instant::now();
+        assert!(gate.admit("RPC down", start, &POLICY));
+        assert!(!gate.admit("RPC down", start + Duration::from_secs(1), &POLICY));
+        assert!(!gate.admit("RPC down", start + Duration::from_secs(2), &POLICY));
+        assert!(gate.flush(start + Duration::from_secs(3), &POLICY).is_empty());
+
+        let later = start + Duration::from_secs(300);
+        let notes = gate.flush(later, &POLICY);
+        assert_eq!(notes, ["Repeated 2 more times in 300s: RPC down"]);
+        assert!(gate.admit("RPC down", later, &POLICY));
+    }
+
+    #[test]
+    fn a_burst_over_the_rate_limit_goes_out_as_one_summary() {
+        let mut gate = Gate::default();
+        let start = Instant::now();
+        let admitted = (0..5)
+            .filter(|n| gate.admit(&format!("error {}", n), start, &POLICY))
+            .count();
+        assert_eq!(admitted, 2);
+        assert!(gate.flush(start + Duration::from_secs(10), &POLICY).is_empty());
+
+        let summary = gate.flush(start + Duration::from_secs(60), &POLICY);
+        assert_eq!(
+            summary,
+            ["3 alerts held back by the rate limit:\n- error 2\n- error 3\n- error 4"]
+        );
+        assert!(gate.held.is_empty());
+    }
+}
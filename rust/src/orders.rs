@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Subcommand};
+use ethers::types::{Address, U256};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::chain;
+use crate::engine::ExecutionClient;
+use crate::exit_strategy::{Exit, ExitKind, ExitStrategy, Position};
+use crate::file_lock;
+use crate::notify::Event;
+use crate::price_feed::PriceWatch;
+use crate::state;
+use crate::app::AppConfig;
+use crate::trading::{round_trip, EntryHints};
+
+#[derive(Debug, Args)]
+pub struct OrderArgs {
+    #[command(subcommand)]
+    pub action: OrderAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum OrderAction {
+    /// Buy TOKEN with --amount once its price drops to --below (native per token).
+    Buy {
+        token: Address,
+        #[arg(long)]
+        below: f64,
+        #[arg(long)]
+        amount: String,
+    },
+    /// Sell the open position in TOKEN once its price rises to --above (native per token).
+    Sell {
+        token: Address,
+        #[arg(long)]
+        above: f64,
+    },
+    /// List resting orders.
+    List,
+    /// Remove a resting order.
+    Cancel { id: u64 },
+    /// Poll prices and fill buy orders as they trigger; sell orders fill wherever the
+    /// position is managed.
+    Watch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LimitOrder {
+    pub id: u64,
+    pub token: Address,
+    pub side: OrderSide,
+    /// Trigger price in native per whole token.
+    pub price: f64,
+    /// Native to spend; unused for sells, which close the whole position.
+    pub amount: U256,
+    pub created_at: u64,
+}
+
+impl LimitOrder {
+    fn describe(&self) -> String {
+        let profile = chain::profile();
+        match self.side {
+            OrderSide::Buy => format!(
+                "#{} buy {:?} with {} at or below {:.3e}",
+                self.id,
+                self.token,
+                profile.format_native(self.amount),
+                self.price
+            ),
+            OrderSide::Sell => {
+                format!("#{} sell {:?} at or above {:.3e}", self.id, self.token, self.price)
+            }
+        }
+    }
+}
+
+/// Resting limit orders persisted to a JSON file so they survive restarts. `order watch`
+/// and the trading processes edit it under the file's lock.
+pub struct OrderBook {
+    path: PathBuf,
+}
+
+impl OrderBook {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn list(&self) -> Result<Vec<LimitOrder>> {
+        self.load()
+    }
+
+    pub fn add(
+        &self,
+        token: Address,
+        side: OrderSide,
+        price: f64,
+        amount: U256,
+    ) -> Result<LimitOrder> {
+        let mut added = None;
+        self.update(|orders| {
+            let order = LimitOrder {
+                id: orders.iter().map(|order| order.id + 1).max().unwrap_or(1),
+                token,
+                side,
+                price,
+                amount,
+                created_at: state::unix_now(),
+            };
+            orders.push(order.clone());
+            added = Some(order);
+        })?;
+        added.ok_or_else(|| anyhow!("order was not added"))
+    }
+
+    /// Removes order `id`, returning whether it was still resting.
+    pub fn remove(&self, id: u64) -> Result<bool> {
+        let mut removed = false;
+        self.update(|orders| {
+            let before = orders.len();
+            orders.retain(|order| order.id != id);
+            removed = orders.len() < before;
+        })?;
+        Ok(removed)
+    }
+
+    /// Removes and returns the first sell order on `token` that `price` triggers.
+    fn take_sell(&self, token: Address, price: f64) -> Result<Option<LimitOrder>> {
+        let mut taken = None;
+        self.update(|orders| {
+            let triggered = orders.iter().position(|order| {
+                order.side == OrderSide::Sell && order.token == token && price >= order.price
+            });
+            taken = triggered.map(|index| orders.remove(index));
+        })?;
+        Ok(taken)
+    }
+
+    /// Puts back an order taken by [`take_sell`](Self::take_sell) whose sell failed.
+    fn restore(&self, order: LimitOrder) -> Result<()> {
+        self.update(|orders| {
+            if !orders.iter().any(|resting| resting.id == order.id) {
+                orders.push(order);
+            }
+        })
+    }
+
+    fn update(&self, change: impl FnOnce(&mut Vec<LimitOrder>)) -> Result<()> {
+        let _lock = file_lock::exclusive(&self.path)?;
+        let mut orders = self.load()?;
+        change(&mut orders);
+        let json = serde_json::to_string_pretty(&orders)?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, json).with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+
+    fn load(&self) -> Result<Vec<LimitOrder>> {
+        match fs::read_to_string(&self.path) {
+            Ok(json) => serde_json::from_str(&json)
+                .with_context(|| format!("corrupt order book {}", self.path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// A token's exit rules plus its resting sell orders, which are consumed as they fire.
+/// The order that fired is kept so [`restore`](Self::restore) can put it back if the sell
+/// doesn't go through.
+pub struct WithLimitSells<'a> {
+    rules: &'a dyn ExitStrategy,
+    book: &'a OrderBook,
+    token: Address,
+    fired: Mutex<Option<LimitOrder>>,
+}
+
+impl<'a> WithLimitSells<'a> {
+    pub fn new(rules: &'a dyn ExitStrategy, book: &'a OrderBook, token: Address) -> Self {
+        Self {
+            rules,
+            book,
+            token,
+            fired: Mutex::new(None),
+        }
+    }
+
+    /// Returns the limit sell that fired, if any, to the book.
+    pub fn restore(&self) {
+        let fired = self.fired.lock().ok().and_then(|mut fired| fired.take());
+        if let Some(order) = fired {
+            match self.book.restore(order.clone()) {
+                Ok(()) => info!("Limit sell {} is resting again", order.describe()),
+                Err(err) => warn!("Failed to restore limit sell #{}: {:#}", order.id, err),
+            }
+        }
+    }
+}
+
+impl ExitStrategy for WithLimitSells<'_> {
+    fn should_exit(&self, position: &Position) -> Option<Exit> {
+        if let Some(exit) = self.rules.should_exit(position) {
+            return Some(exit);
+        }
+        if !self.rules.may_sell(position) {
+            return None;
+        }
+        let price = position.price()?;
+        match self.book.take_sell(self.token, price) {
+            Ok(Some(order)) => {
+                let exit = Exit::new(
+                    ExitKind::LimitSell,
+                    format!(
+                        "limit sell #{} triggered at {:.3e} (target {:.3e})",
+                        order.id, price, order.price
+                    ),
+                );
+                if let Ok(mut fired) = self.fired.lock() {
+                    *fired = Some(order);
+                }
+                Some(exit)
+            }
+            Ok(None) => None,
+            Err(err) => {
+                warn!("Limit sell orders unavailable: {:#}", err);
+                None
+            }
+        }
+    }
+
+    fn may_sell(&self, position: &Position) -> bool {
+        self.rules.may_sell(position)
+    }
+}
+
+/// Adds, lists or cancels orders; `Watch` is handled by [`watch`].
+pub fn edit(cfg: &AppConfig, action: &OrderAction) -> Result<()> {
+    match action {
+        OrderAction::Buy { token, below, amount } => {
+            if *below <= 0.0 {
+                return Err(anyhow!("--below must be positive"));
+            }
+            let amount = chain::profile().parse_native(amount).context("invalid --amount")?;
+            let order = cfg.orders.add(*token, OrderSide::Buy, *below, amount)?;
+            println!("Added {}", order.describe());
+        }
+        OrderAction::Sell { token, above } => {
+            if *above <= 0.0 {
+                return Err(anyhow!("--above must be positive"));
+            }
+            let order = cfg.orders.add(*token, OrderSide::Sell, *above, U256::zero())?;
+            println!("Added {}", order.describe());
+            if !cfg.state.open_positions()?.iter().any(|position| position.token == *token) {
+                println!("No open position in {:?} yet; the order waits for one", token);
+            }
+        }
+        OrderAction::List => {
+            let orders = cfg.orders.list()?;
+            if orders.is_empty() {
+                println!("No resting orders");
+            }
+            for order in &orders {
+                println!("{}", order.describe());
+            }
+        }
+        OrderAction::Cancel { id } => {
+            if !cfg.orders.remove(*id)? {
+                return Err(anyhow!("no resting order #{}", id));
+            }
+            println!("Cancelled order #{}", id);
+        }
+        OrderAction::Watch => return Err(anyhow!("order watch needs a chain connection")),
+    }
+    Ok(())
+}
+
+/// Re-quotes every resting buy order each `EXIT_CHECK_INTERVAL_SECS` and runs the normal
+/// round trip for the ones whose price has dropped to their limit.
+pub async fn watch(cfg: &AppConfig, client: &impl ExecutionClient) -> Result<()> {
+    let mut ticker = tokio::time::interval(Duration::from_secs(cfg.exit_check_interval_secs));
+    let mut in_flight = FuturesUnordered::new();
+    let mut prices: HashMap<Address, PriceWatch> = HashMap::new();
+    info!("Watching limit orders in {}", cfg.orders.path.display());
+
+    loop {
+        if cfg.shutdown.requested() && in_flight.is_empty() {
+            return Ok(());
+        }
+        tokio::select! {
+            _ = ticker.tick(), if !cfg.shutdown.requested() => {
+                let orders = cfg.orders.list()?;
+                if let Some(feed) = &cfg.price_feed {
+                    let buys = || orders.iter().filter(|order| order.side == OrderSide::Buy);
+                    prices.retain(|token, _| buys().any(|order| order.token == *token));
+                    for order in buys() {
+                        prices.entry(order.token).or_insert_with(|| feed.subscribe(order.token));
+                    }
+                }
+                for order in orders.into_iter().filter(|order| order.side == OrderSide::Buy) {
+                    let price = match buy_price(client, &order, prices.get(&order.token)).await {
+                        Ok(price) => price,
+                        Err(err) => {
+                            warn!("Order #{} quote failed: {:#}", order.id, err);
+                            continue;
+                        }
+                    };
+                    if price > order.price || !cfg.orders.remove(order.id)? {
+                        continue;
+                    }
+                    info!("Limit buy {} triggered at {:.3e}", order.describe(), price);
+                    cfg.audit(
+                        "limit_buy",
+                        json!({ "order": order.id, "token": order.token, "price": price }),
+                    );
+                    in_flight.push(fill_buy(cfg, client, order));
+                }
+            }
+            Some(()) = in_flight.next(), if !in_flight.is_empty() => {}
+            _ = cfg.shutdown.wait(), if !cfg.shutdown.requested() => {}
+        }
+    }
+}
+
+/// Native per whole token a buy of the order's size would pay right now, from the price
+/// feed when it has a fresh price.
+async fn buy_price(
+    client: &impl ExecutionClient,
+    order: &LimitOrder,
+    prices: Option<&PriceWatch>,
+) -> Result<f64> {
+    let tokens_out = match prices.and_then(|prices| prices.buy_quote(order.amount)) {
+        Some(tokens_out) => tokens_out,
+        None => {
+            client
+                .quote(order.token, order.amount, true)
+                .await
+                .context("buy quote failed")?
+                .1
+        }
+    };
+    if tokens_out.is_zero() {
+        return Err(anyhow!("buy quote returned zero"));
+    }
+    Ok(order.amount.as_u128() as f64 / tokens_out.as_u128() as f64)
+}
+
+async fn fill_buy(cfg: &AppConfig, client: &impl ExecutionClient, order: LimitOrder) {
+    let hints = EntryHints {
+        amount_in: Some(order.amount),
+        strategy: Some("limit order"),
+        ..EntryHints::default()
+    };
+    let params = cfg.params_for(order.token);
+    if let Err(err) = round_trip(cfg, params, client, order.token, hints).await {
+        warn!("Limit buy #{} failed: {:#}", order.id, err);
+        cfg.notifier.send(Event::Error, format!("Limit buy #{} failed: {:#}", order.id, err));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::SnapshotDiff;
+    use crate::testing::ScriptedClient;
+
+    fn book(name: &str) -> OrderBook {
+        let dir =
+            std::env::temp_dir().join(format!("nadfun-orders-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("orders.json");
+        fs::remove_file(&path).ok();
+        OrderBook::new(path)
+    }
+
+    struct Hold;
+
+    impl ExitStrategy for Hold {
+        fn should_exit(&self, _position: &Position) -> Option<Exit> {
+            None
+        }
+    }
+
+    /// A position whose full exit prices the token at `value / 1000`.
+    fn position(value: u64) -> Position {
+        Position {
+            cost: U256::from(100u64),
+            amount: U256::from(1_000u64),
+            value: U256::from(value),
+            peak_value: U256::from(value),
+            held_for: Duration::from_secs(60),
+            snapshot: SnapshotDiff::default(),
+        }
+    }
+
+    #[test]
+    fn orders_get_fresh_ids_and_cancel_once() {
+        let book = book("ids");
+        let token = Address::repeat_byte(1);
+        let first = book.add(token, OrderSide::Buy, 0.5, U256::from(10u64)).unwrap();
+        let second = book.add(token, OrderSide::Sell, 2.0, U256::zero()).unwrap();
+        assert_eq!((first.id, second.id), (1, 2));
+        assert!(book.remove(first.id).unwrap());
+        assert!(!book.remove(first.id).unwrap());
+        assert_eq!(book.add(token, OrderSide::Buy, 0.4, U256::one()).unwrap().id, 3);
+        let ids: Vec<u64> = book.list().unwrap().iter().map(|order| order.id).collect();
+        assert_eq!(ids, [2, 3]);
+    }
+
+    #[test]
+    fn sells_trigger_at_or_above_their_price_on_their_token() {
+        let book = book("take");
+        let (token, other) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        book.add(token, OrderSide::Buy, 0.1, U256::one()).unwrap();
+        book.add(other, OrderSide::Sell, 0.1, U256::zero()).unwrap();
+        let sell = book.add(token, OrderSide::Sell, 0.5, U256::zero()).unwrap();
+        assert!(book.take_sell(token, 0.49).unwrap().is_none());
+        assert_eq!(book.take_sell(token, 0.5).unwrap().map(|order| order.id), Some(sell.id));
+        assert!(book.take_sell(token, 0.9).unwrap().is_none());
+        assert_eq!(book.list().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn a_fired_limit_sell_can_be_put_back() {
+        let book = book("restore");
+        let token = Address::repeat_byte(1);
+        let sell = book.add(token, OrderSide::Sell, 0.2, U256::zero()).unwrap();
+        let strategy = WithLimitSells::new(&Hold, &book, token);
+        assert!(strategy.should_exit(&position(100)).is_none());
+        let exit = strategy.should_exit(&position(250)).unwrap();
+        assert_eq!(exit.kind, ExitKind::LimitSell);
+        assert!(book.list().unwrap().is_empty());
+
+        strategy.restore();
+        let resting = book.list().unwrap();
+        assert_eq!(resting.iter().map(|order| order.id).collect::<Vec<_>>(), [sell.id]);
+        strategy.restore();
+        assert_eq!(book.list().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn buy_price_is_native_per_token_quoted() {
+        let order = LimitOrder {
+            id: 1,
+            token: Address::repeat_byte(1),
+            side: OrderSide::Buy,
+            price: 0.3,
+            amount: U256::from(1_000u64),
+            created_at: 0,
+        };
+        let client = ScriptedClient::with_buy_quotes([4_000, 0]);
+        assert_eq!(buy_price(&client, &order, None).await.unwrap(), 0.25);
+        assert!(buy_price(&client, &order, None).await.is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::contract::abigen;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, H256};
+use ethers::utils::keccak256;
+use tracing::{info, warn};
+
+abigen!(
+    PinnedToken,
+    r#"[
+        function name() external view returns (string)
+        function symbol() external view returns (string)
+        function owner() external view returns (address)
+    ]"#
+);
+
+/// EIP-1967 implementation slot: `keccak256("eip1967.proxy.implementation") - 1`.
+const IMPLEMENTATION_SLOT: &str = "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinPolicy {
+    Alert,
+    Exit,
+}
+
+impl FromStr for PinPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "alert" => Ok(Self::Alert),
+            "exit" => Ok(Self::Exit),
+            other => Err(anyhow!("unknown pin policy `{other}` (expected alert or exit)")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenPin {
+    pub code_hash: H256,
+    pub implementation: Option<Address>,
+    pub owner: Option<Address>,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+}
+
+impl TokenPin {
+    pub fn changes(&self, current: &TokenPin) -> Vec<String> {
+        let mut changes = Vec::new();
+        if self.code_hash != current.code_hash {
+            changes.push(format!("code hash {:?} -> {:?}", self.code_hash, current.code_hash));
+        }
+        if self.implementation != current.implementation {
+            changes.push(format!(
+                "implementation {:?} -> {:?}",
+                self.implementation, current.implementation
+            ));
+        }
+        if self.owner != current.owner {
+            changes.push(format!("owner {:?} -> {:?}", self.owner, current.owner));
+        }
+        if self.name != current.name {
+            changes.push(format!("name {:?} -> {:?}", self.name, current.name));
+        }
+        if self.symbol != current.symbol {
+            changes.push(format!("symbol {:?} -> {:?}", self.symbol, current.symbol));
+        }
+        changes
+    }
+}
+
+pub struct PinWatch {
+    provider: Arc<Provider<Http>>,
+    token: Address,
+    policy: PinPolicy,
+    pinned: TokenPin,
+}
+
+impl PinWatch {
+    /// Records the token's code, proxy implementation, owner and metadata.
+    pub async fn pin(rpc_url: &str, token: Address, policy: PinPolicy) -> Result<Self> {
+        let provider = Arc::new(Provider::<Http>::try_from(rpc_url).context("invalid RPC_URL")?);
+        let pinned = snapshot(&provider, token).await?;
+        info!(
+            "Pinned token {:?}: code hash {:?}, owner {:?}",
+            token, pinned.code_hash, pinned.owner
+        );
+        Ok(Self {
+            provider,
+            token,
+            policy,
+            pinned,
+        })
+    }
+
+    /// Errors only when something changed and the policy is `Exit`.
+    pub async fn check(&self) -> Result<()> {
+        let current = snapshot(&self.provider, self.token).await?;
+        let changes = self.pinned.changes(&current);
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let summary = changes.join(", ");
+        match self.policy {
+            PinPolicy::Alert => {
+                warn!("token {:?} changed while held: {}", self.token, summary);
+                Ok(())
+            }
+            PinPolicy::Exit => Err(anyhow!("token {:?} changed while held: {}", self.token, summary)),
+        }
+    }
+}
+
+async fn snapshot(provider: &Arc<Provider<Http>>, token: Address) -> Result<TokenPin> {
+    let code = provider.get_code(token, None).await?;
+    let slot: H256 = IMPLEMENTATION_SLOT.parse()?;
+    let implementation = provider
+        .get_storage_at(token, slot, None)
+        .await
+        .ok()
+        .map(Address::from)
+        .filter(|address| !address.is_zero());
+
+    let contract = PinnedToken::new(token, provider.clone());
+    Ok(TokenPin {
+        code_hash: H256::from(keccak256(code.as_ref())),
+        implementation,
+        owner: contract.owner().call().await.ok(),
+        name: contract.name().call().await.ok(),
+        symbol: contract.symbol().call().await.ok(),
+    })
+}
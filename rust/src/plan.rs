@@ -0,0 +1,252 @@
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::U256;
+
+use crate::app::AppConfig;
+use crate::chain;
+use crate::config::TradeParams;
+use crate::tx_manager::RetryPolicy;
+
+#[derive(Debug, Args)]
+pub struct PlanArgs {
+    /// Native balance to plan with.
+    #[arg(long)]
+    pub bankroll: String,
+
+    /// Plan for sniper mode (MAX_CONCURRENT_SNIPES positions at the default size)
+    /// instead of the configured tokens.
+    #[arg(long)]
+    pub sniper: bool,
+
+    /// Gas price to assume, in gwei; the RPC's current price is used if unset.
+    #[arg(long)]
+    pub gas_gwei: Option<String>,
+
+    /// Gas units assumed per transaction.
+    #[arg(long, default_value_t = 300_000)]
+    pub gas_per_tx: u64,
+}
+
+struct Slot<'a> {
+    label: String,
+    params: &'a TradeParams,
+}
+
+struct SlotCost {
+    size: U256,
+    gas: U256,
+    worst_gas: U256,
+    worst_loss: U256,
+}
+
+/// Prints per-position sizes, expected and worst-case gas, and how many back-to-back
+/// worst-case round trips `--bankroll` can fund under the configured stop losses.
+pub async fn run(cfg: &AppConfig, args: &PlanArgs) -> Result<()> {
+    let profile = chain::profile();
+    let bankroll = profile.parse_native(&args.bankroll).context("invalid --bankroll")?;
+    let gas_price = match &args.gas_gwei {
+        Some(gwei) => U256::from(
+            ethers::utils::parse_units(gwei.as_str(), "gwei").context("invalid --gas-gwei")?,
+        ),
+        None => Provider::<Http>::try_from(cfg.rpc_url.as_str())
+            .context("invalid RPC_URL")?
+            .get_gas_price()
+            .await
+            .context("failed to fetch gas price; pass --gas-gwei")?,
+    };
+
+    let slots: Vec<Slot> = if args.sniper {
+        (1..=cfg.sniper.max_concurrent)
+            .map(|n| Slot {
+                label: format!("snipe {n}"),
+                params: &cfg.defaults,
+            })
+            .collect()
+    } else if cfg.targets.is_empty() {
+        return Err(anyhow!(
+            "nothing to plan: pass --token, set TOKEN_ADDRESS, add [[token]] entries or use --sniper"
+        ));
+    } else {
+        cfg.targets
+            .iter()
+            .map(|target| Slot {
+                label: format!("{:?}", target.token),
+                params: &target.params,
+            })
+            .collect()
+    };
+
+    let policy = &cfg.retry_policy;
+    let mut capital = U256::zero();
+    let mut reserve = U256::zero();
+    let mut cycle_loss = U256::zero();
+    let mut cycle_gas = U256::zero();
+
+    println!(
+        "{:<44}  {:>12}  {:>12}  {:>12}  {:>12}",
+        "position", "size", "gas", "worst gas", "worst loss"
+    );
+    for slot in &slots {
+        let cost = slot_cost(slot.params, gas_price, args.gas_per_tx, policy);
+        println!(
+            "{:<44}  {:>12}  {:>12}  {:>12}  {:>12}",
+            slot.label,
+            profile.format_native(cost.size),
+            profile.format_native(cost.gas),
+            profile.format_native(cost.worst_gas),
+            profile.format_native(cost.worst_loss)
+        );
+        capital += cost.size;
+        reserve += cost.worst_gas;
+        cycle_loss += cost.worst_loss + cost.gas;
+        cycle_gas += cost.gas;
+    }
+
+    let required = capital + reserve;
+    println!();
+    println!("Concurrent positions: {}", slots.len());
+    println!("Capital deployed at once: {}", profile.format_native(capital));
+    println!("Worst-case gas reserve: {}", profile.format_native(reserve));
+    println!(
+        "Bankroll needed to fund every position: {}",
+        profile.format_native(required)
+    );
+
+    if bankroll < required {
+        println!(
+            "Bankroll {} is {} short; reduce sizes or concurrency",
+            profile.format_native(bankroll),
+            profile.format_native(required - bankroll)
+        );
+        return Ok(());
+    }
+    match rounds(bankroll, required, cycle_loss) {
+        Some(cycles) => println!(
+            "Bankroll {} lasts {} back-to-back worst-case rounds ({} round trips)",
+            profile.format_native(bankroll),
+            cycles,
+            cycles * U256::from(slots.len())
+        ),
+        None => println!("No loss is possible under these settings"),
+    }
+
+    if let Some(budget) = &cfg.gas_budget {
+        if !cycle_gas.is_zero() {
+            let per_day = budget.cap() * U256::from(slots.len()) / cycle_gas;
+            println!(
+                "MAX_DAILY_GAS_MON {} allows about {} round trips per day",
+                profile.format_native(budget.cap()),
+                per_day
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Size, expected gas, and worst-case gas and loss of one position.
+fn slot_cost(
+    params: &TradeParams,
+    gas_price: U256,
+    gas_per_tx: u64,
+    policy: &RetryPolicy,
+) -> SlotCost {
+    let size = params.amount_in;
+    // Buy, approval, and one sell per tranche plus the final one.
+    let txs = 3 + params.tranches.len() as u64;
+    let gas = U256::from(txs * gas_per_tx) * gas_price;
+    let worst_gas = gas * U256::from(policy.bump_pct) * U256::from(policy.max_attempts.max(1))
+        / U256::from(100u64);
+    // Without a stop loss the position can ride down to nothing before max hold.
+    let loss_bps = match params.stop_loss_pct {
+        Some(pct) => ((pct * 100.0) as u64 + 2 * params.slippage_bps).min(10_000),
+        None => 10_000,
+    };
+    let worst_loss = size * U256::from(loss_bps) / U256::from(10_000u64);
+    SlotCost {
+        size,
+        gas,
+        worst_gas,
+        worst_loss,
+    }
+}
+
+/// Back-to-back worst-case rounds a `bankroll` of at least `required` survives, or
+/// `None` if a round can't lose anything.
+fn rounds(bankroll: U256, required: U256, cycle_loss: U256) -> Option<U256> {
+    if cycle_loss.is_zero() {
+        None
+    } else {
+        Some((bankroll - required) / cycle_loss + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Profile;
+    use crate::exit_strategy::{ExitRules, Tranche};
+    use crate::sizing::Sizing;
+    use crate::tx_manager::TimeInForce;
+    use crate::venue::VenueKind;
+    use tokio::time::Duration;
+
+    fn params(stop_loss_pct: Option<f64>, tranches: usize) -> TradeParams {
+        TradeParams {
+            amount_in: U256::from(1_000_000u64),
+            slippage_bps: 100,
+            exit_rules: ExitRules::default(),
+            stop_loss_pct,
+            tranches: (0..tranches)
+                .map(|_| Tranche {
+                    share_pct: 10,
+                    at_profit_pct: 50.0,
+                })
+                .collect(),
+            snapshot_exits: false,
+            venue: VenueKind::NadFun,
+            profile: Profile::default(),
+            sizing: Sizing::Fixed,
+        }
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(1),
+            confirm_timeout: Duration::from_secs(30),
+            bump_pct: 125,
+            confirmations: 1,
+            buy_tif: TimeInForce::Retry,
+            sell_tif: TimeInForce::Retry,
+        }
+    }
+
+    #[test]
+    fn costs_count_a_sell_per_tranche_and_every_retry() {
+        let cost = slot_cost(&params(Some(10.0), 2), U256::from(2u64), 100, &policy());
+        assert_eq!(cost.size, U256::from(1_000_000u64));
+        // Buy, approval, two tranches and the final sell at 100 gas and price 2.
+        assert_eq!(cost.gas, U256::from(1_000u64));
+        // Three attempts at 125%.
+        assert_eq!(cost.worst_gas, U256::from(3_750u64));
+        // 10% stop plus 1% slippage on both legs.
+        assert_eq!(cost.worst_loss, U256::from(120_000u64));
+    }
+
+    #[test]
+    fn without_a_stop_loss_the_whole_position_is_at_risk() {
+        let cost = slot_cost(&params(None, 0), U256::one(), 1, &policy());
+        assert_eq!(cost.worst_loss, cost.size);
+        let cost = slot_cost(&params(Some(99.0), 0), U256::one(), 1, &policy());
+        assert_eq!(cost.worst_loss, cost.size);
+    }
+
+    #[test]
+    fn rounds_count_the_first_round_and_each_refill() {
+        let n = |v: u64| U256::from(v);
+        assert_eq!(rounds(n(100), n(100), n(30)), Some(n(1)));
+        assert_eq!(rounds(n(190), n(100), n(30)), Some(n(4)));
+        assert_eq!(rounds(n(190), n(100), U256::zero()), None);
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Context, Result};
+use ethers::contract::{parse_log, EthEvent};
+use ethers::providers::{Middleware, Provider, Ws};
+use ethers::types::{Address, Filter, U256};
+use futures_util::stream::StreamExt;
+use tokio::sync::{broadcast, watch};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::caches;
+use crate::curve::{CurveBuyFilter, CurveSellFilter, CurveTracker};
+use crate::protocol;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// Curve trades buffered for a slow [`PriceFeed::trades`] receiver before it lags.
+const TRADE_BUFFER: usize = 1024;
+
+/// A buy or sell on the bonding curve, as the feed saw it.
+#[derive(Debug, Clone, Copy)]
+pub struct CurveTrade {
+    pub token: Address,
+    pub buy: bool,
+    /// Native paid in by a buy or out to a sell.
+    pub mon: U256,
+}
+
+/// A token's virtual curve reserves as last seen by the feed.
+#[derive(Debug, Clone, Copy)]
+pub struct CurvePrice {
+    pub virtual_mon: U256,
+    pub virtual_token: U256,
+    pub at: Instant,
+}
+
+impl CurvePrice {
+    fn apply(&mut self, buy: bool, amount_in: U256, amount_out: U256) {
+        if buy {
+            self.virtual_mon += amount_in;
+            self.virtual_token = self.virtual_token.saturating_sub(amount_out);
+        } else {
+            self.virtual_token += amount_in;
+            self.virtual_mon = self.virtual_mon.saturating_sub(amount_out);
+        }
+        self.at = Instant::now();
+    }
+
+    /// Native a sell of `amount` returns at these reserves, after a curve fee of `fee_bps`.
+    fn sell_out(&self, amount: U256, fee_bps: u64) -> U256 {
+        let out = self.virtual_mon * amount / (self.virtual_token + amount).max(U256::one());
+        out.saturating_sub(out * U256::from(fee_bps) / U256::from(10_000u64))
+    }
+
+    /// Tokens a buy of `amount_in` returns at these reserves, after a curve fee of
+    /// `fee_bps`.
+    fn buy_out(&self, amount_in: U256, fee_bps: u64) -> U256 {
+        let fee = amount_in * U256::from(fee_bps) / U256::from(10_000u64);
+        let net = amount_in.saturating_sub(fee);
+        self.virtual_token * net / (self.virtual_mon + net).max(U256::one())
+    }
+}
+
+/// One consumer's view of a token's price. The token stays tracked while any watch on it
+/// is alive.
+pub struct PriceWatch {
+    prices: watch::Receiver<Option<CurvePrice>>,
+    max_age: Duration,
+}
+
+impl PriceWatch {
+    /// The current price, unless it is older than `PRICE_FEED_MAX_AGE_SECS` or the curve
+    /// fee hasn't been read yet.
+    fn fresh(&self) -> Option<(CurvePrice, u64)> {
+        let price = self.fresh_price()?;
+        Some((price, protocol::current()?.fee_bps()))
+    }
+
+    /// The current price, unless it is older than `max_age`.
+    fn fresh_price(&self) -> Option<CurvePrice> {
+        let price = (*self.prices.borrow())?;
+        (price.at.elapsed() <= self.max_age).then_some(price)
+    }
+
+    /// Native a sell of `amount` would return at the current price, after the curve fee.
+    pub fn sell_quote(&self, amount: U256) -> Option<U256> {
+        let (price, fee_bps) = self.fresh()?;
+        Some(price.sell_out(amount, fee_bps))
+    }
+
+    /// Tokens a buy of `amount_in` would return at the current price, after the curve fee.
+    pub fn buy_quote(&self, amount_in: U256) -> Option<U256> {
+        let (price, fee_bps) = self.fresh()?;
+        Some(price.buy_out(amount_in, fee_bps))
+    }
+}
+
+/// Curve prices shared by every task in the process. One WebSocket subscription to the
+/// bonding curve's buys and sells moves the reserves of each tracked token, seeded from
+/// the curve's state when a token is first watched and again whenever its price goes
+/// stale, so exit checks and limit orders quote from memory instead of one RPC call per
+/// position per check. Graduated tokens trade on the DEX, fall out of the feed and are
+/// quoted over RPC as before.
+pub struct PriceFeed {
+    ws_url: String,
+    curve: CurveTracker,
+    max_age: Duration,
+    prices: RwLock<HashMap<Address, watch::Sender<Option<CurvePrice>>>>,
+    trades: broadcast::Sender<CurveTrade>,
+}
+
+impl PriceFeed {
+    /// Enabled by `PRICE_FEED=true`; needs `WS_URL` and `BONDING_CURVE_ADDRESS`.
+    pub fn from_env(rpc_url: &str, curve: Option<Address>) -> Result<Option<Arc<Self>>> {
+        let enabled = env::var("PRICE_FEED")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+        let ws_url = env::var("WS_URL").context("WS_URL is required for PRICE_FEED")?;
+        let curve = curve.ok_or_else(|| anyhow!("PRICE_FEED needs BONDING_CURVE_ADDRESS"))?;
+        let secs: u64 = env::var("PRICE_FEED_MAX_AGE_SECS")
+            .ok()
+            .map(|v| v.parse().context("invalid PRICE_FEED_MAX_AGE_SECS"))
+            .transpose()?
+            .unwrap_or(30);
+        Ok(Some(Arc::new(Self {
+            ws_url,
+            curve: CurveTracker::new(rpc_url, curve)?,
+            max_age: Duration::from_secs(secs.max(1)),
+            prices: RwLock::new(HashMap::new()),
+            trades: broadcast::channel(TRADE_BUFFER).0,
+        })))
+    }
+
+    /// Watches `token`'s price, starting to track it if nobody was.
+    pub fn subscribe(self: &Arc<Self>, token: Address) -> PriceWatch {
+        let (prices, new) = match self.prices.write() {
+            Ok(mut tracked) => {
+                let new = !tracked.contains_key(&token);
+                let sender = tracked.entry(token).or_insert_with(|| watch::channel(None).0);
+                (sender.subscribe(), new)
+            }
+            Err(_) => (watch::channel(None).1, false),
+        };
+        if new {
+            let feed = self.clone();
+            tokio::spawn(async move { feed.seed(token).await });
+        }
+        PriceWatch {
+            prices,
+            max_age: self.max_age,
+        }
+    }
+
+    /// Every curve trade the subscription sees from now on, tracked token or not.
+    pub fn trades(&self) -> broadcast::Receiver<CurveTrade> {
+        self.trades.subscribe()
+    }
+
+    async fn seed(&self, token: Address) {
+        let price = match self.curve.state(token).await {
+            Ok(state) if state.graduated => None,
+            Ok(state) => Some(CurvePrice {
+                virtual_mon: state.virtual_mon_reserve,
+                virtual_token: state.virtual_token_reserve,
+                at: Instant::now(),
+            }),
+            Err(err) => {
+                warn!("Price feed could not seed {:?}: {:#}", token, err);
+                return;
+            }
+        };
+        if let Some(sender) = self.prices.read().ok().as_ref().and_then(|t| t.get(&token)) {
+            sender.send_replace(price);
+        }
+    }
+
+    fn apply(&self, token: Address, buy: bool, amount_in: U256, amount_out: U256) {
+        let Ok(tracked) = self.prices.read() else {
+            return;
+        };
+        let Some(sender) = tracked.get(&token) else {
+            return;
+        };
+        sender.send_if_modified(|price| match price {
+            Some(price) => {
+                price.apply(buy, amount_in, amount_out);
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Drops tokens nobody watches and reseeds stale ones.
+    async fn maintain(&self) {
+        let stale: Vec<Address> = match self.prices.write() {
+            Ok(mut tracked) => {
+                tracked.retain(|_, sender| sender.receiver_count() > 0);
+                caches::observe("price_feed", tracked.len());
+                tracked
+                    .iter()
+                    .filter(|(_, sender)| {
+                        sender.borrow().is_none_or(|price| price.at.elapsed() > self.max_age)
+                    })
+                    .map(|(token, _)| *token)
+                    .collect()
+            }
+            Err(_) => return,
+        };
+        for token in stale {
+            self.seed(token).await;
+        }
+    }
+
+    async fn listen(&self) -> Result<()> {
+        let ws = Provider::<Ws>::connect(&self.ws_url)
+            .await
+            .context("failed to connect WS_URL")?;
+        let (buy, sell) = (CurveBuyFilter::signature(), CurveSellFilter::signature());
+        let filter = Filter::new().address(self.curve.address()).topic0(vec![buy, sell]);
+        let mut stream = ws.subscribe_logs(&filter).await?;
+        info!("Price feed listening on {}", self.ws_url);
+        // Trades missed while disconnected would leave the reserves off, so start over.
+        let tracked: Vec<Address> = self
+            .prices
+            .read()
+            .map(|tracked| tracked.keys().copied().collect())
+            .unwrap_or_default();
+        for token in tracked {
+            self.seed(token).await;
+        }
+
+        while let Some(log) = stream.next().await {
+            let topic = log.topics.first().copied();
+            let decoded = if topic == Some(buy) {
+                parse_log::<CurveBuyFilter>(log)
+                    .map(|event| (event.token, true, event.amount_in, event.amount_out))
+            } else {
+                parse_log::<CurveSellFilter>(log)
+                    .map(|event| (event.token, false, event.amount_in, event.amount_out))
+            };
+            match decoded {
+                Ok((token, buy, amount_in, amount_out)) => {
+                    self.apply(token, buy, amount_in, amount_out);
+                    let mon = if buy { amount_in } else { amount_out };
+                    // Nobody listening is fine.
+                    let _ = self.trades.send(CurveTrade { token, buy, mon });
+                }
+                Err(err) => warn!("Undecodable curve trade log: {}", err),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs the feed's subscription and upkeep in the background.
+pub fn start(feed: &Arc<PriceFeed>) {
+    let subscription = feed.clone();
+    tokio::spawn(async move {
+        loop {
+            match subscription.listen().await {
+                Ok(()) => info!("Price feed subscription ended, reconnecting"),
+                Err(err) => warn!("Price feed subscription failed: {:#}, reconnecting", err),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+
+    let upkeep = feed.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(upkeep.max_age / 2);
+        loop {
+            ticker.tick().await;
+            upkeep.maintain().await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(virtual_mon: u64, virtual_token: u64) -> CurvePrice {
+        CurvePrice {
+            virtual_mon: U256::from(virtual_mon),
+            virtual_token: U256::from(virtual_token),
+            at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn quotes_follow_the_curve_net_of_the_fee() {
+        let price = price(900, 10_000);
+        assert_eq!(price.buy_out(U256::from(100u64), 0), U256::from(1_000u64));
+        assert_eq!(price.buy_out(U256::from(100u64), 100), U256::from(990u64));
+        assert_eq!(price.sell_out(U256::from(1_000u64), 0), U256::from(81u64));
+        assert_eq!(price.sell_out(U256::from(1_000u64), 100), U256::from(81u64));
+        assert_eq!(price.sell_out(U256::from(10_000u64), 100), U256::from(446u64));
+        assert!(price.buy_out(U256::from(100u64), 20_000).is_zero());
+    }
+
+    #[test]
+    fn trades_move_the_cached_reserves() {
+        let mut price = price(900, 10_000);
+        price.apply(true, U256::from(100u64), U256::from(1_000u64));
+        assert_eq!((price.virtual_mon, price.virtual_token), (1_000.into(), 9_000.into()));
+        price.apply(false, U256::from(9_000u64), U256::from(500u64));
+        assert_eq!((price.virtual_mon, price.virtual_token), (500.into(), 18_000.into()));
+    }
+
+    #[test]
+    fn prices_older_than_the_cutoff_are_not_quoted() {
+        let max_age = Duration::from_secs(5);
+        let (sender, prices) = watch::channel(None);
+        let watch = PriceWatch { prices, max_age };
+        assert!(watch.fresh_price().is_none());
+
+        sender.send_replace(Some(price(900, 10_000)));
+        assert!(watch.fresh_price().is_some());
+
+        let mut stale = price(900, 10_000);
+        stale.at = Instant::now() - max_age - Duration::from_millis(1);
+        sender.send_replace(Some(stale));
+        assert!(watch.fresh_price().is_none());
+        assert!(watch.buy_quote(U256::from(100u64)).is_none());
+    }
+}
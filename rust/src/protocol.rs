@@ -0,0 +1,175 @@
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Context, Result};
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, U256};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::chain;
+use crate::curve::BondingCurve;
+
+/// `protocolFee` is expressed in millionths of the trade amount.
+const FEE_DENOMINATOR: u64 = 1_000_000;
+
+static CURRENT: RwLock<Option<(ProtocolParams, Instant)>> = RwLock::new(None);
+
+/// Fee schedule and launch constants of the nad.fun bonding curve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolParams {
+    pub protocol_fee: u32,
+    pub deploy_fee: U256,
+    pub graduate_fee: U256,
+    pub virtual_mon_reserve: U256,
+    pub virtual_token_reserve: U256,
+    pub target_token_amount: U256,
+}
+
+impl ProtocolParams {
+    /// Fee charged on each buy or sell, in basis points.
+    pub fn fee_bps(&self) -> u64 {
+        self.protocol_fee as u64 * 10_000 / FEE_DENOMINATOR
+    }
+
+    /// Fields that differ from `previous`, as "name: old -> new".
+    fn changes_from(&self, previous: &ProtocolParams) -> Vec<String> {
+        let profile = chain::profile();
+        let mut changes = Vec::new();
+        if self.protocol_fee != previous.protocol_fee {
+            changes.push(format!("fee {} -> {} bps", previous.fee_bps(), self.fee_bps()));
+        }
+        let native = [
+            ("deploy fee", previous.deploy_fee, self.deploy_fee),
+            ("graduation fee", previous.graduate_fee, self.graduate_fee),
+            ("virtual MON reserve", previous.virtual_mon_reserve, self.virtual_mon_reserve),
+        ];
+        for (name, old, new) in native {
+            if old != new {
+                changes.push(format!(
+                    "{name}: {} -> {}",
+                    profile.format_native(old),
+                    profile.format_native(new)
+                ));
+            }
+        }
+        let tokens = [
+            ("virtual token reserve", previous.virtual_token_reserve, self.virtual_token_reserve),
+            ("graduation target", previous.target_token_amount, self.target_token_amount),
+        ];
+        for (name, old, new) in tokens {
+            if old != new {
+                changes.push(format!("{name}: {old} -> {new}"));
+            }
+        }
+        changes
+    }
+}
+
+impl fmt::Display for ProtocolParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let profile = chain::profile();
+        write!(
+            f,
+            "fee {} bps per side, deploy fee {}, graduation fee {}, graduation at {} tokens left",
+            self.fee_bps(),
+            profile.format_native(self.deploy_fee),
+            profile.format_native(self.graduate_fee),
+            ethers::utils::format_units(self.target_token_amount, 18).unwrap_or_default()
+        )
+    }
+}
+
+/// Last parameters read from the curve, if any read has succeeded yet.
+pub fn current() -> Option<ProtocolParams> {
+    CURRENT
+        .read()
+        .ok()
+        .and_then(|current| current.as_ref().map(|(params, _)| params.clone()))
+}
+
+/// Refuses new entries when the parameters haven't been read successfully within
+/// `max_age`, since fee math on stale values can't be trusted. Exits never check this.
+pub fn ensure_fresh(max_age: Duration) -> Result<()> {
+    let fetched_at = CURRENT
+        .read()
+        .ok()
+        .and_then(|current| current.as_ref().map(|(_, at)| *at));
+    match fetched_at {
+        Some(at) if at.elapsed() <= max_age => Ok(()),
+        Some(at) => Err(anyhow!(
+            "protocol parameters are {}s old, newer than {}s required for entries",
+            at.elapsed().as_secs(),
+            max_age.as_secs()
+        )),
+        None => Err(anyhow!("protocol parameters have never been read; entries are paused")),
+    }
+}
+
+/// Reads the curve's parameters now, then re-reads them every `interval` in the
+/// background, printing an alert whenever the protocol changes them.
+pub async fn start_watch(rpc_url: &str, curve_address: Address, interval: Duration) -> Result<()> {
+    let provider = Provider::<Http>::try_from(rpc_url).context("invalid RPC_URL")?;
+    let curve = BondingCurve::new(curve_address, Arc::new(provider));
+    match fetch(&curve).await {
+        Ok(params) => {
+            info!("nad.fun protocol: {}", params);
+            store(params);
+        }
+        Err(err) => warn!("Protocol parameters unavailable: {:#}", err),
+    }
+
+    if interval.is_zero() {
+        return Ok(());
+    }
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            let params = match fetch(&curve).await {
+                Ok(params) => params,
+                Err(err) => {
+                    warn!("Protocol parameter refresh failed: {:#}", err);
+                    continue;
+                }
+            };
+            if let Some(previous) = current() {
+                let changes = params.changes_from(&previous);
+                if !changes.is_empty() {
+                    warn!(
+                        "nad.fun protocol parameters changed: {}",
+                        changes.join(", ")
+                    );
+                }
+            }
+            store(params);
+        }
+    });
+    Ok(())
+}
+
+async fn fetch(curve: &BondingCurve<Provider<Http>>) -> Result<ProtocolParams> {
+    let (virtual_mon_reserve, virtual_token_reserve, target_token_amount) = curve
+        .config()
+        .call()
+        .await
+        .context("failed to read curve config")?;
+    let (deploy_fee, graduate_fee, protocol_fee) = curve
+        .fee_config()
+        .call()
+        .await
+        .context("failed to read curve fee config")?;
+    Ok(ProtocolParams {
+        protocol_fee,
+        deploy_fee,
+        graduate_fee,
+        virtual_mon_reserve,
+        virtual_token_reserve,
+        target_token_amount,
+    })
+}
+
+fn store(params: ProtocolParams) {
+    if let Ok(mut current) = CURRENT.write() {
+        *current = Some((params, Instant::now()));
+    }
+}
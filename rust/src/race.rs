@@ -0,0 +1,595 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::{Arc, Mutex, RwLock};
+
+use anyhow::{anyhow, Context, Result};
+use ethers::abi::{self, Token};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{
+    Address, BlockNumber, Bytes, Eip1559TransactionRequest, TransactionReceipt, H256, U256,
+};
+use ethers::utils::{id, keccak256};
+use futures_util::future::{join_all, select_all};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::OwnedMutexGuard;
+use tokio::time::{Duration, Instant};
+use tracing::{info, instrument, warn};
+
+use crate::chain;
+use crate::curve::CurveTracker;
+use crate::engine::ExecutionClient;
+use crate::execstats::{ExecRecord, Side};
+use crate::gas_strategy::{Fees, GasStrategy};
+use crate::latency;
+use crate::notify::Event;
+use crate::protocol;
+use crate::receipts;
+use crate::signer;
+use crate::sniper::Launch;
+use crate::state::OpenPosition;
+use crate::traces;
+use crate::tx_manager;
+use crate::app::AppConfig;
+use crate::mev;
+use crate::trading::{
+    apply_slippage, balance_besides, ensure_entry_allowed, hold_position, record_execution,
+    record_gas,
+};
+
+/// First-block buys for competitive launches: the nonce and gas fees are kept fresh in
+/// the background, so a launch's buy is built and signed locally the moment it passes
+/// the filters and broadcast to every RPC endpoint at once, with no quote, estimate or
+/// simulation round trips. The minimum out comes from the curve's initial reserves.
+/// The safety checks are skipped; the position is held on the usual exit rules.
+pub struct RaceConfig {
+    /// The bonding-curve router, so no quote is needed to find it.
+    pub router: Address,
+    pub gas_limit: U256,
+    /// How the buy's fees are priced, from `SNIPE_RACE_GAS`.
+    pub gas: GasStrategy,
+    /// A signature slower than `slow_sign` passes its signer over for `retry_after` when
+    /// there is a fallback signer.
+    pub slow_sign: Duration,
+    pub retry_after: Duration,
+}
+
+impl RaceConfig {
+    /// Enabled by `SNIPE_RACE_ROUTER`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(router) = env::var("SNIPE_RACE_ROUTER").ok() else {
+            return Ok(None);
+        };
+        let number = |name: &str, default: u64| -> Result<u64> {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {name}")))
+                .transpose()
+                .map(|v| v.unwrap_or(default))
+        };
+        Ok(Some(Self {
+            router: router.parse().context("invalid SNIPE_RACE_ROUTER")?,
+            gas_limit: number("SNIPE_RACE_GAS_LIMIT", 500_000)?.into(),
+            gas: env::var("SNIPE_RACE_GAS")
+                .ok()
+                .map(|v| v.parse().context("invalid SNIPE_RACE_GAS"))
+                .transpose()?
+                .unwrap_or(GasStrategy::Aggressive(150)),
+            slow_sign: Duration::from_millis(number("SIGNER_SLOW_MS", 20)?),
+            retry_after: Duration::from_secs(number("SIGNER_RETRY_SECS", 60)?),
+        }))
+    }
+}
+
+/// What a buy needs that doesn't depend on the launch.
+#[derive(Debug, Clone, Copy)]
+struct Prepared {
+    nonce: U256,
+    fees: Fees,
+}
+
+const PRIMARY: &str = "primary";
+const FALLBACK: &str = "fallback";
+
+/// Signatures per race signer since start, served by the control API's `/signers`. Their
+/// latency is under the `sign_primary` and `sign_fallback` stages.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignerStats {
+    pub role: &'static str,
+    pub backend: &'static str,
+    pub signed: u64,
+    pub failed: u64,
+    /// Signatures slower than `SIGNER_SLOW_MS`.
+    pub slow: u64,
+}
+
+static SIGNERS: Mutex<BTreeMap<&'static str, SignerStats>> = Mutex::new(BTreeMap::new());
+
+pub fn signer_stats() -> Vec<SignerStats> {
+    SIGNERS.lock().map(|signers| signers.values().cloned().collect()).unwrap_or_default()
+}
+
+/// A wallet race buys can be signed with, its prepared nonce, and until when it is passed
+/// over after a slow or failed signature.
+struct RaceSigner {
+    role: &'static str,
+    backend: &'static str,
+    wallet: LocalWallet,
+    prepared: RwLock<Option<Prepared>>,
+    degraded_until: RwLock<Option<Instant>>,
+}
+
+impl RaceSigner {
+    fn new(role: &'static str, backend: &'static str, key: &str) -> Result<Self> {
+        let wallet = key
+            .parse::<LocalWallet>()
+            .with_context(|| format!("invalid {} race signer key", role))?
+            .with_chain_id(chain::profile().chain_id);
+        Ok(Self {
+            role,
+            backend,
+            wallet,
+            prepared: RwLock::new(None),
+            degraded_until: RwLock::new(None),
+        })
+    }
+
+    fn prepared(&self) -> Result<Prepared> {
+        self.prepared
+            .read()
+            .ok()
+            .and_then(|prepared| *prepared)
+            .ok_or_else(|| anyhow!("race nonce and gas fees not fetched yet"))
+    }
+
+    fn healthy(&self, now: Instant) -> bool {
+        let until = self.degraded_until.read().ok().and_then(|until| *until);
+        until.is_none_or(|until| now >= until)
+    }
+
+    fn degrade(&self, retry_after: Duration) {
+        if let Ok(mut until) = self.degraded_until.write() {
+            *until = Some(Instant::now() + retry_after);
+        }
+    }
+
+    /// The latency stage its signatures are timed under.
+    fn stage(&self) -> &'static str {
+        if self.role == PRIMARY {
+            "sign_primary"
+        } else {
+            "sign_fallback"
+        }
+    }
+
+    fn tally(&self, update: impl FnOnce(&mut SignerStats)) {
+        if let Ok(mut signers) = SIGNERS.lock() {
+            update(signers.entry(self.role).or_insert_with(|| SignerStats {
+                role: self.role,
+                backend: self.backend,
+                signed: 0,
+                failed: 0,
+                slow: 0,
+            }));
+        }
+    }
+
+    /// Signs the buy with its prepared nonce and fees.
+    #[instrument(name = "sign", skip_all, fields(signer = self.role))]
+    async fn sign(
+        &self,
+        race: &RaceConfig,
+        token: Address,
+        recipient: Address,
+        amount_in: U256,
+        min_out: U256,
+        deadline: U256,
+    ) -> Result<(Bytes, U256)> {
+        let prepared = self.prepared()?;
+        let mut data = id("buy((uint256,address,address,uint256))").to_vec();
+        data.extend(abi::encode(&[Token::Tuple(vec![
+            Token::Uint(min_out),
+            Token::Address(token),
+            Token::Address(recipient),
+            Token::Uint(deadline),
+        ])]));
+        let tx: TypedTransaction = Eip1559TransactionRequest::new()
+            .from(self.wallet.address())
+            .to(race.router)
+            .value(amount_in)
+            .data(Bytes::from(data))
+            .nonce(prepared.nonce)
+            .gas(race.gas_limit)
+            .max_fee_per_gas(prepared.fees.max_fee)
+            .max_priority_fee_per_gas(prepared.fees.priority_fee)
+            .chain_id(self.wallet.chain_id())
+            .into();
+        let signature = self.wallet.sign_transaction(&tx).await?;
+        Ok((tx.rlp_signed(&signature), prepared.nonce))
+    }
+}
+
+/// The order signers are tried in: healthy ones first, each group in configured order.
+fn signing_order(signers: &[RaceSigner], now: Instant) -> Vec<&RaceSigner> {
+    let (mut order, degraded): (Vec<_>, Vec<_>) =
+        signers.iter().partition(|signer| signer.healthy(now));
+    order.extend(degraded);
+    order
+}
+
+pub struct Racer {
+    /// The main wallet, then the `SNIPE_FALLBACK_*` one if configured. Tokens always go
+    /// to the main wallet, which holds and sells them as usual.
+    signers: Vec<RaceSigner>,
+    gas: GasStrategy,
+    slow_sign: Duration,
+    retry_after: Duration,
+    endpoints: Vec<(String, Provider<Http>)>,
+    /// Initial virtual (MON, token) reserves, the same for every new curve.
+    initial_reserves: RwLock<Option<(U256, U256)>>,
+}
+
+impl Racer {
+    /// Connects to every endpoint and starts refreshing the nonce and gas fees each block.
+    pub async fn start(cfg: &AppConfig, race: &RaceConfig) -> Result<Arc<Self>> {
+        let endpoints = cfg
+            .rpc_pool
+            .endpoints()
+            .iter()
+            .map(|url| {
+                let provider = Provider::<Http>::try_from(url.as_str())
+                    .with_context(|| format!("invalid RPC endpoint {}", url))?;
+                Ok((url.clone(), provider))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut signers = vec![RaceSigner::new(PRIMARY, signer::backend(), &cfg.private_key)?];
+        if let Some((key, backend)) = signer::fallback_key()? {
+            let fallback = RaceSigner::new(FALLBACK, backend, &key)?;
+            if fallback.wallet.address() == signers[0].wallet.address() {
+                return Err(anyhow!("the fallback race signer is the main wallet"));
+            }
+            info!(
+                "Race buys fall back to {:?} while the main signer is slow or failing",
+                fallback.wallet.address()
+            );
+            signers.push(fallback);
+        }
+        let racer = Arc::new(Self {
+            signers,
+            gas: race.gas,
+            slow_sign: race.slow_sign,
+            retry_after: race.retry_after,
+            endpoints,
+            initial_reserves: RwLock::new(None),
+        });
+        racer.refresh().await?;
+        info!(
+            "Racing launch buys across {} RPC endpoints with {} gas",
+            racer.endpoints.len(),
+            race.gas
+        );
+
+        let refresher = racer.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(chain::profile().block_time);
+            loop {
+                ticker.tick().await;
+                if let Err(err) = refresher.refresh().await {
+                    warn!("Race nonce and gas refresh failed: {:#}", err);
+                }
+            }
+        });
+        Ok(racer)
+    }
+
+    /// The main wallet, which race-bought tokens go to.
+    pub fn wallet(&self) -> Address {
+        self.signers[0].wallet.address()
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        let (_, provider) = &self.endpoints[0];
+        let fees = self.gas.fees(provider).await?;
+        for signer in &self.signers {
+            let nonce = provider
+                .get_transaction_count(signer.wallet.address(), Some(BlockNumber::Pending.into()))
+                .await?;
+            if let Ok(mut prepared) = signer.prepared.write() {
+                *prepared = Some(Prepared { nonce, fees });
+            }
+        }
+        Ok(())
+    }
+
+    /// Tokens out of `amount_in` on a fresh curve, after the protocol fee. The initial
+    /// reserves are read from the first raced curve and reused for every later one.
+    async fn expected_out(
+        &self,
+        curve: &CurveTracker,
+        token: Address,
+        amount_in: U256,
+    ) -> Result<U256> {
+        let known = self.initial_reserves.read().ok().and_then(|reserves| *reserves);
+        let (mon, tokens) = match known {
+            Some(reserves) => reserves,
+            None => {
+                let state = curve.state(token).await.context("failed to read curve reserves")?;
+                let reserves = (state.init_virtual_mon_reserve, state.init_virtual_token_reserve);
+                if let Ok(mut known) = self.initial_reserves.write() {
+                    *known = Some(reserves);
+                }
+                reserves
+            }
+        };
+        let fee_bps = protocol::current().map(|params| params.fee_bps()).unwrap_or(0);
+        curve_out(mon, tokens, amount_in, fee_bps)
+    }
+
+    /// Signs the buy with the first healthy signer, the main wallet's first, and returns
+    /// it with its send lock held. A signer that fails is passed over for the next; a slow
+    /// one's signature is used, and it is passed over for `SIGNER_RETRY_SECS` after.
+    async fn sign_buy(
+        &self,
+        race: &RaceConfig,
+        token: Address,
+        recipient: Address,
+        amount_in: U256,
+        min_out: U256,
+        deadline: U256,
+    ) -> Result<(&RaceSigner, OwnedMutexGuard<()>, Bytes, U256)> {
+        let mut failure = None;
+        for signer in signing_order(&self.signers, Instant::now()) {
+            // Held from the prepared nonce until the broadcast, like every other send.
+            let guard = tx_manager::send_lock(signer.wallet.address()).lock_owned().await;
+            let started = Instant::now();
+            let signed = signer.sign(race, token, recipient, amount_in, min_out, deadline).await;
+            let elapsed = started.elapsed();
+            match signed {
+                Ok((raw, nonce)) => {
+                    latency::record(signer.stage(), elapsed);
+                    let slow = elapsed > self.slow_sign;
+                    signer.tally(|stats| {
+                        stats.signed += 1;
+                        stats.slow += u64::from(slow);
+                    });
+                    if slow && self.signers.len() > 1 {
+                        warn!(
+                            "The {} race signer took {}ms, passing it over for {}s",
+                            signer.role,
+                            elapsed.as_millis(),
+                            self.retry_after.as_secs()
+                        );
+                        signer.degrade(self.retry_after);
+                    }
+                    return Ok((signer, guard, raw, nonce));
+                }
+                Err(err) => {
+                    warn!("The {} race signer failed: {:#}", signer.role, err);
+                    signer.tally(|stats| stats.failed += 1);
+                    signer.degrade(self.retry_after);
+                    failure = Some(err);
+                }
+            }
+        }
+        Err(failure.unwrap_or_else(|| anyhow!("no race signer configured")))
+    }
+
+    /// Sends `raw` to every endpoint at once; succeeds if any accepted it.
+    #[instrument(name = "broadcast", skip_all)]
+    async fn broadcast(&self, raw: &Bytes) -> Result<()> {
+        let started = Instant::now();
+        let results = join_all(self.endpoints.iter().map(|(url, provider)| async move {
+            let result = provider.send_raw_transaction(raw.clone()).await;
+            (url, started.elapsed(), result.map(|_| ()))
+        }))
+        .await;
+        latency::record("broadcast", started.elapsed());
+        let mut errors = Vec::new();
+        for (url, elapsed, result) in results {
+            match result {
+                Ok(()) => info!("Race buy accepted by {} after {}ms", url, elapsed.as_millis()),
+                Err(err) => errors.push(format!("{}: {}", url, err)),
+            }
+        }
+        if errors.len() == self.endpoints.len() {
+            return Err(anyhow!("every endpoint rejected the race buy: {}", errors.join("; ")));
+        }
+        Ok(())
+    }
+
+    /// The first receipt any endpoint returns. An endpoint that fails is dropped from
+    /// the wait; this only fails once every one of them has.
+    #[instrument(name = "confirm", skip_all, fields(tx = ?hash))]
+    async fn inclusion(&self, hash: H256) -> Result<TransactionReceipt> {
+        let mut waits: Vec<_> = self
+            .endpoints
+            .iter()
+            .map(|(url, provider)| {
+                Box::pin(async move {
+                    receipts::wait_for_receipt(provider, hash)
+                        .await
+                        .with_context(|| url.clone())
+                })
+            })
+            .collect();
+        let mut errors = Vec::new();
+        while !waits.is_empty() {
+            let (result, _, rest) = select_all(waits).await;
+            match result {
+                Ok(receipt) => return Ok(receipt),
+                Err(err) => errors.push(format!("{:#}", err)),
+            }
+            waits = rest;
+        }
+        Err(anyhow!("no endpoint returned the race buy's receipt: {}", errors.join("; ")))
+    }
+}
+
+/// Tokens a buy of `amount_in` returns from reserves of `mon` and `tokens`, after a
+/// curve fee of `fee_bps`.
+fn curve_out(mon: U256, tokens: U256, amount_in: U256, fee_bps: u64) -> Result<U256> {
+    let kept_bps = 10_000u64
+        .checked_sub(fee_bps)
+        .ok_or_else(|| anyhow!("curve fee of {} bps is over 100%", fee_bps))?;
+    let amount_in = amount_in * U256::from(kept_bps) / U256::from(10_000u64);
+    Ok(tokens * amount_in / (mon + amount_in).max(U256::one()))
+}
+
+/// Races the buy of `launch`, then holds the position like any other.
+pub async fn snipe(
+    cfg: &AppConfig,
+    race: &RaceConfig,
+    racer: &Racer,
+    client: &impl ExecutionClient,
+    curve: &CurveTracker,
+    launch: &Launch,
+) -> Result<()> {
+    let token = launch.token;
+    ensure_entry_allowed(cfg, racer.wallet(), token)?;
+    let recipient = cfg.recipient.unwrap_or_else(|| racer.wallet());
+    let amount_in = cfg.controls.amount_in(cfg.defaults.amount_in);
+    let expected = racer.expected_out(curve, token, amount_in).await?;
+    let min_out = apply_slippage(expected, cfg.controls.slippage_bps(cfg.defaults.slippage_bps));
+
+    let started = Instant::now();
+    let (signer, guard, raw, nonce) = racer
+        .sign_buy(race, token, recipient, amount_in, min_out, cfg.deadline_u256())
+        .await?;
+    latency::record("sign", started.elapsed());
+    if signer.role != PRIMARY {
+        info!("Race buy of {:?} signed by the {} wallet", token, signer.role);
+    }
+    let hash = H256::from(keccak256(&raw));
+    if cfg.dry_run {
+        info!("Dry run: would race {:?} at nonce {} with {} signed bytes", token, nonce, raw.len());
+        return Ok(());
+    }
+    if let Some(risk) = &cfg.risk {
+        risk.check_entry(cfg, recipient, token, amount_in).await?;
+    }
+    let release = || {
+        if let Some(risk) = &cfg.risk {
+            if let Err(err) = risk.release(recipient, amount_in) {
+                warn!("Failed to release the buy from the risk limits: {:#}", err);
+            }
+        }
+    };
+    // A rejection may only mean the endpoints were briefly unreachable, and any of them
+    // could still have the transaction; resend the same signed bytes so the retry can
+    // never become a second buy at another nonce.
+    if let Err(err) = racer.broadcast(&raw).await {
+        warn!("Race buy of {:?} rejected, resending once: {:#}", token, err);
+        tokio::time::sleep(chain::profile().block_time).await;
+        if let Err(err) = racer.broadcast(&raw).await {
+            release();
+            // Most likely the prepared nonce went stale under another send.
+            if let Err(err) = racer.refresh().await {
+                warn!("Race nonce and gas refresh failed: {:#}", err);
+            }
+            return Err(err);
+        }
+    }
+    if let Ok(mut prepared) = signer.prepared.write() {
+        if let Some(prepared) = prepared.as_mut() {
+            prepared.nonce += U256::one();
+        }
+    }
+    drop(guard);
+
+    let receipt = racer.inclusion(hash).await?;
+    traces::after_receipt(cfg, "race buy", &receipt).await;
+    if let Some(budget) = &cfg.gas_budget {
+        record_gas(budget, &receipt).await;
+    }
+    let inclusion = started.elapsed();
+    latency::record("race_inclusion", inclusion);
+    let blocks_after = match (receipt.block_number, launch.block) {
+        (Some(mined), Some(launched)) => Some(mined.as_u64().saturating_sub(launched)),
+        _ => None,
+    };
+    info!(
+        phase = "race",
+        elapsed_ms = inclusion.as_millis() as u64,
+        "Race buy of {:?} included in {}ms, {:?} blocks after the launch: {}",
+        token,
+        inclusion.as_millis(),
+        blocks_after,
+        chain::profile().tx_url(hash)
+    );
+    if receipt.status.is_some_and(|status| status.is_zero()) {
+        release();
+        record_execution(cfg, &ExecRecord::reverted(token, Side::Buy, expected, inclusion));
+        return Err(anyhow!("race buy {:?} reverted", hash));
+    }
+
+    let received = mev::received_amount(&receipt, token, recipient);
+    record_execution(cfg, &ExecRecord::filled(token, Side::Buy, expected, received, inclusion));
+    cfg.notifier.send(
+        Event::Buy,
+        format!(
+            "Raced {:?} for {}: {}",
+            token,
+            chain::profile().format_native(amount_in),
+            chain::profile().tx_url(hash)
+        ),
+    );
+    cfg.audit(
+        "buy",
+        json!({
+            "token": token,
+            "wallet": recipient,
+            "tx": hash,
+            "router": race.router,
+            "amount_in": amount_in,
+            "quoted_out": expected,
+            "amount_out_min": min_out,
+            "raced": true,
+        }),
+    );
+
+    let quoted_out = if received.is_zero() { expected } else { received };
+    let mut position =
+        OpenPosition::new(token, recipient, hash, race.router, amount_in, quoted_out);
+    position.gas_spent = receipts::gas_cost(&receipt);
+    position.creator = Some(launch.creator);
+    position.strategy = Some("sniper".into());
+    match balance_besides(client, token, recipient, received).await {
+        Ok(held) => position.held_back = held,
+        Err(err) => warn!("Could not check for tokens held before the buy: {:#}", err),
+    }
+    if let Err(err) = cfg.state.open(position.clone()) {
+        warn!("Failed to persist open position: {:#}", err);
+    }
+    hold_position(cfg, client, &position).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve_out_takes_the_fee_and_refuses_one_over_100_pct() {
+        let (mon, tokens) = (U256::from(900u64), U256::from(10_000u64));
+        assert_eq!(curve_out(mon, tokens, U256::from(100u64), 0).unwrap(), U256::from(1_000u64));
+        assert_eq!(curve_out(mon, tokens, U256::from(100u64), 100).unwrap(), U256::from(990u64));
+        assert!(curve_out(mon, tokens, U256::from(100u64), 10_000).unwrap().is_zero());
+        assert!(curve_out(mon, tokens, U256::from(100u64), 10_001).is_err());
+    }
+
+    #[test]
+    fn degraded_signers_are_tried_last_until_their_retry() {
+        let key = |byte: u8| format!("{:064x}", byte);
+        let signers = [
+            RaceSigner::new(PRIMARY, "keystore", &key(1)).unwrap(),
+            RaceSigner::new(FALLBACK, "private key", &key(2)).unwrap(),
+        ];
+        let roles = |now| signing_order(&signers, now).iter().map(|s| s.role).collect::<Vec<_>>();
+        assert_eq!(roles(Instant::now()), [PRIMARY, FALLBACK]);
+
+        signers[0].degrade(Duration::from_secs(60));
+        assert_eq!(roles(Instant::now()), [FALLBACK, PRIMARY]);
+        assert_eq!(roles(Instant::now() + Duration::from_secs(61)), [PRIMARY, FALLBACK]);
+    }
+}
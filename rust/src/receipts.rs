@@ -0,0 +1,70 @@
+use anyhow::{anyhow, Result};
+use ethers::abi::{self, ParamType, Token};
+use ethers::providers::{Http, Middleware, Provider, RpcError};
+use ethers::types::{TransactionReceipt, TransactionRequest, H256, U256};
+use tokio::time::{Duration, Instant};
+
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const RECEIPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub async fn wait_for_receipt(provider: &Provider<Http>, tx_hash: H256) -> Result<TransactionReceipt> {
+    wait_for_receipt_within(provider, tx_hash, RECEIPT_TIMEOUT).await
+}
+
+pub async fn wait_for_receipt_within(
+    provider: &Provider<Http>,
+    tx_hash: H256,
+    timeout: Duration,
+) -> Result<TransactionReceipt> {
+    let until = Instant::now() + timeout;
+    loop {
+        if let Some(receipt) = provider.get_transaction_receipt(tx_hash).await? {
+            return Ok(receipt);
+        }
+        if Instant::now() >= until {
+            return Err(anyhow!("no receipt for {:?} after {:?}", tx_hash, timeout));
+        }
+        tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+    }
+}
+
+/// Native token paid for gas by a mined transaction.
+pub fn gas_cost(receipt: &TransactionReceipt) -> U256 {
+    receipt.gas_used.unwrap_or_default() * receipt.effective_gas_price.unwrap_or_default()
+}
+
+/// Replays a reverted transaction against the state before its block to recover the
+/// revert reason. Earlier transactions in the same block aren't replayed, so this is
+/// best effort.
+pub async fn revert_reason(provider: &Provider<Http>, receipt: &TransactionReceipt) -> Option<String> {
+    let tx = provider.get_transaction(receipt.transaction_hash).await.ok()??;
+    let block = receipt.block_number?.as_u64().checked_sub(1)?;
+    let mut call = TransactionRequest::new()
+        .from(tx.from)
+        .data(tx.input)
+        .value(tx.value)
+        .gas(tx.gas);
+    if let Some(to) = tx.to {
+        call = call.to(to);
+    }
+    let err = match provider.call(&call.into(), Some(block.into())).await {
+        Ok(_) => return None,
+        Err(err) => err,
+    };
+
+    let response = err.as_error_response()?;
+    let data = response
+        .data
+        .as_ref()
+        .and_then(|data| data.as_str())
+        .and_then(|hex| ethers::utils::hex::decode(hex.trim_start_matches("0x")).ok());
+    // Error(string)
+    if let Some(data) = data.filter(|data| data.starts_with(&[0x08, 0xc3, 0x79, 0xa0])) {
+        if let Ok(tokens) = abi::decode(&[ParamType::String], &data[4..]) {
+            if let Some(Token::String(reason)) = tokens.into_iter().next() {
+                return Some(reason);
+            }
+        }
+    }
+    Some(response.message.clone())
+}
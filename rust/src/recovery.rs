@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, H256, U256};
+use tracing::{info, warn};
+
+use crate::chain;
+use crate::engine::ExecutionClient;
+use crate::notify::Event;
+use crate::state::OpenPosition;
+use crate::app::AppConfig;
+
+/// What to do with discrepancies found at startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryMode {
+    /// List them and carry on.
+    Report,
+    /// Ask before applying each proposed fix.
+    Prompt,
+    /// Apply every proposed fix.
+    Auto,
+}
+
+impl FromStr for RecoveryMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "report" => Ok(Self::Report),
+            "prompt" => Ok(Self::Prompt),
+            "auto" => Ok(Self::Auto),
+            other => Err(anyhow!("unknown RECOVERY {:?}; expected report, prompt or auto", other)),
+        }
+    }
+}
+
+impl RecoveryMode {
+    pub fn from_env() -> Result<Self> {
+        env::var("RECOVERY")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()
+            .map(|mode| mode.unwrap_or(Self::Report))
+    }
+}
+
+/// What to do with a balance of a configured token the bot has no position for, such as
+/// a manual buy or an airdrop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdoptionPolicy {
+    /// Leave it alone.
+    Ignore,
+    /// Propose managing it as a position, with its current exit quote as the cost basis.
+    Adopt,
+    /// Send an alert and leave it alone.
+    Alert,
+}
+
+impl FromStr for AdoptionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ignore" => Ok(Self::Ignore),
+            "adopt" => Ok(Self::Adopt),
+            "alert" => Ok(Self::Alert),
+            other => Err(anyhow!(
+                "unknown ADOPTION_POLICY {:?}; expected ignore, adopt or alert",
+                other
+            )),
+        }
+    }
+}
+
+impl AdoptionPolicy {
+    pub fn from_env() -> Result<Self> {
+        env::var("ADOPTION_POLICY")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()
+            .map(|policy| policy.unwrap_or(Self::Adopt))
+    }
+}
+
+enum Issue {
+    /// The ledger has the round trip, but the position store still lists it as open.
+    AlreadyClosed(OpenPosition),
+    /// The store lists the position, but the wallet holds none of the token.
+    NoBalance(OpenPosition),
+    /// The wallet holds a configured token the store doesn't know about.
+    Untracked { token: Address, balance: U256 },
+}
+
+impl Issue {
+    fn describe(&self) -> String {
+        match self {
+            Self::AlreadyClosed(position) => format!(
+                "{:?} from {} is in the trade ledger but still open in the position store",
+                position.token,
+                position.origin()
+            ),
+            Self::NoBalance(position) => format!(
+                "{:?} from {} is open in the position store but the wallet holds none",
+                position.token,
+                position.origin()
+            ),
+            Self::Untracked { token, balance } => format!(
+                "wallet holds {} of {:?} with no open position",
+                ethers::utils::format_units(*balance, 18).unwrap_or_default(),
+                token
+            ),
+        }
+    }
+
+    fn proposal(&self) -> &'static str {
+        match self {
+            Self::AlreadyClosed(_) => "clear it from the position store",
+            Self::NoBalance(_) => "mark it closed (no ledger entry, proceeds unknown)",
+            Self::Untracked { .. } => "adopt the balance as a position priced at its current exit quote",
+        }
+    }
+}
+
+/// Cross-checks the position store, trade ledger and on-chain balances for `wallet`
+/// and resolves what disagrees according to `RECOVERY`, before anything is resumed.
+pub async fn reconcile(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    wallet: Address,
+) -> Result<()> {
+    let mut mode = cfg.recovery;
+    if mode == RecoveryMode::Prompt && !io::stdin().is_terminal() {
+        warn!("RECOVERY=prompt needs a terminal; only reporting discrepancies");
+        mode = RecoveryMode::Report;
+    }
+    if cfg.dry_run {
+        mode = RecoveryMode::Report;
+    }
+
+    let closed: HashSet<H256> =
+        cfg.ledger.load()?.iter().filter_map(|record| record.buy_tx).collect();
+    let positions: Vec<OpenPosition> = cfg
+        .state
+        .open_positions()?
+        .into_iter()
+        .filter(|position| position.wallet == wallet)
+        .collect();
+
+    let mut issues = Vec::new();
+    for position in &positions {
+        if position.buy_tx.is_some_and(|tx| closed.contains(&tx)) {
+            issues.push(Issue::AlreadyClosed(position.clone()));
+            continue;
+        }
+        let balance = client
+            .token_balance(position.token, wallet)
+            .await
+            .context("failed to fetch wallet balance")?;
+        if balance.is_zero() {
+            issues.push(Issue::NoBalance(position.clone()));
+        }
+    }
+    for target in &cfg.targets {
+        if positions.iter().any(|position| position.token == target.token) {
+            continue;
+        }
+        let balance = client
+            .token_balance(target.token, wallet)
+            .await
+            .context("failed to fetch wallet balance")?;
+        if balance.is_zero() {
+            continue;
+        }
+        let untracked = Issue::Untracked {
+            token: target.token,
+            balance,
+        };
+        match cfg.adoption {
+            AdoptionPolicy::Adopt => issues.push(untracked),
+            AdoptionPolicy::Alert => {
+                warn!("Not adopting: {}", untracked.describe());
+                cfg.notifier.send(
+                    Event::Error,
+                    format!("{:?}: {}; left unmanaged", wallet, untracked.describe()),
+                );
+            }
+            AdoptionPolicy::Ignore => info!("Ignoring: {}", untracked.describe()),
+        }
+    }
+    if issues.is_empty() {
+        return Ok(());
+    }
+
+    info!("Startup state check found {} discrepancies:", issues.len());
+    for (n, issue) in issues.iter().enumerate() {
+        info!("  {}. {}; proposed fix: {}", n + 1, issue.describe(), issue.proposal());
+    }
+    if mode == RecoveryMode::Report {
+        info!("Set RECOVERY=auto or RECOVERY=prompt to apply the proposed fixes");
+        return Ok(());
+    }
+
+    for issue in &issues {
+        let question = format!("{}: {}?", issue.describe(), issue.proposal());
+        if mode == RecoveryMode::Prompt && !confirm(&question)? {
+            continue;
+        }
+        apply(cfg, client, wallet, issue).await?;
+    }
+    Ok(())
+}
+
+async fn apply(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    wallet: Address,
+    issue: &Issue,
+) -> Result<()> {
+    match issue {
+        Issue::AlreadyClosed(position) | Issue::NoBalance(position) => {
+            cfg.state.close(position.token, wallet)?;
+            info!("Closed {:?} in the position store", position.token);
+        }
+        Issue::Untracked { token, balance } => {
+            let (router, value) = client
+                .quote(*token, *balance, false)
+                .await
+                .context("failed to quote untracked balance")?;
+            cfg.state.open(OpenPosition::adopted(*token, wallet, router, value, *balance))?;
+            info!(
+                "Adopted {:?} at {}; it will be managed like any open position",
+                token,
+                chain::profile().format_native(value)
+            );
+        }
+    }
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovery_mode_parses_case_insensitively() {
+        assert_eq!("report".parse::<RecoveryMode>().unwrap(), RecoveryMode::Report);
+        assert_eq!(" Prompt ".parse::<RecoveryMode>().unwrap(), RecoveryMode::Prompt);
+        assert_eq!("AUTO".parse::<RecoveryMode>().unwrap(), RecoveryMode::Auto);
+        assert!("fix".parse::<RecoveryMode>().is_err());
+    }
+
+    #[test]
+    fn adoption_policy_parses_case_insensitively() {
+        assert_eq!("ignore".parse::<AdoptionPolicy>().unwrap(), AdoptionPolicy::Ignore);
+        assert_eq!("Adopt".parse::<AdoptionPolicy>().unwrap(), AdoptionPolicy::Adopt);
+        assert_eq!(" alert".parse::<AdoptionPolicy>().unwrap(), AdoptionPolicy::Alert);
+        assert!("".parse::<AdoptionPolicy>().is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, U256};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::app::AppConfig;
+use crate::engine::ExecutionClient;
+
+/// The sell that closed a position on its stop loss.
+#[derive(Debug, Clone, Copy)]
+pub struct StopOut {
+    pub tokens: U256,
+    pub proceeds: U256,
+    pub at: Instant,
+}
+
+/// Buys a watched token again after a stop loss, once its price reclaims `reclaim_pct`
+/// above the stop-out or `cooldown` has passed, at most `max` times per token.
+pub struct ReentryConfig {
+    pub max: u32,
+    pub cooldown: Option<Duration>,
+    pub reclaim_pct: Option<f64>,
+    stop_outs: Mutex<HashMap<Address, StopOut>>,
+    counts: Mutex<HashMap<Address, u32>>,
+}
+
+impl ReentryConfig {
+    /// Enabled by `REENTRY_MAX`, with `REENTRY_COOLDOWN_SECS` and/or `REENTRY_RECLAIM_PCT`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(max) = env::var("REENTRY_MAX").ok() else {
+            return Ok(None);
+        };
+        let max = max.parse().context("invalid REENTRY_MAX")?;
+        let cooldown = env::var("REENTRY_COOLDOWN_SECS")
+            .ok()
+            .map(|v| v.parse().map(Duration::from_secs).context("invalid REENTRY_COOLDOWN_SECS"))
+            .transpose()?;
+        let reclaim_pct: Option<f64> = env::var("REENTRY_RECLAIM_PCT")
+            .ok()
+            .map(|v| v.parse().context("invalid REENTRY_RECLAIM_PCT"))
+            .transpose()?;
+        if cooldown.is_none() && reclaim_pct.is_none() {
+            return Err(anyhow!(
+                "REENTRY_MAX needs REENTRY_COOLDOWN_SECS and/or REENTRY_RECLAIM_PCT"
+            ));
+        }
+        if reclaim_pct.is_some_and(|pct| pct < 0.0) {
+            return Err(anyhow!("REENTRY_RECLAIM_PCT must not be negative"));
+        }
+        Ok(Some(Self {
+            max,
+            cooldown,
+            reclaim_pct,
+            stop_outs: Mutex::default(),
+            counts: Mutex::default(),
+        }))
+    }
+
+    pub fn record_stop_out(&self, token: Address, tokens: U256, proceeds: U256) {
+        let stop_out = StopOut {
+            tokens,
+            proceeds,
+            at: Instant::now(),
+        };
+        if let Ok(mut stop_outs) = self.stop_outs.lock() {
+            stop_outs.insert(token, stop_out);
+        }
+    }
+
+    /// The stop-out that ended the last round trip in `token`, if it is due a re-entry.
+    pub fn take(&self, token: Address) -> Option<StopOut> {
+        let stop_out = self.stop_outs.lock().ok()?.remove(&token)?;
+        let count = self.counts.lock().ok()?.get(&token).copied().unwrap_or(0);
+        if count >= self.max {
+            info!("No re-entries left in {:?} after {} stop-outs", token, count + 1);
+            return None;
+        }
+        Some(stop_out)
+    }
+
+    /// What the stop-out's tokens must sell for again to count as reclaimed.
+    fn reclaim_target(&self, stop_out: &StopOut) -> Option<U256> {
+        let pct = self.reclaim_pct?;
+        let bps = (10_000.0 + pct * 100.0) as u64;
+        Some(stop_out.proceeds * U256::from(bps) / U256::from(10_000u64))
+    }
+
+    /// Waits for the cooldown or the reclaim, whichever comes first. Counts the re-entry
+    /// and returns true, or false if the token left the watchlist or the bot is stopping.
+    pub async fn wait(
+        &self,
+        cfg: &AppConfig,
+        client: &impl ExecutionClient,
+        token: Address,
+        stop_out: &StopOut,
+    ) -> bool {
+        let target = self.reclaim_target(stop_out);
+        let interval = Duration::from_secs(cfg.exit_check_interval_secs.max(1));
+        loop {
+            if cfg.shutdown.requested() || !cfg.controls.is_watched(token) {
+                return false;
+            }
+            if self.cooldown.is_some_and(|cooldown| stop_out.at.elapsed() >= cooldown) {
+                info!("Re-entry cooldown of {:?} over", token);
+                break;
+            }
+            if let Some(target) = target {
+                match client.quote(token, stop_out.tokens, false).await {
+                    Ok((_, value)) if value >= target => {
+                        info!("{:?} reclaimed its stop-out level", token);
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(err) => warn!("Re-entry quote for {:?} failed: {:#}", token, err),
+                }
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = cfg.shutdown.wait() => {}
+            }
+        }
+        if let Ok(mut counts) = self.counts.lock() {
+            *counts.entry(token).or_default() += 1;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reentry(max: u32, reclaim_pct: Option<f64>) -> ReentryConfig {
+        ReentryConfig {
+            max,
+            cooldown: None,
+            reclaim_pct,
+            stop_outs: Mutex::default(),
+            counts: Mutex::default(),
+        }
+    }
+
+    #[test]
+    fn the_reclaim_level_is_above_the_stop_out_proceeds() {
+        let stop_out = StopOut {
+            tokens: U256::from(1_000u64),
+            proceeds: U256::from(10_000u64),
+            at: Instant::now(),
+        };
+        assert_eq!(reentry(1, Some(5.0)).reclaim_target(&stop_out), Some(10_500u64.into()));
+        assert_eq!(reentry(1, Some(0.0)).reclaim_target(&stop_out), Some(10_000u64.into()));
+        assert_eq!(reentry(1, None).reclaim_target(&stop_out), None);
+    }
+
+    #[test]
+    fn stop_outs_are_due_a_re_entry_until_the_max() {
+        let token = Address::repeat_byte(1);
+        let reentry = reentry(1, Some(0.0));
+        assert!(reentry.take(token).is_none());
+
+        reentry.record_stop_out(token, U256::one(), U256::one());
+        assert!(reentry.take(token).is_some());
+        assert!(reentry.take(token).is_none());
+
+        reentry.counts.lock().unwrap().insert(token, 1);
+        reentry.record_stop_out(token, U256::one(), U256::one());
+        assert!(reentry.take(token).is_none());
+    }
+}
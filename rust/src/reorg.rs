@@ -0,0 +1,310 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::future::Future;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, H256};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::app::AppConfig;
+use crate::chain;
+use crate::engine::ExecutionClient;
+use crate::notify::Event;
+use crate::recovery;
+
+/// Follows the chain's blocks and, when one's parent isn't the block seen before it,
+/// re-checks the wallet's open buys and recent sells and reconciles the position store.
+pub struct ReorgConfig {
+    /// Blocks of hashes kept to find where a reorg forked.
+    pub depth: u64,
+}
+
+impl ReorgConfig {
+    /// Enabled by `REORG_WATCH_DEPTH`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(depth) = env::var("REORG_WATCH_DEPTH").ok() else {
+            return Ok(None);
+        };
+        let depth: u64 = depth.parse().context("invalid REORG_WATCH_DEPTH")?;
+        if depth == 0 {
+            return Err(anyhow!("REORG_WATCH_DEPTH must be positive"));
+        }
+        Ok(Some(Self { depth }))
+    }
+}
+
+/// A block as far as following the chain goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Header {
+    number: u64,
+    hash: H256,
+    parent: H256,
+    timestamp: u64,
+}
+
+async fn header(provider: &Provider<Http>, number: u64) -> Result<Header> {
+    let block = provider
+        .get_block(number)
+        .await?
+        .ok_or_else(|| anyhow!("block {} not found", number))?;
+    Ok(Header {
+        number,
+        hash: block
+            .hash
+            .ok_or_else(|| anyhow!("block {} has no hash", number))?,
+        parent: block.parent_hash,
+        timestamp: block.timestamp.as_u64(),
+    })
+}
+
+/// A reorg: the first block replaced, and the timestamp of the last one kept.
+#[derive(Debug, PartialEq)]
+struct Reorg {
+    fork: u64,
+    depth: u64,
+    since: u64,
+}
+
+/// The hashes of the last `depth` blocks followed.
+struct Blocks {
+    depth: u64,
+    seen: BTreeMap<u64, Header>,
+}
+
+impl Blocks {
+    fn new(depth: u64) -> Self {
+        Self {
+            depth,
+            seen: BTreeMap::new(),
+        }
+    }
+
+    fn last(&self) -> Option<u64> {
+        self.seen.keys().next_back().copied()
+    }
+
+    /// Records `head`, the block after the last one followed. If its parent isn't the
+    /// block seen at that height, walks back through `fetch`, the canonical block at a
+    /// height, until the chains agree again.
+    async fn follow<F, Fut>(&mut self, head: Header, fetch: F) -> Result<Option<Reorg>>
+    where
+        F: Fn(u64) -> Fut,
+        Fut: Future<Output = Result<Header>>,
+    {
+        let replaced_from = head.number;
+        let old_head = self.last().unwrap_or(head.number);
+        let mut parent = head.parent;
+        let mut number = head.number;
+        self.seen.insert(head.number, head);
+        while let Some(seen) = number.checked_sub(1).and_then(|n| self.seen.get(&n)) {
+            if seen.hash == parent {
+                break;
+            }
+            number -= 1;
+            let canonical = fetch(number).await?;
+            parent = canonical.parent;
+            self.seen.insert(number, canonical);
+        }
+        let floor = (head.number + 1).saturating_sub(self.depth);
+        self.seen = self.seen.split_off(&floor);
+        if number == replaced_from {
+            return Ok(None);
+        }
+        let since = number
+            .checked_sub(1)
+            .and_then(|kept| self.seen.get(&kept))
+            .map_or(0, |kept| kept.timestamp);
+        Ok(Some(Reorg {
+            fork: number,
+            depth: old_head + 1 - number,
+            since,
+        }))
+    }
+}
+
+/// Polls for new blocks until the process stops, handling each reorg it finds. Errors
+/// are logged and the next poll tries again.
+pub async fn watch(cfg: &AppConfig, client: &impl ExecutionClient, reorgs: &ReorgConfig) {
+    let provider = match Provider::<Http>::try_from(cfg.rpc_url.as_str()) {
+        Ok(provider) => provider,
+        Err(err) => {
+            warn!("Reorg watch not started, invalid RPC_URL: {}", err);
+            return;
+        }
+    };
+    let mut blocks = Blocks::new(reorgs.depth);
+    info!("Watching for reorgs up to {} blocks deep", reorgs.depth);
+    loop {
+        if let Err(err) = poll(cfg, client, &provider, &mut blocks).await {
+            warn!("Reorg watch: {:#}", err);
+        }
+        tokio::time::sleep(chain::profile().block_poll_interval()).await;
+    }
+}
+
+async fn poll(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    provider: &Provider<Http>,
+    blocks: &mut Blocks,
+) -> Result<()> {
+    let head = provider.get_block_number().await?.as_u64();
+    let next = match blocks.last() {
+        Some(last) => (last + 1).max(head.saturating_sub(blocks.depth)),
+        None => head,
+    };
+    for number in next..=head {
+        let header = header(provider, number).await?;
+        let mut reorg = blocks.follow(header, |n| self::header(provider, n)).await?;
+        if reorg.is_none() && cfg.chaos.as_ref().is_some_and(|chaos| chaos.reorg()) {
+            reorg = Some(Reorg {
+                fork: number,
+                depth: 1,
+                since: header.timestamp,
+            });
+        }
+        if let Some(reorg) = reorg {
+            reconcile(cfg, client, provider, &reorg).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Re-checks the buys of the wallet's open positions and the round trips closed since
+/// the fork, then runs the startup reconciliation so positions whose buy was dropped
+/// are closed under `RECOVERY`.
+async fn reconcile(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    provider: &Provider<Http>,
+    reorg: &Reorg,
+) -> Result<()> {
+    let wallet = cfg.recipient.unwrap_or_else(|| client.wallet());
+    let message = format!(
+        "Chain reorganized from block {} ({} blocks replaced)",
+        reorg.fork, reorg.depth
+    );
+    warn!("{}", message);
+    cfg.notifier.send(Event::Error, message);
+    cfg.audit("reorg", json!({ "fork": reorg.fork, "depth": reorg.depth }));
+
+    let mut findings = Vec::new();
+    for position in cfg.state.open_positions()? {
+        let Some(buy_tx) = position.buy_tx.filter(|_| position.wallet == wallet) else {
+            continue;
+        };
+        match provider.get_transaction_receipt(buy_tx).await? {
+            None => findings.push(format!(
+                "the buy {:?} of {:?} was dropped by the reorg",
+                buy_tx, position.token
+            )),
+            Some(receipt) if receipt.status != Some(1u64.into()) => findings.push(format!(
+                "the buy {:?} of {:?} reverted when re-included",
+                buy_tx, position.token
+            )),
+            Some(_) => {}
+        }
+    }
+    let open: Vec<Address> = cfg
+        .state
+        .open_positions()?
+        .iter()
+        .map(|p| p.token)
+        .collect();
+    for record in cfg.ledger.load()? {
+        let affected = record.wallet == wallet && record.closed_at >= reorg.since;
+        if !affected || open.contains(&record.token) {
+            continue;
+        }
+        let balance = client.token_balance(record.token, wallet).await?;
+        if !balance.is_zero() {
+            findings.push(format!(
+                "the sell of {:?} closed at {} looks reorged out: the wallet holds {} again, \
+                 and its ledger entry counts proceeds it didn't get",
+                record.token,
+                record.closed_at,
+                ethers::utils::format_units(balance, 18).unwrap_or_default()
+            ));
+        }
+    }
+    for finding in &findings {
+        warn!("Reorg: {}", finding);
+        cfg.notifier
+            .send(Event::Error, format!("Reorg: {}", finding));
+    }
+    if !findings.is_empty() {
+        cfg.audit(
+            "reorg_findings",
+            json!({ "fork": reorg.fork, "findings": findings }),
+        );
+    }
+    recovery::reconcile(cfg, client, wallet).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(number: u64, fork: u8) -> Header {
+        let hash = |number: u64, fork: u8| {
+            let mut bytes = [fork; 32];
+            bytes[..8].copy_from_slice(&number.to_be_bytes());
+            H256::from(bytes)
+        };
+        Header {
+            number,
+            hash: hash(number, fork),
+            parent: hash(number.wrapping_sub(1), if number <= 3 { 0 } else { fork }),
+            timestamp: number * 10,
+        }
+    }
+
+    async fn canonical(number: u64) -> Result<Header> {
+        Ok(header(number, 1))
+    }
+
+    #[tokio::test]
+    async fn a_linear_chain_is_not_a_reorg() {
+        let mut blocks = Blocks::new(10);
+        for number in 1..=5 {
+            assert_eq!(
+                blocks.follow(header(number, 0), canonical).await.unwrap(),
+                None
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn a_replaced_tip_is_walked_back_to_the_fork() {
+        let mut blocks = Blocks::new(10);
+        for number in 1..=5 {
+            blocks.follow(header(number, 0), canonical).await.unwrap();
+        }
+        // Blocks from 3 on were replaced by fork 1, which the new block 6 builds on.
+        let reorg = blocks.follow(header(6, 1), canonical).await.unwrap();
+        assert_eq!(
+            reorg,
+            Some(Reorg {
+                fork: 3,
+                depth: 3,
+                since: 20
+            })
+        );
+        assert_eq!(blocks.seen[&4], header(4, 1));
+        assert_eq!(blocks.follow(header(7, 1), canonical).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn only_depth_blocks_are_kept() {
+        let mut blocks = Blocks::new(3);
+        for number in 1..=10 {
+            blocks.follow(header(number, 0), canonical).await.unwrap();
+        }
+        assert_eq!(
+            blocks.seen.keys().copied().collect::<Vec<_>>(),
+            vec![8, 9, 10]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::io::{self, Write};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, BlockNumber, TransactionRequest, U256};
+use ethers::utils::format_units;
+
+use crate::chain;
+use crate::nadfun::{TokenHelper, Trade};
+use crate::signer;
+
+#[derive(Debug, Args)]
+pub struct RepairArgs {
+    /// Apply every proposed fix without prompting.
+    #[arg(long)]
+    pub yes: bool,
+
+    /// Gas price for replacement transactions, in percent of the current gas price.
+    #[arg(long, default_value_t = 150)]
+    pub bump_pct: u64,
+}
+
+/// Scans the configured wallet for stuck pending transactions, nonce gaps and
+/// dangling router approvals, and replaces or revokes them.
+pub async fn run(args: &RepairArgs) -> Result<()> {
+    let rpc_url = env::var("RPC_URL").context("RPC_URL missing")?;
+    let private_key = signer::private_key()?;
+
+    let provider = Provider::<Http>::try_from(rpc_url.as_str()).context("invalid RPC_URL")?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    let wallet = private_key
+        .parse::<LocalWallet>()
+        .context("invalid PRIVATE_KEY")?
+        .with_chain_id(chain_id);
+    let address = wallet.address();
+    let client = SignerMiddleware::new(provider, wallet);
+
+    let latest = client
+        .get_transaction_count(address, Some(BlockNumber::Latest.into()))
+        .await?;
+    let pending = client
+        .get_transaction_count(address, Some(BlockNumber::Pending.into()))
+        .await?;
+    println!(
+        "Wallet {:?}: confirmed nonce {}, pending nonce {}",
+        address, latest, pending
+    );
+
+    let mut nonces: BTreeSet<U256> = (latest.as_u64()..pending.as_u64()).map(U256::from).collect();
+    if !nonces.is_empty() {
+        println!("{} pending transactions are stuck", nonces.len());
+    }
+    let gaps = queued_gaps(&client, address, pending).await;
+    if !gaps.is_empty() {
+        println!("Nonce gaps before queued transactions: {:?}", gaps);
+    }
+    nonces.extend(gaps);
+
+    if nonces.is_empty() {
+        println!("No stuck transactions or nonce gaps");
+    } else {
+        let gas_price = client.get_gas_price().await? * U256::from(args.bump_pct) / U256::from(100u64);
+        for nonce in nonces {
+            let prompt = format!(
+                "Replace nonce {} with a 0 {} self-transfer at {} gwei?",
+                nonce,
+                chain::profile().native_symbol,
+                format_units(gas_price, "gwei")?
+            );
+            if !confirm(args.yes, &prompt)? {
+                continue;
+            }
+            let tx = TransactionRequest::new()
+                .to(address)
+                .value(U256::zero())
+                .nonce(nonce)
+                .gas(21_000u64)
+                .gas_price(gas_price);
+            let sent = client
+                .send_transaction(tx, None)
+                .await
+                .with_context(|| format!("failed to replace nonce {}", nonce))?;
+            println!("Replacement for nonce {} sent: {:?}", nonce, sent.tx_hash());
+        }
+    }
+
+    if let Ok(token) = env::var("TOKEN_ADDRESS") {
+        let token: Address = token.parse().context("invalid TOKEN_ADDRESS")?;
+        repair_approval(&rpc_url, &private_key, token, address, args.yes).await?;
+    }
+
+    Ok(())
+}
+
+/// Revokes the router allowance for `token` if the wallet no longer holds any of it.
+async fn repair_approval(
+    rpc_url: &str,
+    private_key: &str,
+    token: Address,
+    owner: Address,
+    auto: bool,
+) -> Result<()> {
+    let trade = Trade::new(rpc_url.to_string(), private_key.to_string()).await?;
+    let token_helper = TokenHelper::new(rpc_url.to_string(), private_key.to_string()).await?;
+
+    let balance = token_helper.balance_of(token, owner).await?;
+    let (router, _) = trade
+        .get_amount_out(token, U256::exp10(18), false)
+        .await
+        .context("failed to resolve router")?;
+    let allowance = token_helper.allowance(token, owner, router).await?;
+
+    if allowance.is_zero() || !balance.is_zero() {
+        println!("No dangling approval for {:?} on router {:?}", token, router);
+        return Ok(());
+    }
+
+    let prompt = format!(
+        "Wallet holds no {:?} but router {:?} may still spend {}; revoke?",
+        token, router, allowance
+    );
+    if confirm(auto, &prompt)? {
+        let receipt = token_helper
+            .approve(token, router, U256::zero())
+            .await
+            .context("failed to revoke approval")?;
+        println!("Revoke submitted: {:?}", receipt.tx_hash);
+    }
+    Ok(())
+}
+
+/// Nonces missing between the pending nonce and transactions queued behind them.
+/// Returns nothing when the endpoint doesn't expose `txpool_content`.
+async fn queued_gaps<M: Middleware>(client: &M, address: Address, pending: U256) -> Vec<U256> {
+    let content = match client.txpool_content().await {
+        Ok(content) => content,
+        Err(_) => {
+            println!("RPC does not expose txpool_content; skipping nonce gap scan");
+            return Vec::new();
+        }
+    };
+
+    let queued: BTreeSet<u64> = content
+        .queued
+        .get(&address)
+        .map(|txs| txs.keys().filter_map(|nonce| nonce.parse().ok()).collect())
+        .unwrap_or_default();
+    let Some(&highest) = queued.iter().next_back() else {
+        return Vec::new();
+    };
+
+    (pending.as_u64()..highest)
+        .filter(|nonce| !queued.contains(nonce))
+        .map(U256::from)
+        .collect()
+}
+
+fn confirm(auto: bool, prompt: &str) -> Result<bool> {
+    if auto {
+        println!("{} yes", prompt);
+        return Ok(true);
+    }
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
@@ -0,0 +1,223 @@
+use std::io::Write;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::Address;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::warn;
+
+use crate::chain;
+use crate::engine::ExecutionClient;
+use crate::app::AppConfig;
+use crate::logging;
+use crate::trade_watchlist;
+use crate::trading::{round_trip, EntryHints};
+
+const HELP: &str = "\
+quote <token> [mon]      quote a buy and the sell of what it returns
+buy <token> [mon]        buy and hold under the token's exit rules
+sell <token>             sell an open position at its next exit check
+positions                list open positions
+watch <token>            add a token to the watchlist
+unwatch <token>          remove a token from the watchlist
+set <size|slippage|tp|sl|trail|hold> <value|off>
+                         override buy size in MON, slippage bps, take profit,
+                         stop loss or trailing stop %, or max hold secs
+pause | resume           hold or allow new entries, manual buys included
+log [filter]             show or replace the log filter, in RUST_LOG syntax
+quit                     stop taking entries and exit once trades in flight finish";
+
+/// Trades the watchlist like the control API mode while reading operator commands from
+/// stdin. Manual buys go through the same round trip, state file and transaction lock
+/// as the automated ones, so the two never race over the wallet's nonce.
+pub async fn run(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    resume_only: bool,
+) -> Result<()> {
+    let watchlist = trade_watchlist(cfg, client, resume_only);
+    tokio::pin!(watchlist);
+    let mut manual = FuturesUnordered::new();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    println!("Type `help` for commands");
+
+    loop {
+        if !cfg.shutdown.requested() {
+            print!("> ");
+            std::io::stdout().flush().ok();
+        }
+        tokio::select! {
+            line = lines.next_line(), if !cfg.shutdown.requested() => {
+                let Some(line) = line.context("failed to read stdin")? else {
+                    cfg.shutdown.request();
+                    continue;
+                };
+                let words: Vec<&str> = line.split_whitespace().collect();
+                match words.as_slice() {
+                    [] => {}
+                    ["buy", token, amount @ ..] => match buy_hints(amount) {
+                        Ok(hints) => {
+                            let token: Address = match token.parse() {
+                                Ok(token) => token,
+                                Err(_) => {
+                                    println!("invalid token {:?}", token);
+                                    continue;
+                                }
+                            };
+                            manual.push(manual_trip(cfg, client, token, hints));
+                        }
+                        Err(err) => println!("{:#}", err),
+                    },
+                    words => {
+                        if let Err(err) = command(cfg, client, words).await {
+                            println!("{:#}", err);
+                        }
+                    }
+                }
+            }
+            Some(()) = manual.next(), if !manual.is_empty() => {}
+            result = &mut watchlist => {
+                while manual.next().await.is_some() {}
+                return result;
+            }
+        }
+    }
+}
+
+fn buy_hints(amount: &[&str]) -> Result<EntryHints> {
+    let amount_in = match amount {
+        [] => None,
+        [amount] => Some(
+            chain::profile()
+                .parse_native(amount)
+                .with_context(|| format!("invalid amount {:?}", amount))?,
+        ),
+        _ => return Err(anyhow!("usage: buy <token> [mon]")),
+    };
+    Ok(EntryHints {
+        amount_in,
+        strategy: Some("repl"),
+        ..EntryHints::default()
+    })
+}
+
+async fn manual_trip(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    token: Address,
+    hints: EntryHints,
+) {
+    if let Err(err) = round_trip(cfg, cfg.params_for(token), client, token, hints).await {
+        warn!("Manual round trip for {:?} failed: {:#}", token, err);
+    }
+}
+
+async fn command(cfg: &AppConfig, client: &impl ExecutionClient, words: &[&str]) -> Result<()> {
+    let profile = chain::profile();
+    let token = |word: &str| -> Result<Address> {
+        word.parse().map_err(|_| anyhow!("invalid token {:?}", word))
+    };
+    match words {
+        ["help"] => println!("{}", HELP),
+        ["quit"] | ["exit"] => cfg.shutdown.request(),
+        ["quote", address, amount @ ..] => {
+            let token = token(address)?;
+            let amount_in = match amount {
+                [] => cfg.params_for(token).amount_in,
+                [amount] => profile.parse_native(amount)?,
+                _ => return Err(anyhow!("usage: quote <token> [mon]")),
+            };
+            let (router, tokens_out) = client.quote(token, amount_in, true).await?;
+            let (_, exit) = client.quote(token, tokens_out, false).await?;
+            println!(
+                "{} buys {} tokens via {:?}; selling them returns {}",
+                profile.format_native(amount_in),
+                ethers::utils::format_units(tokens_out, 18)?,
+                router,
+                profile.format_native(exit)
+            );
+        }
+        ["sell", address] => {
+            let token = token(address)?;
+            let open = cfg.state.open_positions()?;
+            if !open.iter().any(|position| position.token == token) {
+                return Err(anyhow!("no open position in {:?}", token));
+            }
+            cfg.controls.force_sell(token);
+            println!("selling {:?} at its next exit check", token);
+        }
+        ["positions"] => {
+            let open = cfg.state.open_positions()?;
+            if open.is_empty() {
+                println!("no open positions");
+            }
+            for position in open {
+                println!(
+                    "{:?}  in {}  by {:?}  opened {}",
+                    position.token,
+                    profile.format_native(position.amount_in),
+                    position.wallet,
+                    position.opened_at
+                );
+            }
+        }
+        ["watch", address] => {
+            let token = token(address)?;
+            match cfg.controls.watch(token)? {
+                true => println!("watching {:?}", token),
+                false => println!("{:?} is already watched", token),
+            }
+        }
+        ["unwatch", address] => {
+            let token = token(address)?;
+            match cfg.controls.unwatch(token) {
+                true => println!("{:?} removed from the watchlist", token),
+                false => println!("{:?} is not watched", token),
+            }
+        }
+        ["set", key, value] => {
+            let mut overrides = cfg.controls.overrides();
+            let off = *value == "off";
+            let pct = || value.parse::<f64>().map_err(|_| anyhow!("invalid value {:?}", value));
+            match *key {
+                "slippage" if off => overrides.slippage_bps = None,
+                "slippage" => {
+                    overrides.slippage_bps =
+                        Some(value.parse().map_err(|_| anyhow!("invalid bps {:?}", value))?)
+                }
+                "tp" => overrides.take_profit_pct = if off { None } else { Some(pct()?) },
+                "sl" => overrides.stop_loss_pct = if off { None } else { Some(pct()?) },
+                "trail" => overrides.trailing_stop_pct = if off { None } else { Some(pct()?) },
+                "size" => overrides.amount_in_mon = (!off).then(|| value.to_string()),
+                "hold" if off => overrides.max_hold_secs = None,
+                "hold" => {
+                    overrides.max_hold_secs =
+                        Some(value.parse().map_err(|_| anyhow!("invalid secs {:?}", value))?)
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "unknown setting {:?}; try size, slippage, tp, sl, trail or hold",
+                        key
+                    ))
+                }
+            }
+            cfg.controls.set_overrides(overrides.clone(), "repl")?;
+            println!("{}", serde_json::to_string(&overrides)?);
+        }
+        ["pause"] => {
+            cfg.controls.set_paused(true);
+            println!("new entries are paused");
+        }
+        ["resume"] => {
+            cfg.controls.set_paused(false);
+            println!("new entries resumed");
+        }
+        ["log"] => println!("{}", logging::filter()?),
+        ["log", filter] => {
+            logging::set_filter(filter)?;
+            println!("log filter set to {}", filter);
+        }
+        _ => return Err(anyhow!("unknown command; type `help`")),
+    }
+    Ok(())
+}
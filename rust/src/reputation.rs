@@ -0,0 +1,416 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Context, Result};
+use ethers::contract::{parse_log, EthEvent};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Address, Bytes, Filter, TransactionRequest, H256, U256};
+use ethers::utils::id;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::caches;
+use crate::chain;
+use crate::curve::{CurveBuyFilter, CurveCreateFilter, CurveSellFilter};
+use crate::sniper::Launch;
+
+/// Launches whose early trading is replayed per indexing pass, to bound the RPC load.
+const EVALUATIONS_PER_PASS: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LaunchRecord {
+    token: Address,
+    block: u64,
+    virtual_mon: U256,
+    virtual_token: U256,
+    /// Set once the rug window has passed and the launch's trades were replayed.
+    #[serde(default)]
+    rugged: Option<bool>,
+    /// Price change over the rug window from the close of the launch block, set with
+    /// `rugged`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    return_pct: Option<f64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    indexed_to: u64,
+    creators: BTreeMap<Address, Vec<LaunchRecord>>,
+}
+
+/// A creator's earlier launches; `score` is the share of the evaluated ones that didn't
+/// rug, or `CREATOR_NEW_SCORE` before any are evaluated.
+pub struct CreatorRecord {
+    pub launches: usize,
+    pub evaluated: usize,
+    pub rugged: usize,
+    pub score: f64,
+}
+
+/// Creator reputation for the sniper: every `CurveCreate` is indexed by creator into
+/// `CREATOR_INDEX_FILE` in the background, and once a launch is `RUG_WINDOW_MINS` old
+/// its curve trades over that window are replayed to see whether the price fell more
+/// than `RUG_DROP_PCT` from its peak. A creator's score is the share of their evaluated
+/// launches that didn't rug; snipes below `CREATOR_MIN_SCORE` are skipped, and so are
+/// creators with more than `CREATOR_MAX_LAUNCHES` earlier launches. With
+/// `CREATOR_INDEX_RETENTION_BLOCKS` set, older launches are forgotten.
+pub struct ReputationConfig {
+    pub min_score: f64,
+    /// Score given to creators with no evaluated launches yet.
+    pub new_creator_score: f64,
+    pub max_launches: Option<usize>,
+    pub rug_drop_pct: f64,
+    rug_window_blocks: u64,
+    retention_blocks: Option<u64>,
+    path: PathBuf,
+    backfill_blocks: u64,
+    chunk_blocks: u64,
+    index: Arc<RwLock<Index>>,
+}
+
+impl ReputationConfig {
+    /// Enabled by `CREATOR_MIN_SCORE` or `CREATOR_MAX_LAUNCHES`, and by
+    /// `SNIPER_MIN_EDGE_PCT`, as the edge is estimated from the index.
+    pub fn from_env() -> Result<Option<Self>> {
+        fn parsed<T: std::str::FromStr>(name: &str) -> Result<Option<T>>
+        where
+            T::Err: std::error::Error + Send + Sync + 'static,
+        {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {name}")))
+                .transpose()
+        }
+        let min_score = parsed::<f64>("CREATOR_MIN_SCORE")?;
+        let max_launches = parsed::<usize>("CREATOR_MAX_LAUNCHES")?;
+        let edge = env::var("SNIPER_MIN_EDGE_PCT").is_ok();
+        if min_score.is_none() && max_launches.is_none() && !edge {
+            return Ok(None);
+        }
+        let window_mins = parsed::<f64>("RUG_WINDOW_MINS")?.unwrap_or(30.0);
+        let block_secs = chain::profile().block_time.as_secs_f64().max(0.001);
+
+        let path = PathBuf::from(
+            env::var("CREATOR_INDEX_FILE").unwrap_or_else(|_| "creator_index.json".into()),
+        );
+        let index = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("corrupt creator index {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Index::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Some(Self {
+            min_score: min_score.unwrap_or(0.0),
+            new_creator_score: parsed("CREATOR_NEW_SCORE")?.unwrap_or(0.5),
+            max_launches,
+            rug_drop_pct: parsed("RUG_DROP_PCT")?.unwrap_or(80.0),
+            rug_window_blocks: (window_mins * 60.0 / block_secs) as u64,
+            retention_blocks: parsed("CREATOR_INDEX_RETENTION_BLOCKS")?,
+            path,
+            backfill_blocks: parsed("CREATOR_INDEX_BACKFILL_BLOCKS")?.unwrap_or(500_000),
+            chunk_blocks: parsed("CREATOR_INDEX_CHUNK_BLOCKS")?.unwrap_or(2_000).max(1),
+            index: Arc::new(RwLock::new(index)),
+        }))
+    }
+
+    /// The creator's record before `launch`, or `None` if the index is unreadable.
+    pub fn record(&self, launch: &Launch) -> Option<CreatorRecord> {
+        let index = self.index.read().ok()?;
+        let earlier: Vec<&LaunchRecord> = index
+            .creators
+            .get(&launch.creator)
+            .map(|launches| launches.iter().filter(|l| l.token != launch.token).collect())
+            .unwrap_or_default();
+        let evaluated = earlier.iter().filter(|l| l.rugged.is_some()).count();
+        let rugged = earlier.iter().filter(|l| l.rugged == Some(true)).count();
+        let score = if evaluated == 0 {
+            self.new_creator_score
+        } else {
+            1.0 - rugged as f64 / evaluated as f64
+        };
+        Some(CreatorRecord {
+            launches: earlier.len(),
+            evaluated,
+            rugged,
+            score,
+        })
+    }
+
+    /// Returns over the rug window of the creator's evaluated earlier launches, and of the
+    /// latest `recent` evaluated launches by anyone.
+    pub fn returns(&self, launch: &Launch, recent: usize) -> Option<(Vec<f64>, Vec<f64>)> {
+        let index = self.index.read().ok()?;
+        let creator = index
+            .creators
+            .get(&launch.creator)
+            .map(|launches| {
+                launches
+                    .iter()
+                    .filter(|l| l.token != launch.token)
+                    .filter_map(|l| l.return_pct)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut evaluated: Vec<(u64, f64)> = index
+            .creators
+            .values()
+            .flatten()
+            .filter_map(|l| Some((l.block, l.return_pct?)))
+            .collect();
+        evaluated.sort_unstable_by_key(|(block, _)| std::cmp::Reverse(*block));
+        let latest = evaluated.into_iter().take(recent).map(|(_, pct)| pct).collect();
+        Some((creator, latest))
+    }
+
+    /// The reason to skip `launch`, if its creator's record is below the bar.
+    pub async fn screen(&self, provider: &Provider<Http>, launch: &Launch) -> Option<String> {
+        let CreatorRecord {
+            launches,
+            evaluated,
+            rugged,
+            score,
+        } = self.record(launch)?;
+        let supply = match total_supply(provider, launch.token).await {
+            Ok(supply) => ethers::utils::format_units(supply, 18).unwrap_or_default(),
+            Err(err) => format!("unknown ({:#})", err),
+        };
+        info!(
+            "{} ({}) supply {} by {:?}: {} earlier launches, {}/{} evaluated rugged, score {:.2}",
+            launch.name, launch.symbol, supply, launch.creator, launches, rugged, evaluated, score
+        );
+
+        if let Some(max) = self.max_launches.filter(|max| launches > *max) {
+            return Some(format!("creator has {} earlier launches, over {}", launches, max));
+        }
+        if score < self.min_score {
+            return Some(format!(
+                "creator score {:.2} below CREATOR_MIN_SCORE {:.2}",
+                score, self.min_score
+            ));
+        }
+        None
+    }
+
+    fn prune(&self, index: &mut Index) {
+        if let Some(retention) = self.retention_blocks {
+            let cutoff = index.indexed_to.saturating_sub(retention);
+            for launches in index.creators.values_mut() {
+                launches.retain(|launch| launch.block >= cutoff);
+            }
+            index.creators.retain(|_, launches| !launches.is_empty());
+        }
+        caches::observe("creator_index", index.creators.values().map(Vec::len).sum());
+    }
+
+    fn save(&self) -> Result<()> {
+        let json = {
+            let index = self.index.read().map_err(|_| anyhow!("creator index lock poisoned"))?;
+            serde_json::to_string(&*index)?
+        };
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json).with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to replace {}", self.path.display()))
+    }
+}
+
+async fn total_supply(provider: &Provider<Http>, token: Address) -> Result<U256> {
+    let call = TransactionRequest::new()
+        .to(token)
+        .data(Bytes::from(id("totalSupply()").to_vec()));
+    let output = provider.call(&call.into(), None).await?;
+    if output.len() < 32 {
+        return Err(anyhow!("totalSupply returned {} bytes", output.len()));
+    }
+    Ok(U256::from_big_endian(&output[..32]))
+}
+
+/// Indexes new launches and evaluates the ones past their rug window, every block.
+pub fn start(rpc_url: &str, curve: Address, reputation: &ReputationConfig) -> Result<()> {
+    let provider = Provider::<Http>::try_from(rpc_url).context("invalid RPC_URL")?;
+    let reputation = ReputationConfig {
+        path: reputation.path.clone(),
+        index: reputation.index.clone(),
+        ..*reputation
+    };
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(chain::profile().block_time);
+        loop {
+            ticker.tick().await;
+            let indexed = index_launches(&provider, curve, &reputation).await;
+            if let Err(err) = &indexed {
+                warn!("Creator index update failed: {:#}", err);
+            }
+            let evaluated = evaluate_launches(&provider, curve, &reputation).await;
+            if let Err(err) = &evaluated {
+                warn!("Rug evaluation failed: {:#}", err);
+            }
+            if matches!(indexed, Ok(true)) || matches!(evaluated, Ok(true)) {
+                if let Err(err) = reputation.save() {
+                    warn!("Failed to save the creator index: {:#}", err);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Adds the launches since the last pass; returns whether anything changed.
+async fn index_launches(
+    provider: &Provider<Http>,
+    curve: Address,
+    reputation: &ReputationConfig,
+) -> Result<bool> {
+    let head = provider.get_block_number().await?.as_u64();
+    let mut from = {
+        let index = reputation.index.read().map_err(|_| anyhow!("creator index lock poisoned"))?;
+        if index.indexed_to == 0 {
+            let from = head.saturating_sub(reputation.backfill_blocks);
+            info!("Indexing launch creators from block {}", from);
+            from
+        } else {
+            index.indexed_to + 1
+        }
+    };
+    if from > head {
+        return Ok(false);
+    }
+
+    while from <= head {
+        let to = (from + reputation.chunk_blocks - 1).min(head);
+        let filter = Filter::new()
+            .address(curve)
+            .from_block(from)
+            .to_block(to)
+            .event(&CurveCreateFilter::abi_signature());
+        let logs = provider.get_logs(&filter).await?;
+
+        let mut index =
+            reputation.index.write().map_err(|_| anyhow!("creator index lock poisoned"))?;
+        for log in logs {
+            let Some(block) = log.block_number.map(|block| block.as_u64()) else {
+                continue;
+            };
+            let event: CurveCreateFilter = match parse_log(log) {
+                Ok(event) => event,
+                Err(err) => {
+                    warn!("Undecodable CurveCreate log: {}", err);
+                    continue;
+                }
+            };
+            index.creators.entry(event.creator).or_default().push(LaunchRecord {
+                token: event.token,
+                block,
+                virtual_mon: event.virtual_mon,
+                virtual_token: event.virtual_token,
+                rugged: None,
+                return_pct: None,
+            });
+        }
+        index.indexed_to = to;
+        from = to + 1;
+    }
+    if let Ok(mut index) = reputation.index.write() {
+        reputation.prune(&mut index);
+    }
+    Ok(true)
+}
+
+/// Replays the early trades of launches whose rug window has passed.
+async fn evaluate_launches(
+    provider: &Provider<Http>,
+    curve: Address,
+    reputation: &ReputationConfig,
+) -> Result<bool> {
+    let due: Vec<LaunchRecord> = {
+        let index = reputation.index.read().map_err(|_| anyhow!("creator index lock poisoned"))?;
+        let head = index.indexed_to;
+        index
+            .creators
+            .values()
+            .flatten()
+            .filter(|l| l.rugged.is_none() && l.block + reputation.rug_window_blocks <= head)
+            .take(EVALUATIONS_PER_PASS)
+            .cloned()
+            .collect()
+    };
+    if due.is_empty() {
+        return Ok(false);
+    }
+
+    let mut outcomes = Vec::new();
+    for launch in &due {
+        let (drawdown, return_pct) = replay(provider, curve, launch, reputation).await?;
+        outcomes.push((launch.token, drawdown >= reputation.rug_drop_pct, return_pct));
+    }
+    let mut index = reputation.index.write().map_err(|_| anyhow!("creator index lock poisoned"))?;
+    for (token, rugged, return_pct) in outcomes {
+        for launch in index.creators.values_mut().flatten() {
+            if launch.token == token {
+                launch.rugged = Some(rugged);
+                launch.return_pct = return_pct;
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// The largest fall of the curve price from its running peak over the rug window, and
+/// its change from the close of the launch block to the end of the window, replayed from
+/// the launch's virtual reserves and every buy and sell in the window.
+async fn replay(
+    provider: &Provider<Http>,
+    curve: Address,
+    launch: &LaunchRecord,
+    reputation: &ReputationConfig,
+) -> Result<(f64, Option<f64>)> {
+    let to_f64 = |value: U256| value.to_string().parse::<f64>().unwrap_or(0.0);
+    let mut mon = to_f64(launch.virtual_mon);
+    let mut tokens = to_f64(launch.virtual_token);
+    let mut peak = 0.0f64;
+    let mut drawdown = 0.0f64;
+    let mut entry = None;
+    let mut price = if tokens > 0.0 { mon / tokens } else { 0.0 };
+
+    let buy = CurveBuyFilter::signature();
+    let sell = CurveSellFilter::signature();
+    let mut from = launch.block;
+    let end = launch.block + reputation.rug_window_blocks;
+    while from <= end {
+        let to = (from + reputation.chunk_blocks - 1).min(end);
+        let filter = Filter::new()
+            .address(curve)
+            .from_block(from)
+            .to_block(to)
+            .topic0(vec![buy, sell])
+            .topic2(H256::from(launch.token));
+        for log in provider.get_logs(&filter).await? {
+            let block = log.block_number.map_or(launch.block, |block| block.as_u64());
+            if block > launch.block && entry.is_none() && price > 0.0 {
+                entry = Some(price);
+            }
+            let topic = log.topics.first().copied();
+            if topic == Some(buy) {
+                let event: CurveBuyFilter = parse_log(log)?;
+                mon += to_f64(event.amount_in);
+                tokens -= to_f64(event.amount_out);
+            } else if topic == Some(sell) {
+                let event: CurveSellFilter = parse_log(log)?;
+                tokens += to_f64(event.amount_in);
+                mon -= to_f64(event.amount_out);
+            }
+            if mon <= 0.0 || tokens <= 0.0 {
+                continue;
+            }
+            price = mon / tokens;
+            peak = peak.max(price);
+            drawdown = drawdown.max((peak - price) / peak * 100.0);
+        }
+        from = to + 1;
+    }
+    let entry = entry.or((price > 0.0).then_some(price));
+    Ok((drawdown, entry.map(|entry| (price / entry - 1.0) * 100.0)))
+}
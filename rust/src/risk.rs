@@ -0,0 +1,448 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Subcommand};
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::chain;
+use crate::file_lock;
+use crate::ledger::TradeRecord;
+use crate::notify::Event;
+use crate::app::AppConfig;
+
+#[derive(Debug, Args)]
+pub struct RiskArgs {
+    #[command(subcommand)]
+    pub action: RiskAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RiskAction {
+    /// Show today's spend, the losing streak and whether the breaker has tripped.
+    Status,
+    /// Clear a tripped breaker and the losing streak so buys can resume.
+    Reset,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RiskState {
+    date: String,
+    #[serde(default)]
+    spent: U256,
+    #[serde(default)]
+    spent_by_wallet: BTreeMap<Address, U256>,
+    #[serde(default)]
+    losing_streak: u32,
+    /// Why the breaker tripped; buys stay refused until `risk reset` clears it.
+    #[serde(default)]
+    tripped: Option<String>,
+}
+
+/// Hard caps on what the bot may put at risk, shared by every process pointed at the
+/// same file, which they update under a lock on its `.lock` sibling. A buy that would
+/// break a cap is refused and trips the breaker: new entries pause, a notification goes
+/// out, and buys stay refused, across restarts too, until the operator runs
+/// `risk reset`. Exits are always allowed.
+pub struct RiskLimits {
+    path: PathBuf,
+    /// Largest single buy.
+    pub max_trade: Option<U256>,
+    /// Largest combined entry into one token across open positions.
+    pub max_token_exposure: Option<U256>,
+    /// Native spent on buys per UTC day, across every wallet.
+    pub max_daily_spend: Option<U256>,
+    /// Native spent on buys per UTC day by any one wallet.
+    pub max_wallet_daily_spend: Option<U256>,
+    /// Closed round trips in a row at a loss, net of gas.
+    pub max_losing_streak: Option<u32>,
+}
+
+impl RiskLimits {
+    /// Enabled by any of `MAX_TRADE_MON`, `MAX_TOKEN_EXPOSURE_MON`, `MAX_DAILY_SPEND_MON`,
+    /// `MAX_WALLET_DAILY_SPEND_MON` and `MAX_LOSING_STREAK`; state goes to
+    /// `RISK_STATE_FILE`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let native = |name: &str| -> Result<Option<U256>> {
+            env::var(name)
+                .ok()
+                .filter(|v| !v.is_empty())
+                .map(|v| {
+                    chain::profile()
+                        .parse_native(&v)
+                        .with_context(|| format!("invalid {}", name))
+                })
+                .transpose()
+        };
+        let max_trade = native("MAX_TRADE_MON")?;
+        let max_token_exposure = native("MAX_TOKEN_EXPOSURE_MON")?;
+        let max_daily_spend = native("MAX_DAILY_SPEND_MON")?;
+        let max_wallet_daily_spend = native("MAX_WALLET_DAILY_SPEND_MON")?;
+        let max_losing_streak: Option<u32> = env::var("MAX_LOSING_STREAK")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|v| v.parse().context("invalid MAX_LOSING_STREAK"))
+            .transpose()?;
+        if max_trade.is_none()
+            && max_token_exposure.is_none()
+            && max_daily_spend.is_none()
+            && max_wallet_daily_spend.is_none()
+            && max_losing_streak.is_none()
+        {
+            return Ok(None);
+        }
+        let path = env::var("RISK_STATE_FILE").unwrap_or_else(|_| "risk_state.json".into());
+        Ok(Some(Self {
+            path: PathBuf::from(path),
+            max_trade,
+            max_token_exposure,
+            max_daily_spend,
+            max_wallet_daily_spend,
+            max_losing_streak: max_losing_streak.map(|streak| streak.max(1)),
+        }))
+    }
+
+    /// Refuses a buy of `amount_in` into `token` from `wallet` that the breaker or any cap
+    /// forbids, tripping the breaker on a breach. An allowed buy is counted towards today's
+    /// spend straight away, so concurrent entries can't overshoot a cap together; call
+    /// [`release`](Self::release) if it is not sent after all.
+    pub async fn check_entry(
+        &self,
+        cfg: &AppConfig,
+        wallet: Address,
+        token: Address,
+        amount_in: U256,
+    ) -> Result<()> {
+        let breach = {
+            let _lock = file_lock::exclusive_async(&self.path).await?;
+            let exposure = exposure(cfg, token)?;
+            self.reserve(exposure, wallet, token, amount_in)?
+        };
+        match breach {
+            Some(reason) => {
+                self.trip(cfg, &reason)?;
+                Err(anyhow!("risk limit hit, refusing entry: {}", reason))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Why [`check_entry`](Self::check_entry) would refuse the buy, without counting it
+    /// or tripping the breaker.
+    pub fn would_refuse(
+        &self,
+        cfg: &AppConfig,
+        wallet: Address,
+        token: Address,
+        amount_in: U256,
+    ) -> Result<Option<String>> {
+        let _lock = self.locked()?;
+        let exposure = exposure(cfg, token)?;
+        let state = self.load()?;
+        if let Some(reason) = &state.tripped {
+            return Ok(Some(format!("risk breaker tripped ({})", reason)));
+        }
+        Ok(self.breach(&state, exposure, wallet, token, amount_in))
+    }
+
+    /// Adds the buy to today's spend unless the breaker has tripped or it breaks a cap, in
+    /// which case the cap is returned and nothing is recorded. The caller holds the lock.
+    fn reserve(
+        &self,
+        exposure: U256,
+        wallet: Address,
+        token: Address,
+        amount_in: U256,
+    ) -> Result<Option<String>> {
+        let mut state = self.load()?;
+        if let Some(reason) = &state.tripped {
+            return Err(anyhow!(
+                "risk breaker tripped ({}); run `risk reset` to resume buys",
+                reason
+            ));
+        }
+        if let Some(reason) = self.breach(&state, exposure, wallet, token, amount_in) {
+            return Ok(Some(reason));
+        }
+        state.spent += amount_in;
+        *state.spent_by_wallet.entry(wallet).or_default() += amount_in;
+        self.save(&state)?;
+        Ok(None)
+    }
+
+    /// Takes a buy allowed by [`check_entry`](Self::check_entry) that failed to send back
+    /// out of today's spend.
+    pub fn release(&self, wallet: Address, amount_in: U256) -> Result<()> {
+        let _lock = self.locked()?;
+        let mut state = self.load()?;
+        state.spent = state.spent.saturating_sub(amount_in);
+        if let Some(spent) = state.spent_by_wallet.get_mut(&wallet) {
+            *spent = spent.saturating_sub(amount_in);
+        }
+        self.save(&state)
+    }
+
+    /// The cap a buy of `amount_in` into `token` from `wallet` would break, given
+    /// `exposure` already open in the token.
+    fn breach(
+        &self,
+        state: &RiskState,
+        exposure: U256,
+        wallet: Address,
+        token: Address,
+        amount_in: U256,
+    ) -> Option<String> {
+        let profile = chain::profile();
+        if let Some(cap) = self.max_trade.filter(|cap| amount_in > *cap) {
+            return Some(format!(
+                "buy of {} exceeds MAX_TRADE_MON {}",
+                profile.format_native(amount_in),
+                profile.format_native(cap)
+            ));
+        }
+        if let Some(cap) = self.max_token_exposure.filter(|cap| exposure + amount_in > *cap) {
+            return Some(format!(
+                "{} more in {:?} on top of {} open exceeds MAX_TOKEN_EXPOSURE_MON {}",
+                profile.format_native(amount_in),
+                token,
+                profile.format_native(exposure),
+                profile.format_native(cap)
+            ));
+        }
+        if let Some(cap) = self.max_daily_spend.filter(|cap| state.spent + amount_in > *cap) {
+            return Some(format!(
+                "{} more on top of {} spent today exceeds MAX_DAILY_SPEND_MON {}",
+                profile.format_native(amount_in),
+                profile.format_native(state.spent),
+                profile.format_native(cap)
+            ));
+        }
+        let wallet_spent = state.spent_by_wallet.get(&wallet).copied().unwrap_or_default();
+        self.max_wallet_daily_spend
+            .filter(|cap| wallet_spent + amount_in > *cap)
+            .map(|cap| {
+                format!(
+                    "{} more from {:?} on top of {} spent today exceeds \
+                     MAX_WALLET_DAILY_SPEND_MON {}",
+                    profile.format_native(amount_in),
+                    wallet,
+                    profile.format_native(wallet_spent),
+                    profile.format_native(cap)
+                )
+            })
+    }
+
+    /// Counts a closed round trip towards the losing streak, tripping the breaker once the
+    /// streak reaches `MAX_LOSING_STREAK`. A profitable close ends the streak.
+    pub fn record_close(&self, cfg: &AppConfig, record: &TradeRecord) -> Result<()> {
+        let streak = {
+            let _lock = self.locked()?;
+            let mut state = self.load()?;
+            state.losing_streak = if record.pnl().is_negative() {
+                state.losing_streak + 1
+            } else {
+                0
+            };
+            self.save(&state)?;
+            state.losing_streak
+        };
+        match self.max_losing_streak {
+            Some(max) if streak >= max => {
+                self.trip(cfg, &format!("{} losing trades in a row", streak))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn trip(&self, cfg: &AppConfig, reason: &str) -> Result<()> {
+        {
+            let _lock = self.locked()?;
+            let mut state = self.load()?;
+            if state.tripped.is_none() {
+                state.tripped = Some(reason.to_string());
+                self.save(&state)?;
+            }
+        }
+        cfg.controls.set_paused(true);
+        warn!("Risk breaker tripped, new entries paused: {}", reason);
+        cfg.notifier.send(
+            Event::Error,
+            format!("Risk breaker tripped, new entries paused: {}", reason),
+        );
+        cfg.audit("risk_tripped", serde_json::json!({ "reason": reason }));
+        Ok(())
+    }
+
+    /// Holds the file lock until dropped; it serializes threads of this process too.
+    fn locked(&self) -> Result<fs::File> {
+        file_lock::exclusive(&self.path)
+    }
+
+    fn load(&self) -> Result<RiskState> {
+        let today = chrono::Utc::now().date_naive().to_string();
+        let state = match fs::read_to_string(&self.path) {
+            Ok(json) => serde_json::from_str::<RiskState>(&json)
+                .with_context(|| format!("corrupt risk state file {}", self.path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => RiskState::default(),
+            Err(err) => return Err(err.into()),
+        };
+        if state.date == today {
+            return Ok(state);
+        }
+        // Spend resets daily; the streak and a tripped breaker carry over.
+        Ok(RiskState {
+            date: today,
+            losing_streak: state.losing_streak,
+            tripped: state.tripped,
+            ..RiskState::default()
+        })
+    }
+
+    fn save(&self, state: &RiskState) -> Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(state)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to replace {}", self.path.display()))
+    }
+}
+
+/// Native already in open positions in `token`.
+fn exposure(cfg: &AppConfig, token: Address) -> Result<U256> {
+    Ok(cfg
+        .state
+        .open_positions()?
+        .iter()
+        .filter(|position| position.token == token)
+        .fold(U256::zero(), |sum, position| sum + position.amount_in))
+}
+
+/// Reads the limits from the env itself, so it runs without a wallet or RPC.
+pub fn run(args: &RiskArgs) -> Result<()> {
+    let limits = RiskLimits::from_env()?.ok_or_else(|| anyhow!("no risk limits are configured"))?;
+    let _lock = limits.locked()?;
+    let mut state = limits.load()?;
+    match args.action {
+        RiskAction::Status => {
+            let profile = chain::profile();
+            let cap = |cap: Option<U256>| {
+                cap.map_or_else(|| "no cap".to_string(), |cap| profile.format_native(cap))
+            };
+            println!(
+                "spent today: {} of {}",
+                profile.format_native(state.spent),
+                cap(limits.max_daily_spend)
+            );
+            for (wallet, spent) in &state.spent_by_wallet {
+                println!(
+                    "  {:?}: {} of {}",
+                    wallet,
+                    profile.format_native(*spent),
+                    cap(limits.max_wallet_daily_spend)
+                );
+            }
+            println!(
+                "losing streak: {} of {}",
+                state.losing_streak,
+                limits
+                    .max_losing_streak
+                    .map_or_else(|| "no cap".to_string(), |max| max.to_string())
+            );
+            match &state.tripped {
+                Some(reason) => println!("breaker: tripped ({})", reason),
+                None => println!("breaker: armed"),
+            }
+        }
+        RiskAction::Reset => {
+            state.tripped = None;
+            state.losing_streak = 0;
+            limits.save(&state)?;
+            info!("Risk breaker reset; buys resume on the next start or `resume`");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(name: &str) -> RiskLimits {
+        let dir = env::temp_dir().join(format!("nadfun-risk-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("risk_state.json");
+        fs::remove_file(&path).ok();
+        RiskLimits {
+            path,
+            max_trade: None,
+            max_token_exposure: None,
+            max_daily_spend: None,
+            max_wallet_daily_spend: None,
+            max_losing_streak: None,
+        }
+    }
+
+    fn mon(amount: u64) -> U256 {
+        U256::exp10(18) * amount
+    }
+
+    #[test]
+    fn caps_refuse_the_buy_that_breaks_them() {
+        let limits = RiskLimits {
+            max_trade: Some(mon(5)),
+            max_token_exposure: Some(mon(8)),
+            ..limits("caps")
+        };
+        let (wallet, token) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        assert!(limits.reserve(U256::zero(), wallet, token, mon(6)).unwrap().is_some());
+        assert!(limits.reserve(mon(4), wallet, token, mon(5)).unwrap().is_some());
+        assert!(limits.reserve(mon(3), wallet, token, mon(5)).unwrap().is_none());
+        assert_eq!(limits.load().unwrap().spent, mon(5));
+    }
+
+    #[test]
+    fn reservations_count_towards_the_daily_caps_until_released() {
+        let limits = RiskLimits {
+            max_daily_spend: Some(mon(10)),
+            max_wallet_daily_spend: Some(mon(6)),
+            ..limits("daily")
+        };
+        let (alice, bob) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let token = Address::repeat_byte(3);
+        assert!(limits.reserve(U256::zero(), alice, token, mon(4)).unwrap().is_none());
+        assert!(limits.reserve(U256::zero(), alice, token, mon(4)).unwrap().is_some());
+        assert!(limits.reserve(U256::zero(), bob, token, mon(6)).unwrap().is_none());
+        assert!(limits.reserve(U256::zero(), bob, token, mon(1)).unwrap().is_some());
+
+        limits.release(bob, mon(6)).unwrap();
+        let state = limits.load().unwrap();
+        assert_eq!(state.spent, mon(4));
+        assert_eq!(state.spent_by_wallet[&bob], U256::zero());
+        assert!(limits.reserve(U256::zero(), bob, token, mon(6)).unwrap().is_none());
+    }
+
+    #[test]
+    fn spend_rolls_over_but_the_streak_and_breaker_carry() {
+        let limits = limits("rollover");
+        let yesterday = RiskState {
+            date: "2000-01-01".into(),
+            spent: mon(50),
+            spent_by_wallet: BTreeMap::from([(Address::repeat_byte(1), mon(50))]),
+            losing_streak: 2,
+            tripped: Some("3 losing trades in a row".into()),
+        };
+        fs::write(&limits.path, serde_json::to_string(&yesterday).unwrap()).unwrap();
+
+        let state = limits.load().unwrap();
+        assert!(state.spent.is_zero());
+        assert!(state.spent_by_wallet.is_empty());
+        assert_eq!(state.losing_streak, 2);
+        assert!(state.tripped.is_some());
+        let (wallet, token) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        assert!(limits.reserve(U256::zero(), wallet, token, mon(1)).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use ethers::types::{Address, H256, U256};
+use tracing::info;
+
+use crate::approvals::{self, ApprovalConfig};
+use crate::chain;
+use crate::engine::ExecutionClient;
+
+pub struct SellRoute {
+    pub router: Address,
+    pub quoted_out: U256,
+    pub approve_tx: Option<H256>,
+}
+
+/// Re-derives the router for a sell and makes sure it may spend `amount`.
+///
+/// A token that graduates while held moves from the bonding-curve router to the
+/// DEX router, so the router used for the buy can't be reused blindly.
+pub async fn resolve_sell_router(
+    approval: &ApprovalConfig,
+    client: &impl ExecutionClient,
+    token: Address,
+    owner: Address,
+    amount: U256,
+    entry_router: Address,
+) -> Result<SellRoute> {
+    let (router, quoted_out) = client
+        .quote(token, amount, false)
+        .await
+        .context("failed to re-quote sell")?;
+
+    if router != entry_router {
+        info!(
+            "Token {} migrated since entry, selling via router {} instead of {}",
+            token, router, entry_router
+        );
+    }
+    info!("Sell quote: {}", chain::profile().format_native(quoted_out));
+
+    let approve_tx = approvals::ensure(approval, client, token, owner, router, amount).await?;
+
+    Ok(SellRoute {
+        router,
+        quoted_out,
+        approve_tx,
+    })
+}
+
+/// Whether a sell failed because the bonding curve no longer trades the token.
+pub fn is_listed_error(err: &anyhow::Error) -> bool {
+    format!("{:#}", err).contains("IS_LISTED")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn listed_errors_are_found_anywhere_in_the_chain() {
+        let revert = anyhow!("execution reverted: IS_LISTED");
+        assert!(is_listed_error(&revert));
+        assert!(is_listed_error(&revert.context("sell transaction failed")));
+        assert!(!is_listed_error(&anyhow!("execution reverted: INSUFFICIENT_OUTPUT")));
+    }
+}
@@ -0,0 +1,109 @@
+use std::env;
+
+use anyhow::{anyhow, Result};
+use ethers::providers::{Http, Middleware, Provider};
+use futures_util::future::join_all;
+use tokio::time::{timeout, Duration, Instant};
+use tracing::{info, warn};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub struct EndpointHealth {
+    pub url: String,
+    pub latency: Duration,
+    pub block: Option<u64>,
+}
+
+/// RPC endpoints from `RPC_URL` plus any extras in `RPC_URLS`, ranked by health.
+pub struct RpcPool {
+    endpoints: Vec<String>,
+    max_lag_blocks: u64,
+    pub max_failovers: u32,
+}
+
+impl RpcPool {
+    pub fn from_env(primary: &str) -> Self {
+        let mut endpoints = vec![primary.to_string()];
+        if let Ok(list) = env::var("RPC_URLS") {
+            for url in list.split(',').map(str::trim).filter(|url| !url.is_empty()) {
+                if !endpoints.iter().any(|known| known == url) {
+                    endpoints.push(url.to_string());
+                }
+            }
+        }
+        let number = |name: &str, default: u64| -> u64 {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            endpoints,
+            max_lag_blocks: number("MAX_RPC_BLOCK_LAG", 5),
+            max_failovers: number("MAX_RPC_FAILOVERS", 3) as u32,
+        }
+    }
+
+    pub fn endpoints(&self) -> &[String] {
+        &self.endpoints
+    }
+
+    pub fn has_fallbacks(&self) -> bool {
+        self.endpoints.len() > 1
+    }
+
+    /// Block height and latency of every endpoint; `block` is `None` when it didn't answer.
+    pub async fn probe(&self) -> Vec<EndpointHealth> {
+        join_all(self.endpoints.iter().map(|url| async move {
+            let started = Instant::now();
+            let block = match Provider::<Http>::try_from(url.as_str()) {
+                Ok(provider) => timeout(PROBE_TIMEOUT, provider.get_block_number())
+                    .await
+                    .ok()
+                    .and_then(|result| result.ok())
+                    .map(|block| block.as_u64()),
+                Err(_) => None,
+            };
+            EndpointHealth {
+                url: url.clone(),
+                latency: started.elapsed(),
+                block,
+            }
+        }))
+        .await
+    }
+
+    /// The fastest endpoint within `MAX_RPC_BLOCK_LAG` blocks of the highest one seen,
+    /// skipping `exclude`.
+    pub async fn best(&self, exclude: Option<&str>) -> Result<String> {
+        let health = self.probe().await;
+        let tip = health.iter().filter_map(|h| h.block).max().unwrap_or(0);
+
+        let mut healthy: Vec<&EndpointHealth> = Vec::new();
+        for endpoint in &health {
+            match endpoint.block {
+                Some(block) if tip - block <= self.max_lag_blocks => healthy.push(endpoint),
+                Some(block) => warn!(
+                    "RPC {} is {} blocks behind, skipping",
+                    endpoint.url,
+                    tip - block
+                ),
+                None => info!("RPC {} is unreachable", endpoint.url),
+            }
+        }
+        healthy
+            .into_iter()
+            .filter(|endpoint| Some(endpoint.url.as_str()) != exclude)
+            .min_by_key(|endpoint| endpoint.latency)
+            .map(|endpoint| {
+                info!(
+                    "Using RPC {} ({:?}, block {})",
+                    endpoint.url,
+                    endpoint.latency,
+                    endpoint.block.unwrap_or_default()
+                );
+                endpoint.url.clone()
+            })
+            .ok_or_else(|| anyhow!("no healthy RPC endpoint"))
+    }
+}
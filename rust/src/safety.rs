@@ -0,0 +1,110 @@
+use std::env;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, H256, U256};
+use ethers::utils::keccak256;
+use tracing::info;
+
+use crate::chain;
+use crate::curve::CurveTracker;
+use crate::engine::ExecutionClient;
+use crate::exit_guard;
+use crate::protocol;
+use crate::warmer;
+
+pub struct SafetyConfig {
+    pub max_round_trip_cost_bps: u64,
+    pub curve_code_hash: Option<H256>,
+    pub token_code_hash: Option<H256>,
+}
+
+impl SafetyConfig {
+    pub fn from_env() -> Result<Self> {
+        let hash = |name: &str| -> Result<Option<H256>> {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {name}")))
+                .transpose()
+        };
+        Ok(Self {
+            max_round_trip_cost_bps: env::var("MAX_ROUND_TRIP_COST_BPS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(500),
+            curve_code_hash: hash("NADFUN_CURVE_CODE_HASH")?,
+            token_code_hash: hash("NADFUN_TOKEN_CODE_HASH")?,
+        })
+    }
+}
+
+/// Refuses tokens that look like honeypots: contracts that don't match known nad.fun
+/// bytecode, tokens the curve doesn't know, exits that fail to quote, and round trips
+/// that cost more than `max_round_trip_cost_bps` (sell taxes show up here).
+///
+/// Runs after the entry simulation, which already proves buying is enabled.
+pub async fn check(
+    safety: &SafetyConfig,
+    client: &impl ExecutionClient,
+    curve: Option<&CurveTracker>,
+    token: Address,
+    amount_in: U256,
+    quoted_out: U256,
+) -> Result<()> {
+    verify_code(client, token, "token", safety.token_code_hash).await?;
+
+    if let Some(curve) = curve {
+        verify_code(client, curve.address(), "bonding curve", safety.curve_code_hash).await?;
+        let state = match warmer::curve_state(token) {
+            Some(state) => state,
+            None => curve.state(token).await?,
+        };
+        if state.virtual_token_reserve.is_zero() && !state.graduated {
+            return Err(anyhow!("token {:?} has no curve on the bonding curve contract", token));
+        }
+    }
+
+    let simulated_exit = exit_guard::simulate_exit(client, token, quoted_out)
+        .await
+        .context("simulated full exit fails")?;
+    let cost_bps = amount_in.saturating_sub(simulated_exit) * U256::from(10_000u64) / amount_in;
+    let fees = protocol::current()
+        .map(|params| format!(", {} bps of it protocol fees", 2 * params.fee_bps()))
+        .unwrap_or_default();
+    info!(
+        "Simulated full exit: {} ({} bps round-trip cost{})",
+        chain::profile().format_native(simulated_exit),
+        cost_bps,
+        fees
+    );
+    if cost_bps > U256::from(safety.max_round_trip_cost_bps) {
+        return Err(anyhow!(
+            "round-trip cost {} bps exceeds MAX_ROUND_TRIP_COST_BPS {}; likely a sell tax",
+            cost_bps,
+            safety.max_round_trip_cost_bps
+        ));
+    }
+    Ok(())
+}
+
+async fn verify_code(
+    client: &impl ExecutionClient,
+    address: Address,
+    what: &str,
+    expected: Option<H256>,
+) -> Result<()> {
+    let code = client.code(address).await?;
+    if code.as_ref().is_empty() {
+        return Err(anyhow!("{} {:?} has no contract code", what, address));
+    }
+    let hash = H256::from(keccak256(code.as_ref()));
+    match expected {
+        Some(expected) if expected != hash => Err(anyhow!(
+            "{} {:?} code hash {:?} does not match known nad.fun bytecode {:?}",
+            what,
+            address,
+            hash,
+            expected
+        )),
+        _ => Ok(()),
+    }
+}
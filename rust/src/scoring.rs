@@ -0,0 +1,292 @@
+use std::collections::HashSet;
+use std::env;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::contract::{parse_log, EthEvent};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{Filter, H256};
+use tracing::info;
+
+use crate::chain;
+use crate::curve::{CurveBuyFilter, CurveTracker};
+use crate::reputation::ReputationConfig;
+use crate::sniper::Launch;
+
+/// Feature names in the order [`Features::values`] returns them, which is also the column
+/// order of the `features` dataset and the input order an ONNX model is fed.
+pub const FEATURE_NAMES: [&str; 8] = [
+    "liquidity_mon",
+    "creator_launches",
+    "creator_score",
+    "name_len",
+    "symbol_len",
+    "buyers",
+    "buys",
+    "blocks_since_launch",
+];
+
+/// What is known about a launch when it is screened.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Features {
+    /// Real MON in the curve.
+    pub liquidity_mon: f64,
+    /// The creator's earlier launches.
+    pub creator_launches: f64,
+    /// Share of the creator's evaluated launches that didn't rug; see `reputation`.
+    pub creator_score: f64,
+    pub name_len: f64,
+    pub symbol_len: f64,
+    /// Distinct wallets that bought since the launch block.
+    pub buyers: f64,
+    pub buys: f64,
+    pub blocks_since_launch: f64,
+}
+
+impl Features {
+    pub fn values(&self) -> [f64; 8] {
+        [
+            self.liquidity_mon,
+            self.creator_launches,
+            self.creator_score,
+            self.name_len,
+            self.symbol_len,
+            self.buyers,
+            self.buys,
+            self.blocks_since_launch,
+        ]
+    }
+
+    /// `name=value` pairs for the log.
+    fn summary(&self) -> String {
+        let pairs: Vec<String> = FEATURE_NAMES
+            .iter()
+            .zip(self.values())
+            .map(|(name, value)| format!("{name}={value:.2}"))
+            .collect();
+        pairs.join(" ")
+    }
+
+    /// Reads the curve, the buys since the launch and the creator's record. Without
+    /// `reputation` the creator counts as new, scored `new_creator_score`.
+    pub async fn collect(
+        provider: &Provider<Http>,
+        curve: &CurveTracker,
+        reputation: Option<&ReputationConfig>,
+        launch: &Launch,
+        new_creator_score: f64,
+    ) -> Result<Self> {
+        let state = curve.state(launch.token).await?;
+        let head = provider.get_block_number().await?.as_u64();
+        let launched = launch.block.unwrap_or(head);
+        let filter = Filter::new()
+            .address(curve.address())
+            .from_block(launched)
+            .to_block(head)
+            .event(&CurveBuyFilter::abi_signature())
+            .topic2(H256::from(launch.token));
+        let mut buyers = HashSet::new();
+        let mut buys = 0u64;
+        for log in provider.get_logs(&filter).await.context("failed to fetch buys")? {
+            let event: CurveBuyFilter = parse_log(log)?;
+            buyers.insert(event.sender);
+            buys += 1;
+        }
+        let (creator_launches, creator_score) = match reputation.and_then(|r| r.record(launch)) {
+            Some(record) => (record.launches as f64, record.score),
+            None => (0.0, new_creator_score),
+        };
+        Ok(Self {
+            liquidity_mon: chain::profile().native_f64(state.real_mon_reserve),
+            creator_launches,
+            creator_score,
+            name_len: launch.name.chars().count() as f64,
+            symbol_len: launch.symbol.chars().count() as f64,
+            buyers: buyers.len() as f64,
+            buys: buys as f64,
+            blocks_since_launch: head.saturating_sub(launched) as f64,
+        })
+    }
+}
+
+/// Turns a launch's features into a score between 0 and 1, higher for launches more
+/// worth buying.
+pub trait Scorer: Send + Sync {
+    fn score(&self, features: &Features) -> Result<f64>;
+}
+
+/// The default scorer: points for liquidity, the creator's record, buyers and plain
+/// metadata, each capped so no single feature carries a launch.
+pub struct Heuristic {
+    /// Liquidity, in MON, that earns the full liquidity points.
+    pub full_liquidity_mon: f64,
+    /// Distinct buyers that earn the full buyer points.
+    pub full_buyers: f64,
+}
+
+impl Scorer for Heuristic {
+    fn score(&self, features: &Features) -> Result<f64> {
+        let share = |value: f64, full: f64| (value / full.max(f64::EPSILON)).clamp(0.0, 1.0);
+        let plain_metadata = (2.0..=6.0).contains(&features.symbol_len)
+            && (1.0..=32.0).contains(&features.name_len);
+        Ok(0.35 * share(features.liquidity_mon, self.full_liquidity_mon)
+            + 0.30 * features.creator_score.clamp(0.0, 1.0)
+            + 0.25 * share(features.buyers, self.full_buyers)
+            + if plain_metadata { 0.10 } else { 0.0 })
+    }
+}
+
+/// An ONNX model trained on the `features` dataset: one float input of shape
+/// `[1, FEATURE_NAMES.len()]`, scored by the last value of its last output. sklearn
+/// classifiers have to be converted with `zipmap=False` for the probabilities to come
+/// out as a tensor.
+#[cfg(feature = "onnx")]
+pub struct OnnxModel {
+    model: std::sync::Arc<tract_onnx::prelude::TypedRunnableModel>,
+}
+
+#[cfg(feature = "onnx")]
+impl OnnxModel {
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        use tract_onnx::prelude::*;
+        let model = tract_onnx::onnx()
+            .model_for_path(path)
+            .and_then(|model| model.with_input_fact(0, f32::fact([1, FEATURE_NAMES.len()]).into()))
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .with_context(|| format!("failed to load ONNX model {}", path.display()))?;
+        Ok(Self { model })
+    }
+}
+
+#[cfg(feature = "onnx")]
+impl Scorer for OnnxModel {
+    fn score(&self, features: &Features) -> Result<f64> {
+        use tract_onnx::prelude::*;
+        let values: Vec<f32> = features.values().iter().map(|value| *value as f32).collect();
+        let input = Tensor::from_shape(&[1, values.len()], &values)?;
+        let outputs = self.model.run(tvec!(input.into()))?;
+        let output = outputs.last().ok_or_else(|| anyhow!("the model has no outputs"))?;
+        let score = output.to_plain_array_view::<f32>()?.iter().last().copied();
+        score.map(f64::from).ok_or_else(|| anyhow!("the model's output is empty"))
+    }
+}
+
+/// Skips snipes scoring under `SNIPER_MIN_SCORE`, scored by the ONNX model at
+/// `SCORER_MODEL` (with the `onnx` feature) or else by [`Heuristic`].
+pub struct ScoringConfig {
+    pub min_score: f64,
+    /// Creator score for creators without a record, as `CREATOR_NEW_SCORE`.
+    pub new_creator_score: f64,
+    scorer: Box<dyn Scorer>,
+}
+
+impl ScoringConfig {
+    /// Enabled by `SNIPER_MIN_SCORE`.
+    pub fn from_env() -> Result<Option<Self>> {
+        let parsed = |name: &str, default: f64| -> Result<f64> {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {name}")))
+                .transpose()
+                .map(|value| value.unwrap_or(default))
+        };
+        if env::var("SNIPER_MIN_SCORE").is_err() {
+            return Ok(None);
+        }
+        let min_score = parsed("SNIPER_MIN_SCORE", 0.0)?;
+        let scorer: Box<dyn Scorer> = match env::var("SCORER_MODEL").ok() {
+            #[cfg(feature = "onnx")]
+            Some(path) => Box::new(OnnxModel::load(std::path::Path::new(&path))?),
+            #[cfg(not(feature = "onnx"))]
+            Some(_) => return Err(anyhow!("SCORER_MODEL needs a build with the onnx feature")),
+            None => Box::new(Heuristic {
+                full_liquidity_mon: parsed("SCORER_FULL_LIQUIDITY_MON", 10.0)?,
+                full_buyers: parsed("SCORER_FULL_BUYERS", 20.0)?,
+            }),
+        };
+        Ok(Some(Self {
+            min_score,
+            new_creator_score: parsed("CREATOR_NEW_SCORE", 0.5)?,
+            scorer,
+        }))
+    }
+
+    /// The reason to skip `launch`, if it scores under `min_score`, `SNIPER_MIN_SCORE` or
+    /// its tuned value, or can't be scored.
+    pub async fn screen(
+        &self,
+        provider: &Provider<Http>,
+        curve: &CurveTracker,
+        reputation: Option<&ReputationConfig>,
+        launch: &Launch,
+        min_score: f64,
+    ) -> Option<String> {
+        let features =
+            Features::collect(provider, curve, reputation, launch, self.new_creator_score).await;
+        let score = features.and_then(|features| {
+            let score = self.scorer.score(&features)?;
+            info!("{:?} scored {:.3}: {}", launch.token, score, features.summary());
+            Ok(score)
+        });
+        match score {
+            Ok(score) if score < min_score => Some(format!(
+                "score {:.3} below the minimum {:.3}",
+                score, min_score
+            )),
+            Ok(_) => None,
+            Err(err) => Some(format!("scoring failed: {:#}", err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heuristic() -> Heuristic {
+        Heuristic {
+            full_liquidity_mon: 10.0,
+            full_buyers: 20.0,
+        }
+    }
+
+    #[test]
+    fn a_strong_launch_scores_near_one_and_an_empty_one_near_zero() {
+        let strong = Features {
+            liquidity_mon: 25.0,
+            creator_score: 1.0,
+            name_len: 8.0,
+            symbol_len: 4.0,
+            buyers: 40.0,
+            ..Features::default()
+        };
+        assert!((heuristic().score(&strong).unwrap() - 1.0).abs() < 1e-9);
+        let empty = Features {
+            symbol_len: 12.0,
+            ..Features::default()
+        };
+        assert_eq!(heuristic().score(&empty).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn features_are_capped_so_liquidity_alone_cannot_pass() {
+        let whale = Features {
+            liquidity_mon: 10_000.0,
+            ..Features::default()
+        };
+        assert!((heuristic().score(&whale).unwrap() - 0.35).abs() < 1e-9);
+    }
+
+    #[test]
+    fn values_follow_the_feature_names() {
+        let features = Features {
+            liquidity_mon: 1.0,
+            blocks_since_launch: 8.0,
+            ..Features::default()
+        };
+        let values = features.values();
+        assert_eq!(values.len(), FEATURE_NAMES.len());
+        assert_eq!(values[0], 1.0);
+        assert_eq!(values[FEATURE_NAMES.len() - 1], 8.0);
+    }
+}
@@ -0,0 +1,193 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use ethers::types::{Address, Signature};
+use ethers::utils::hex;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::state::{self, StateStore};
+
+#[derive(Debug, Args)]
+pub struct UpdateArgs {
+    /// Only report whether a newer release is published.
+    #[arg(long)]
+    pub check: bool,
+    /// Leave the binary alone while the state file has open positions, so a fleet's
+    /// scheduled update waits for them to close.
+    #[arg(long, env = "SELF_UPDATE_DEFER")]
+    pub defer_while_open: bool,
+}
+
+/// The release the feed at `SELF_UPDATE_FEED` points to. `signature` is an EIP-191
+/// signature by `SELF_UPDATE_SIGNER` over [`Release::message`], e.g. from
+/// `cast wallet sign`.
+#[derive(Debug, Deserialize)]
+struct Release {
+    version: String,
+    url: String,
+    sha256: String,
+    signature: String,
+}
+
+impl Release {
+    /// What the release signer signs: the version and the binary's digest.
+    fn message(&self) -> String {
+        format!("nadfun_trading_bot {} {}", self.version, self.sha256.to_ascii_lowercase())
+    }
+
+    fn verify(&self, signer: Address) -> Result<()> {
+        let signature: Signature =
+            self.signature.parse().context("the release signature is malformed")?;
+        let recovered = signature
+            .recover(self.message())
+            .context("the release signature is unrecoverable")?;
+        if recovered != signer {
+            return Err(anyhow!(
+                "release {} is signed by {:?}, not {:?}",
+                self.version,
+                recovered,
+                signer
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_digest(&self, binary: &[u8]) -> Result<()> {
+        let digest = hex::encode(Sha256::digest(binary));
+        if !digest.eq_ignore_ascii_case(self.sha256.trim_start_matches("0x")) {
+            return Err(anyhow!(
+                "the downloaded binary hashes to {}, not the signed {}",
+                digest,
+                self.sha256
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether `version` is later than `current`, comparing dot-separated numbers.
+fn newer(version: &str, current: &str) -> Result<bool> {
+    let parts = |version: &str| -> Result<Vec<u64>> {
+        version
+            .trim_start_matches('v')
+            .split('.')
+            .map(|part| part.parse().with_context(|| format!("invalid version {:?}", version)))
+            .collect()
+    };
+    Ok(parts(version)? > parts(current)?)
+}
+
+/// Fetches the release feed and, when it names a newer version signed by
+/// `SELF_UPDATE_SIGNER`, downloads the binary, checks its digest and renames it over the
+/// running one. The running process is left as is; the new binary runs from the next
+/// start.
+pub async fn run(args: &UpdateArgs) -> Result<()> {
+    let feed = env::var("SELF_UPDATE_FEED").context("set SELF_UPDATE_FEED to update")?;
+    let signer: Address = env::var("SELF_UPDATE_SIGNER")
+        .context("set SELF_UPDATE_SIGNER to update")?
+        .parse()
+        .context("invalid SELF_UPDATE_SIGNER")?;
+    let client = reqwest::Client::new();
+    let release: Release = client
+        .get(&feed)
+        .send()
+        .await
+        .context("release feed unreachable")?
+        .error_for_status()?
+        .json()
+        .await
+        .context("the release feed is malformed")?;
+    release.verify(signer)?;
+
+    let current = env!("CARGO_PKG_VERSION");
+    if !newer(&release.version, current)? {
+        println!("Up to date: {} is the latest release", current);
+        return Ok(());
+    }
+    if args.check {
+        println!("Release {} is available; running {}", release.version, current);
+        return Ok(());
+    }
+    if args.defer_while_open {
+        let open = StateStore::new(state::state_file()).open_positions()?.len();
+        if open > 0 {
+            println!(
+                "Deferring the update to {}: {} positions are open",
+                release.version, open
+            );
+            return Ok(());
+        }
+    }
+
+    let binary = client
+        .get(&release.url)
+        .send()
+        .await
+        .context("release binary unreachable")?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    release.check_digest(&binary)?;
+    let exe = env::current_exe().context("failed to locate the running binary")?;
+    replace(&exe, &binary)?;
+    println!(
+        "Updated {} from {} to {}; restart to run it",
+        exe.display(),
+        current,
+        release.version
+    );
+    Ok(())
+}
+
+/// Writes `binary` next to `exe` and renames it into place, so a failed write never leaves
+/// a partial binary behind.
+fn replace(exe: &Path, binary: &[u8]) -> Result<()> {
+    let staged = PathBuf::from(format!("{}.update", exe.display()));
+    fs::write(&staged, binary)
+        .with_context(|| format!("failed to write {}", staged.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+    fs::rename(&staged, exe).with_context(|| format!("failed to replace {}", exe.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::{LocalWallet, Signer};
+
+    #[test]
+    fn versions_compare_by_number() {
+        assert!(newer("0.10.0", "0.9.3").unwrap());
+        assert!(newer("v1.0.1", "1.0.0").unwrap());
+        assert!(!newer("1.0.0", "1.0.0").unwrap());
+        assert!(!newer("0.9.0", "0.10.0").unwrap());
+        assert!(newer("1.0.0-beta", "1.0.0").is_err());
+    }
+
+    #[tokio::test]
+    async fn only_the_release_signers_signature_and_digest_pass() {
+        let wallet: LocalWallet = format!("{:064x}", 7).parse().unwrap();
+        let binary = b"new binary";
+        let mut release = Release {
+            version: "9.0.0".into(),
+            url: "https://releases.invalid/nadfun_trading_bot".into(),
+            sha256: hex::encode(Sha256::digest(binary)),
+            signature: String::new(),
+        };
+        release.signature = wallet.sign_message(release.message()).await.unwrap().to_string();
+        release.verify(wallet.address()).unwrap();
+        release.check_digest(binary).unwrap();
+        assert!(release.check_digest(b"tampered").is_err());
+        assert!(release.verify(Address::repeat_byte(1)).is_err());
+
+        release.version = "9.0.1".into();
+        assert!(release.verify(wallet.address()).is_err());
+    }
+}
@@ -0,0 +1,443 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveTime, Timelike};
+use ethers::types::{Address, I256, U256};
+use serde::Serialize;
+use serde_json::json;
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::app::AppConfig;
+use crate::chain;
+use crate::engine::{ExecutionClient, RpcClient};
+use crate::notify::Event;
+use crate::recovery;
+use crate::state::{self, OpenPosition};
+use crate::trading::hold_position;
+
+/// What settlement does with the positions still open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettlePolicy {
+    /// Sell every one.
+    Flatten,
+    /// Keep them into the next day, valued at their exit quote in the report.
+    Roll,
+}
+
+impl FromStr for SettlePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "flatten" => Ok(Self::Flatten),
+            "roll" => Ok(Self::Roll),
+            other => Err(anyhow!(
+                "unknown SETTLE_POLICY `{other}` (expected flatten or roll)"
+            )),
+        }
+    }
+}
+
+/// The end-of-day routine: positions are flattened or rolled per `SETTLE_POLICY`, the
+/// day's realized profit is swept to `SETTLE_SWEEP_TO`, the position store is reconciled
+/// against the chain, the day's report is written and alerted, and `SETTLE_ROTATE_FILES`
+/// are rotated. `settle` runs it once; `SETTLE_AT` runs it daily in the long-running
+/// modes.
+pub struct SettleConfig {
+    /// UTC time of day, `HH:MM`.
+    pub at: Option<NaiveTime>,
+    policy: SettlePolicy,
+    sweep_to: Option<Address>,
+    /// Native left in the wallet on top of the gas reserve, whatever the day made.
+    keep: U256,
+    report_dir: PathBuf,
+    /// Copied to `<file>.<day>` and truncated, so a process appending to them carries on.
+    rotate: Vec<PathBuf>,
+    /// How long a running bot's positions get to sell when flattening.
+    flatten_timeout: Duration,
+}
+
+impl SettleConfig {
+    /// `SETTLE_POLICY` defaults to `roll`, `SETTLE_KEEP_MON` to 0, `SETTLE_REPORT_DIR` to
+    /// `settlements` and `SETTLE_FLATTEN_TIMEOUT_SECS` to 300.
+    pub fn from_env() -> Result<Self> {
+        let at = env::var("SETTLE_AT")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|v| NaiveTime::parse_from_str(&v, "%H:%M").context("invalid SETTLE_AT"))
+            .transpose()?;
+        let sweep_to = env::var("SETTLE_SWEEP_TO")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(|v| v.parse().context("invalid SETTLE_SWEEP_TO"))
+            .transpose()?;
+        let keep = env::var("SETTLE_KEEP_MON")
+            .ok()
+            .map(|v| {
+                chain::profile()
+                    .parse_native(&v)
+                    .context("invalid SETTLE_KEEP_MON")
+            })
+            .transpose()?
+            .unwrap_or_default();
+        let timeout = env::var("SETTLE_FLATTEN_TIMEOUT_SECS")
+            .ok()
+            .map(|v| v.parse().context("invalid SETTLE_FLATTEN_TIMEOUT_SECS"))
+            .transpose()?
+            .unwrap_or(300);
+        Ok(Self {
+            at,
+            policy: env::var("SETTLE_POLICY")
+                .ok()
+                .map(|v| v.parse())
+                .transpose()?
+                .unwrap_or(SettlePolicy::Roll),
+            sweep_to,
+            keep,
+            report_dir: PathBuf::from(
+                env::var("SETTLE_REPORT_DIR").unwrap_or_else(|_| "settlements".into()),
+            ),
+            rotate: env::var("SETTLE_ROTATE_FILES")
+                .map(|list| {
+                    list.split(',')
+                        .map(str::trim)
+                        .filter(|path| !path.is_empty())
+                        .map(PathBuf::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            flatten_timeout: Duration::from_secs(timeout),
+        })
+    }
+}
+
+/// The next time `at` falls on after `now`.
+fn next_run(now: u64, at: NaiveTime) -> u64 {
+    let today = now - now % 86_400 + u64::from(at.num_seconds_from_midnight());
+    if today > now {
+        today
+    } else {
+        today + 86_400
+    }
+}
+
+/// What may be swept: the day's realized profit, leaving `keep` in the wallet.
+fn sweep_amount(pnl: I256, balance: U256, keep: U256) -> U256 {
+    if pnl <= I256::zero() {
+        return U256::zero();
+    }
+    pnl.into_raw().min(balance.saturating_sub(keep))
+}
+
+/// Copies `path` onto `<path>.<day>` and truncates it. `None` if there was nothing to
+/// rotate.
+fn rotate(path: &Path, day: &str) -> Result<Option<PathBuf>> {
+    let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("failed to open {}", path.display())),
+    };
+    if file.metadata()?.len() == 0 {
+        return Ok(None);
+    }
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", day));
+    let rotated = PathBuf::from(name);
+    // Appended to, so a second settlement on the same day keeps the first one's lines.
+    let mut archive = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&rotated)
+        .with_context(|| format!("failed to open {}", rotated.display()))?;
+    io::copy(&mut file, &mut archive)
+        .with_context(|| format!("failed to copy {}", path.display()))?;
+    file.set_len(0)
+        .with_context(|| format!("failed to truncate {}", path.display()))?;
+    Ok(Some(rotated))
+}
+
+#[derive(Debug, Serialize)]
+struct Rolled {
+    token: Address,
+    value: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Sweep {
+    to: Address,
+    amount: f64,
+    tx: Option<String>,
+}
+
+/// The day's report, written to `SETTLE_REPORT_DIR/settlement-<day>.json`.
+#[derive(Debug, Serialize)]
+struct Settlement {
+    day: String,
+    at: u64,
+    wallet: Address,
+    dry_run: bool,
+    flattened: Vec<Address>,
+    /// Positions flattening didn't close in time, or at all.
+    still_open: Vec<Address>,
+    rolled: Vec<Rolled>,
+    trades: usize,
+    volume: f64,
+    pnl: f64,
+    balance: f64,
+    sweep: Option<Sweep>,
+    rotated: Vec<PathBuf>,
+}
+
+impl Settlement {
+    fn summary(&self) -> String {
+        let mut summary = format!(
+            "Settlement {}: {} trades, {:+.4} {} realized, {} flattened, {} rolled",
+            self.day,
+            self.trades,
+            self.pnl,
+            chain::profile().native_symbol,
+            self.flattened.len(),
+            self.rolled.len()
+        );
+        if let Some(sweep) = &self.sweep {
+            summary.push_str(&format!(", {:.4} swept to {:?}", sweep.amount, sweep.to));
+        }
+        if !self.still_open.is_empty() {
+            summary.push_str(&format!(", {} still open", self.still_open.len()));
+        }
+        summary
+    }
+}
+
+/// Runs the settlement once. `running` is true inside a trading mode, whose own tasks
+/// hold the positions and sell them on a force-sell; the `settle` command sells them
+/// itself, so it must not run next to a bot managing the same wallet.
+pub async fn settle(cfg: &AppConfig, client: &RpcClient, running: bool) -> Result<()> {
+    let settle = &cfg.settle;
+    let wallet = cfg.recipient.unwrap_or_else(|| client.wallet());
+    let now = state::unix_now();
+    let day = DateTime::from_timestamp(now as i64, 0)
+        .map(|at| at.date_naive().to_string())
+        .unwrap_or_default();
+    let profile = chain::profile();
+    info!("Settling {} for {:?}", day, wallet);
+
+    let positions: Vec<OpenPosition> = cfg
+        .state
+        .open_positions()?
+        .into_iter()
+        .filter(|position| position.wallet == wallet)
+        .collect();
+    let (to_flatten, to_roll): (Vec<OpenPosition>, Vec<OpenPosition>) =
+        positions.into_iter().partition(|position| {
+            // DCA positions belong to their schedule until its last buy.
+            settle.policy == SettlePolicy::Flatten && !position.accumulating && !cfg.dry_run
+        });
+    let mut report = Settlement {
+        day: day.clone(),
+        at: now,
+        wallet,
+        dry_run: cfg.dry_run,
+        flattened: Vec::new(),
+        still_open: Vec::new(),
+        rolled: Vec::new(),
+        trades: 0,
+        volume: 0.0,
+        pnl: 0.0,
+        balance: 0.0,
+        sweep: None,
+        rotated: Vec::new(),
+    };
+
+    let tokens: Vec<Address> = to_flatten.iter().map(|position| position.token).collect();
+    for token in &tokens {
+        cfg.controls.force_sell(*token);
+    }
+    if running {
+        let deadline = Instant::now() + settle.flatten_timeout;
+        loop {
+            let open = open_tokens(cfg, wallet)?;
+            if tokens.iter().all(|token| !open.contains(token)) || Instant::now() >= deadline {
+                break;
+            }
+            tokio::time::sleep(Duration::from_secs(cfg.exit_check_interval_secs.max(1))).await;
+        }
+    } else {
+        let sells = to_flatten
+            .iter()
+            .map(|position| hold_position(cfg, client, position));
+        for (token, sold) in tokens
+            .iter()
+            .zip(futures_util::future::join_all(sells).await)
+        {
+            if let Err(err) = sold {
+                warn!("Settlement sell of {:?} failed: {:#}", token, err);
+            }
+        }
+    }
+    let open = open_tokens(cfg, wallet)?;
+    for token in tokens {
+        if open.contains(&token) {
+            report.still_open.push(token);
+        } else {
+            report.flattened.push(token);
+        }
+    }
+    for position in &to_roll {
+        let held = client.token_balance(position.token, wallet).await?;
+        let value = match client.quote(position.token, held, false).await {
+            Ok((_, value)) => profile.native_f64(value),
+            Err(err) => {
+                warn!(
+                    "Could not value {:?} for the settlement: {:#}",
+                    position.token, err
+                );
+                0.0
+            }
+        };
+        report.rolled.push(Rolled {
+            token: position.token,
+            value,
+        });
+    }
+
+    recovery::reconcile(cfg, client, wallet).await?;
+
+    let since = now.saturating_sub(86_400);
+    let mut pnl = I256::zero();
+    let mut volume = U256::zero();
+    for record in cfg.ledger.load()? {
+        if record.wallet == wallet && record.closed_at > since {
+            report.trades += 1;
+            pnl += record.pnl();
+            volume += record.amount_in + record.proceeds;
+        }
+    }
+    report.volume = profile.native_f64(volume);
+    report.pnl = match profile.native_f64(pnl.unsigned_abs()) {
+        loss if pnl.is_negative() => -loss,
+        profit => profit,
+    };
+
+    let balance = client.native_balance(wallet, None).await?;
+    report.balance = profile.native_f64(balance);
+    if let Some(to) = settle.sweep_to {
+        let transfer_gas = client.gas_price().await? * U256::from(21_000u64);
+        let amount = sweep_amount(pnl, balance, settle.keep + cfg.gas_reserve + transfer_gas);
+        if !amount.is_zero() {
+            let tx = if cfg.dry_run {
+                None
+            } else {
+                let receipt = client
+                    .transfer(to, amount)
+                    .await
+                    .context("profit sweep failed")?;
+                Some(format!("{:?}", receipt.transaction_hash))
+            };
+            info!("Swept {} to {:?}", profile.format_native(amount), to);
+            report.sweep = Some(Sweep {
+                to,
+                amount: profile.native_f64(amount),
+                tx,
+            });
+        }
+    }
+
+    for path in &settle.rotate {
+        match rotate(path, &day) {
+            Ok(Some(rotated)) => report.rotated.push(rotated),
+            Ok(None) => {}
+            Err(err) => warn!("Failed to rotate {}: {:#}", path.display(), err),
+        }
+    }
+
+    fs::create_dir_all(&settle.report_dir)
+        .with_context(|| format!("failed to create {}", settle.report_dir.display()))?;
+    let path = settle.report_dir.join(format!("settlement-{}.json", day));
+    fs::write(&path, serde_json::to_string_pretty(&report)?)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    let summary = report.summary();
+    info!("{}; report in {}", summary, path.display());
+    cfg.notifier.send(Event::Report, summary);
+    cfg.audit("settlement", json!(report));
+    Ok(())
+}
+
+fn open_tokens(cfg: &AppConfig, wallet: Address) -> Result<Vec<Address>> {
+    Ok(cfg
+        .state
+        .open_positions()?
+        .into_iter()
+        .filter(|position| position.wallet == wallet)
+        .map(|position| position.token)
+        .collect())
+}
+
+/// Runs the settlement every day at `SETTLE_AT` until shutdown. A failed one is alerted
+/// and the next day's runs as usual.
+pub async fn schedule(cfg: &AppConfig, client: &RpcClient) {
+    let Some(at) = cfg.settle.at else {
+        return std::future::pending().await;
+    };
+    loop {
+        let now = state::unix_now();
+        let wait = Duration::from_secs(next_run(now, at) - now);
+        info!(
+            "Next settlement at {} UTC, in {}s",
+            at.format("%H:%M"),
+            wait.as_secs()
+        );
+        tokio::select! {
+            () = tokio::time::sleep(wait) => {}
+            () = cfg.shutdown.wait() => return,
+        }
+        if let Err(err) = settle(cfg, client, true).await {
+            warn!("Settlement failed: {:#}", err);
+            cfg.notifier
+                .send(Event::Error, format!("Settlement failed: {:#}", err));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_next_run_is_today_if_still_ahead_else_tomorrow() {
+        let at = NaiveTime::from_hms_opt(23, 30, 0).unwrap();
+        let midnight = 1_792_022_400;
+        assert_eq!(next_run(midnight + 3_600, at), midnight + 84_600);
+        assert_eq!(next_run(midnight + 84_600, at), midnight + 86_400 + 84_600);
+    }
+
+    #[test]
+    fn only_profit_above_what_is_kept_is_swept() {
+        let mon = U256::exp10(18);
+        let profit = I256::from_raw(mon * 2);
+        assert_eq!(sweep_amount(profit, mon * 10, mon), mon * 2);
+        assert_eq!(sweep_amount(profit, mon * 2, mon), mon);
+        assert_eq!(sweep_amount(-profit, mon * 10, mon), U256::zero());
+    }
+
+    #[test]
+    fn rotation_archives_and_truncates_in_place() {
+        let path = std::env::temp_dir().join(format!("nadfun-settle-{}.log", std::process::id()));
+        let archive = PathBuf::from(format!("{}.2026-10-14", path.display()));
+        let _ = fs::remove_file(&archive);
+        fs::write(&path, "first\n").unwrap();
+        assert_eq!(rotate(&path, "2026-10-14").unwrap(), Some(archive.clone()));
+        fs::write(&path, "second\n").unwrap();
+        rotate(&path, "2026-10-14").unwrap();
+        assert_eq!(fs::read_to_string(&archive).unwrap(), "first\nsecond\n");
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        assert_eq!(rotate(&path, "2026-10-14").unwrap(), None);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&archive).unwrap();
+    }
+}
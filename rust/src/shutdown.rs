@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Graceful stop on SIGINT or SIGTERM: new entries are refused, transactions already
+/// sent are seen through and every mode winds down once its trades in flight are done.
+/// Held positions stay open in the state file for the next start, or with
+/// `exit_positions` are sold straight away. A second signal exits immediately.
+pub struct Shutdown {
+    requested: Arc<watch::Sender<bool>>,
+    pub exit_positions: bool,
+}
+
+impl Shutdown {
+    pub fn new(exit_positions: bool) -> Self {
+        Self {
+            requested: Arc::new(watch::channel(false).0),
+            exit_positions,
+        }
+    }
+
+    pub fn requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    /// Starts a shutdown from inside the process, as the first signal would.
+    pub fn request(&self) {
+        info!("Shutting down: no new entries");
+        self.requested.send_replace(true);
+    }
+
+    /// Resolves once a shutdown has been requested.
+    pub async fn wait(&self) {
+        let mut requested = self.requested.subscribe();
+        let _ = requested.wait_for(|requested| *requested).await;
+    }
+
+    /// Starts listening for the shutdown signals in the background.
+    pub fn listen(&self) -> Result<()> {
+        let mut terminate =
+            signal(SignalKind::terminate()).context("failed to listen for SIGTERM")?;
+        let requested = self.requested.clone();
+        let exit_positions = self.exit_positions;
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                if *requested.borrow() {
+                    warn!("Second shutdown signal, exiting without waiting");
+                    std::process::exit(130);
+                }
+                if exit_positions {
+                    info!("Shutting down: no new entries, selling every open position");
+                } else {
+                    info!("Shutting down: no new entries, open positions kept for the next start");
+                }
+                requested.send_replace(true);
+            }
+        });
+        Ok(())
+    }
+}
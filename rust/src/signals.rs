@@ -0,0 +1,563 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, I256};
+use ethers::utils::hex;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::Peer;
+use crate::ledger::signed_native;
+use crate::state;
+
+/// The header carrying a signal's HMAC-SHA256, as `sha256=<hex>` of the body.
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// How far ahead of this clock a signal's `at` may be.
+const MAX_CLOCK_SKEW_SECS: u64 = 60;
+
+/// What a bot found out about a token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SignalKind {
+    /// A launch that passed the sender's filters.
+    Opportunity,
+    /// The outcome of the sender's safety checks.
+    Verdict { safe: bool, reason: Option<String> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Signal {
+    pub token: Address,
+    #[serde(flatten)]
+    pub kind: SignalKind,
+    pub at: u64,
+}
+
+pub struct SignalConfig {
+    /// Combined peer weight needed before a shared signal is acted on.
+    pub min_trust: f64,
+    /// Signals older than this no longer count.
+    pub ttl: Duration,
+    /// Providers whose backed trades win less often than this are disabled, once they
+    /// have backed `min_trades` of them.
+    pub min_win_rate: Option<f64>,
+    pub min_trades: u32,
+    /// Where each provider's record is kept across restarts.
+    pub stats_file: PathBuf,
+}
+
+impl SignalConfig {
+    pub fn from_env() -> Result<Self> {
+        let min_trust: f64 = env::var("SIGNAL_MIN_TRUST")
+            .ok()
+            .map(|v| v.parse().context("invalid SIGNAL_MIN_TRUST"))
+            .transpose()?
+            .unwrap_or(1.0);
+        if min_trust <= 0.0 {
+            return Err(anyhow!("SIGNAL_MIN_TRUST must be positive"));
+        }
+        let min_win_rate: Option<f64> = env::var("SIGNAL_MIN_WIN_RATE")
+            .ok()
+            .map(|v| v.parse().context("invalid SIGNAL_MIN_WIN_RATE"))
+            .transpose()?;
+        if min_win_rate.is_some_and(|rate| !(0.0..=1.0).contains(&rate)) {
+            return Err(anyhow!("SIGNAL_MIN_WIN_RATE must be between 0 and 1"));
+        }
+        Ok(Self {
+            min_trust,
+            ttl: Duration::from_secs(
+                env::var("SIGNAL_TTL_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(600),
+            ),
+            min_win_rate,
+            min_trades: env::var("SIGNAL_MIN_TRADES")
+                .ok()
+                .map(|v| v.parse().context("invalid SIGNAL_MIN_TRADES"))
+                .transpose()?
+                .unwrap_or(5),
+            stats_file: PathBuf::from(
+                env::var("SIGNAL_STATS_FILE").unwrap_or_else(|_| "signal_stats.json".into()),
+            ),
+        })
+    }
+}
+
+/// Signals received per token, keyed by the peer that sent them; a peer's newer
+/// signal replaces its older one.
+type Received = BTreeMap<Address, BTreeMap<String, (f64, Signal)>>;
+
+/// Why an inbound signal was turned away.
+#[derive(Debug, PartialEq)]
+pub enum Rejection {
+    /// No known bearer token, or a missing or wrong signature.
+    Unauthorized,
+    /// Over the sender's `max_signals_per_min`.
+    RateLimited,
+    /// The same body was already taken.
+    Replayed,
+    /// The sender was disabled for the trades it backed.
+    Disabled(String),
+    Invalid(String),
+}
+
+/// How the trades a provider backed turned out.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ProviderStats {
+    /// Signals taken from it.
+    pub signals: u64,
+    /// Trades its signals helped trigger that have closed.
+    pub trades: u32,
+    pub wins: u32,
+    pub pnl: I256,
+    /// Set once its win rate falls below `SIGNAL_MIN_WIN_RATE`; its signals are refused
+    /// until it is re-enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProviderReport {
+    pub name: String,
+    pub weight: f64,
+    #[serde(flatten)]
+    pub stats: ProviderStats,
+}
+
+/// What was recently taken from one sender, for rate limits and replay protection.
+#[derive(Default)]
+struct Traffic {
+    taken: VecDeque<Instant>,
+    /// Digests of the bodies taken, with their signals' `at`.
+    seen: HashMap<[u8; 32], u64>,
+}
+
+/// Shares opportunities and safety verdicts with the `[[peer]]` instances and weighs
+/// theirs by trust: an opportunity is traded once enough trusted peers back it, and an
+/// entry is refused once enough of them flag the token as unsafe. Inbound signals are
+/// checked against each sender's signature key, rate limit and track record.
+pub struct Signals {
+    config: SignalConfig,
+    peers: Vec<Peer>,
+    client: reqwest::Client,
+    received: Mutex<Received>,
+    traffic: Mutex<HashMap<String, Traffic>>,
+    stats: Mutex<BTreeMap<String, ProviderStats>>,
+    /// The senders whose signals triggered the trade in each token, until it closes.
+    backers: Mutex<BTreeMap<Address, Vec<String>>>,
+}
+
+impl Signals {
+    pub fn new(config: SignalConfig, peers: Vec<Peer>) -> Result<Self> {
+        if let Some(peer) = peers.iter().find(|peer| !(0.0..=1.0).contains(&peer.weight)) {
+            return Err(anyhow!("peer {} weight must be between 0 and 1", peer.name));
+        }
+        if let Some(peer) = peers.iter().find(|peer| peer.inbound_secret.is_none()) {
+            return Err(anyhow!(
+                "peer {} has no inbound_secret; signals are only taken signed",
+                peer.name
+            ));
+        }
+        let path = &config.stats_file;
+        let stats = if path.exists() {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("invalid signal stats in {}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            config,
+            peers,
+            client: reqwest::Client::new(),
+            received: Mutex::default(),
+            traffic: Mutex::default(),
+            stats: Mutex::new(stats),
+            backers: Mutex::default(),
+        })
+    }
+
+    /// Authenticates an inbound signal and checks it against its sender's rate limit,
+    /// the signals already taken and the sender's track record.
+    pub fn admit(
+        &self,
+        bearer: Option<&str>,
+        signature: Option<&str>,
+        body: &[u8],
+    ) -> Result<(&Peer, Signal), Rejection> {
+        // Compared in constant time so response timing doesn't leak the tokens.
+        let peer = bearer
+            .and_then(|bearer| {
+                self.peers
+                    .iter()
+                    .find(|peer| bool::from(peer.inbound_token.as_bytes().ct_eq(bearer.as_bytes())))
+            })
+            .ok_or(Rejection::Unauthorized)?;
+        let secret = peer.inbound_secret.as_ref().ok_or(Rejection::Unauthorized)?;
+        let signature = signature
+            .and_then(|header| header.strip_prefix("sha256="))
+            .and_then(|hex| hex::decode(hex).ok())
+            .ok_or(Rejection::Unauthorized)?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .map_err(|_| Rejection::Unauthorized)?;
+        mac.update(body);
+        mac.verify_slice(&signature).map_err(|_| Rejection::Unauthorized)?;
+        let disabled = self
+            .stats
+            .lock()
+            .ok()
+            .and_then(|stats| stats.get(&peer.name).and_then(|stats| stats.disabled.clone()));
+        if let Some(reason) = disabled {
+            return Err(Rejection::Disabled(reason));
+        }
+        let signal: Signal = serde_json::from_slice(body)
+            .map_err(|err| Rejection::Invalid(format!("invalid signal: {}", err)))?;
+        let now = state::unix_now();
+        if signal.at > now + MAX_CLOCK_SKEW_SECS {
+            return Err(Rejection::Invalid("signal is dated in the future".into()));
+        }
+
+        let mut traffic = self.traffic.lock().map_err(|_| Rejection::RateLimited)?;
+        let traffic = traffic.entry(peer.name.clone()).or_default();
+        let minute_ago = Instant::now().checked_sub(Duration::from_secs(60));
+        while traffic.taken.front().is_some_and(|at| Some(*at) < minute_ago) {
+            traffic.taken.pop_front();
+        }
+        if let Some(limit) = peer.max_signals_per_min {
+            if traffic.taken.len() >= limit as usize {
+                return Err(Rejection::RateLimited);
+            }
+        }
+        // Signals past the TTL are ignored anyway, so only newer bodies are remembered.
+        let cutoff = now.saturating_sub(self.config.ttl.as_secs());
+        traffic.seen.retain(|_, at| *at >= cutoff);
+        let digest: [u8; 32] = Sha256::digest(body).into();
+        if traffic.seen.insert(digest, signal.at).is_some() {
+            return Err(Rejection::Replayed);
+        }
+        traffic.taken.push_back(Instant::now());
+
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.entry(peer.name.clone()).or_default().signals += 1;
+        }
+        Ok((peer, signal))
+    }
+
+    /// Sends a signal about `token` to every peer in the background.
+    pub fn publish(&self, token: Address, kind: SignalKind) {
+        let signal = Signal {
+            token,
+            kind,
+            at: state::unix_now(),
+        };
+        let body = match serde_json::to_vec(&signal) {
+            Ok(body) => body,
+            Err(err) => {
+                warn!("Failed to encode signal: {}", err);
+                return;
+            }
+        };
+        for peer in &self.peers {
+            let Some(url) = &peer.url else {
+                continue;
+            };
+            let mut request = self
+                .client
+                .post(format!("{}/signals", url.trim_end_matches('/')))
+                .timeout(Duration::from_secs(5))
+                .header(reqwest::header::CONTENT_TYPE, "application/json");
+            if let Some(bearer) = &peer.token {
+                request = request.bearer_auth(bearer);
+            }
+            if let Some(secret) = &peer.secret {
+                request = request.header(SIGNATURE_HEADER, sign(secret, &body));
+            }
+            let request = request.body(body.clone());
+            let name = peer.name.clone();
+            tokio::spawn(async move {
+                let result = request.send().await.and_then(|r| r.error_for_status());
+                if let Err(err) = result {
+                    warn!("Failed to share signal with peer {}: {}", name, err);
+                }
+            });
+        }
+    }
+
+    /// Records a signal from `peer`. Returns true when it tips the token's opportunity
+    /// support over `SIGNAL_MIN_TRUST`, so the caller should trade it.
+    pub fn receive(&self, peer: &Peer, signal: Signal) -> Result<bool> {
+        if peer.weight == 0.0 {
+            return Ok(false);
+        }
+        let now = state::unix_now();
+        if signal.at + self.config.ttl.as_secs() < now {
+            return Ok(false);
+        }
+        let mut received = self.received.lock().map_err(|_| anyhow!("signals lock poisoned"))?;
+        prune(&mut received, now.saturating_sub(self.config.ttl.as_secs()));
+
+        let token = signal.token;
+        let before = support(received.get(&token));
+        info!("Signal from peer {} (weight {}): {:?}", peer.name, peer.weight, signal);
+        received
+            .entry(token)
+            .or_default()
+            .insert(peer.name.clone(), (peer.weight, signal));
+        let after = support(received.get(&token));
+        let tipped = before < self.config.min_trust && after >= self.config.min_trust;
+        if tipped {
+            let names = received[&token]
+                .iter()
+                .filter(|(_, (_, signal))| backs(signal))
+                .map(|(name, _)| name.clone())
+                .collect();
+            if let Ok(mut backers) = self.backers.lock() {
+                backers.insert(token, names);
+            }
+        }
+        Ok(tipped)
+    }
+
+    /// Credits the closed trade in `token` to the senders whose signals triggered it,
+    /// disabling any whose win rate has fallen below `SIGNAL_MIN_WIN_RATE`.
+    pub fn record_outcome(&self, token: Address, pnl: I256) -> Result<()> {
+        let Some(names) = self.backers.lock().ok().and_then(|mut backers| backers.remove(&token))
+        else {
+            return Ok(());
+        };
+        let mut stats = self.stats.lock().map_err(|_| anyhow!("signal stats lock poisoned"))?;
+        for name in names {
+            let provider = stats.entry(name.clone()).or_default();
+            provider.trades += 1;
+            if pnl > I256::zero() {
+                provider.wins += 1;
+            }
+            provider.pnl += pnl;
+            let win_rate = f64::from(provider.wins) / f64::from(provider.trades);
+            if let Some(min) = self.config.min_win_rate {
+                if provider.disabled.is_none()
+                    && provider.trades >= self.config.min_trades
+                    && win_rate < min
+                {
+                    let reason = format!(
+                        "won {} of {} backed trades for {}",
+                        provider.wins,
+                        provider.trades,
+                        signed_native(provider.pnl)
+                    );
+                    warn!("Disabling signal provider {}: {}", name, reason);
+                    provider.disabled = Some(reason);
+                }
+            }
+        }
+        self.save(&stats)
+    }
+
+    /// Every configured sender with its record.
+    pub fn providers(&self) -> Vec<ProviderReport> {
+        let stats = self.stats.lock().map(|stats| stats.clone()).unwrap_or_default();
+        self.peers
+            .iter()
+            .map(|peer| ProviderReport {
+                name: peer.name.clone(),
+                weight: peer.weight,
+                stats: stats.get(&peer.name).cloned().unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Takes signals from `name` again, with a fresh record. False if no such provider.
+    pub fn enable(&self, name: &str) -> Result<bool> {
+        if !self.peers.iter().any(|peer| peer.name == name) {
+            return Ok(false);
+        }
+        let mut stats = self.stats.lock().map_err(|_| anyhow!("signal stats lock poisoned"))?;
+        stats.remove(name);
+        self.save(&stats)?;
+        Ok(true)
+    }
+
+    fn save(&self, stats: &BTreeMap<String, ProviderStats>) -> Result<()> {
+        let path = &self.config.stats_file;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(stats)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))
+    }
+
+    /// Why an entry into `token` should be refused, if enough trusted peers flagged it.
+    pub fn vetoed(&self, token: Address) -> Option<String> {
+        let cutoff = state::unix_now().saturating_sub(self.config.ttl.as_secs());
+        let received = self.received.lock().ok()?;
+        let mut weight = 0.0;
+        let mut reasons = Vec::new();
+        for (name, (peer_weight, signal)) in received.get(&token)? {
+            if signal.at < cutoff {
+                continue;
+            }
+            if let SignalKind::Verdict { safe: false, reason } = &signal.kind {
+                weight += peer_weight;
+                reasons.push(format!("{}: {}", name, reason.as_deref().unwrap_or("unsafe")));
+            }
+        }
+        (weight >= self.config.min_trust)
+            .then(|| format!("flagged unsafe by peers ({})", reasons.join("; ")))
+    }
+}
+
+/// Combined weight of the peers backing a token, as an opportunity or a safe verdict.
+fn support(signals: Option<&BTreeMap<String, (f64, Signal)>>) -> f64 {
+    signals
+        .into_iter()
+        .flat_map(|signals| signals.values())
+        .filter(|(_, signal)| backs(signal))
+        .map(|(weight, _)| weight)
+        .sum()
+}
+
+/// Whether `signal` argues for trading its token.
+fn backs(signal: &Signal) -> bool {
+    matches!(
+        signal.kind,
+        SignalKind::Opportunity | SignalKind::Verdict { safe: true, .. }
+    )
+}
+
+/// The `X-Signature` value of `body` under `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn prune(received: &mut Received, cutoff: u64) {
+    for signals in received.values_mut() {
+        signals.retain(|_, (_, signal)| signal.at >= cutoff);
+    }
+    received.retain(|_, signals| !signals.is_empty());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str, min_win_rate: Option<f64>) -> SignalConfig {
+        let dir = env::temp_dir().join(format!("nadfun-signals-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let stats_file = dir.join("signal_stats.json");
+        fs::remove_file(&stats_file).ok();
+        SignalConfig {
+            min_trust: 1.0,
+            ttl: Duration::from_secs(600),
+            min_win_rate,
+            min_trades: 2,
+            stats_file,
+        }
+    }
+
+    fn provider() -> Peer {
+        Peer {
+            name: "feed".into(),
+            url: None,
+            weight: 1.0,
+            token: None,
+            inbound_token: "bearer".into(),
+            secret: None,
+            inbound_secret: Some("key".into()),
+            max_signals_per_min: Some(2),
+        }
+    }
+
+    fn signals(name: &str, min_win_rate: Option<f64>) -> Signals {
+        Signals::new(config(name, min_win_rate), vec![provider()]).unwrap()
+    }
+
+    #[test]
+    fn peers_without_an_inbound_secret_are_refused() {
+        let unsigned = Peer {
+            inbound_secret: None,
+            ..provider()
+        };
+        let err = Signals::new(config("unsigned", None), vec![unsigned]).err().unwrap();
+        assert!(err.to_string().contains("no inbound_secret"));
+    }
+
+    fn body(token: Address, at: u64) -> Vec<u8> {
+        serde_json::to_vec(&Signal {
+            token,
+            kind: SignalKind::Opportunity,
+            at,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn admit_checks_the_signature_replays_and_rate() {
+        let signals = signals("admit", None);
+        let now = state::unix_now();
+        let first = body(Address::repeat_byte(1), now);
+        let signed = sign("key", &first);
+
+        let rejection = |bearer, signature: Option<&str>, body: &[u8]| {
+            signals.admit(Some(bearer), signature, body).unwrap_err()
+        };
+        assert_eq!(rejection("other", Some(&signed), &first), Rejection::Unauthorized);
+        assert_eq!(rejection("bearer", None, &first), Rejection::Unauthorized);
+        let forged = sign("guess", &first);
+        assert_eq!(rejection("bearer", Some(&forged), &first), Rejection::Unauthorized);
+
+        assert!(signals.admit(Some("bearer"), Some(&signed), &first).is_ok());
+        assert_eq!(rejection("bearer", Some(&signed), &first), Rejection::Replayed);
+
+        let second = body(Address::repeat_byte(2), now);
+        assert!(signals.admit(Some("bearer"), Some(&sign("key", &second)), &second).is_ok());
+        let third = body(Address::repeat_byte(3), now);
+        assert_eq!(rejection("bearer", Some(&sign("key", &third)), &third), Rejection::RateLimited);
+
+        let future = body(Address::repeat_byte(4), now + 3_600);
+        assert!(matches!(
+            signals.admit(Some("bearer"), Some(&sign("key", &future)), &future),
+            Err(Rejection::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn losing_providers_are_disabled_until_re_enabled() {
+        let signals = signals("outcomes", Some(0.5));
+        let peer = signals.peers[0].clone();
+        let loss = I256::from(-5);
+        for byte in [1, 2] {
+            let token = Address::repeat_byte(byte);
+            let signal = Signal {
+                token,
+                kind: SignalKind::Opportunity,
+                at: state::unix_now(),
+            };
+            assert!(signals.receive(&peer, signal).unwrap());
+            signals.record_outcome(token, loss).unwrap();
+        }
+        let report = &signals.providers()[0];
+        assert_eq!((report.stats.trades, report.stats.wins), (2, 0));
+        assert!(report.stats.disabled.is_some());
+
+        let next = body(Address::repeat_byte(3), state::unix_now());
+        assert!(matches!(
+            signals.admit(Some("bearer"), Some(&sign("key", &next)), &next),
+            Err(Rejection::Disabled(_))
+        ));
+        assert!(signals.enable("feed").unwrap());
+        assert!(signals.admit(Some("bearer"), Some(&sign("key", &next)), &next).is_ok());
+    }
+}
@@ -0,0 +1,82 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::signers::LocalWallet;
+use tracing::info;
+
+/// The main wallet's key: decrypted from the JSON keystore at `KEYSTORE_FILE` when set,
+/// else the plaintext `PRIVATE_KEY`.
+///
+/// The nad.fun SDK clients sign with a key string of their own, so the key is still
+/// held in memory once unlocked; hardware wallets can't drive them and aren't offered.
+pub fn private_key() -> Result<String> {
+    if let Some(path) = env::var("KEYSTORE_FILE").ok().filter(|v| !v.is_empty()) {
+        let password = keystore_password()?;
+        let key = decrypt(Path::new(&path), &password)?;
+        info!("Unlocked keystore {}", path);
+        return Ok(key);
+    }
+    env::var("PRIVATE_KEY").context("PRIVATE_KEY or KEYSTORE_FILE missing")
+}
+
+/// Where [`private_key`] takes the main wallet's key from, to label its signing metrics.
+pub fn backend() -> &'static str {
+    if env::var("KEYSTORE_FILE").is_ok_and(|v| !v.is_empty()) {
+        "keystore"
+    } else {
+        "private key"
+    }
+}
+
+/// The secondary wallet's key and backend, for race buys while the main wallet's signer
+/// is slow or failing: the keystore at `SNIPE_FALLBACK_KEYSTORE_FILE`, unlocked from the
+/// same password sources as `KEYSTORE_FILE`, else `SNIPE_FALLBACK_PRIVATE_KEY`.
+pub fn fallback_key() -> Result<Option<(String, &'static str)>> {
+    if let Some(path) = env::var("SNIPE_FALLBACK_KEYSTORE_FILE").ok().filter(|v| !v.is_empty()) {
+        let key = decrypt(Path::new(&path), &keystore_password()?)?;
+        info!("Unlocked fallback keystore {}", path);
+        return Ok(Some((key, "keystore")));
+    }
+    Ok(env::var("SNIPE_FALLBACK_PRIVATE_KEY")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(|key| (key, "private key")))
+}
+
+/// The keystore password, from the first of `KEYSTORE_PASSWORD`,
+/// `KEYSTORE_PASSWORD_FILE` (e.g. a mounted secret), `KEYSTORE_PASSWORD_CMD` (e.g. a
+/// secrets manager CLI, its stdout is the password) or a terminal prompt.
+pub fn keystore_password() -> Result<String> {
+    if let Ok(password) = env::var("KEYSTORE_PASSWORD") {
+        return Ok(password);
+    }
+    if let Ok(path) = env::var("KEYSTORE_PASSWORD_FILE") {
+        let password = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read KEYSTORE_PASSWORD_FILE {}", path))?;
+        return Ok(password.trim_end_matches(['\r', '\n']).to_string());
+    }
+    if let Ok(cmd) = env::var("KEYSTORE_PASSWORD_CMD") {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&cmd)
+            .output()
+            .context("failed to run KEYSTORE_PASSWORD_CMD")?;
+        if !output.status.success() {
+            return Err(anyhow!("KEYSTORE_PASSWORD_CMD exited with {}", output.status));
+        }
+        let password = String::from_utf8(output.stdout)
+            .context("KEYSTORE_PASSWORD_CMD printed invalid UTF-8")?;
+        return Ok(password.trim_end_matches(['\r', '\n']).to_string());
+    }
+    rpassword::prompt_password("Keystore password: ").context("failed to read keystore password")
+}
+
+/// The hex private key held in an encrypted JSON keystore.
+pub fn decrypt(path: &Path, password: &str) -> Result<String> {
+    let wallet = LocalWallet::decrypt_keystore(path, password)
+        .with_context(|| format!("failed to decrypt keystore {}", path.display()))?;
+    Ok(format!("0x{}", ethers::utils::hex::encode(wallet.signer().to_bytes())))
+}
@@ -0,0 +1,214 @@
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, U256};
+use serde::Serialize;
+use tokio::sync::oneshot;
+use tracing::warn;
+
+use crate::app::AppConfig;
+use crate::curve::CurveTracker;
+use crate::engine::ExecutionClient;
+use crate::entry::{self, EntryRequest};
+use crate::execstats::Side;
+use crate::exit_guard;
+use crate::impact;
+use crate::safety;
+use crate::trading::ensure_entry_allowed;
+use crate::warmer;
+
+/// A trade to run through the decision pipeline without sending it.
+pub struct SimulationRequest {
+    pub token: Address,
+    pub side: Side,
+    /// MON for a buy, tokens for a sell; a buy defaults to the token's size.
+    pub amount: Option<U256>,
+    pub reply: oneshot::Sender<Result<Simulation>>,
+}
+
+/// One step of the pipeline and how it went.
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// What the bot would make of a trade right now.
+#[derive(Debug, Default, Serialize)]
+pub struct Simulation {
+    pub token: Address,
+    pub side: Option<Side>,
+    pub amount_in: U256,
+    pub router: Option<Address>,
+    pub quoted_out: Option<U256>,
+    pub amount_out_min: Option<U256>,
+    /// Price impact of the buy on the bonding curve.
+    pub impact_bps: Option<u64>,
+    pub gas: Option<U256>,
+    pub gas_cost: Option<U256>,
+    /// What selling the bought tokens straight back would return.
+    pub immediate_exit: Option<U256>,
+    pub checks: Vec<Check>,
+    pub would_trade: bool,
+    /// The failed checks' details.
+    pub reasons: Vec<String>,
+}
+
+impl Simulation {
+    fn check(&mut self, name: &'static str, result: Result<()>) {
+        let detail = result.as_ref().err().map(|err| format!("{:#}", err));
+        if let Some(detail) = &detail {
+            self.reasons.push(format!("{}: {}", name, detail));
+        }
+        self.checks.push(Check {
+            name,
+            passed: detail.is_none(),
+            detail,
+        });
+    }
+}
+
+/// Answers the control API's `/simulate` requests until the process exits.
+pub async fn serve(cfg: &AppConfig, client: &impl ExecutionClient) {
+    let mut requests = cfg.controls.simulations().await;
+    while let Some(request) = requests.recv().await {
+        let simulation = run(cfg, client, request.token, request.side, request.amount).await;
+        if request.reply.send(simulation).is_err() {
+            warn!("Simulation of {:?} finished after its caller left", request.token);
+        }
+    }
+}
+
+/// Runs a trade through the same steps as a live one, stopping short of the send.
+pub async fn run(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    token: Address,
+    side: Side,
+    amount: Option<U256>,
+) -> Result<Simulation> {
+    match side {
+        Side::Buy => buy(cfg, client, token, amount).await,
+        Side::Sell => sell(client, token, amount).await,
+    }
+}
+
+async fn buy(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    token: Address,
+    amount: Option<U256>,
+) -> Result<Simulation> {
+    let params = cfg.params_for(token);
+    let amount_in = match amount {
+        Some(amount) => amount,
+        None if params.sizing.needs_balance() => {
+            let balance = client.native_balance(client.wallet(), None).await?;
+            let fixed = cfg.controls.amount_in(params.amount_in);
+            params.sizing.size(fixed, balance, params.stop_loss_pct, cfg.gas_reserve)
+        }
+        None => cfg.controls.amount_in(params.amount_in),
+    };
+    let mut sim = Simulation {
+        token,
+        side: Some(Side::Buy),
+        amount_in,
+        ..Simulation::default()
+    };
+    let recipient = cfg.recipient.unwrap_or_else(|| client.wallet());
+
+    sim.check("entry_allowed", ensure_entry_allowed(cfg, client.wallet(), token));
+    let risk = match &cfg.risk {
+        Some(risk) => match risk.would_refuse(cfg, recipient, token, amount_in) {
+            Ok(Some(reason)) => Err(anyhow!(reason)),
+            Ok(None) => Ok(()),
+            Err(err) => Err(err),
+        },
+        None => Ok(()),
+    };
+    sim.check("risk_limits", risk);
+
+    let request = EntryRequest {
+        token,
+        amount_in,
+        recipient,
+        deadline: cfg.deadline_u256(),
+        slippage_bps: cfg.controls.slippage_bps(params.slippage_bps),
+    };
+    let entry = match entry::first_allowed_entry(client, &request, 0).await {
+        Ok(entry) => entry,
+        Err(err) => {
+            sim.check("quote", Err(err));
+            return Ok(sim);
+        }
+    };
+    sim.check("quote", Ok(()));
+    sim.router = Some(entry.router);
+    sim.quoted_out = Some(entry.quoted_out);
+    sim.amount_out_min = Some(entry.amount_out_min);
+    sim.gas = Some(entry.buy_gas);
+    sim.gas_cost = Some(entry.buy_gas * client.gas_price().await?);
+
+    let curve = cfg
+        .bonding_curve
+        .map(|address| CurveTracker::new(&cfg.rpc_url, address))
+        .transpose()?;
+    if let (Some(impact), Some(curve)) = (&cfg.impact, curve.as_ref()) {
+        let state = match warmer::curve_state(token) {
+            Some(state) => Ok(state),
+            None => curve.state(token).await,
+        };
+        let checked = state.and_then(|state| {
+            sim.impact_bps = Some(impact::buy_impact_bps(&state, amount_in));
+            impact.buy_min_out(token, &state, amount_in, entry.quoted_out)
+        });
+        if let Ok(Some(min_out)) = &checked {
+            sim.amount_out_min = Some(*min_out);
+        }
+        sim.check("impact", checked.map(|_| ()));
+    }
+
+    let quoted_out = entry.quoted_out;
+    let safe = safety::check(&cfg.safety, client, curve.as_ref(), token, amount_in, quoted_out);
+    sim.check("safety", safe.await);
+    match exit_guard::simulate_exit(client, token, entry.quoted_out).await {
+        Ok(exit) => sim.immediate_exit = Some(exit),
+        Err(err) => warn!("Simulated exit of {:?} failed: {:#}", token, err),
+    }
+
+    sim.would_trade = sim.reasons.is_empty();
+    Ok(sim)
+}
+
+async fn sell(
+    client: &impl ExecutionClient,
+    token: Address,
+    amount: Option<U256>,
+) -> Result<Simulation> {
+    let amount_in = match amount {
+        Some(amount) => amount,
+        None => client.token_balance(token, client.wallet()).await?,
+    };
+    let mut sim = Simulation {
+        token,
+        side: Some(Side::Sell),
+        amount_in,
+        ..Simulation::default()
+    };
+    if amount_in.is_zero() {
+        sim.check("quote", Err(anyhow!("nothing to sell")));
+        return Ok(sim);
+    }
+    match client.quote(token, amount_in, false).await {
+        Ok((router, quoted_out)) => {
+            sim.check("quote", Ok(()));
+            sim.router = Some(router);
+            sim.quoted_out = Some(quoted_out);
+            // Exits take whatever the pool gives rather than risk being stuck.
+            sim.amount_out_min = Some(U256::zero());
+        }
+        Err(err) => sim.check("quote", Err(err)),
+    }
+    sim.would_trade = sim.reasons.is_empty();
+    Ok(sim)
+}
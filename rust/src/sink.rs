@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use serde_json::json;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::chain;
+use crate::execstats::{ExecRecord, Side};
+use crate::ledger::TradeRecord;
+use crate::state;
+
+/// Tables the sink writes, created on start when missing.
+const TABLES: [(&str, &str); 3] = [
+    (
+        "trades",
+        "token String, wallet String, buy_tx Nullable(String), opened_at UInt64, \
+         closed_at UInt64, amount_in UInt256, proceeds UInt256, gas_spent UInt256, \
+         pnl Int256, exploration Nullable(String), chain_id UInt64",
+    ),
+    (
+        "executions",
+        "token String, side LowCardinality(String), at UInt64, quoted UInt256, \
+         received UInt256, reverted Bool, inclusion_ms UInt64, chain_id UInt64",
+    ),
+    ("events", "event LowCardinality(String), at UInt64, details String, chain_id UInt64"),
+];
+
+/// Batches of rows kept per table while ClickHouse is unreachable; past that the oldest
+/// rows are dropped.
+const MAX_BUFFERED_BATCHES: usize = 10;
+
+static SINK: OnceLock<mpsc::Sender<Message>> = OnceLock::new();
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Mirrors closed round trips, executions and audit events into ClickHouse for fleets
+/// that outgrow the local JSONL files, which stay the record the bot itself reads.
+/// Rows are batched and inserted over ClickHouse's HTTP interface in the background, so
+/// a slow or unreachable database never holds up a trade.
+#[derive(Debug, Clone)]
+pub struct SinkConfig {
+    url: String,
+    database: String,
+    user: Option<String>,
+    password: Option<String>,
+    batch_size: usize,
+    flush_interval: Duration,
+}
+
+impl SinkConfig {
+    /// Enabled by `CLICKHOUSE_URL`; `CLICKHOUSE_DATABASE`, `CLICKHOUSE_USER`,
+    /// `CLICKHOUSE_PASSWORD`, `SINK_BATCH_SIZE` and `SINK_FLUSH_SECS` tune it.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(url) = env::var("CLICKHOUSE_URL").ok().filter(|v| !v.is_empty()) else {
+            return Ok(None);
+        };
+        let batch_size: usize = env::var("SINK_BATCH_SIZE")
+            .ok()
+            .map(|v| v.parse().context("invalid SINK_BATCH_SIZE"))
+            .transpose()?
+            .unwrap_or(1_000);
+        let flush_secs: u64 = env::var("SINK_FLUSH_SECS")
+            .ok()
+            .map(|v| v.parse().context("invalid SINK_FLUSH_SECS"))
+            .transpose()?
+            .unwrap_or(5);
+        Ok(Some(Self {
+            url: url.trim_end_matches('/').to_string(),
+            database: env::var("CLICKHOUSE_DATABASE").unwrap_or_else(|_| "default".into()),
+            user: env::var("CLICKHOUSE_USER").ok(),
+            password: env::var("CLICKHOUSE_PASSWORD").ok(),
+            batch_size: batch_size.max(1),
+            flush_interval: Duration::from_secs(flush_secs.max(1)),
+        }))
+    }
+
+    async fn execute(&self, client: &reqwest::Client, query: &str, body: String) -> Result<()> {
+        let mut request = client.post(&self.url).query(&[("query", query)]).body(body);
+        if let Some(user) = &self.user {
+            request = request.header("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.password {
+            request = request.header("X-ClickHouse-Key", password);
+        }
+        let response = request.send().await.context("ClickHouse unreachable")?;
+        if let Err(err) = response.error_for_status_ref() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(err).context(body.trim().to_string());
+        }
+        Ok(())
+    }
+}
+
+enum Message {
+    Row { table: &'static str, json: String },
+    Flush(oneshot::Sender<()>),
+}
+
+/// Rows waiting to be inserted, per table.
+struct Batches {
+    config: SinkConfig,
+    client: reqwest::Client,
+    pending: BTreeMap<&'static str, Vec<String>>,
+}
+
+impl Batches {
+    fn new(config: SinkConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            pending: BTreeMap::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.pending.values().map(Vec::len).sum()
+    }
+
+    fn push(&mut self, table: &'static str, json: String) {
+        let rows = self.pending.entry(table).or_default();
+        rows.push(json);
+        let max = self.config.batch_size * MAX_BUFFERED_BATCHES;
+        if rows.len() > max {
+            let excess = rows.len() - max;
+            rows.drain(..excess);
+            DROPPED.fetch_add(excess as u64, Ordering::Relaxed);
+        }
+    }
+
+    async fn create_tables(&self) -> Result<()> {
+        for (table, columns) in TABLES {
+            let query = format!(
+                "CREATE TABLE IF NOT EXISTS {}.{} ({}) ENGINE = MergeTree ORDER BY tuple()",
+                self.config.database, table, columns
+            );
+            self.config.execute(&self.client, &query, String::new()).await?;
+        }
+        Ok(())
+    }
+
+    /// Inserts every table's pending rows, keeping those of a failed insert for the next
+    /// flush. Returns whether every insert went through.
+    async fn flush(&mut self) -> bool {
+        let mut healthy = true;
+        for (table, rows) in self.pending.iter_mut().filter(|(_, rows)| !rows.is_empty()) {
+            let query = format!(
+                "INSERT INTO {}.{} FORMAT JSONEachRow",
+                self.config.database, table
+            );
+            match self.config.execute(&self.client, &query, rows.join("\n")).await {
+                Ok(()) => rows.clear(),
+                Err(err) => {
+                    warn!("{} rows for {} kept for later: {:#}", rows.len(), table, err);
+                    healthy = false;
+                }
+            }
+        }
+        let dropped = DROPPED.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!("ClickHouse sink dropped {} rows it could not keep up with", dropped);
+        }
+        healthy
+    }
+}
+
+/// Starts the background writer; rows recorded before this are not mirrored.
+pub fn start(config: &SinkConfig) {
+    let (sender, mut messages) = mpsc::channel(config.batch_size * 4);
+    if SINK.set(sender).is_err() {
+        return;
+    }
+    let mut batches = Batches::new(config.clone());
+    tokio::spawn(async move {
+        match batches.create_tables().await {
+            Ok(()) => info!("Mirroring trades to ClickHouse at {}", batches.config.url),
+            Err(err) => warn!("ClickHouse tables not created: {:#}", err),
+        }
+        let mut ticker = tokio::time::interval(batches.config.flush_interval);
+        // While inserts fail, only the ticker retries, rather than every new row.
+        let mut healthy = true;
+        loop {
+            tokio::select! {
+                message = messages.recv() => match message {
+                    Some(Message::Row { table, json }) => {
+                        batches.push(table, json);
+                        if healthy && batches.len() >= batches.config.batch_size {
+                            healthy = batches.flush().await;
+                        }
+                    }
+                    Some(Message::Flush(done)) => {
+                        healthy = batches.flush().await;
+                        let _ = done.send(());
+                    }
+                    None => {
+                        batches.flush().await;
+                        return;
+                    }
+                },
+                _ = ticker.tick() => healthy = batches.flush().await,
+            }
+        }
+    });
+}
+
+/// Inserts whatever is pending, e.g. before the process exits.
+pub async fn flush() {
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+    let (done, flushed) = oneshot::channel();
+    if sink.send(Message::Flush(done)).await.is_ok() {
+        let _ = flushed.await;
+    }
+}
+
+pub fn trade(record: &TradeRecord) {
+    send("trades", trade_row(record));
+}
+
+pub fn execution(record: &ExecRecord) {
+    send("executions", execution_row(record));
+}
+
+pub fn event(event: &str, details: &serde_json::Value) {
+    let row = json!({
+        "event": event,
+        "at": state::unix_now(),
+        "details": details.to_string(),
+        "chain_id": chain::profile().chain_id,
+    });
+    send("events", row);
+}
+
+fn send(table: &'static str, row: serde_json::Value) {
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+    let message = Message::Row {
+        table,
+        json: row.to_string(),
+    };
+    if sink.try_send(message).is_err() {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Amounts go as decimal strings, which ClickHouse reads into its 256-bit columns.
+fn trade_row(record: &TradeRecord) -> serde_json::Value {
+    json!({
+        "token": format!("{:?}", record.token),
+        "wallet": format!("{:?}", record.wallet),
+        "buy_tx": record.buy_tx.map(|tx| format!("{:?}", tx)),
+        "opened_at": record.opened_at,
+        "closed_at": record.closed_at,
+        "amount_in": record.amount_in.to_string(),
+        "proceeds": record.proceeds.to_string(),
+        "gas_spent": record.gas_spent.to_string(),
+        "pnl": record.pnl().to_string(),
+        "exploration": record.exploration,
+        "chain_id": chain::profile().chain_id,
+    })
+}
+
+fn execution_row(record: &ExecRecord) -> serde_json::Value {
+    json!({
+        "token": format!("{:?}", record.token),
+        "side": match record.side {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        },
+        "at": record.at,
+        "quoted": record.quoted.to_string(),
+        "received": record.received.to_string(),
+        "reverted": record.reverted,
+        "inclusion_ms": record.inclusion_ms,
+        "chain_id": chain::profile().chain_id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::{Query, State};
+    use ethers::types::{Address, U256};
+
+    use super::*;
+
+    type Received = Arc<Mutex<Vec<(String, String)>>>;
+
+    async fn receive(
+        State(received): State<Received>,
+        Query(params): Query<BTreeMap<String, String>>,
+        body: String,
+    ) {
+        received.lock().unwrap().push((params["query"].clone(), body));
+    }
+
+    fn config(url: String, batch_size: usize) -> SinkConfig {
+        SinkConfig {
+            url,
+            database: "bot".into(),
+            user: None,
+            password: None,
+            batch_size,
+            flush_interval: Duration::from_secs(5),
+        }
+    }
+
+    fn trade() -> TradeRecord {
+        TradeRecord {
+            token: Address::repeat_byte(2),
+            wallet: Address::repeat_byte(1),
+            buy_tx: None,
+            opened_at: 1_700_000_000,
+            closed_at: 1_700_000_060,
+            amount_in: U256::exp10(18),
+            proceeds: U256::exp10(18) * 2,
+            gas_spent: U256::from(5u64),
+            exploration: None,
+            strategy: None,
+        }
+    }
+
+    #[test]
+    fn trade_rows_carry_amounts_as_decimal_wei() {
+        let row = trade_row(&trade());
+        assert_eq!(row["amount_in"], "1000000000000000000");
+        assert_eq!(row["pnl"], "999999999999999995");
+        assert!(row["buy_tx"].is_null());
+    }
+
+    #[test]
+    fn buffered_rows_past_the_limit_drop_the_oldest() {
+        let mut batches = Batches::new(config("http://unused".into(), 1));
+        for n in 0..MAX_BUFFERED_BATCHES + 3 {
+            batches.push("events", n.to_string());
+        }
+        let rows = &batches.pending["events"];
+        assert_eq!(rows.len(), MAX_BUFFERED_BATCHES);
+        assert_eq!(rows[0], "3");
+    }
+
+    #[tokio::test]
+    async fn flush_inserts_each_tables_rows_in_one_request() {
+        let received = Received::default();
+        let app = axum::Router::new()
+            .route("/", axum::routing::post(receive))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut batches = Batches::new(config(url, 100));
+        batches.push("trades", trade_row(&trade()).to_string());
+        batches.push("trades", trade_row(&trade()).to_string());
+        batches.push("events", "{}".into());
+        batches.flush().await;
+        assert_eq!(batches.len(), 0);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].0, "INSERT INTO bot.events FORMAT JSONEachRow");
+        assert_eq!(received[1].0, "INSERT INTO bot.trades FORMAT JSONEachRow");
+        assert_eq!(received[1].1.lines().count(), 2);
+    }
+}
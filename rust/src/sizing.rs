@@ -0,0 +1,139 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::U256;
+
+use crate::chain;
+
+/// How a buy is sized at trade time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sizing {
+    /// `amount_in_mon` as configured.
+    Fixed,
+    /// A share of the wallet's MON balance, in percent.
+    BalancePct(f64),
+    /// Sized so that hitting the stop loss loses about this much MON.
+    Risk(U256),
+}
+
+impl Sizing {
+    pub fn needs_balance(&self) -> bool {
+        !matches!(self, Self::Fixed)
+    }
+
+    /// The buy size given the configured `fixed` amount, the wallet `balance` and the
+    /// stop loss. Never more than the balance less `gas_reserve`, which is kept back so
+    /// the buy, its approval and the sells can still pay for gas.
+    pub fn size(
+        &self,
+        fixed: U256,
+        balance: U256,
+        stop_loss_pct: Option<f64>,
+        gas_reserve: U256,
+    ) -> U256 {
+        let sized = match *self {
+            Self::Fixed => return fixed,
+            Self::BalancePct(pct) => balance * ppm(pct) / U256::from(PPM_PER_PCT * 100),
+            Self::Risk(risk) => match stop_loss_pct.map(ppm) {
+                // A stop too tight to register would size the buy past any balance.
+                Some(stop) if stop.is_zero() => balance,
+                Some(stop) => risk * U256::from(PPM_PER_PCT * 100) / stop,
+                None => fixed,
+            },
+        };
+        sized.min(balance.saturating_sub(gas_reserve))
+    }
+}
+
+const PPM_PER_PCT: u64 = 10_000;
+
+/// `pct` in parts per million.
+fn ppm(pct: f64) -> U256 {
+    U256::from((pct * PPM_PER_PCT as f64).round() as u64)
+}
+
+impl FromStr for Sizing {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, arg) = match s.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg.trim())),
+            None => (s, None),
+        };
+
+        match (kind.trim().to_ascii_lowercase().as_str(), arg) {
+            ("fixed", None) => Ok(Self::Fixed),
+            ("balance", Some(pct)) => {
+                let pct: f64 = pct.parse().context("invalid balance percent")?;
+                if pct <= 0.0 || pct > 100.0 {
+                    return Err(anyhow!("balance percent must be between 0 and 100"));
+                }
+                Ok(Self::BalancePct(pct))
+            }
+            ("risk", Some(mon)) => {
+                let risk = chain::profile().parse_native(mon).context("invalid risk amount")?;
+                if risk.is_zero() {
+                    return Err(anyhow!("risk amount must be positive"));
+                }
+                Ok(Self::Risk(risk))
+            }
+            _ => Err(anyhow!(
+                "unknown sizing `{s}` (expected fixed, balance:PCT or risk:MON)"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mon(amount: u64) -> U256 {
+        U256::exp10(18) * amount
+    }
+
+    #[test]
+    fn fixed_ignores_the_balance() {
+        assert_eq!(Sizing::Fixed.size(mon(3), mon(1), Some(10.0), U256::zero()), mon(3));
+    }
+
+    #[test]
+    fn balance_share_keeps_fractional_percents() {
+        let sizing = Sizing::BalancePct(12.5);
+        assert_eq!(sizing.size(mon(1), mon(80), None, U256::zero()), mon(10));
+        let sizing = Sizing::BalancePct(0.001);
+        assert_eq!(sizing.size(mon(1), mon(1000), None, U256::zero()), U256::exp10(16));
+    }
+
+    #[test]
+    fn risk_sizes_from_the_stop_loss_up_to_the_balance() {
+        let sizing = Sizing::Risk(mon(1));
+        assert_eq!(sizing.size(mon(1), mon(100), Some(20.0), U256::zero()), mon(5));
+        assert_eq!(sizing.size(mon(1), mon(100), Some(0.5), U256::zero()), mon(100));
+        assert_eq!(sizing.size(mon(1), mon(1000), Some(0.5), U256::zero()), mon(200));
+        assert_eq!(sizing.size(mon(1), mon(100), Some(0.000_01), U256::zero()), mon(100));
+        assert_eq!(sizing.size(mon(2), mon(100), None, U256::zero()), mon(2));
+    }
+
+    #[test]
+    fn the_gas_reserve_is_never_spent() {
+        let reserve = U256::exp10(17);
+        let sizing = Sizing::Risk(mon(1));
+        assert_eq!(sizing.size(mon(1), mon(100), Some(0.5), reserve), mon(100) - reserve);
+        assert_eq!(sizing.size(mon(1), mon(100), Some(20.0), reserve), mon(5));
+        let sizing = Sizing::BalancePct(100.0);
+        assert_eq!(sizing.size(mon(1), mon(2), None, reserve), mon(2) - reserve);
+        assert!(sizing.size(mon(1), reserve / 2, None, reserve).is_zero());
+    }
+
+    #[test]
+    fn parses_each_kind() {
+        assert_eq!("fixed".parse::<Sizing>().unwrap(), Sizing::Fixed);
+        assert_eq!("balance: 25".parse::<Sizing>().unwrap(), Sizing::BalancePct(25.0));
+        assert_eq!("Risk:0.5".parse::<Sizing>().unwrap(), Sizing::Risk(U256::exp10(17) * 5));
+        assert!("balance:0".parse::<Sizing>().is_err());
+        assert!("balance:101".parse::<Sizing>().is_err());
+        assert!("risk:0".parse::<Sizing>().is_err());
+        assert!("kelly".parse::<Sizing>().is_err());
+    }
+}
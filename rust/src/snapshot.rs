@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use ethers::contract::abigen;
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, U256};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::curve::BondingCurve;
+
+abigen!(
+    SnapshotToken,
+    r#"[
+        function balanceOf(address owner) external view returns (uint256)
+    ]"#
+);
+
+/// On-chain facts about a held token at one point in time; `None` where unavailable.
+#[derive(Debug, Clone, Default)]
+pub struct TokenSnapshot {
+    pub real_mon_reserve: Option<U256>,
+    pub creator_balance: Option<U256>,
+}
+
+/// How far each fact fell since the previous snapshot, in percent.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SnapshotDiff {
+    pub reserve_drop_pct: Option<f64>,
+    pub creator_drop_pct: Option<f64>,
+}
+
+/// Snapshots a held token every `interval` and diffs each snapshot against the last.
+pub struct SnapshotWatch {
+    token: SnapshotToken<Provider<Http>>,
+    curve: Option<BondingCurve<Provider<Http>>>,
+    creator: Option<Address>,
+    interval: Duration,
+    last: Option<(TokenSnapshot, Instant)>,
+}
+
+impl SnapshotWatch {
+    pub fn new(
+        rpc_url: &str,
+        token: Address,
+        curve: Option<Address>,
+        creator: Option<Address>,
+        interval: Duration,
+    ) -> Result<Self> {
+        let provider = Arc::new(Provider::<Http>::try_from(rpc_url).context("invalid RPC_URL")?);
+        Ok(Self {
+            token: SnapshotToken::new(token, provider.clone()),
+            curve: curve.map(|address| BondingCurve::new(address, provider)),
+            creator,
+            interval,
+            last: None,
+        })
+    }
+
+    /// Takes a snapshot if `interval` has passed since the last one and returns the
+    /// diff; between snapshots the diff is empty.
+    pub async fn poll(&mut self) -> SnapshotDiff {
+        if let Some((_, at)) = &self.last {
+            if at.elapsed() < self.interval {
+                return SnapshotDiff::default();
+            }
+        }
+        let snapshot = self.take().await;
+        let diff = match &self.last {
+            Some((previous, _)) => SnapshotDiff {
+                reserve_drop_pct: drop_pct(previous.real_mon_reserve, snapshot.real_mon_reserve),
+                creator_drop_pct: drop_pct(previous.creator_balance, snapshot.creator_balance),
+            },
+            None => SnapshotDiff::default(),
+        };
+        let reserve_drop = diff.reserve_drop_pct.unwrap_or(0.0);
+        let creator_drop = diff.creator_drop_pct.unwrap_or(0.0);
+        if reserve_drop > 0.0 || creator_drop > 0.0 {
+            info!(
+                "Token snapshot: curve reserve down {:.1}%, creator balance down {:.1}%",
+                reserve_drop, creator_drop
+            );
+        }
+        self.last = Some((snapshot, Instant::now()));
+        diff
+    }
+
+    async fn take(&self) -> TokenSnapshot {
+        let real_mon_reserve = match &self.curve {
+            Some(curve) => match curve.curves(self.token.address()).call().await {
+                Ok((real_mon_reserve, ..)) => Some(real_mon_reserve),
+                Err(err) => {
+                    warn!("Curve reserve snapshot failed: {:#}", err);
+                    None
+                }
+            },
+            None => None,
+        };
+        let creator_balance = match self.creator {
+            Some(creator) => match self.token.balance_of(creator).call().await {
+                Ok(balance) => Some(balance),
+                Err(err) => {
+                    warn!("Creator balance snapshot failed: {:#}", err);
+                    None
+                }
+            },
+            None => None,
+        };
+        TokenSnapshot {
+            real_mon_reserve,
+            creator_balance,
+        }
+    }
+}
+
+fn drop_pct(before: Option<U256>, after: Option<U256>) -> Option<f64> {
+    let (before, after) = (before?, after?);
+    if before.is_zero() {
+        return None;
+    }
+    Some(before.saturating_sub(after).as_u128() as f64 / before.as_u128() as f64 * 100.0)
+}
@@ -0,0 +1,578 @@
+use std::collections::HashMap;
+use std::env;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::contract::{parse_log, EthEvent};
+use ethers::providers::{Http, Middleware, Provider, Ws};
+use ethers::types::{Address, Filter, U256};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use regex::Regex;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+use tracing::{info, info_span, warn, Instrument, Span};
+
+use crate::caches;
+use crate::calendar::{self, LaunchCalendar};
+use crate::chain;
+use crate::curve::{CurveCreateFilter, CurveTracker};
+use crate::edge::EdgeConfig;
+use crate::engine::{ExecutionClient, RpcClient};
+use crate::explore::ExploreConfig;
+use crate::latency;
+use crate::notify::Event;
+use crate::race::{self, RaceConfig, Racer};
+use crate::reputation::{self, ReputationConfig};
+use crate::scoring::ScoringConfig;
+use crate::signals::SignalKind;
+use crate::state;
+use crate::tuning::{FilterTuning, Threshold};
+use crate::utilization::Utilization;
+use crate::app::AppConfig;
+use crate::trading::{round_trip, EntryHints};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+pub struct SniperConfig {
+    pub ws_url: Option<String>,
+    pub creators: Vec<Address>,
+    pub name_regex: Option<Regex>,
+    pub symbol_regex: Option<Regex>,
+    pub min_initial_liquidity: Option<U256>,
+    pub max_concurrent: usize,
+    /// Blocks after the launch block to hold the buy back, out of the launch-block bot war.
+    pub entry_delay_blocks: u64,
+    /// Drop the liquidity filter instead of skipping a launch when curve state can't be read.
+    pub degraded_filters: bool,
+    /// Race the buys of launches that pass the filters; see `race`.
+    pub race: Option<RaceConfig>,
+    /// Skip launches by creators with a record of rugs; see `reputation`.
+    pub reputation: Option<ReputationConfig>,
+    /// Skip launches without enough expected edge after fees; see `edge`. Required.
+    pub edge: Option<EdgeConfig>,
+    /// Skip launches that score too low; see `scoring`.
+    pub scoring: Option<ScoringConfig>,
+    /// Move the liquidity and score thresholds with realized outcomes; see `tuning`.
+    pub tuning: Option<FilterTuning>,
+    /// Announced launches to get ready for; see `calendar`.
+    pub calendar: Option<LaunchCalendar>,
+}
+
+impl SniperConfig {
+    pub fn from_env() -> Result<Self> {
+        let creators = env::var("SNIPER_CREATORS")
+            .ok()
+            .map(|list| {
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| v.parse().context("invalid address in SNIPER_CREATORS"))
+                    .collect::<Result<Vec<Address>>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        let name_regex = env::var("SNIPER_NAME_REGEX")
+            .ok()
+            .map(|v| Regex::new(&v).context("invalid SNIPER_NAME_REGEX"))
+            .transpose()?;
+        let symbol_regex = env::var("SNIPER_SYMBOL_REGEX")
+            .ok()
+            .map(|v| Regex::new(&v).context("invalid SNIPER_SYMBOL_REGEX"))
+            .transpose()?;
+
+        let min_initial_liquidity = env::var("SNIPER_MIN_LIQUIDITY_MON")
+            .ok()
+            .map(|v| chain::profile().parse_native(&v).context("invalid SNIPER_MIN_LIQUIDITY_MON"))
+            .transpose()?;
+
+        let max_concurrent = env::var("MAX_CONCURRENT_SNIPES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1);
+
+        let scoring = ScoringConfig::from_env()?;
+        let tuning =
+            FilterTuning::from_env(min_initial_liquidity, scoring.as_ref().map(|s| s.min_score))?;
+        Ok(Self {
+            ws_url: env::var("WS_URL").ok(),
+            creators,
+            name_regex,
+            symbol_regex,
+            min_initial_liquidity,
+            max_concurrent,
+            entry_delay_blocks: env::var("SNIPE_ENTRY_DELAY_BLOCKS")
+                .ok()
+                .map(|v| v.parse().context("invalid SNIPE_ENTRY_DELAY_BLOCKS"))
+                .transpose()?
+                .unwrap_or(0),
+            degraded_filters: env::var("SNIPER_DEGRADED_FILTERS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(false),
+            race: RaceConfig::from_env()?,
+            reputation: ReputationConfig::from_env()?,
+            edge: EdgeConfig::from_env()?,
+            scoring,
+            tuning,
+            calendar: LaunchCalendar::from_env()?,
+        })
+    }
+
+    /// The creator, name and symbol filters, which need nothing but the launch event.
+    pub fn screen_metadata(&self, launch: &Launch) -> Option<String> {
+        if !self.creators.is_empty() && !self.creators.contains(&launch.creator) {
+            return Some(format!("creator {:?} not in SNIPER_CREATORS", launch.creator));
+        }
+        if let Some(regex) = &self.name_regex {
+            if !regex.is_match(&launch.name) {
+                return Some(format!("name {:?} does not match", launch.name));
+            }
+        }
+        if let Some(regex) = &self.symbol_regex {
+            if !regex.is_match(&launch.symbol) {
+                return Some(format!("symbol {:?} does not match", launch.symbol));
+            }
+        }
+        None
+    }
+
+    /// The calendar entry `launch` was announced as, if it is inside its window.
+    fn expected(&self, launch: &Launch) -> Option<&str> {
+        let calendar = self.calendar.as_ref()?;
+        calendar.expected(launch, state::unix_now())
+    }
+
+    /// Runs the launch through the filters. Near misses are only reported with `explore`.
+    /// Launches the calendar announced skip the metadata filters.
+    async fn screen(
+        &self,
+        launch: &Launch,
+        curve: &CurveTracker,
+        explore: Option<&ExploreConfig>,
+    ) -> Screening {
+        if let Some(label) = self.expected(launch) {
+            info!("{:?} is the expected launch {}", launch.token, label);
+        } else if let Some(reason) = self.screen_metadata(launch) {
+            return Screening::Reject(reason);
+        }
+        if let Some(min) = self.min_initial_liquidity {
+            let min = self.tuning.as_ref().map_or(min, |tuning| tuning.min_liquidity(min));
+            match curve.state(launch.token).await {
+                Ok(state) if state.real_mon_reserve < min => {
+                    let reason = format!(
+                        "initial liquidity {} below minimum",
+                        chain::profile().format_native(state.real_mon_reserve)
+                    );
+                    if explore.is_some_and(|e| state.real_mon_reserve >= e.lower_bound(min)) {
+                        return Screening::NearMiss {
+                            filter: "min_liquidity",
+                            reason,
+                        };
+                    }
+                    return Screening::Reject(reason);
+                }
+                Ok(_) => {}
+                Err(err) if self.degraded_filters => {
+                    warn!(
+                        "Curve state unavailable for {:?}, sniping on reduced filters: {:#}",
+                        launch.token, err
+                    );
+                }
+                Err(err) => {
+                    return Screening::Reject(format!("curve state unavailable: {:#}", err))
+                }
+            }
+        }
+        Screening::Pass
+    }
+}
+
+enum Screening {
+    Pass,
+    Reject(String),
+    /// Failed only `filter`, by less than the exploration margin.
+    NearMiss { filter: &'static str, reason: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct Launch {
+    pub token: Address,
+    pub creator: Address,
+    pub name: String,
+    pub symbol: String,
+    pub block: Option<u64>,
+}
+
+/// Listens for new bonding curves and runs the buy/hold/sell round trip for every launch
+/// that passes the configured filters.
+pub async fn run(
+    cfg: &AppConfig,
+    sniper: &SniperConfig,
+    client: &RpcClient,
+) -> Result<()> {
+    let provider = client.provider();
+    let ws_url = sniper
+        .ws_url
+        .clone()
+        .ok_or_else(|| anyhow!("WS_URL is required for sniper mode"))?;
+    let curve_address = cfg
+        .bonding_curve
+        .ok_or_else(|| anyhow!("BONDING_CURVE_ADDRESS is required for sniper mode"))?;
+    if sniper.edge.is_none() {
+        return Err(anyhow!(
+            "SNIPER_MIN_EDGE_PCT is required for sniper mode: the expected return after fees \
+             a launch needs before it is sniped, e.g. 5"
+        ));
+    }
+    let curve = CurveTracker::new(&cfg.rpc_url, curve_address)?;
+
+    let (launch_tx, mut launches) = mpsc::unbounded_channel();
+    tokio::spawn(listen(ws_url, curve_address, launch_tx));
+
+    let racer = match &sniper.race {
+        Some(race) => Some(Racer::start(cfg, race).await?),
+        None => None,
+    };
+    if let Some(reputation) = &sniper.reputation {
+        reputation::start(&cfg.rpc_url, curve_address, reputation)?;
+    }
+    // Launches only repeat when the listener reconnects and replays recent blocks, so
+    // dedup entries expire after the idle TTL instead of piling up.
+    let mut seen: HashMap<Address, Instant> = HashMap::new();
+    let mut prune = tokio::time::interval(cfg.retention.prune_interval);
+    let mut screens = FuturesUnordered::new();
+    let mut in_flight = FuturesUnordered::new();
+    let mut utilization = Utilization::new(
+        "sniper",
+        cfg.defaults.amount_in * U256::from(sniper.max_concurrent),
+    );
+    let mut report = tokio::time::interval(cfg.utilization.report_interval);
+    report.tick().await;
+    let mut calendar = std::pin::pin!(calendar::prepare(
+        cfg,
+        client,
+        sniper.calendar.as_ref(),
+        sniper.max_concurrent
+    ));
+
+    loop {
+        if cfg.shutdown.requested() && in_flight.is_empty() {
+            return Ok(());
+        }
+        tokio::select! {
+            launch = launches.recv(), if !cfg.shutdown.requested() => {
+                let launch: Launch = launch.ok_or_else(|| anyhow!("launch listener stopped"))?;
+                if seen.insert(launch.token, Instant::now()).is_some() {
+                    continue;
+                }
+                info!(
+                    "Launch detected: {} ({}) {} by {:?}",
+                    launch.name,
+                    launch.symbol,
+                    chain::profile().address_url(launch.token),
+                    launch.creator
+                );
+
+                if cfg.controls.paused() {
+                    info!("Skipping {:?}: sniping is paused", launch.token);
+                    continue;
+                }
+                if cfg.blocklist.contains(&launch.token) {
+                    info!("Skipping {:?}: on the blocklist", launch.token);
+                    continue;
+                }
+                // Each launch is screened on its own, so a slow curve or reputation read
+                // holds up only that launch.
+                screens.push(screen_launch(cfg, sniper, provider, &curve, launch));
+            }
+            Some((launch, span, screening)) = screens.next(),
+                if !screens.is_empty() && !cfg.shutdown.requested() =>
+            {
+                let near_miss = match screening {
+                    Screening::Pass => None,
+                    Screening::Reject(reason) => {
+                        info!("Skipping {:?}: {}", launch.token, reason);
+                        continue;
+                    }
+                    Screening::NearMiss { filter, reason } => Some((filter, reason)),
+                };
+                if near_miss.is_none() {
+                    if let Some(signals) = &cfg.signals {
+                        signals.publish(launch.token, SignalKind::Opportunity);
+                    }
+                }
+                if in_flight.len() >= sniper.max_concurrent {
+                    info!(
+                        "Skipping {:?}: {} snipes already in flight",
+                        launch.token,
+                        in_flight.len()
+                    );
+                    continue;
+                }
+                let exploration = match (near_miss, &cfg.explore) {
+                    (Some((filter, reason)), Some(explore)) => {
+                        if !reserve_exploration(explore, launch.token, filter, &reason) {
+                            continue;
+                        }
+                        Some((filter, explore.amount_in))
+                    }
+                    _ => None,
+                };
+
+                cfg.notifier.send(
+                    Event::Snipe,
+                    format!(
+                        "Sniping {} ({}): {}",
+                        launch.name,
+                        launch.symbol,
+                        chain::profile().address_url(launch.token)
+                    ),
+                );
+                // Near misses are small and unhurried, so only full passes are raced.
+                let race = match (&sniper.race, &racer, exploration) {
+                    (Some(race), Some(racer), None) => Some((race, racer.as_ref())),
+                    _ => None,
+                };
+                in_flight.push(
+                    snipe(cfg, sniper, client, &curve, launch, exploration, race).instrument(span),
+                );
+            }
+            Some(()) = in_flight.next(), if !in_flight.is_empty() => {}
+            () = &mut calendar => {}
+            _ = cfg.shutdown.wait(), if !cfg.shutdown.requested() => {}
+            _ = report.tick() => {
+                utilization.observe(cfg.defaults.amount_in * U256::from(in_flight.len()), &cfg.utilization);
+            }
+            _ = prune.tick() => {
+                seen.retain(|_, at| at.elapsed() < cfg.retention.idle_ttl);
+                caches::observe("sniper_seen", seen.len());
+            }
+        }
+    }
+}
+
+/// Runs `launch` through the sniper's filters, the creator's reputation, the expected
+/// edge and then the scorer, inside the span that follows the launch from detection to
+/// the end of its trade.
+async fn screen_launch(
+    cfg: &AppConfig,
+    sniper: &SniperConfig,
+    provider: &Provider<Http>,
+    curve: &CurveTracker,
+    launch: Launch,
+) -> (Launch, Span, Screening) {
+    let span = info_span!("launch", token = ?launch.token, block = ?launch.block);
+    let started = Instant::now();
+    let screening = async {
+        let screening = sniper.screen(&launch, curve, cfg.explore.as_ref()).await;
+        if let (Some(reputation), Screening::Pass | Screening::NearMiss { .. }) =
+            (&sniper.reputation, &screening)
+        {
+            if let Some(reason) = reputation.screen(provider, &launch).await {
+                return Screening::Reject(reason);
+            }
+        }
+        if let (Some(edge), Some(reputation), Screening::Pass | Screening::NearMiss { .. }) =
+            (&sniper.edge, &sniper.reputation, &screening)
+        {
+            let amount_in = cfg.defaults.amount_in;
+            if let Some(reason) = edge.screen(provider, reputation, &launch, amount_in).await {
+                return Screening::Reject(reason);
+            }
+        }
+        if let (Some(scoring), Screening::Pass | Screening::NearMiss { .. }) =
+            (&sniper.scoring, &screening)
+        {
+            let reputation = sniper.reputation.as_ref();
+            let min_score = sniper.tuning.as_ref().map_or(scoring.min_score, |tuning| {
+                tuning.threshold(Threshold::MinScore, scoring.min_score)
+            });
+            if let Some(reason) =
+                scoring.screen(provider, curve, reputation, &launch, min_score).await
+            {
+                return Screening::Reject(reason);
+            }
+        }
+        screening
+    };
+    let screening = screening.instrument(info_span!(parent: &span, "detection")).await;
+    latency::record("filter", started.elapsed());
+    (launch, span, screening)
+}
+
+/// Takes an exploration trade out of the budget, logging why the launch is skipped if not.
+fn reserve_exploration(
+    explore: &ExploreConfig,
+    token: Address,
+    filter: &str,
+    reason: &str,
+) -> bool {
+    match explore.reserve() {
+        Ok(true) => {
+            info!("Exploring {:?} past the {} filter: {}", token, filter, reason);
+            true
+        }
+        Ok(false) => {
+            info!("Skipping {:?}: {}; exploration budget spent", token, reason);
+            false
+        }
+        Err(err) => {
+            warn!("Skipping {:?}: exploration budget unavailable: {:#}", token, err);
+            false
+        }
+    }
+}
+
+/// `exploration` is the filter the launch was let past and the exploration trade size.
+async fn snipe(
+    cfg: &AppConfig,
+    sniper: &SniperConfig,
+    client: &impl ExecutionClient,
+    curve: &CurveTracker,
+    launch: Launch,
+    exploration: Option<(&'static str, U256)>,
+    race: Option<(&RaceConfig, &Racer)>,
+) {
+    // The buy is quoted and checked again when it fires, so the delay prices it afresh.
+    // An announced launch is what the window was waited for, so it isn't held back.
+    if sniper.entry_delay_blocks > 0 && sniper.expected(&launch).is_none() {
+        if let Err(err) = wait_for_entry_block(client, &launch, sniper.entry_delay_blocks).await {
+            warn!("Delayed entry into {:?} abandoned: {:#}", launch.token, err);
+            return;
+        }
+    }
+    if let (Some(launch_block), Ok(current)) = (launch.block, client.block_number().await) {
+        info!(
+            "Firing buy for {:?} at block {} (launched in block {})",
+            launch.token, current, launch_block
+        );
+    }
+    if let Some((race, racer)) = race {
+        if let Err(err) = race::snipe(cfg, race, racer, client, curve, &launch).await {
+            warn!("Race for {:?} failed: {:#}", launch.token, err);
+            let message = format!("Race for {:?} failed: {:#}", launch.token, err);
+            cfg.notifier.send(Event::Error, message);
+        }
+        return;
+    }
+    let hints = EntryHints {
+        creator: Some(launch.creator),
+        amount_in: exploration.map(|(_, amount)| amount),
+        exploration: exploration.map(|(filter, _)| filter),
+        strategy: Some("sniper"),
+        ..EntryHints::default()
+    };
+    if let Err(err) = round_trip(cfg, &cfg.defaults, client, launch.token, hints).await {
+        warn!("Snipe of {:?} failed: {:#}", launch.token, err);
+        cfg.notifier.send(Event::Error, format!("Snipe of {:?} failed: {:#}", launch.token, err));
+    }
+}
+
+/// Waits until `delay` blocks after the launch block, or after the current block if the
+/// launch log carried none.
+async fn wait_for_entry_block(
+    client: &impl ExecutionClient,
+    launch: &Launch,
+    delay: u64,
+) -> Result<()> {
+    let launched = match launch.block {
+        Some(block) => block,
+        None => client.block_number().await?,
+    };
+    info!("Holding the buy of {:?} until block {}", launch.token, launched + delay);
+    while client.block_number().await? < launched + delay {
+        tokio::time::sleep(chain::profile().block_poll_interval()).await;
+    }
+    Ok(())
+}
+
+/// Subscribes to `CurveCreate` logs, reconnecting whenever the socket drops.
+async fn listen(ws_url: String, curve_address: Address, launches: mpsc::UnboundedSender<Launch>) {
+    let filter = Filter::new()
+        .address(curve_address)
+        .event(&CurveCreateFilter::abi_signature());
+
+    loop {
+        match subscribe(&ws_url, &filter, &launches).await {
+            Ok(()) => info!("Launch subscription ended, reconnecting"),
+            Err(err) => warn!("Launch subscription failed: {:#}, reconnecting", err),
+        }
+        if launches.is_closed() {
+            return;
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn subscribe(
+    ws_url: &str,
+    filter: &Filter,
+    launches: &mpsc::UnboundedSender<Launch>,
+) -> Result<()> {
+    let ws = Provider::<Ws>::connect(ws_url)
+        .await
+        .context("failed to connect WS_URL")?;
+    let mut stream = ws.subscribe_logs(filter).await?;
+    info!("Listening for launches on {}", ws_url);
+
+    while let Some(log) = stream.next().await {
+        let block = log.block_number.map(|b| b.as_u64());
+        let decode_started = Instant::now();
+        let decoded = parse_log(log);
+        latency::record("decode", decode_started.elapsed());
+        let event: CurveCreateFilter = match decoded {
+            Ok(event) => event,
+            Err(err) => {
+                warn!("Undecodable CurveCreate log: {}", err);
+                continue;
+            }
+        };
+        let launch = Launch {
+            token: event.token,
+            creator: event.creator,
+            name: event.name,
+            symbol: event.symbol,
+            block,
+        };
+        if launches.send(launch).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ScriptedClient;
+
+    fn launch(block: Option<u64>) -> Launch {
+        Launch {
+            token: Address::repeat_byte(1),
+            creator: Address::repeat_byte(2),
+            name: "Test".into(),
+            symbol: "TST".into(),
+            block,
+        }
+    }
+
+    #[tokio::test]
+    async fn entry_waits_for_the_delay_after_the_launch_block() {
+        let client = ScriptedClient::at_block(10);
+        wait_for_entry_block(&client, &launch(Some(10)), 3).await.unwrap();
+        // Blocks 10 to 13 were read, so the buy fires at 13.
+        assert_eq!(client.block_number().await.unwrap(), 14);
+
+        let client = ScriptedClient::at_block(20);
+        wait_for_entry_block(&client, &launch(Some(5)), 3).await.unwrap();
+        assert_eq!(client.block_number().await.unwrap(), 21);
+    }
+
+    #[tokio::test]
+    async fn entry_counts_from_the_current_block_without_a_launch_block() {
+        let client = ScriptedClient::at_block(7);
+        wait_for_entry_block(&client, &launch(None), 2).await.unwrap();
+        // 7 is taken as the launch block, then 8 and 9 are read.
+        assert_eq!(client.block_number().await.unwrap(), 10);
+    }
+}
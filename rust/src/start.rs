@@ -0,0 +1,118 @@
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use ethers::providers::{Http, Middleware, Provider};
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration};
+use tracing::info;
+
+use crate::chain;
+
+const NTP_UNIX_OFFSET_SECS: f64 = 2_208_988_800.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartAt {
+    Block(u64),
+    Timestamp(u64),
+}
+
+impl FromStr for StartAt {
+    type Err = anyhow::Error;
+
+    /// Accepts unix seconds or an RFC 3339 timestamp.
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(secs) = s.trim().parse::<u64>() {
+            return Ok(Self::Timestamp(secs));
+        }
+        let parsed = chrono::DateTime::parse_from_rfc3339(s.trim())
+            .context("expected unix seconds or an RFC 3339 timestamp")?;
+        let secs = u64::try_from(parsed.timestamp())
+            .map_err(|_| anyhow!("start time is before the unix epoch"))?;
+        Ok(Self::Timestamp(secs))
+    }
+}
+
+pub struct ClockCheck {
+    pub server: String,
+    pub max_drift_ms: u64,
+}
+
+pub async fn wait_for_start(start: StartAt, rpc_url: &str, clock: &ClockCheck) -> Result<()> {
+    match start {
+        StartAt::Block(target) => {
+            let provider = Provider::<Http>::try_from(rpc_url).context("invalid RPC_URL")?;
+            let mut current = provider.get_block_number().await?.as_u64();
+            if current >= target {
+                return Err(anyhow!(
+                    "start block {target} already passed (current block {current})"
+                ));
+            }
+            info!("Waiting for block {target} (current block {current})");
+            while current < target {
+                tokio::time::sleep(chain::profile().block_poll_interval()).await;
+                current = provider.get_block_number().await?.as_u64();
+            }
+        }
+        StartAt::Timestamp(target) => {
+            let offset_ms = ntp_offset_ms(&clock.server)
+                .await
+                .with_context(|| format!("NTP query to {} failed", clock.server))?;
+            if offset_ms.unsigned_abs() > clock.max_drift_ms {
+                return Err(anyhow!(
+                    "local clock is off by {offset_ms} ms (max {} ms); fix time sync before a coordinated start",
+                    clock.max_drift_ms
+                ));
+            }
+            info!("Clock offset vs {}: {offset_ms} ms", clock.server);
+
+            let now_ms = unix_now_ms();
+            let target_ms = target as i128 * 1_000;
+            if target_ms <= now_ms {
+                return Err(anyhow!("start time {target} already passed"));
+            }
+            let wait_ms = (target_ms - now_ms - offset_ms as i128).max(0) as u64;
+            info!("Waiting {} ms for start time {target}", wait_ms);
+            tokio::time::sleep(Duration::from_millis(wait_ms)).await;
+        }
+    }
+    Ok(())
+}
+
+/// SNTP offset of the server clock relative to the local clock, in milliseconds.
+async fn ntp_offset_ms(server: &str) -> Result<i64> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(server).await?;
+
+    let mut request = [0u8; 48];
+    request[0] = 0x1b; // LI = 0, version 3, client mode
+
+    let sent = unix_now_ms() as f64 / 1_000.0;
+    socket.send(&request).await?;
+    let mut response = [0u8; 48];
+    let len = timeout(Duration::from_secs(2), socket.recv(&mut response))
+        .await
+        .context("NTP request timed out")??;
+    let received = unix_now_ms() as f64 / 1_000.0;
+    if len < 48 {
+        return Err(anyhow!("short NTP response"));
+    }
+
+    let server_rx = ntp_timestamp(&response[32..40]);
+    let server_tx = ntp_timestamp(&response[40..48]);
+    let offset = ((server_rx - sent) + (server_tx - received)) / 2.0;
+    Ok((offset * 1_000.0).round() as i64)
+}
+
+fn ntp_timestamp(bytes: &[u8]) -> f64 {
+    let secs = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64;
+    let frac = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as f64;
+    secs + frac / 4_294_967_296.0 - NTP_UNIX_OFFSET_SECS
+}
+
+fn unix_now_ms() -> i128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i128
+}
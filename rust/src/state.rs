@@ -0,0 +1,230 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use ethers::types::{Address, H256, U256};
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+
+use crate::file_lock;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenPosition {
+    pub token: Address,
+    pub wallet: Address,
+    /// The buy, or `None` for a balance adopted at startup.
+    pub buy_tx: Option<H256>,
+    pub router: Address,
+    pub amount_in: U256,
+    pub quoted_out: U256,
+    pub opened_at: u64,
+    /// Number of exit tranches already sold.
+    #[serde(default)]
+    pub filled_tranches: usize,
+    /// Native returned by the sells so far.
+    #[serde(default)]
+    pub proceeds: U256,
+    /// Gas paid for the buy, approvals and sells so far.
+    #[serde(default)]
+    pub gas_spent: U256,
+    /// Launch creator, when the position came from a snipe.
+    #[serde(default)]
+    pub creator: Option<Address>,
+    /// The filter this entry was let past as an exploration trade.
+    #[serde(default)]
+    pub exploration: Option<String>,
+    /// Tokens the wallet already held at the buy, which the sells leave alone.
+    #[serde(default)]
+    pub held_back: U256,
+    /// Still being bought into by a DCA schedule, which hands it to the exit rules after
+    /// its last buy; other modes leave it alone until then.
+    #[serde(default)]
+    pub accumulating: bool,
+    /// The mode that entered the position.
+    #[serde(default)]
+    pub strategy: Option<String>,
+}
+
+impl OpenPosition {
+    pub fn new(
+        token: Address,
+        wallet: Address,
+        buy_tx: H256,
+        router: Address,
+        amount_in: U256,
+        quoted_out: U256,
+    ) -> Self {
+        Self {
+            token,
+            wallet,
+            buy_tx: Some(buy_tx),
+            router,
+            amount_in,
+            quoted_out,
+            opened_at: unix_now(),
+            filled_tranches: 0,
+            proceeds: U256::zero(),
+            gas_spent: U256::zero(),
+            creator: None,
+            exploration: None,
+            held_back: U256::zero(),
+            accumulating: false,
+            strategy: None,
+        }
+    }
+
+    /// A balance found in the wallet with no buy on record, priced at `value`.
+    pub fn adopted(
+        token: Address,
+        wallet: Address,
+        router: Address,
+        value: U256,
+        balance: U256,
+    ) -> Self {
+        Self {
+            buy_tx: None,
+            ..Self::new(token, wallet, H256::zero(), router, value, balance)
+        }
+    }
+
+    /// How the position was entered, for logs.
+    pub fn origin(&self) -> String {
+        match self.buy_tx {
+            Some(tx) => format!("buy {:?}", tx),
+            None => "an adopted balance".to_string(),
+        }
+    }
+
+    /// Native spent per whole token at entry, using the quoted fill.
+    pub fn entry_price(&self) -> f64 {
+        if self.quoted_out.is_zero() {
+            return 0.0;
+        }
+        self.amount_in.as_u128() as f64 / self.quoted_out.as_u128() as f64
+    }
+
+    pub fn held_for(&self) -> Duration {
+        Duration::from_secs(unix_now().saturating_sub(self.opened_at))
+    }
+}
+
+/// Open positions persisted to a JSON file so they survive a crash between buy and sell.
+/// Every process pointed at the file shares it: writes rename a whole new file into place
+/// under the file's lock, so readers never see a partial one.
+pub struct StateStore {
+    path: PathBuf,
+}
+
+impl StateStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn open_positions(&self) -> Result<Vec<OpenPosition>> {
+        self.load()
+    }
+
+    pub fn open(&self, position: OpenPosition) -> Result<()> {
+        self.update(|positions| {
+            positions.retain(|p| !(p.token == position.token && p.wallet == position.wallet));
+            positions.push(position);
+        })
+    }
+
+    pub fn close(&self, token: Address, wallet: Address) -> Result<()> {
+        self.update(|positions| positions.retain(|p| !(p.token == token && p.wallet == wallet)))
+    }
+
+    fn update(&self, change: impl FnOnce(&mut Vec<OpenPosition>)) -> Result<()> {
+        let _lock = file_lock::exclusive(&self.path)?;
+        let mut positions = self.load()?;
+        change(&mut positions);
+        let json = serde_json::to_string_pretty(&positions)?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, json).with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+
+    fn load(&self) -> Result<Vec<OpenPosition>> {
+        match fs::read_to_string(&self.path) {
+            Ok(json) => serde_json::from_str(&json)
+                .with_context(|| format!("corrupt state file {}", self.path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// `STATE_FILE`, `positions.json` by default.
+pub fn state_file() -> PathBuf {
+    PathBuf::from(env::var("STATE_FILE").unwrap_or_else(|_| "positions.json".into()))
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adopted_positions_have_no_buy() {
+        let token = Address::repeat_byte(1);
+        let adopted =
+            OpenPosition::adopted(token, Address::repeat_byte(2), Address::zero(), 5.into(), 9.into());
+        let json = serde_json::to_string(&adopted).unwrap();
+        let parsed: OpenPosition = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.buy_tx, None);
+        assert_eq!(parsed.origin(), "an adopted balance");
+
+        let bought = OpenPosition::new(
+            token,
+            Address::repeat_byte(2),
+            H256::repeat_byte(3),
+            Address::zero(),
+            5.into(),
+            9.into(),
+        );
+        let parsed: OpenPosition =
+            serde_json::from_str(&serde_json::to_string(&bought).unwrap()).unwrap();
+        assert_eq!(parsed.buy_tx, Some(H256::repeat_byte(3)));
+    }
+
+    #[test]
+    fn stores_sharing_a_file_keep_each_others_positions() {
+        let path = std::env::temp_dir().join(format!("nadfun-state-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let wallet = Address::repeat_byte(2);
+        std::thread::scope(|scope| {
+            for worker in 0..4u8 {
+                let store = StateStore::new(path.clone());
+                scope.spawn(move || {
+                    for n in 0..10u8 {
+                        let token = Address::repeat_byte(worker * 10 + n + 1);
+                        let position = OpenPosition::adopted(
+                            token,
+                            wallet,
+                            Address::zero(),
+                            1.into(),
+                            1.into(),
+                        );
+                        store.open(position).unwrap();
+                    }
+                });
+            }
+        });
+        let store = StateStore::new(path.clone());
+        assert_eq!(store.open_positions().unwrap().len(), 40);
+        store.close(Address::repeat_byte(1), wallet).unwrap();
+        assert_eq!(store.open_positions().unwrap().len(), 39);
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(path.with_extension("lock"));
+    }
+}
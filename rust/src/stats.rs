@@ -0,0 +1,214 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate};
+use ethers::types::{Address, I256, U256};
+use serde::Serialize;
+
+use crate::chain;
+use crate::ledger::TradeRecord;
+
+/// What trades are counted under when the ledger didn't record the mode that made them.
+const UNKNOWN_STRATEGY: &str = "unknown";
+
+#[derive(Default)]
+struct Totals {
+    trades: usize,
+    volume: U256,
+    pnl: I256,
+}
+
+impl Totals {
+    fn add(&mut self, record: &TradeRecord) {
+        self.trades += 1;
+        self.volume += record.amount_in + record.proceeds;
+        self.pnl += record.pnl();
+    }
+}
+
+/// Signed native amounts as JSON numbers, which dashboards plot as they are.
+fn native(value: I256) -> f64 {
+    let amount = chain::profile().native_f64(value.unsigned_abs());
+    if value.is_negative() {
+        -amount
+    } else {
+        amount
+    }
+}
+
+/// Round trips closed on a UTC day. Volume counts the buy and the sells.
+#[derive(Debug, Serialize)]
+pub struct DayStats {
+    /// `YYYY-MM-DD`.
+    pub day: String,
+    pub trades: usize,
+    pub volume: f64,
+    pub pnl: f64,
+}
+
+pub fn daily(records: &[TradeRecord]) -> Vec<DayStats> {
+    let mut by_day: BTreeMap<NaiveDate, Totals> = BTreeMap::new();
+    for record in records {
+        if let Some(at) = DateTime::from_timestamp(record.closed_at as i64, 0) {
+            by_day.entry(at.date_naive()).or_default().add(record);
+        }
+    }
+    by_day
+        .into_iter()
+        .map(|(day, totals)| DayStats {
+            day: day.to_string(),
+            trades: totals.trades,
+            volume: chain::profile().native_f64(totals.volume),
+            pnl: native(totals.pnl),
+        })
+        .collect()
+}
+
+/// Realized PnL, net of gas, after each round trip closed.
+#[derive(Debug, Serialize)]
+pub struct PnlPoint {
+    pub closed_at: u64,
+    pub token: Address,
+    pub pnl: f64,
+    pub cumulative: f64,
+}
+
+pub fn pnl_curve(records: &[TradeRecord]) -> Vec<PnlPoint> {
+    let mut sorted: Vec<&TradeRecord> = records.iter().collect();
+    sorted.sort_by_key(|record| record.closed_at);
+    let mut cumulative = I256::zero();
+    sorted
+        .into_iter()
+        .map(|record| {
+            cumulative += record.pnl();
+            PnlPoint {
+                closed_at: record.closed_at,
+                token: record.token,
+                pnl: native(record.pnl()),
+                cumulative: native(cumulative),
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct StrategyStats {
+    pub strategy: String,
+    pub trades: usize,
+    pub wins: usize,
+    pub pnl: f64,
+}
+
+/// Round trips per mode that entered them, busiest first.
+pub fn by_strategy(records: &[TradeRecord]) -> Vec<StrategyStats> {
+    let mut by_strategy: BTreeMap<&str, (Totals, usize)> = BTreeMap::new();
+    for record in records {
+        let strategy = record.strategy.as_deref().unwrap_or(UNKNOWN_STRATEGY);
+        let (totals, wins) = by_strategy.entry(strategy).or_default();
+        totals.add(record);
+        if record.pnl() > I256::zero() {
+            *wins += 1;
+        }
+    }
+    let mut stats: Vec<StrategyStats> = by_strategy
+        .into_iter()
+        .map(|(strategy, (totals, wins))| StrategyStats {
+            strategy: strategy.to_string(),
+            trades: totals.trades,
+            wins,
+            pnl: native(totals.pnl),
+        })
+        .collect();
+    stats.sort_by_key(|stats| Reverse(stats.trades));
+    stats
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenStats {
+    pub token: Address,
+    pub trades: usize,
+    pub volume: f64,
+    pub pnl: f64,
+}
+
+/// The `limit` tokens with the highest realized PnL.
+pub fn top_tokens(records: &[TradeRecord], limit: usize) -> Vec<TokenStats> {
+    let mut by_token: BTreeMap<Address, Totals> = BTreeMap::new();
+    for record in records {
+        by_token.entry(record.token).or_default().add(record);
+    }
+    let mut ranked: Vec<(Address, Totals)> = by_token.into_iter().collect();
+    ranked.sort_by_key(|(_, totals)| Reverse(totals.pnl));
+    ranked
+        .into_iter()
+        .take(limit)
+        .map(|(token, totals)| TokenStats {
+            token,
+            trades: totals.trades,
+            volume: chain::profile().native_f64(totals.volume),
+            pnl: native(totals.pnl),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MON: u64 = 1_000_000_000_000_000_000;
+
+    fn trade(token: u8, proceeds: u64, closed_at: u64, strategy: Option<&str>) -> TradeRecord {
+        TradeRecord {
+            token: Address::repeat_byte(token),
+            wallet: Address::repeat_byte(9),
+            buy_tx: None,
+            opened_at: closed_at - 60,
+            closed_at,
+            amount_in: U256::from(MON),
+            proceeds: U256::from(proceeds),
+            gas_spent: U256::zero(),
+            exploration: None,
+            strategy: strategy.map(String::from),
+        }
+    }
+
+    #[test]
+    fn days_and_the_pnl_curve_follow_the_closes() {
+        // Two trades on 2026-10-14 and one the day after, out of order.
+        let records = [
+            trade(1, 2 * MON, 1_792_000_000, Some("sniper")),
+            trade(2, MON / 2, 1_792_090_000, Some("sniper")),
+            trade(3, MON, 1_791_990_000, None),
+        ];
+        let days = daily(&records);
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].day, "2026-10-14");
+        assert_eq!(days[0].trades, 2);
+        assert!((days[0].volume - 5.0).abs() < 1e-9);
+        assert!((days[0].pnl - 1.0).abs() < 1e-9);
+        assert!((days[1].pnl + 0.5).abs() < 1e-9);
+
+        let curve = pnl_curve(&records);
+        let cumulative: Vec<f64> = curve.iter().map(|point| point.cumulative).collect();
+        assert_eq!(cumulative, vec![0.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn trades_are_grouped_by_strategy_and_tokens_ranked_by_pnl() {
+        let records = [
+            trade(1, 2 * MON, 100, Some("sniper")),
+            trade(1, MON / 2, 200, Some("sniper")),
+            trade(2, 3 * MON, 300, Some("copy")),
+            trade(3, 0, 400, None),
+        ];
+        let strategies = by_strategy(&records);
+        assert_eq!(strategies[0].strategy, "sniper");
+        assert_eq!((strategies[0].trades, strategies[0].wins), (2, 1));
+        assert!(strategies.iter().any(|stats| stats.strategy == UNKNOWN_STRATEGY));
+
+        let tokens = top_tokens(&records, 2);
+        let ranked: Vec<Address> = tokens.iter().map(|stats| stats.token).collect();
+        assert_eq!(ranked, vec![Address::repeat_byte(2), Address::repeat_byte(1)]);
+        assert!((tokens[1].pnl - 0.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::Error;
+use ethers::types::Address;
+
+/// Sends panics and fatal errors to Sentry when built with the `sentry` feature
+/// and `SENTRY_DSN` is set; otherwise does nothing.
+pub struct ErrorReporter {
+    #[cfg(feature = "sentry")]
+    _guard: Option<sentry::ClientInitGuard>,
+}
+
+impl ErrorReporter {
+    pub fn init(dsn: Option<&str>) -> Self {
+        #[cfg(feature = "sentry")]
+        {
+            let guard = dsn.map(|dsn| {
+                sentry::init((
+                    dsn,
+                    sentry::ClientOptions {
+                        release: sentry::release_name!(),
+                        attach_stacktrace: true,
+                        ..Default::default()
+                    },
+                ))
+            });
+            Self { _guard: guard }
+        }
+
+        #[cfg(not(feature = "sentry"))]
+        {
+            if dsn.is_some() {
+                tracing::info!("SENTRY_DSN is set but this build has no `sentry` feature; ignoring");
+            }
+            Self {}
+        }
+    }
+
+    pub fn capture(&self, err: &Error, token: Address, wallet: Address) {
+        #[cfg(feature = "sentry")]
+        sentry::with_scope(
+            |scope| {
+                scope.set_tag("error_class", classify(err));
+                scope.set_tag("token", format!("{:?}", token));
+                scope.set_tag("wallet", short_address(wallet));
+            },
+            || sentry::integrations::anyhow::capture_anyhow(err),
+        );
+
+        #[cfg(not(feature = "sentry"))]
+        let _ = (err, token, wallet);
+    }
+}
+
+pub fn classify(err: &Error) -> &'static str {
+    let message = format!("{:#}", err).to_ascii_lowercase();
+    if message.contains("revert") {
+        "revert"
+    } else if message.contains("insufficient funds") {
+        "insufficient_funds"
+    } else if message.contains("underpriced") || message.contains("nonce") {
+        "nonce"
+    } else if message.contains("timed out") || message.contains("timeout") {
+        "timeout"
+    } else if message.contains("connection") || message.contains("rpc") {
+        "rpc"
+    } else {
+        "other"
+    }
+}
+
+#[cfg(feature = "sentry")]
+fn short_address(address: Address) -> String {
+    let full = format!("{:?}", address);
+    format!("{}…{}", &full[..6], &full[full.len() - 4..])
+}
@@ -0,0 +1,161 @@
+//! Stand-ins for the chain in unit tests.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, Bytes, TransactionReceipt, H256, U64, U256};
+
+use crate::engine::ExecutionClient;
+use crate::nadfun::{BuyParams, GasEstimationParams, SellParams};
+use crate::tx_manager::{TimeInForce, TxChain};
+
+/// An [`ExecutionClient`] that answers quotes from a script and counts a block per block
+/// number read. Every other call panics.
+#[derive(Default)]
+pub struct ScriptedClient {
+    buy_quotes: Mutex<VecDeque<U256>>,
+    sell_quotes: Mutex<VecDeque<U256>>,
+    block: AtomicU64,
+}
+
+impl ScriptedClient {
+    /// Quotes `values` for the next sells, one per quote, then fails.
+    pub fn with_sell_quotes(values: impl IntoIterator<Item = u64>) -> Self {
+        Self {
+            sell_quotes: Mutex::new(values.into_iter().map(U256::from).collect()),
+            ..Self::default()
+        }
+    }
+
+    /// Starts the block count at `block`.
+    pub fn at_block(block: u64) -> Self {
+        Self {
+            block: AtomicU64::new(block),
+            ..Self::default()
+        }
+    }
+
+    /// Quotes `tokens` out for the next buys, one per quote, then fails.
+    pub fn with_buy_quotes(tokens: impl IntoIterator<Item = u64>) -> Self {
+        Self {
+            buy_quotes: Mutex::new(tokens.into_iter().map(U256::from).collect()),
+            ..Self::default()
+        }
+    }
+}
+
+impl ExecutionClient for ScriptedClient {
+    fn wallet(&self) -> Address {
+        Address::repeat_byte(0x11)
+    }
+
+    async fn native_balance(&self, _owner: Address, _block: Option<u64>) -> Result<U256> {
+        unimplemented!("native_balance")
+    }
+
+    async fn token_balance(&self, _token: Address, _owner: Address) -> Result<U256> {
+        unimplemented!("token_balance")
+    }
+
+    async fn allowance(&self, _token: Address, _owner: Address, _spender: Address) -> Result<U256> {
+        unimplemented!("allowance")
+    }
+
+    async fn approve(&self, _token: Address, _spender: Address, _amount: U256) -> Result<H256> {
+        unimplemented!("approve")
+    }
+
+    async fn gas_price(&self) -> Result<U256> {
+        unimplemented!("gas_price")
+    }
+
+    async fn block_number(&self) -> Result<u64> {
+        Ok(self.block.fetch_add(1, Ordering::SeqCst))
+    }
+
+    async fn code(&self, _address: Address) -> Result<Bytes> {
+        unimplemented!("code")
+    }
+
+    async fn quote(&self, _token: Address, _amount: U256, is_buy: bool) -> Result<(Address, U256)> {
+        let quotes = if is_buy { &self.buy_quotes } else { &self.sell_quotes };
+        let value = quotes.lock().unwrap().pop_front();
+        let value = value.ok_or_else(|| anyhow!("quote script ran out"))?;
+        Ok((Address::repeat_byte(0x22), value))
+    }
+
+    async fn estimate_gas(&self, _router: Address, _params: GasEstimationParams) -> Result<U256> {
+        unimplemented!("estimate_gas")
+    }
+
+    async fn receipt(&self, _tx_hash: H256) -> Result<TransactionReceipt> {
+        unimplemented!("receipt")
+    }
+
+    async fn buy(
+        &self,
+        _router: Address,
+        _params: BuyParams,
+        _tif: TimeInForce,
+    ) -> Result<TransactionReceipt> {
+        unimplemented!("buy")
+    }
+
+    async fn sell(
+        &self,
+        _router: Address,
+        _params: SellParams,
+        _tif: TimeInForce,
+    ) -> Result<TransactionReceipt> {
+        unimplemented!("sell")
+    }
+}
+
+/// A [`TxChain`] on which every transaction is already mined, successfully, in block 1, and
+/// a block passes per block number read. Cancelling panics.
+#[derive(Default)]
+pub struct MinedChain {
+    block: AtomicU64,
+}
+
+impl TxChain for MinedChain {
+    fn address(&self) -> Address {
+        Address::repeat_byte(0x11)
+    }
+
+    async fn nonce(&self, _pending: bool) -> Result<U256> {
+        Ok(U256::zero())
+    }
+
+    async fn gas_price(&self) -> Result<U256> {
+        Ok(U256::one())
+    }
+
+    async fn block_number(&self) -> Result<u64> {
+        Ok(self.block.fetch_add(1, Ordering::SeqCst))
+    }
+
+    async fn transaction_receipt(&self, tx_hash: H256) -> Result<Option<TransactionReceipt>> {
+        Ok(Some(TransactionReceipt {
+            transaction_hash: tx_hash,
+            block_number: Some(U64::one()),
+            block_hash: Some(H256::repeat_byte(0x01)),
+            status: Some(U64::one()),
+            ..TransactionReceipt::default()
+        }))
+    }
+
+    async fn offered_fee(&self, _tx_hash: H256) -> Result<Option<U256>> {
+        Ok(None)
+    }
+
+    async fn send_cancel(&self, _nonce: U256, _gas_price: U256) -> Result<H256> {
+        unimplemented!("send_cancel")
+    }
+
+    async fn revert_reason(&self, _receipt: &TransactionReceipt) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,171 @@
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{Context, Result};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{
+    GethDebugBuiltInTracerType, GethDebugTracerType, GethDebugTracingOptions, TransactionReceipt,
+    H256, U256,
+};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::app::AppConfig;
+use crate::tx_manager;
+
+/// Set once the endpoint has refused `debug_traceTransaction`, to stop asking.
+static UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Fetches the call trace of each of the bot's transactions that reverted or used more
+/// gas than `TRACE_GAS_ABOVE`, and records it in the audit log for post-mortems.
+pub struct TraceConfig {
+    pub gas_above: Option<U256>,
+}
+
+impl TraceConfig {
+    /// Enabled by `TRACE_TXS=1`. Needs an endpoint serving `debug_traceTransaction`, and
+    /// `AUDIT_LOG_FILE` for the traces to be kept.
+    pub fn from_env() -> Result<Option<Self>> {
+        let enabled = env::var("TRACE_TXS")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+        let gas_above = env::var("TRACE_GAS_ABOVE")
+            .ok()
+            .map(|v| U256::from_dec_str(&v).context("invalid TRACE_GAS_ABOVE"))
+            .transpose()?;
+        Ok(Some(Self { gas_above }))
+    }
+
+    /// Why `receipt`'s transaction is worth a trace, if it is.
+    fn reason(&self, receipt: &TransactionReceipt) -> Option<&'static str> {
+        if receipt.status != Some(1u64.into()) {
+            return Some("reverted");
+        }
+        let gas_used = receipt.gas_used.unwrap_or_default();
+        self.gas_above
+            .filter(|limit| gas_used > *limit)
+            .map(|_| "expensive")
+    }
+}
+
+/// Traces the transaction of a submission that reverted or was mined expensively.
+pub async fn after_submit(cfg: &AppConfig, label: &str, submitted: &Result<TransactionReceipt>) {
+    match submitted {
+        Ok(receipt) => after_receipt(cfg, label, receipt).await,
+        Err(err) => {
+            if let (Some(_), Some(tx_hash)) = (&cfg.traces, tx_manager::reverted_tx(err)) {
+                capture(cfg, label, tx_hash, "reverted", None).await;
+            }
+        }
+    }
+}
+
+/// Traces `receipt`'s transaction if it reverted or was expensive.
+pub async fn after_receipt(cfg: &AppConfig, label: &str, receipt: &TransactionReceipt) {
+    let Some(reason) = cfg
+        .traces
+        .as_ref()
+        .and_then(|traces| traces.reason(receipt))
+    else {
+        return;
+    };
+    capture(
+        cfg,
+        label,
+        receipt.transaction_hash,
+        reason,
+        receipt.gas_used,
+    )
+    .await;
+}
+
+async fn capture(
+    cfg: &AppConfig,
+    label: &str,
+    tx_hash: H256,
+    reason: &str,
+    gas_used: Option<U256>,
+) {
+    if UNSUPPORTED.load(Ordering::Relaxed) {
+        return;
+    }
+    let trace = match trace(&cfg.rpc_url, tx_hash).await {
+        Ok(trace) => trace,
+        Err(err) => {
+            let message = format!("{:#}", err).to_ascii_lowercase();
+            if [
+                "method not found",
+                "not supported",
+                "does not exist",
+                "not available",
+            ]
+            .iter()
+            .any(|needle| message.contains(needle))
+            {
+                warn!(
+                    "RPC_URL doesn't serve debug_traceTransaction; not tracing: {:#}",
+                    err
+                );
+                UNSUPPORTED.store(true, Ordering::Relaxed);
+            } else {
+                warn!("Failed to trace {} {:?}: {:#}", label, tx_hash, err);
+            }
+            return;
+        }
+    };
+    info!(
+        "Traced {} {:?} ({}) into the audit log",
+        label, tx_hash, reason
+    );
+    cfg.audit(
+        "tx_trace",
+        json!({
+            "tx": tx_hash,
+            "label": label,
+            "reason": reason,
+            "gas_used": gas_used,
+            "trace": trace,
+        }),
+    );
+}
+
+async fn trace(rpc_url: &str, tx_hash: H256) -> Result<serde_json::Value> {
+    let provider = Provider::<Http>::try_from(rpc_url).context("invalid RPC_URL")?;
+    let options = GethDebugTracingOptions {
+        tracer: Some(GethDebugTracerType::BuiltInTracer(
+            GethDebugBuiltInTracerType::CallTracer,
+        )),
+        ..GethDebugTracingOptions::default()
+    };
+    let trace = provider.debug_trace_transaction(tx_hash, options).await?;
+    Ok(serde_json::to_value(trace)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt(status: u64, gas_used: u64) -> TransactionReceipt {
+        TransactionReceipt {
+            status: Some(status.into()),
+            gas_used: Some(gas_used.into()),
+            ..TransactionReceipt::default()
+        }
+    }
+
+    #[test]
+    fn reverts_and_expensive_transactions_are_traced() {
+        let traces = TraceConfig {
+            gas_above: Some(U256::from(300_000u64)),
+        };
+        assert_eq!(traces.reason(&receipt(0, 50_000)), Some("reverted"));
+        assert_eq!(traces.reason(&receipt(1, 400_000)), Some("expensive"));
+        assert_eq!(traces.reason(&receipt(1, 200_000)), None);
+
+        let reverts_only = TraceConfig { gas_above: None };
+        assert_eq!(reverts_only.reason(&receipt(1, 10_000_000)), None);
+    }
+}
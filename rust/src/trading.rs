@@ -0,0 +1,1062 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, TransactionReceipt, H256, U256};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use serde_json::json;
+use tokio::time::{Duration, Instant};
+use tracing::{info, info_span, instrument, warn, Instrument};
+
+use crate::app::AppConfig;
+use crate::approvals;
+use crate::chain;
+use crate::config::TradeParams;
+use crate::control::{ExecOrder, LiveRules};
+use crate::curve::CurveTracker;
+use crate::engine::{ExecutionClient, Strategy};
+use crate::entry::{self, Entry, EntryRequest};
+use crate::execstats::{ExecRecord, Side};
+use crate::exit_guard::{self, ExitDecision};
+use crate::exit_strategy::{Exit, ExitKind};
+use crate::gas_budget::GasBudget;
+use crate::latency;
+use crate::ledger::{self, TradeRecord};
+use crate::logging;
+use crate::mev;
+use crate::nadfun::{BuyParams, SellParams};
+use crate::notify::Event;
+use crate::orders::WithLimitSells;
+use crate::pinning::PinWatch;
+use crate::protocol;
+use crate::receipts;
+use crate::routing;
+use crate::safety;
+use crate::signals::SignalKind;
+use crate::sink;
+use crate::snapshot::SnapshotWatch;
+use crate::state::OpenPosition;
+use crate::traces;
+use crate::tx_manager;
+use crate::venue::VenueKind;
+use crate::warmer;
+
+/// Runs the full round trip of every order `strategy` places, one at a time per token,
+/// until the strategy runs out of orders or shutdown is requested, and every trade in
+/// flight has finished.
+pub async fn run_strategy(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    strategy: &mut impl Strategy,
+) -> Result<()> {
+    let mut trading = HashSet::new();
+    let mut in_flight = FuturesUnordered::new();
+    let mut exhausted = false;
+
+    loop {
+        if (exhausted || cfg.shutdown.requested()) && in_flight.is_empty() {
+            return Ok(());
+        }
+        tokio::select! {
+            order = strategy.next_order(), if !exhausted && !cfg.shutdown.requested() => {
+                let Some(order) = order else {
+                    exhausted = true;
+                    continue;
+                };
+                if !trading.insert(order.token) {
+                    warn!("Ignoring order for {:?}: already trading it", order.token);
+                    continue;
+                }
+                in_flight.push(executed_order(cfg, client, order));
+            }
+            Some(token) = in_flight.next(), if !in_flight.is_empty() => {
+                trading.remove(&token);
+            }
+            _ = cfg.shutdown.wait(), if !cfg.shutdown.requested() => {}
+        }
+    }
+}
+
+async fn executed_order(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    order: ExecOrder,
+) -> Address {
+    let token = order.token;
+    let hints = EntryHints {
+        amount_in: order.amount_in,
+        slippage_bps: order.slippage_bps,
+        strategy: Some("exec"),
+        ..EntryHints::default()
+    };
+    if let Err(err) = round_trip(cfg, cfg.params_for(token), client, token, hints).await {
+        warn!("Order for {:?} failed: {:#}", token, err);
+        cfg.notifier.send(Event::Error, format!("Order for {:?} failed: {:#}", token, err));
+    }
+    token
+}
+
+/// What the caller knows about an entry beyond the token's trade params.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EntryHints {
+    /// The launch creator, for creator-balance exit rules.
+    pub creator: Option<Address>,
+    /// Buy size to use instead of the params' `amount_in`.
+    pub amount_in: Option<U256>,
+    /// Slippage to use instead of the configured or overridden value.
+    pub slippage_bps: Option<u64>,
+    /// Traded off the runtime watchlist; the buy is dropped if the token is removed first.
+    pub watchlist: bool,
+    /// The filter a sniper exploration trade was let past.
+    pub exploration: Option<&'static str>,
+    /// A scheduled DCA buy: added to the token's accumulating position, which is left
+    /// for the schedule to hand to the exit rules.
+    pub accumulate: bool,
+    /// The mode making the entry, which the stats API counts trades by.
+    pub strategy: Option<&'static str>,
+}
+
+/// Buys `token`, holds it while watching the exit, then sells the whole balance.
+#[instrument(name = "trade", skip_all, fields(id = %logging::trade_id(), token = ?token))]
+pub async fn round_trip(
+    cfg: &AppConfig,
+    params: &TradeParams,
+    client: &impl ExecutionClient,
+    token: Address,
+    hints: EntryHints,
+) -> Result<()> {
+    ensure_entry_allowed(cfg, client.wallet(), token)?;
+    if let Some(age) = &cfg.age {
+        age.wait_until_eligible(token).await.context("refusing entry")?;
+    }
+    let recipient = cfg.recipient.unwrap_or_else(|| client.wallet());
+
+    // Tokens on another venue have no curve to read.
+    let mut curve = cfg
+        .bonding_curve
+        .filter(|_| params.venue == VenueKind::NadFun)
+        .map(|address| CurveTracker::new(&cfg.rpc_url, address))
+        .transpose()?;
+    if let Some(curve) = curve.as_mut() {
+        report_curve_progress(curve, token).await;
+    }
+
+    let deadline = cfg.deadline_u256();
+    let amount_in = match hints.amount_in {
+        Some(amount) => amount,
+        None if params.sizing.needs_balance() => {
+            let balance = client
+                .native_balance(client.wallet(), None)
+                .await
+                .context("failed to read wallet balance for sizing")?;
+            let fixed = cfg.controls.amount_in(params.amount_in);
+            params.sizing.size(fixed, balance, params.stop_loss_pct, cfg.gas_reserve)
+        }
+        None => cfg.controls.amount_in(params.amount_in),
+    };
+    if amount_in.is_zero() {
+        return Err(anyhow!("sized buy for {:?} is zero", token));
+    }
+
+    info!(
+        "Preparing buy for token {} with {}",
+        chain::profile().address_url(token),
+        chain::profile().format_native(amount_in)
+    );
+
+    let configured_slippage = hints
+        .slippage_bps
+        .unwrap_or_else(|| cfg.controls.slippage_bps(params.slippage_bps));
+    let slippage_bps = if cfg.exec_auto_tune {
+        match cfg.exec_log.tuned_slippage_bps(token, configured_slippage) {
+            Ok(bps) => bps,
+            Err(err) => {
+                warn!("Slippage tuning unavailable: {:#}", err);
+                configured_slippage
+            }
+        }
+    } else {
+        configured_slippage
+    };
+    if slippage_bps != configured_slippage {
+        info!(
+            "Slippage tuned to {} bps from recent fills (configured {} bps)",
+            slippage_bps, configured_slippage
+        );
+    }
+
+    let quote_started = Instant::now();
+    let mut entry = entry::first_allowed_entry(
+        client,
+        &EntryRequest {
+            token,
+            amount_in,
+            recipient,
+            deadline,
+            slippage_bps,
+        },
+        cfg.max_entry_wait_blocks,
+    )
+    .instrument(info_span!("quote"))
+    .await?;
+    if let (Some(impact), Some(curve)) = (&cfg.impact, curve.as_ref()) {
+        let state = match warmer::curve_state(token) {
+            Some(state) => state,
+            None => curve.state(token).await?,
+        };
+        let min_out = impact
+            .buy_min_out(token, &state, amount_in, entry.quoted_out)
+            .context("refusing entry")?;
+        if let Some(min_out) = min_out {
+            entry.amount_out_min = min_out;
+        }
+    }
+    let router = entry.router;
+    let amount_out_min = entry.amount_out_min;
+
+    latency::record("quote", quote_started.elapsed());
+    info!(
+        phase = "quote",
+        elapsed_ms = quote_started.elapsed().as_millis() as u64,
+        buy_gas = %entry.buy_gas,
+        "Quoted {} tokens, minimum {}",
+        format_units(entry.quoted_out)?,
+        format_units(amount_out_min)?
+    );
+
+    ensure_entry_allowed(cfg, client.wallet(), token)?;
+    let safety_started = Instant::now();
+    if let Err(err) = safety::check(
+        &cfg.safety,
+        client,
+        curve.as_ref(),
+        token,
+        amount_in,
+        entry.quoted_out,
+    )
+    .await
+    {
+        cfg.audit("entry_refused", json!({ "token": token, "reason": format!("{:#}", err) }));
+        if let Some(signals) = &cfg.signals {
+            let reason = Some(format!("{:#}", err));
+            signals.publish(token, SignalKind::Verdict { safe: false, reason });
+        }
+        return Err(err.context("safety check failed, refusing entry"));
+    }
+    if let Some(signals) = &cfg.signals {
+        signals.publish(token, SignalKind::Verdict { safe: true, reason: None });
+    }
+    info!(
+        phase = "safety",
+        elapsed_ms = safety_started.elapsed().as_millis() as u64,
+        "Safety checks passed"
+    );
+
+    if let (Some(_), Some(max_age)) = (&curve, cfg.protocol_max_age) {
+        protocol::ensure_fresh(max_age).context("refusing entry")?;
+    }
+    ensure_entry_allowed(cfg, client.wallet(), token)
+        .context("entry no longer allowed before the buy")?;
+    if hints.watchlist && !cfg.controls.is_watched(token) {
+        return Err(anyhow!("token {:?} was removed from the watchlist before the buy", token));
+    }
+    if cfg.dry_run {
+        return report_dry_run(client, token, amount_in, &entry).await;
+    }
+
+    let pin = match cfg.pin_policy {
+        Some(policy) => Some(PinWatch::pin(&cfg.rpc_url, token, policy).await?),
+        None => None,
+    };
+
+    if let Some(risk) = &cfg.risk {
+        risk.check_entry(cfg, recipient, token, amount_in).await?;
+    }
+    let submitted_at = Instant::now();
+    let buy = BuyParams {
+        token,
+        amount_in,
+        amount_out_min,
+        recipient,
+        deadline,
+    };
+    let submitted = client.buy(router, buy, cfg.retry_policy.buy_tif).await;
+    traces::after_submit(cfg, "buy", &submitted).await;
+    let buy_receipt = match submitted {
+        Ok(receipt) => receipt,
+        Err(err) => {
+            if let Some(risk) = &cfg.risk {
+                if let Err(err) = risk.release(recipient, amount_in) {
+                    warn!("Failed to release the buy from the risk limits: {:#}", err);
+                }
+            }
+            if tx_manager::is_revert(&err) {
+                let record = ExecRecord::reverted(
+                    token,
+                    Side::Buy,
+                    entry.quoted_out,
+                    submitted_at.elapsed(),
+                );
+                record_execution(cfg, &record);
+            }
+            return Err(err);
+        }
+    };
+    let buy_tx = buy_receipt.transaction_hash;
+    latency::record("inclusion", submitted_at.elapsed());
+    let received = mev::received_amount(&buy_receipt, token, recipient);
+    record_execution(
+        cfg,
+        &ExecRecord::filled(token, Side::Buy, entry.quoted_out, received, submitted_at.elapsed()),
+    );
+
+    info!(
+        phase = "buy",
+        elapsed_ms = submitted_at.elapsed().as_millis() as u64,
+        gas_used = %buy_receipt.gas_used.unwrap_or_default(),
+        "Buy mined: {}",
+        chain::profile().tx_url(buy_tx)
+    );
+    cfg.notifier.send(
+        Event::Buy,
+        format!(
+            "Bought {:?} for {}: {}",
+            token,
+            chain::profile().format_native(amount_in),
+            chain::profile().tx_url(buy_tx)
+        ),
+    );
+    cfg.audit(
+        "buy",
+        json!({
+            "token": token,
+            "wallet": recipient,
+            "tx": buy_tx,
+            "router": router,
+            "amount_in": amount_in,
+            "quoted_out": entry.quoted_out,
+            "amount_out_min": amount_out_min,
+        }),
+    );
+
+    if let Some(path) = &cfg.mev_report_file {
+        report_mev_impact(
+            &cfg.rpc_url,
+            token,
+            buy_tx,
+            recipient,
+            entry.quoted_out,
+            path,
+        )
+        .await;
+    }
+    if let Some(budget) = &cfg.gas_budget {
+        record_gas_spend(client, budget, buy_tx).await;
+    }
+
+    let mut position = OpenPosition::new(
+        token,
+        recipient,
+        buy_tx,
+        router,
+        amount_in,
+        entry.quoted_out,
+    );
+    position.gas_spent = receipts::gas_cost(&buy_receipt);
+    position.creator = hints.creator;
+    position.exploration = hints.exploration.map(String::from);
+    position.accumulating = hints.accumulate;
+    position.strategy = hints.strategy.map(String::from);
+    let accumulated = if hints.accumulate {
+        cfg.state.open_positions()?.into_iter().find(|open| {
+            open.token == token && open.wallet == recipient && open.accumulating
+        })
+    } else {
+        None
+    };
+    match accumulated {
+        Some(open) => {
+            position.buy_tx = open.buy_tx;
+            position.opened_at = open.opened_at;
+            position.amount_in += open.amount_in;
+            position.quoted_out += open.quoted_out;
+            position.gas_spent += open.gas_spent;
+            position.held_back = open.held_back;
+            info!(
+                "Accumulated {} of {:?} so far",
+                chain::profile().format_native(position.amount_in),
+                token
+            );
+        }
+        None => match balance_besides(client, token, recipient, received).await {
+            Ok(held) if !held.is_zero() => {
+                info!(
+                    "Leaving {} tokens the wallet already held out of this position",
+                    format_units(held)?
+                );
+                position.held_back = held;
+            }
+            Ok(_) => {}
+            Err(err) => warn!("Could not check for tokens held before the buy: {:#}", err),
+        },
+    }
+    if let Err(err) = cfg.state.open(position.clone()) {
+        warn!("Failed to persist open position: {:#}", err);
+    }
+    if hints.accumulate {
+        return Ok(());
+    }
+
+    manage_position(cfg, params, client, &position, pin.as_ref(), curve.as_mut()).await
+}
+
+/// The checks every buy of `token` from `wallet` passes, however it is sent: the
+/// blocklist, pause, shutdown, wallet lockdown, peer vetoes and the daily gas budget.
+/// Cheap enough to repeat right before the send.
+pub fn ensure_entry_allowed(cfg: &AppConfig, wallet: Address, token: Address) -> Result<()> {
+    if cfg.blocklist.contains(&token) {
+        return Err(anyhow!("token {:?} is on the blocklist", token));
+    }
+    if cfg.controls.paused() {
+        return Err(anyhow!("entries are paused"));
+    }
+    if cfg.shutdown.requested() {
+        return Err(anyhow!("shutting down"));
+    }
+    if cfg.locks.contains(wallet)? {
+        return Err(anyhow!("wallet {:?} is locked down", wallet));
+    }
+    if let Some(reason) = cfg.signals.as_ref().and_then(|signals| signals.vetoed(token)) {
+        cfg.audit("entry_refused", json!({ "token": token, "reason": reason }));
+        return Err(anyhow!("refusing entry: {}", reason));
+    }
+    if let Some(budget) = &cfg.gas_budget {
+        budget.ensure_entry_allowed()?;
+    }
+    Ok(())
+}
+
+/// Tokens of `token` in `wallet` other than the `received` from the buy, such as manual
+/// buys or airdrops, which the position's sells leave alone.
+pub async fn balance_besides(
+    client: &impl ExecutionClient,
+    token: Address,
+    wallet: Address,
+    received: U256,
+) -> Result<U256> {
+    if received.is_zero() {
+        return Err(anyhow!("the buy's token transfer was not found in its receipt"));
+    }
+    let balance = client.token_balance(token, wallet).await?;
+    Ok(balance.saturating_sub(received))
+}
+
+/// Logs the buy `round_trip` would send and its projected PnL if sold straight back.
+async fn report_dry_run(
+    client: &impl ExecutionClient,
+    token: Address,
+    amount_in: U256,
+    entry: &Entry,
+) -> Result<()> {
+    let profile = chain::profile();
+    let gas_cost = entry.buy_gas * client.gas_price().await?;
+    let exit = exit_guard::simulate_exit(client, token, entry.quoted_out).await?;
+    let pnl = exit.as_u128() as f64 - amount_in.as_u128() as f64 - gas_cost.as_u128() as f64;
+
+    info!(
+        "Dry run: would buy {} with {} via {:?}, minimum {} tokens",
+        profile.address_url(token),
+        profile.format_native(amount_in),
+        entry.router,
+        format_units(entry.amount_out_min)?
+    );
+    if let Some(params) = protocol::current() {
+        info!("Dry run: protocol fee {} bps on each side", params.fee_bps());
+    }
+    info!(
+        "Dry run: buy gas {} ({}), immediate exit {}, projected PnL {:+.6} ({:+.2}%)",
+        entry.buy_gas,
+        profile.format_native(gas_cost),
+        profile.format_native(exit),
+        pnl / 1e18,
+        pnl / amount_in.as_u128() as f64 * 100.0
+    );
+    Ok(())
+}
+
+/// Holds an open position, selling each exit tranche as its target is reached and
+/// the rest of the balance once the exit rules fire.
+#[instrument(name = "position", skip_all, fields(buy_tx = ?position.buy_tx))]
+async fn manage_position(
+    cfg: &AppConfig,
+    params: &TradeParams,
+    client: &impl ExecutionClient,
+    position: &OpenPosition,
+    pin: Option<&PinWatch>,
+    curve: Option<&mut CurveTracker>,
+) -> Result<()> {
+    let token = position.token;
+    let recipient = position.wallet;
+    let mut position = position.clone();
+    if cfg.approvals.ahead && position.creator.is_some() {
+        approve_ahead(cfg, client, &mut position).await;
+    }
+
+    let mut snapshots = if params.snapshot_exits {
+        Some(SnapshotWatch::new(
+            &cfg.rpc_url,
+            token,
+            cfg.bonding_curve,
+            position.creator,
+            Duration::from_secs(cfg.snapshot_interval_secs),
+        )?)
+    } else {
+        None
+    };
+
+    let prices = cfg
+        .price_feed
+        .as_ref()
+        .filter(|_| params.venue == VenueKind::NadFun)
+        .map(|feed| feed.subscribe(token));
+    let mut stopped_out = false;
+    let mut peak_value = U256::zero();
+    let rules = LiveRules::new(params, &cfg.controls, token);
+    let strategy = WithLimitSells::new(&rules, &cfg.orders, token);
+    loop {
+        let tranche = params.tranches.get(position.filled_tranches).copied();
+        let held = tokio::select! {
+            held = exit_guard::hold(
+                client,
+                &position,
+                &strategy,
+                tranche,
+                Duration::from_secs(cfg.exit_check_interval_secs),
+                &mut peak_value,
+                exit_guard::Watches {
+                    pin,
+                    snapshots: snapshots.as_mut(),
+                    prices: prices.as_ref(),
+                },
+            ) => held,
+            _ = cfg.shutdown.wait() => {
+                if !cfg.shutdown.exit_positions {
+                    info!("Shutting down; {:?} stays open for the next start", token);
+                    return Ok(());
+                }
+                Ok(ExitDecision::Full(Exit::new(ExitKind::Shutdown, "shutting down")))
+            }
+        };
+        match held {
+            Ok(ExitDecision::Tranche(tranche)) => {
+                let unsold_pct: u64 = 100
+                    - params.tranches[..position.filled_tranches]
+                        .iter()
+                        .map(|t| t.share_pct)
+                        .sum::<u64>();
+                let balance = client
+                    .token_balance(token, recipient)
+                    .await
+                    .context("failed to fetch wallet balance")?
+                    .saturating_sub(position.held_back);
+                let amount = balance * U256::from(tranche.share_pct) / U256::from(unsold_pct);
+                info!(
+                    "Tranche {} reached at +{}%, selling {}% of the position",
+                    position.filled_tranches + 1,
+                    tranche.at_profit_pct,
+                    tranche.share_pct
+                );
+                cfg.notifier.send(
+                    Event::Exit,
+                    format!(
+                        "{:?}: tranche {} hit +{}%, selling {}%",
+                        token,
+                        position.filled_tranches + 1,
+                        tranche.at_profit_pct,
+                        tranche.share_pct
+                    ),
+                );
+                cfg.audit(
+                    "exit_tranche",
+                    json!({
+                        "token": token,
+                        "tranche": position.filled_tranches + 1,
+                        "at_profit_pct": tranche.at_profit_pct,
+                        "share_pct": tranche.share_pct,
+                    }),
+                );
+                let fill = sell(cfg, client, &position, amount).await?;
+                position.proceeds += fill.proceeds;
+                position.gas_spent += fill.gas;
+                position.router = fill.router;
+
+                position.filled_tranches += 1;
+                if let Err(err) = cfg.state.open(position.clone()) {
+                    warn!("Failed to persist tranche progress: {:#}", err);
+                }
+                if unsold_pct == tranche.share_pct {
+                    break;
+                }
+            }
+            Ok(ExitDecision::Full(exit)) => {
+                info!("Exiting position: {}", exit);
+                cfg.notifier.send(Event::Exit, format!("{:?}: exiting, {}", token, exit));
+                cfg.audit("exit", json!({ "token": token, "reason": exit.reason }));
+                stopped_out = exit.kind == ExitKind::StopLoss;
+                break;
+            }
+            Err(err) => {
+                warn!("Exit check failed while holding, selling early: {:#}", err);
+                cfg.notifier.send(
+                    Event::Error,
+                    format!("{:?}: exit check failed, selling early: {:#}", token, err),
+                );
+                cfg.audit("exit_forced", json!({ "token": token, "reason": format!("{:#}", err) }));
+                break;
+            }
+        }
+    }
+
+    let balance = client
+        .token_balance(token, recipient)
+        .await
+        .context("failed to fetch wallet balance")?
+        .saturating_sub(position.held_back);
+
+    if balance.is_zero() {
+        cfg.state.close(token, recipient)?;
+        if position.filled_tranches > 0 {
+            record_round_trip(cfg, &position);
+            return Ok(());
+        }
+        return Err(anyhow!("no balance available to sell"));
+    }
+
+    if let Some(curve) = curve {
+        report_curve_progress(curve, token).await;
+    }
+
+    let fill = match sell(cfg, client, &position, balance).await {
+        Ok(fill) => fill,
+        Err(err) => {
+            strategy.restore();
+            return Err(err);
+        }
+    };
+    position.proceeds += fill.proceeds;
+    position.gas_spent += fill.gas;
+    if let (true, Some(reentry)) = (stopped_out, &cfg.reentry) {
+        reentry.record_stop_out(token, balance, fill.proceeds);
+    }
+    record_round_trip(cfg, &position);
+    if let Err(err) = cfg.state.close(token, recipient) {
+        warn!("Failed to clear closed position: {:#}", err);
+    }
+    Ok(())
+}
+
+/// Approves the sell router for a sniped position's holdings while it is held, and adds
+/// the approval's gas to the position.
+async fn approve_ahead(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    position: &mut OpenPosition,
+) {
+    let (token, wallet) = (position.token, position.wallet);
+    let approved = async {
+        let balance = client.token_balance(token, wallet).await?;
+        let amount = balance.saturating_sub(position.held_back);
+        approvals::prepare(&cfg.approvals, client, token, wallet, amount).await
+    };
+    let approve_tx = match approved.await {
+        Ok(Some(approve_tx)) => approve_tx,
+        Ok(None) => return,
+        Err(err) => {
+            warn!("Approving the exit of {:?} ahead of time failed: {:#}", token, err);
+            return;
+        }
+    };
+    match client.receipt(approve_tx).await {
+        Ok(receipt) => position.gas_spent += receipts::gas_cost(&receipt),
+        Err(err) => warn!("Approval gas for {:?} not counted: {:#}", approve_tx, err),
+    }
+    if let Some(budget) = &cfg.gas_budget {
+        record_gas_spend(client, budget, approve_tx).await;
+    }
+    if let Err(err) = cfg.state.open(position.clone()) {
+        warn!("Failed to persist open position: {:#}", err);
+    }
+}
+
+/// What one sell returned and cost, including its approval, and the router it went through.
+struct SellFill {
+    proceeds: U256,
+    gas: U256,
+    router: Address,
+}
+
+/// Sells `amount` of the position's token, approving the router first if needed.
+async fn sell(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    position: &OpenPosition,
+    amount: U256,
+) -> Result<SellFill> {
+    let token = position.token;
+    let recipient = position.wallet;
+
+    info!(
+        "Selling {} tokens from {}",
+        format_units(amount)?,
+        chain::profile().address_url(recipient)
+    );
+
+    let mut rejected_router = None;
+    let (sell_route, sell_receipt, inclusion) = loop {
+        let sell_route = routing::resolve_sell_router(
+            &cfg.approvals,
+            client,
+            token,
+            recipient,
+            amount,
+            position.router,
+        )
+        .await?;
+        if let (Some(budget), Some(approve_tx)) = (&cfg.gas_budget, sell_route.approve_tx) {
+            record_gas_spend(client, budget, approve_tx).await;
+        }
+        if rejected_router == Some(sell_route.router) {
+            return Err(anyhow!(
+                "token {:?} is listed but quotes still route through {:?}",
+                token,
+                sell_route.router
+            ));
+        }
+
+        let submitted_at = Instant::now();
+        let params = SellParams {
+            token,
+            amount_in: amount,
+            amount_out_min: U256::zero(),
+            recipient,
+            deadline: cfg.deadline_u256(),
+        };
+        let submitted = client
+            .sell(sell_route.router, params, cfg.retry_policy.sell_tif)
+            .await;
+        traces::after_submit(cfg, "sell", &submitted).await;
+        match submitted {
+            Ok(receipt) => {
+                approvals::spent(token, sell_route.router, recipient, amount);
+                break (sell_route, receipt, submitted_at.elapsed());
+            }
+            // The token graduated between the quote and the sell.
+            Err(err) if rejected_router.is_none() && routing::is_listed_error(&err) => {
+                warn!(
+                    "Sell via {:?} rejected, token graduated to the DEX; re-resolving the router",
+                    sell_route.router
+                );
+                rejected_router = Some(sell_route.router);
+            }
+            Err(err) => {
+                // The allowance may be what failed it; read it afresh next time.
+                approvals::forget(token, sell_route.router, recipient);
+                if tx_manager::is_revert(&err) {
+                    let record = ExecRecord::reverted(
+                        token,
+                        Side::Sell,
+                        sell_route.quoted_out,
+                        submitted_at.elapsed(),
+                    );
+                    record_execution(cfg, &record);
+                }
+                return Err(err);
+            }
+        }
+    };
+    let sell_tx = sell_receipt.transaction_hash;
+
+    info!(
+        phase = "sell",
+        elapsed_ms = inclusion.as_millis() as u64,
+        gas_used = %sell_receipt.gas_used.unwrap_or_default(),
+        "Sell mined: {}",
+        chain::profile().tx_url(sell_tx)
+    );
+
+    if let Some(budget) = &cfg.gas_budget {
+        record_gas_spend(client, budget, sell_tx).await;
+    }
+
+    let mut gas = receipts::gas_cost(&sell_receipt);
+    if let Some(approve_tx) = sell_route.approve_tx {
+        match client.receipt(approve_tx).await {
+            Ok(receipt) => gas += receipts::gas_cost(&receipt),
+            Err(err) => warn!("Approval gas for {:?} not counted: {:#}", approve_tx, err),
+        }
+    }
+    let proceeds = match ledger::native_received(client, &sell_receipt, recipient).await {
+        Ok(proceeds) => proceeds,
+        Err(err) => {
+            warn!("Sell proceeds unavailable, using the quote: {:#}", err);
+            sell_route.quoted_out
+        }
+    };
+    record_execution(
+        cfg,
+        &ExecRecord::filled(token, Side::Sell, sell_route.quoted_out, proceeds, inclusion),
+    );
+    cfg.notifier.send(
+        Event::Sell,
+        format!(
+            "Sold {:?} for {}: {}",
+            token,
+            chain::profile().format_native(proceeds),
+            chain::profile().tx_url(sell_tx)
+        ),
+    );
+    cfg.audit(
+        "sell",
+        json!({
+            "token": token,
+            "wallet": recipient,
+            "tx": sell_tx,
+            "amount": amount,
+            "proceeds": proceeds,
+        }),
+    );
+    Ok(SellFill {
+        proceeds,
+        gas,
+        router: sell_route.router,
+    })
+}
+
+/// Appends the closed position to the trade ledger and mirrors it to the sink.
+fn record_round_trip(cfg: &AppConfig, position: &OpenPosition) {
+    let record = TradeRecord::closed(position);
+    info!(
+        "Round trip closed: {} in, {} out, {} gas",
+        chain::profile().format_native(record.amount_in),
+        chain::profile().format_native(record.proceeds),
+        chain::profile().format_native(record.gas_spent)
+    );
+    if let Err(err) = cfg.ledger.record(&record) {
+        warn!("Failed to record round trip: {:#}", err);
+    }
+    sink::trade(&record);
+    if let Some(signals) = &cfg.signals {
+        if let Err(err) = signals.record_outcome(record.token, record.pnl()) {
+            warn!("Failed to credit the round trip to its signal providers: {:#}", err);
+        }
+    }
+    if let Some(risk) = &cfg.risk {
+        if let Err(err) = risk.record_close(cfg, &record) {
+            warn!("Failed to record round trip against the risk limits: {:#}", err);
+        }
+    }
+    if let Some(tuning) = &cfg.sniper.tuning {
+        let tuned = cfg.ledger.load().and_then(|ledger| tuning.record_close(cfg, &ledger));
+        if let Err(err) = tuned {
+            warn!("Failed to tune the sniper filters: {:#}", err);
+        }
+    }
+}
+
+/// Appends a buy or sell to the execution log, mirrors it to the sink and alerts on drift
+/// in execution quality.
+pub fn record_execution(cfg: &AppConfig, record: &ExecRecord) {
+    if let Err(err) = cfg.exec_log.record(record) {
+        warn!("Failed to record execution: {:#}", err);
+    }
+    sink::execution(record);
+    if let Some(watch) = &cfg.exec_anomaly {
+        match cfg.exec_log.load() {
+            Ok(records) => {
+                for alert in watch.check(&records) {
+                    warn!("{}", alert);
+                    cfg.notifier.send(Event::Error, alert);
+                }
+            }
+            Err(err) => warn!("Failed to check execution quality: {:#}", err),
+        }
+    }
+}
+
+/// Picks up positions a previous run left open for this wallet and sees them through to the sell.
+pub async fn resume_positions(cfg: &AppConfig, client: &impl ExecutionClient) -> Result<()> {
+    let wallet = cfg.recipient.unwrap_or_else(|| client.wallet());
+    let positions: Vec<OpenPosition> = cfg
+        .state
+        .open_positions()?
+        .into_iter()
+        .filter(|position| position.wallet == wallet)
+        .filter(|position| {
+            if position.accumulating {
+                info!("Leaving {:?} to its DCA schedule; run `dca` to continue it", position.token);
+            }
+            !position.accumulating
+        })
+        .collect();
+    if positions.is_empty() {
+        return Ok(());
+    }
+    info!("Resuming {} open positions", positions.len());
+    if cfg.dry_run {
+        for position in &positions {
+            info!("Dry run: leaving {:?} open", position.token);
+        }
+        return Ok(());
+    }
+
+    let results = futures_util::future::join_all(positions.iter().map(|position| async move {
+        info!(
+            "Resuming {:?} from {} {}s ago at {:.3e} per token",
+            position.token,
+            position.origin(),
+            position.held_for().as_secs(),
+            position.entry_price()
+        );
+        hold_position(cfg, client, position).await
+    }))
+    .await;
+
+    for (position, result) in positions.iter().zip(results) {
+        if let Err(err) = result {
+            warn!("Resumed position {:?} failed: {:#}", position.token, err);
+        }
+    }
+    Ok(())
+}
+
+/// Manages an already open position with its token's params until it is sold.
+pub async fn hold_position(
+    cfg: &AppConfig,
+    client: &impl ExecutionClient,
+    position: &OpenPosition,
+) -> Result<()> {
+    let pin = match cfg.pin_policy {
+        Some(policy) => Some(PinWatch::pin(&cfg.rpc_url, position.token, policy).await?),
+        None => None,
+    };
+    let mut curve = cfg
+        .bonding_curve
+        .map(|address| CurveTracker::new(&cfg.rpc_url, address))
+        .transpose()?;
+    let params = cfg.params_for(position.token);
+    manage_position(cfg, params, client, position, pin.as_ref(), curve.as_mut()).await
+}
+
+pub fn apply_slippage(amount: U256, slippage_bps: u64) -> U256 {
+    let basis: U256 = U256::from(10_000u64);
+    let slip: U256 = U256::from(slippage_bps);
+    amount * (basis - slip) / basis
+}
+
+async fn report_curve_progress(curve: &mut CurveTracker, token: Address) {
+    match curve.observe(token).await {
+        Ok(progress) if progress.state.graduated => {
+            info!("Curve for {} has graduated", token);
+        }
+        Ok(progress) => {
+            let rate = progress
+                .pct_per_min
+                .map(|rate| format!(", {:+.2}%/min", rate))
+                .unwrap_or_default();
+            info!(
+                "Curve progress {:.2}%, {} to graduation{}",
+                progress.pct,
+                chain::profile().format_native(progress.mon_to_graduation),
+                rate
+            );
+        }
+        Err(err) => warn!("Curve progress unavailable: {:#}", err),
+    }
+}
+
+async fn report_mev_impact(
+    rpc_url: &str,
+    token: Address,
+    tx_hash: H256,
+    recipient: Address,
+    quoted_out: U256,
+    path: &Path,
+) {
+    let provider = match Provider::<Http>::try_from(rpc_url) {
+        Ok(provider) => provider,
+        Err(err) => {
+            warn!("MEV analysis failed: {:#}", err);
+            return;
+        }
+    };
+    match mev::analyze(&provider, tx_hash, token, recipient, quoted_out).await {
+        Ok(report) => {
+            if let Some(attacker) = report.sandwiched_by {
+                info!(
+                    "Buy was sandwiched by {:?} in block {}, cost {} bps vs quote",
+                    attacker, report.block, report.cost_bps
+                );
+            } else if !report.front_runners.is_empty() {
+                info!(
+                    "Buy was preceded by {} other trades on the token in block {}, cost {} bps vs quote",
+                    report.front_runners.len(),
+                    report.block,
+                    report.cost_bps
+                );
+            } else {
+                info!("No front-running detected, cost {} bps vs quote", report.cost_bps);
+            }
+
+            if let Err(err) = mev::append_report(path, &report) {
+                warn!("Failed to record MEV report: {:#}", err);
+            }
+            if let Ok(stats) = mev::load_stats(path) {
+                info!(
+                    "MEV history: {} trades, {} front-run, {} sandwiched, avg cost {:.1} bps",
+                    stats.trades, stats.front_run, stats.sandwiched, stats.avg_cost_bps
+                );
+            }
+        }
+        Err(err) => warn!("MEV analysis failed: {:#}", err),
+    }
+}
+
+async fn record_gas_spend(client: &impl ExecutionClient, budget: &GasBudget, tx_hash: H256) {
+    match client.receipt(tx_hash).await {
+        Ok(receipt) => record_gas(budget, &receipt).await,
+        Err(err) => warn!("Gas spend for {:?} not recorded: {:#}", tx_hash, err),
+    }
+}
+
+/// Adds the gas `receipt` paid to today's budget.
+pub async fn record_gas(budget: &GasBudget, receipt: &TransactionReceipt) {
+    match budget.record(receipts::gas_cost(receipt)).await {
+        Ok(total) => info!(
+            "Gas spent today: {}",
+            chain::profile().format_native(total)
+        ),
+        Err(err) => warn!(
+            "Gas spend for {:?} not recorded: {:#}",
+            receipt.transaction_hash, err
+        ),
+    }
+}
+
+fn format_units(value: U256) -> Result<String> {
+    Ok(ethers::utils::format_units(value, 18)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slippage_takes_basis_points_off_the_amount() {
+        let amount = U256::from(1_000_000u64);
+        assert_eq!(apply_slippage(amount, 0), amount);
+        assert_eq!(apply_slippage(amount, 100), U256::from(990_000u64));
+        assert_eq!(apply_slippage(amount, 10_000), U256::zero());
+        assert_eq!(apply_slippage(U256::from(999u64), 1), U256::from(998u64));
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Subcommand};
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+use crate::app::AppConfig;
+use crate::chain;
+use crate::file_lock;
+use crate::ledger::TradeRecord;
+use crate::state;
+
+/// Exploration trades past a filter it takes before their outcomes may loosen it.
+const MIN_EXPLORATIONS: usize = 3;
+
+#[derive(Debug, Args)]
+pub struct TuningArgs {
+    #[command(subcommand)]
+    pub action: TuningAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TuningAction {
+    /// Show the tuned thresholds and every adjustment made to them.
+    Show,
+    /// Undo the most recent adjustments, newest first.
+    Revert {
+        #[arg(long, default_value_t = 1, conflicts_with = "all")]
+        steps: usize,
+        /// Undo every adjustment, back to the configured thresholds.
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+/// A sniper threshold the tuner moves. The names match the `exploration` tags the
+/// ledger gives trades let past a filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    /// `SNIPER_MIN_LIQUIDITY_MON`, in MON.
+    MinLiquidity,
+    /// `SNIPER_MIN_SCORE`.
+    MinScore,
+}
+
+impl Threshold {
+    fn name(self) -> &'static str {
+        match self {
+            Threshold::MinLiquidity => "min_liquidity",
+            Threshold::MinScore => "min_score",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Adjustment {
+    at: u64,
+    filter: String,
+    from: f64,
+    to: f64,
+    reason: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Tuned {
+    #[serde(default)]
+    values: BTreeMap<String, f64>,
+    /// Ledger length at the last evaluation, so each window of closes is judged once.
+    #[serde(default)]
+    closes_seen: usize,
+    #[serde(default)]
+    history: Vec<Adjustment>,
+}
+
+/// Bounds one filter may be tuned within, and the threshold it starts from.
+#[derive(Debug, Clone, Copy)]
+struct Tunable {
+    filter: Threshold,
+    configured: f64,
+    low: f64,
+    high: f64,
+}
+
+/// Moves the sniper's liquidity and score thresholds within configured bounds as round
+/// trips close. Every `TUNE_WINDOW_TRADES` closes, a win rate of the window's regular
+/// trades under `TUNE_TARGET_WIN_RATE` raises every tuned threshold by `TUNE_STEP_PCT`.
+/// Otherwise a threshold is lowered by a step when the exploration trades let past it
+/// won at the target rate, or, for the score, which explorations don't test, when the
+/// regular trades beat the target by `TUNE_LOOSEN_MARGIN`. Adjustments go to the log,
+/// the audit log and the history in `FILTER_TUNING_FILE`, and `tuning revert` undoes
+/// them.
+pub struct FilterTuning {
+    path: PathBuf,
+    tunables: Vec<Tunable>,
+    window: usize,
+    step_pct: f64,
+    target_win_rate: f64,
+    loosen_margin: f64,
+}
+
+impl FilterTuning {
+    /// Enabled by `TUNE_MIN_LIQUIDITY_BOUNDS` or `TUNE_MIN_SCORE_BOUNDS`, each `low,high`,
+    /// for a filter that is configured too.
+    pub fn from_env(min_liquidity: Option<U256>, min_score: Option<f64>) -> Result<Option<Self>> {
+        let parsed = |name: &str, default: f64| -> Result<f64> {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {name}")))
+                .transpose()
+                .map(|value| value.unwrap_or(default))
+        };
+        let bounds = |name: &str| -> Result<Option<(f64, f64)>> {
+            let Ok(value) = env::var(name) else {
+                return Ok(None);
+            };
+            let parsed = value.split_once(',').and_then(|(low, high)| {
+                Some((low.trim().parse().ok()?, high.trim().parse().ok()?))
+            });
+            match parsed {
+                Some((low, high)) if low <= high => Ok(Some((low, high))),
+                _ => Err(anyhow!("{name} must be low,high")),
+            }
+        };
+
+        let mut tunables = Vec::new();
+        let filters = [
+            (
+                Threshold::MinLiquidity,
+                "TUNE_MIN_LIQUIDITY_BOUNDS",
+                "SNIPER_MIN_LIQUIDITY_MON",
+                min_liquidity.map(|min| chain::profile().native_f64(min)),
+            ),
+            (
+                Threshold::MinScore,
+                "TUNE_MIN_SCORE_BOUNDS",
+                "SNIPER_MIN_SCORE",
+                min_score,
+            ),
+        ];
+        for (filter, bounds_var, configured_var, configured) in filters {
+            let Some((low, high)) = bounds(bounds_var)? else {
+                continue;
+            };
+            let configured = configured
+                .ok_or_else(|| anyhow!("{bounds_var} needs {configured_var} to be set"))?;
+            tunables.push(Tunable {
+                filter,
+                configured: configured.clamp(low, high),
+                low,
+                high,
+            });
+        }
+        if tunables.is_empty() {
+            return Ok(None);
+        }
+        let step_pct = parsed("TUNE_STEP_PCT", 10.0)?;
+        if !(0.0..100.0).contains(&step_pct) {
+            return Err(anyhow!("TUNE_STEP_PCT must be between 0 and 100"));
+        }
+        Ok(Some(Self {
+            path: tuning_path(),
+            tunables,
+            window: (parsed("TUNE_WINDOW_TRADES", 20.0)? as usize).max(1),
+            step_pct,
+            target_win_rate: parsed("TUNE_TARGET_WIN_RATE", 0.5)?,
+            loosen_margin: parsed("TUNE_LOOSEN_MARGIN", 0.2)?,
+        }))
+    }
+
+    /// The filter's tuned threshold, or `configured` if it isn't tuned.
+    pub fn threshold(&self, filter: Threshold, configured: f64) -> f64 {
+        if !self.tunables.iter().any(|tunable| tunable.filter == filter) {
+            return configured;
+        }
+        match load(&self.path) {
+            Ok(tuned) => tuned
+                .values
+                .get(filter.name())
+                .copied()
+                .unwrap_or(configured),
+            Err(_) => configured,
+        }
+    }
+
+    /// [`threshold`](Self::threshold) for the liquidity filter, in wei.
+    pub fn min_liquidity(&self, configured: U256) -> U256 {
+        let profile = chain::profile();
+        let tuned = self.threshold(Threshold::MinLiquidity, profile.native_f64(configured));
+        profile
+            .parse_native(&format!("{tuned:.9}"))
+            .unwrap_or(configured)
+    }
+
+    /// Judges the latest window of closed round trips once it is full, moving the
+    /// thresholds it calls for.
+    pub fn record_close(&self, cfg: &AppConfig, ledger: &[TradeRecord]) -> Result<()> {
+        let adjustments = {
+            let _lock = file_lock::exclusive(&self.path)?;
+            let mut tuned = load(&self.path)?;
+            if ledger.len() < tuned.closes_seen + self.window {
+                return Ok(());
+            }
+            tuned.closes_seen = ledger.len();
+            let recent = &ledger[ledger.len() - self.window..];
+            let adjustments = self.adjust(&mut tuned, recent);
+            save(&self.path, &tuned)?;
+            adjustments
+        };
+        for adjustment in adjustments {
+            info!(
+                "Tuned {} from {} to {}: {}",
+                adjustment.filter, adjustment.from, adjustment.to, adjustment.reason
+            );
+            cfg.audit("filter_tuned", json!(adjustment));
+        }
+        Ok(())
+    }
+
+    fn adjust(&self, tuned: &mut Tuned, recent: &[TradeRecord]) -> Vec<Adjustment> {
+        let regular: Vec<&TradeRecord> = recent
+            .iter()
+            .filter(|record| record.exploration.is_none())
+            .collect();
+        let regular_rate = win_rate(&regular);
+        let mut adjustments = Vec::new();
+        for tunable in &self.tunables {
+            let name = tunable.filter.name();
+            let current = tuned
+                .values
+                .get(name)
+                .copied()
+                .unwrap_or(tunable.configured);
+            let explored: Vec<&TradeRecord> = recent
+                .iter()
+                .filter(|record| record.exploration.as_deref() == Some(name))
+                .collect();
+            let explored_rate = win_rate(&explored).filter(|_| explored.len() >= MIN_EXPLORATIONS);
+
+            let (to, reason) = match (regular_rate, explored_rate) {
+                (Some(rate), _) if rate < self.target_win_rate => (
+                    current * (1.0 + self.step_pct / 100.0),
+                    format!("regular trades won {:.0}%, under target", rate * 100.0),
+                ),
+                (_, Some(rate)) if rate >= self.target_win_rate => (
+                    current * (1.0 - self.step_pct / 100.0),
+                    format!("trades let past it won {:.0}%", rate * 100.0),
+                ),
+                (Some(rate), _)
+                    if tunable.filter == Threshold::MinScore
+                        && rate >= self.target_win_rate + self.loosen_margin =>
+                {
+                    (
+                        current * (1.0 - self.step_pct / 100.0),
+                        format!("regular trades won {:.0}%, well over target", rate * 100.0),
+                    )
+                }
+                _ => continue,
+            };
+            let to = to.clamp(tunable.low, tunable.high);
+            if to == current {
+                continue;
+            }
+            tuned.values.insert(name.to_string(), to);
+            let adjustment = Adjustment {
+                at: state::unix_now(),
+                filter: name.to_string(),
+                from: current,
+                to,
+                reason,
+            };
+            tuned.history.push(adjustment.clone());
+            adjustments.push(adjustment);
+        }
+        adjustments
+    }
+}
+
+fn win_rate(records: &[&TradeRecord]) -> Option<f64> {
+    if records.is_empty() {
+        return None;
+    }
+    let wins = records
+        .iter()
+        .filter(|record| record.pnl().is_positive())
+        .count();
+    Some(wins as f64 / records.len() as f64)
+}
+
+fn tuning_path() -> PathBuf {
+    PathBuf::from(env::var("FILTER_TUNING_FILE").unwrap_or_else(|_| "filter_tuning.json".into()))
+}
+
+fn load(path: &PathBuf) -> Result<Tuned> {
+    match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json)
+            .with_context(|| format!("corrupt filter tuning file {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Tuned::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn save(path: &PathBuf, tuned: &Tuned) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(tuned)?)
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))
+}
+
+/// Undoes the newest `steps` adjustments; a filter with none left goes back to its
+/// configured threshold.
+fn revert(tuned: &mut Tuned, steps: usize) -> Vec<Adjustment> {
+    let keep = tuned.history.len().saturating_sub(steps);
+    let reverted: Vec<Adjustment> = tuned.history.drain(keep..).rev().collect();
+    for adjustment in &reverted {
+        tuned
+            .values
+            .insert(adjustment.filter.clone(), adjustment.from);
+    }
+    tuned.values.retain(|filter, _| {
+        tuned
+            .history
+            .iter()
+            .any(|adjustment| &adjustment.filter == filter)
+    });
+    reverted
+}
+
+pub fn run(args: &TuningArgs) -> Result<()> {
+    let path = tuning_path();
+    let _lock = file_lock::exclusive(&path)?;
+    let mut tuned = load(&path)?;
+    match args.action {
+        TuningAction::Show => {
+            if tuned.values.is_empty() {
+                println!("No thresholds tuned; the configured ones apply");
+            }
+            for (filter, value) in &tuned.values {
+                println!("{filter}: {value}");
+            }
+            for adjustment in &tuned.history {
+                println!(
+                    "  {} {}: {} -> {} ({})",
+                    adjustment.at,
+                    adjustment.filter,
+                    adjustment.from,
+                    adjustment.to,
+                    adjustment.reason
+                );
+            }
+        }
+        TuningAction::Revert { steps, all } => {
+            let steps = if all { tuned.history.len() } else { steps };
+            let reverted = revert(&mut tuned, steps);
+            save(&path, &tuned)?;
+            for adjustment in &reverted {
+                println!(
+                    "Reverted {} from {} back to {}",
+                    adjustment.filter, adjustment.to, adjustment.from
+                );
+            }
+            if reverted.is_empty() {
+                println!("Nothing to revert");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::Address;
+
+    fn tuning() -> FilterTuning {
+        FilterTuning {
+            path: PathBuf::new(),
+            tunables: vec![
+                Tunable {
+                    filter: Threshold::MinLiquidity,
+                    configured: 2.0,
+                    low: 1.0,
+                    high: 2.2,
+                },
+                Tunable {
+                    filter: Threshold::MinScore,
+                    configured: 0.5,
+                    low: 0.3,
+                    high: 0.8,
+                },
+            ],
+            window: 4,
+            step_pct: 10.0,
+            target_win_rate: 0.5,
+            loosen_margin: 0.2,
+        }
+    }
+
+    fn trade(win: bool, exploration: Option<&str>) -> TradeRecord {
+        TradeRecord {
+            token: Address::zero(),
+            wallet: Address::zero(),
+            buy_tx: None,
+            opened_at: 0,
+            closed_at: 0,
+            amount_in: U256::from(100),
+            proceeds: U256::from(if win { 150 } else { 50 }),
+            gas_spent: U256::zero(),
+            exploration: exploration.map(str::to_string),
+            strategy: None,
+        }
+    }
+
+    #[test]
+    fn losing_regular_trades_tighten_every_filter_within_its_bounds() {
+        let mut tuned = Tuned::default();
+        let recent = [trade(false, None), trade(false, None), trade(true, None)];
+        let adjustments = tuning().adjust(&mut tuned, &recent);
+        assert_eq!(adjustments.len(), 2);
+        assert_eq!(tuned.values["min_liquidity"], 2.2);
+        assert!((tuned.values["min_score"] - 0.55).abs() < 1e-9);
+    }
+
+    #[test]
+    fn winning_explorations_loosen_only_the_filter_they_were_let_past() {
+        let mut tuned = Tuned::default();
+        let explored = Some("min_liquidity");
+        let recent = [
+            trade(true, None),
+            trade(false, None),
+            trade(true, explored),
+            trade(true, explored),
+            trade(false, explored),
+        ];
+        let adjustments = tuning().adjust(&mut tuned, &recent);
+        assert_eq!(adjustments.len(), 1);
+        assert!((tuned.values["min_liquidity"] - 1.8).abs() < 1e-9);
+        assert!(!tuned.values.contains_key("min_score"));
+    }
+
+    #[test]
+    fn reverting_restores_the_previous_thresholds() {
+        let mut tuned = Tuned::default();
+        let tuning = tuning();
+        tuning.adjust(&mut tuned, &[trade(false, None)]);
+        tuning.adjust(&mut tuned, &[trade(false, None)]);
+        assert_eq!(tuned.history.len(), 3);
+
+        let reverted = revert(&mut tuned, 1);
+        assert_eq!(reverted[0].filter, "min_score");
+        assert!((tuned.values["min_score"] - 0.55).abs() < 1e-9);
+        revert(&mut tuned, 5);
+        assert!(tuned.values.is_empty() && tuned.history.is_empty());
+    }
+}
@@ -0,0 +1,543 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use anyhow::{anyhow, Context, Result};
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{
+    Address, BlockNumber, Bytes, Eip1559TransactionRequest, TransactionReceipt, TransactionRequest,
+    H256, U256,
+};
+use tokio::time::{Duration, Instant};
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::chain;
+use crate::nadfun::TxOptions;
+use crate::receipts;
+use crate::telemetry;
+
+static SEND_LOCKS: OnceLock<Mutex<HashMap<Address, Arc<tokio::sync::Mutex<()>>>>> = OnceLock::new();
+
+/// Serializes nonce assignment for `address` across every task trading from it:
+/// hold the guard from reading the pending nonce until the transaction is broadcast.
+pub fn send_lock(address: Address) -> Arc<tokio::sync::Mutex<()>> {
+    let locks = SEND_LOCKS.get_or_init(Default::default);
+    let mut locks = locks.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    locks.entry(address).or_default().clone()
+}
+
+/// How long a submission may keep trying before it is given up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeInForce {
+    /// Up to `TX_MAX_ATTEMPTS`, each waiting `TX_CONFIRM_TIMEOUT_SECS` to be mined.
+    Retry,
+    /// Immediate-or-cancel: one attempt, cancelled if not mined within this many blocks.
+    Ioc(u64),
+    /// Good-til-date: keep cancelling and resending until this long after submission.
+    Gtd(Duration),
+    /// Fill-or-kill: one attempt that must be mined in the next block. Legs sent as
+    /// separate transactions can't be made atomic, so this applies per transaction.
+    Fok,
+}
+
+impl FromStr for TimeInForce {
+    type Err = anyhow::Error;
+
+    /// `retry`, `ioc:<blocks>`, `gtd:<secs>` or `fok`.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_ascii_lowercase();
+        let (kind, arg) = s.split_once(':').unwrap_or((s.as_str(), ""));
+        let number = || -> Result<u64> {
+            arg.parse()
+                .with_context(|| format!("time in force `{s}` needs a number after `:`"))
+        };
+        match kind {
+            "retry" => Ok(Self::Retry),
+            "ioc" => Ok(Self::Ioc(number()?.max(1))),
+            "gtd" => Ok(Self::Gtd(Duration::from_secs(number()?))),
+            "fok" => Ok(Self::Fok),
+            other => Err(anyhow!(
+                "unknown time in force `{other}` (expected retry, ioc:<blocks>, gtd:<secs> or fok)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TimeInForce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Retry => write!(f, "retry"),
+            Self::Ioc(blocks) => write!(f, "ioc:{blocks}"),
+            Self::Gtd(until) => write!(f, "gtd:{}", until.as_secs()),
+            Self::Fok => write!(f, "fok"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub confirm_timeout: Duration,
+    pub bump_pct: u64,
+    /// Blocks a transaction must be buried under, counting its own, before it counts as final.
+    pub confirmations: u64,
+    pub buy_tif: TimeInForce,
+    pub sell_tif: TimeInForce,
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> Result<Self> {
+        let number = |name: &str, default: u64| -> u64 {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        let tif = |name: &str| -> Result<TimeInForce> {
+            env::var(name)
+                .ok()
+                .map(|v| v.parse().with_context(|| format!("invalid {name}")))
+                .transpose()
+                .map(|tif| tif.unwrap_or(TimeInForce::Retry))
+        };
+        Ok(Self {
+            max_attempts: number("TX_MAX_ATTEMPTS", 3).max(1) as u32,
+            base_delay: Duration::from_millis(number("TX_RETRY_BASE_MS", 500)),
+            confirm_timeout: Duration::from_secs(number("TX_CONFIRM_TIMEOUT_SECS", 30)),
+            bump_pct: number("TX_BUMP_PCT", 130),
+            confirmations: number("TX_CONFIRMATIONS", 1).max(1),
+            buy_tif: tif("BUY_TIME_IN_FORCE")?,
+            sell_tif: tif("SELL_TIME_IN_FORCE")?,
+        })
+    }
+}
+
+/// The chain calls a [`TxManager`] makes to confirm and cancel what it sends. The bot
+/// uses [`SignerChain`]; an `ExecutionClient` over another chain can implement this to get
+/// the same retries and time in force.
+pub trait TxChain: Sync {
+    /// The wallet transactions are sent from.
+    fn address(&self) -> Address;
+
+    /// The wallet's next nonce after its mined transactions, or after its pending ones too.
+    fn nonce(&self, pending: bool) -> impl Future<Output = Result<U256>> + Send;
+
+    fn gas_price(&self) -> impl Future<Output = Result<U256>> + Send;
+
+    fn block_number(&self) -> impl Future<Output = Result<u64>> + Send;
+
+    /// The receipt of `tx_hash`, or `None` while it isn't mined.
+    fn transaction_receipt(
+        &self,
+        tx_hash: H256,
+    ) -> impl Future<Output = Result<Option<TransactionReceipt>>> + Send;
+
+    /// The most `tx_hash` offered to pay per gas, if the node knows the transaction.
+    fn offered_fee(&self, tx_hash: H256) -> impl Future<Output = Result<Option<U256>>> + Send;
+
+    /// Sends a 0-value self-transfer at `nonce`, to replace what is pending there.
+    fn send_cancel(&self, nonce: U256, gas_price: U256)
+        -> impl Future<Output = Result<H256>> + Send;
+
+    /// Why the transaction of `receipt` reverted, where the node can tell.
+    fn revert_reason(
+        &self,
+        receipt: &TransactionReceipt,
+    ) -> impl Future<Output = Option<String>> + Send;
+}
+
+/// The configured wallet, over `RPC_URL`.
+pub struct SignerChain {
+    client: SignerMiddleware<Provider<Http>, LocalWallet>,
+    address: Address,
+}
+
+impl SignerChain {
+    pub fn new(provider: &Provider<Http>, private_key: &str) -> Result<Self> {
+        let wallet = private_key
+            .parse::<LocalWallet>()
+            .context("invalid PRIVATE_KEY")?
+            .with_chain_id(chain::profile().chain_id);
+        let address = wallet.address();
+        Ok(Self {
+            client: SignerMiddleware::new(provider.clone(), wallet),
+            address,
+        })
+    }
+}
+
+impl SignerChain {
+    /// Sends `value` of native to `to` at `nonce`.
+    pub async fn send_value(
+        &self,
+        to: Address,
+        value: U256,
+        nonce: U256,
+        gas_price: U256,
+    ) -> Result<H256> {
+        let tx = TransactionRequest::new()
+            .to(to)
+            .value(value)
+            .nonce(nonce)
+            .gas(21_000u64)
+            .gas_price(gas_price);
+        Ok(self.client.send_transaction(tx, None).await?.tx_hash())
+    }
+
+    /// Sends a call of `data` with `value` to the contract at `to`, at the nonce and gas of
+    /// `tx`.
+    pub async fn send_call(
+        &self,
+        to: Address,
+        data: Bytes,
+        value: U256,
+        tx: TxOptions,
+    ) -> Result<H256> {
+        let request = Eip1559TransactionRequest::new()
+            .to(to)
+            .data(data)
+            .value(value)
+            .nonce(tx.nonce)
+            .gas(tx.gas_limit)
+            .max_fee_per_gas(tx.fees.max_fee)
+            .max_priority_fee_per_gas(tx.fees.priority_fee);
+        Ok(self.client.send_transaction(request, None).await?.tx_hash())
+    }
+}
+
+impl TxChain for SignerChain {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn nonce(&self, pending: bool) -> Result<U256> {
+        let block = if pending {
+            BlockNumber::Pending
+        } else {
+            BlockNumber::Latest
+        };
+        Ok(self
+            .client
+            .get_transaction_count(self.address, Some(block.into()))
+            .await?)
+    }
+
+    async fn gas_price(&self) -> Result<U256> {
+        Ok(self.client.get_gas_price().await?)
+    }
+
+    async fn block_number(&self) -> Result<u64> {
+        Ok(self.client.get_block_number().await?.as_u64())
+    }
+
+    async fn transaction_receipt(&self, tx_hash: H256) -> Result<Option<TransactionReceipt>> {
+        Ok(self.client.get_transaction_receipt(tx_hash).await?)
+    }
+
+    async fn offered_fee(&self, tx_hash: H256) -> Result<Option<U256>> {
+        let tx = self.client.get_transaction(tx_hash).await?;
+        Ok(tx.and_then(|tx| tx.max_fee_per_gas.or(tx.gas_price)))
+    }
+
+    async fn send_cancel(&self, nonce: U256, gas_price: U256) -> Result<H256> {
+        let tx = TransactionRequest::new()
+            .to(self.address)
+            .value(U256::zero())
+            .nonce(nonce)
+            .gas(21_000u64)
+            .gas_price(gas_price);
+        Ok(self.client.send_transaction(tx, None).await?.tx_hash())
+    }
+
+    async fn revert_reason(&self, receipt: &TransactionReceipt) -> Option<String> {
+        receipts::revert_reason(self.client.inner(), receipt).await
+    }
+}
+
+/// Submits transactions until they are mined: transient RPC and nonce errors are
+/// retried with exponential backoff, and a submission that isn't mined in time is
+/// cancelled by replacing its nonce at a bumped gas price before trying again.
+pub struct TxManager<'a, C = SignerChain> {
+    chain: &'a C,
+    policy: &'a RetryPolicy,
+}
+
+impl<'a, C: TxChain> TxManager<'a, C> {
+    pub fn new(chain: &'a C, policy: &'a RetryPolicy) -> Self {
+        Self { chain, policy }
+    }
+
+    pub async fn submit<F, Fut>(
+        &self,
+        label: &str,
+        tif: TimeInForce,
+        send: F,
+    ) -> Result<TransactionReceipt>
+    where
+        F: Fn(U256) -> Fut,
+        Fut: Future<Output = Result<H256>>,
+    {
+        let started = Instant::now();
+        let confirm_within = match tif {
+            TimeInForce::Retry => self.policy.confirm_timeout,
+            TimeInForce::Ioc(blocks) => chain::profile().block_time * blocks as u32,
+            TimeInForce::Gtd(until) => self.policy.confirm_timeout.min(until),
+            TimeInForce::Fok => chain::profile().block_time,
+        };
+        let mut delay = self.policy.base_delay;
+        let lock = send_lock(self.chain.address());
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let last_attempt = match tif {
+                TimeInForce::Retry => attempt >= self.policy.max_attempts,
+                TimeInForce::Ioc(_) | TimeInForce::Fok => true,
+                TimeInForce::Gtd(until) => started.elapsed() + confirm_within + delay >= until,
+            };
+
+            let guard = lock.lock().await;
+            let nonce = self.chain.nonce(true).await?;
+            let sent = send(nonce).await;
+            // Checked before releasing the lock, so only our own send can have used it.
+            let nonce_used = match &sent {
+                Ok(_) => true,
+                Err(_) => self.chain.nonce(true).await? > nonce,
+            };
+            drop(guard);
+
+            match sent {
+                Ok(tx_hash) => {
+                    match self
+                        .wait_mined(tx_hash, confirm_within)
+                        .instrument(info_span!("confirm", tx = ?tx_hash))
+                    .await
+                    {
+                        Ok(receipt) => return self.confirm(label, receipt).await,
+                        Err(_) => {
+                            info!(
+                                "{} {:?} not mined after {:?}, cancelling nonce {}",
+                                label, tx_hash, confirm_within, nonce
+                            );
+                            if let Some(receipt) = self.cancel(nonce, tx_hash).await? {
+                                return self.confirm(label, receipt).await;
+                            }
+                            if last_attempt {
+                                return Err(anyhow!(
+                                    "{} not filled within its time in force ({}), cancelled",
+                                    label,
+                                    tif
+                                ));
+                            }
+                        }
+                    }
+                }
+                Err(err) => {
+                    let class = telemetry::classify(&err);
+                    let retryable = matches!(class, "nonce" | "timeout" | "rpc");
+                    if !retryable || last_attempt {
+                        return Err(err);
+                    }
+                    // A timed-out RPC call may still have broadcast the transaction;
+                    // resending then would trade twice.
+                    if nonce_used {
+                        return Err(err.context(format!(
+                            "{} outcome unknown: nonce {} was used, not retrying",
+                            label, nonce
+                        )));
+                    }
+                    warn!(
+                        "{} attempt {} failed ({}): {:#}, retrying in {:?}",
+                        label, attempt, class, err, delay
+                    );
+                }
+            }
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+
+    /// The receipt of `tx_hash` once it is mined, or an error if it isn't within `timeout`.
+    async fn wait_mined(&self, tx_hash: H256, timeout: Duration) -> Result<TransactionReceipt> {
+        let until = Instant::now() + timeout;
+        loop {
+            if let Some(receipt) = self.chain.transaction_receipt(tx_hash).await? {
+                return Ok(receipt);
+            }
+            if Instant::now() >= until {
+                return Err(anyhow!("no receipt for {:?} after {:?}", tx_hash, timeout));
+            }
+            tokio::time::sleep(chain::profile().block_poll_interval()).await;
+        }
+    }
+
+    /// Fails on a revert, with its reason where the node gives one, then waits for
+    /// `confirmations` blocks and makes sure the receipt survived any reorg meanwhile.
+    async fn confirm(&self, label: &str, receipt: TransactionReceipt) -> Result<TransactionReceipt> {
+        let tx_hash = receipt.transaction_hash;
+        let block = receipt.block_number.unwrap_or_default().as_u64();
+        if receipt.status != Some(1u64.into()) {
+            let reason = self
+                .chain
+                .revert_reason(&receipt)
+                .await
+                .unwrap_or_else(|| "no reason given".into());
+            return Err(anyhow!(
+                "{} {:?} reverted in block {}: {}",
+                label,
+                tx_hash,
+                block,
+                reason
+            ));
+        }
+        if self.policy.confirmations <= 1 {
+            return Ok(receipt);
+        }
+
+        let target = block + self.policy.confirmations - 1;
+        while self.chain.block_number().await? < target {
+            tokio::time::sleep(chain::profile().block_poll_interval()).await;
+        }
+        match self.chain.transaction_receipt(tx_hash).await? {
+            Some(confirmed) if confirmed.block_hash == receipt.block_hash => Ok(confirmed),
+            _ => Err(anyhow!(
+                "{} {:?} was reorged out of block {} before {} confirmations",
+                label,
+                tx_hash,
+                block,
+                self.policy.confirmations
+            )),
+        }
+    }
+
+    /// Replaces `nonce` with a 0-value self-transfer and waits until a transaction at it
+    /// is mined, bumping the replacement each `confirm_timeout` it isn't. Returns the
+    /// receipt of `original` if that is what got mined, `None` if the cancellation was.
+    ///
+    /// The nonce stays pinned until then: sending anything else first could see both the
+    /// original and the retry filled.
+    async fn cancel(&self, nonce: U256, original: H256) -> Result<Option<TransactionReceipt>> {
+        let mut gas_price = self.chain.gas_price().await?;
+        // A replacement has to outbid what the original offered, not just the going price.
+        if let Some(offered) = self.chain.offered_fee(original).await? {
+            gas_price = gas_price.max(offered);
+        }
+        let mut cancels = Vec::new();
+        for _ in 0..self.policy.max_attempts {
+            if let Some(receipt) = self.chain.transaction_receipt(original).await? {
+                return Ok(Some(receipt));
+            }
+            gas_price = gas_price * U256::from(self.policy.bump_pct) / U256::from(100u64);
+            match self.chain.send_cancel(nonce, gas_price).await {
+                Ok(cancel) => cancels.push(cancel),
+                // The nonce is taken or the node holds a pricier replacement; either way
+                // the transactions already sent at it are what to wait for.
+                Err(err) if is_nonce_taken(&format!("{:#}", err)) => {
+                    info!("Cancellation of nonce {} not sent: {}", nonce, err);
+                }
+                Err(err) => return Err(err.context(format!("failed to cancel nonce {}", nonce))),
+            }
+
+            let deadline = Instant::now() + self.policy.confirm_timeout;
+            while Instant::now() < deadline {
+                if self.chain.nonce(false).await? > nonce {
+                    return self.mined_at(nonce, original, &cancels).await;
+                }
+                tokio::time::sleep(chain::profile().block_poll_interval()).await;
+            }
+            info!("Nothing at nonce {} mined yet, bumping the cancellation", nonce);
+        }
+        Err(anyhow!(
+            "nonce {} still pending after {} cancellations; check {:?} before trading again",
+            nonce,
+            self.policy.max_attempts,
+            original
+        ))
+    }
+
+    /// Which of `original` and `cancels` was mined at `nonce`, once one of them was.
+    async fn mined_at(
+        &self,
+        nonce: U256,
+        original: H256,
+        cancels: &[H256],
+    ) -> Result<Option<TransactionReceipt>> {
+        // A receipt can lag the nonce by a poll or two.
+        let deadline = Instant::now() + self.policy.confirm_timeout;
+        loop {
+            if let Some(receipt) = self.chain.transaction_receipt(original).await? {
+                return Ok(Some(receipt));
+            }
+            for &cancel in cancels {
+                if self.chain.transaction_receipt(cancel).await?.is_some() {
+                    info!("Nonce {} cleared by {}", nonce, chain::profile().tx_url(cancel));
+                    return Ok(None);
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "nonce {} was mined, but neither {:?} nor its cancellation was",
+                    nonce,
+                    original
+                ));
+            }
+            tokio::time::sleep(chain::profile().block_poll_interval()).await;
+        }
+    }
+}
+
+/// Whether a node refused a transaction because its nonce is already used or has a
+/// pending transaction it won't replace.
+fn is_nonce_taken(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    ["nonce too low", "replacement transaction underpriced", "already known"]
+        .iter()
+        .any(|needle| message.contains(needle))
+}
+
+/// Whether a submission failed because the transaction was mined and reverted.
+pub fn is_revert(err: &anyhow::Error) -> bool {
+    format!("{:#}", err).contains("reverted in block")
+}
+
+/// The transaction a submission failed with, when it was mined and reverted.
+pub fn reverted_tx(err: &anyhow::Error) -> Option<H256> {
+    let message = format!("{:#}", err);
+    let (before, _) = message.split_once(" reverted in block")?;
+    before.rsplit(' ').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taken_nonces_are_recognised() {
+        assert!(is_nonce_taken("(code: -32000, message: nonce too low, data: None)"));
+        assert!(is_nonce_taken("Replacement transaction underpriced"));
+        assert!(is_nonce_taken("already known"));
+        assert!(!is_nonce_taken("insufficient funds for gas * price + value"));
+    }
+
+    #[test]
+    fn reverted_transactions_are_read_back_from_the_error() {
+        let tx_hash = H256::repeat_byte(0x5a);
+        let err = anyhow!("buy {:?} reverted in block 7: no reason given", tx_hash);
+        assert_eq!(reverted_tx(&err.context("round trip failed")), Some(tx_hash));
+        assert_eq!(reverted_tx(&anyhow!("buy not filled within its time in force")), None);
+    }
+
+    #[test]
+    fn time_in_force_round_trips_through_its_display() {
+        for tif in ["retry", "ioc:3", "gtd:60", "fok"] {
+            assert_eq!(tif.parse::<TimeInForce>().unwrap().to_string(), tif);
+        }
+        assert_eq!("ioc:0".parse::<TimeInForce>().unwrap(), TimeInForce::Ioc(1));
+        assert!("gtd".parse::<TimeInForce>().is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::env;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::contract::{abigen, ContractCall};
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, H256, U256};
+use tracing::info;
+
+use crate::nadfun::{BuyParams, GasEstimationParams, SellParams, TxOptions};
+use crate::tx_manager::{SignerChain, TxChain};
+use crate::venue::Venue;
+
+abigen!(
+    UniswapV2Router,
+    r#"[
+        function WETH() external view returns (address)
+        function getAmountsOut(uint256 amountIn, address[] path) external view returns (uint256[] amounts)
+        function swapExactETHForTokensSupportingFeeOnTransferTokens(uint256 amountOutMin, address[] path, address to, uint256 deadline) external payable
+        function swapExactTokensForETHSupportingFeeOnTransferTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline) external
+    ]"#
+);
+
+/// Trades tokens against wrapped MON through a Uniswap V2 router, for tokens that never
+/// launched on nad.fun. Swaps use the fee-on-transfer variants, which also suit plain
+/// tokens.
+pub struct UniswapV2 {
+    router: UniswapV2Router<Provider<Http>>,
+    wrapped: Address,
+    signer: SignerChain,
+}
+
+impl UniswapV2 {
+    /// Enabled by `UNISWAP_V2_ROUTER`; the wrapped native token is read from the router.
+    pub async fn from_env(provider: &Provider<Http>, private_key: &str) -> Result<Option<Self>> {
+        let Some(router) = env::var("UNISWAP_V2_ROUTER").ok().filter(|v| !v.is_empty()) else {
+            return Ok(None);
+        };
+        let router: Address = router.parse().context("invalid UNISWAP_V2_ROUTER")?;
+        let router = UniswapV2Router::new(router, Arc::new(provider.clone()));
+        let wrapped = router
+            .weth()
+            .call()
+            .await
+            .context("UNISWAP_V2_ROUTER did not answer WETH()")?;
+        info!("Uniswap V2 venue at {:?}, trading against {:?}", router.address(), wrapped);
+        Ok(Some(Self {
+            router,
+            wrapped,
+            signer: SignerChain::new(provider, private_key)?,
+        }))
+    }
+
+    /// The swap path between native and `token`.
+    fn path(&self, token: Address, is_buy: bool) -> Vec<Address> {
+        if is_buy {
+            vec![self.wrapped, token]
+        } else {
+            vec![token, self.wrapped]
+        }
+    }
+
+    fn check_router(&self, router: Address) -> Result<()> {
+        if router != self.router.address() {
+            return Err(anyhow!("{:?} is not the Uniswap V2 router", router));
+        }
+        Ok(())
+    }
+
+    fn buy_call(
+        &self,
+        token: Address,
+        amount_in: U256,
+        amount_out_min: U256,
+        to: Address,
+        deadline: U256,
+    ) -> ContractCall<Provider<Http>, ()> {
+        self.router
+            .swap_exact_eth_for_tokens_supporting_fee_on_transfer_tokens(
+                amount_out_min,
+                self.path(token, true),
+                to,
+                deadline,
+            )
+            .value(amount_in)
+    }
+
+    fn sell_call(
+        &self,
+        token: Address,
+        amount_in: U256,
+        amount_out_min: U256,
+        to: Address,
+        deadline: U256,
+    ) -> ContractCall<Provider<Http>, ()> {
+        self.router.swap_exact_tokens_for_eth_supporting_fee_on_transfer_tokens(
+            amount_in,
+            amount_out_min,
+            self.path(token, false),
+            to,
+            deadline,
+        )
+    }
+}
+
+impl Venue for UniswapV2 {
+    async fn quote(&self, token: Address, amount: U256, is_buy: bool) -> Result<(Address, U256)> {
+        let amounts = self
+            .router
+            .get_amounts_out(amount, self.path(token, is_buy))
+            .call()
+            .await
+            .context("Uniswap V2 quote failed")?;
+        let out = amounts.last().copied().unwrap_or_default();
+        Ok((self.router.address(), out))
+    }
+
+    async fn estimate_gas(&self, router: Address, params: GasEstimationParams) -> Result<U256> {
+        self.check_router(router)?;
+        let call = match params {
+            GasEstimationParams::Buy {
+                token,
+                amount_in,
+                amount_out_min,
+                to,
+                deadline,
+            } => self.buy_call(token, amount_in, amount_out_min, to, deadline),
+            GasEstimationParams::Sell {
+                token,
+                amount_in,
+                amount_out_min,
+                to,
+                deadline,
+            } => self.sell_call(token, amount_in, amount_out_min, to, deadline),
+        };
+        Ok(call.from(self.signer.address()).estimate_gas().await?)
+    }
+
+    async fn buy(&self, router: Address, params: BuyParams, tx: TxOptions) -> Result<H256> {
+        self.check_router(router)?;
+        let call = self.buy_call(
+            params.token,
+            params.amount_in,
+            params.amount_out_min,
+            params.recipient,
+            params.deadline,
+        );
+        let data = call.calldata().ok_or_else(|| anyhow!("swap has no calldata"))?;
+        self.signer.send_call(router, data, params.amount_in, tx).await
+    }
+
+    async fn sell(&self, router: Address, params: SellParams, tx: TxOptions) -> Result<H256> {
+        self.check_router(router)?;
+        let call = self.sell_call(
+            params.token,
+            params.amount_in,
+            params.amount_out_min,
+            params.recipient,
+            params.deadline,
+        );
+        let data = call.calldata().ok_or_else(|| anyhow!("swap has no calldata"))?;
+        self.signer.send_call(router, data, U256::zero(), tx).await
+    }
+}
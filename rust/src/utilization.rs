@@ -0,0 +1,115 @@
+use std::env;
+
+use anyhow::{Context, Result};
+use ethers::types::U256;
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::chain;
+
+pub struct UtilizationConfig {
+    pub report_interval: Duration,
+    pub alert_below_pct: Option<f64>,
+    pub alert_after: Duration,
+}
+
+impl UtilizationConfig {
+    pub fn from_env() -> Result<Self> {
+        let secs = |name: &str, default: u64| -> u64 {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        Ok(Self {
+            report_interval: Duration::from_secs(secs("UTILIZATION_REPORT_SECS", 60).max(1)),
+            alert_below_pct: env::var("UTILIZATION_ALERT_PCT")
+                .ok()
+                .map(|v| v.parse().context("invalid UTILIZATION_ALERT_PCT"))
+                .transpose()?,
+            alert_after: Duration::from_secs(secs("UTILIZATION_ALERT_AFTER_SECS", 600)),
+        })
+    }
+}
+
+/// Time-weighted share of a strategy's allocated capital that is deployed in positions.
+pub struct Utilization {
+    strategy: &'static str,
+    allocated: U256,
+    started: Instant,
+    last_sample: Instant,
+    last_pct: f64,
+    weighted_pct_secs: f64,
+    low_since: Option<Instant>,
+    alerted: bool,
+}
+
+impl Utilization {
+    pub fn new(strategy: &'static str, allocated: U256) -> Self {
+        let now = Instant::now();
+        Self {
+            strategy,
+            allocated,
+            started: now,
+            last_sample: now,
+            last_pct: 0.0,
+            weighted_pct_secs: 0.0,
+            low_since: None,
+            alerted: false,
+        }
+    }
+
+    /// Records the currently deployed amount, prints the utilization line and raises an
+    /// alert once utilization has stayed below the configured threshold for too long.
+    pub fn observe(&mut self, deployed: U256, cfg: &UtilizationConfig) {
+        let now = Instant::now();
+        self.weighted_pct_secs += self.last_pct * (now - self.last_sample).as_secs_f64();
+        self.last_sample = now;
+        self.last_pct = self.pct(deployed);
+
+        let elapsed = (now - self.started).as_secs_f64();
+        let average = if elapsed > 0.0 {
+            self.weighted_pct_secs / elapsed
+        } else {
+            self.last_pct
+        };
+        let profile = chain::profile();
+        info!(
+            "Capital utilization [{}]: {} of {} deployed ({:.1}%), {} idle, {:.1}% average over {}m",
+            self.strategy,
+            profile.format_native(deployed.min(self.allocated)),
+            profile.format_native(self.allocated),
+            self.last_pct,
+            profile.format_native(self.allocated.saturating_sub(deployed)),
+            average,
+            (elapsed / 60.0).round()
+        );
+
+        let Some(threshold) = cfg.alert_below_pct else {
+            return;
+        };
+        if self.last_pct >= threshold {
+            self.low_since = None;
+            self.alerted = false;
+            return;
+        }
+        let low_since = *self.low_since.get_or_insert(now);
+        if !self.alerted && now - low_since >= cfg.alert_after {
+            self.alerted = true;
+            warn!(
+                "{} utilization below {:.1}% for {}m; filters may be too strict or events are being missed",
+                self.strategy,
+                threshold,
+                (now - low_since).as_secs() / 60
+            );
+        }
+    }
+
+    fn pct(&self, deployed: U256) -> f64 {
+        if self.allocated.is_zero() {
+            return 0.0;
+        }
+        let bps = deployed.min(self.allocated) * U256::from(10_000u64) / self.allocated;
+        bps.as_u64() as f64 / 100.0
+    }
+}
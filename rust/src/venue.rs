@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, H256, U256};
+
+use crate::nadfun::{BuyParams, GasEstimationParams, SellParams, Trade, TxOptions};
+use crate::uniswap_v2::UniswapV2;
+
+/// The exchange a token is bought and sold on, picked per token by its `venue` setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VenueKind {
+    /// nad.fun's bonding curve, and its DEX once the token graduates.
+    #[default]
+    NadFun,
+    /// The Uniswap V2 router at `UNISWAP_V2_ROUTER`, trading against wrapped MON.
+    UniswapV2,
+}
+
+impl FromStr for VenueKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "nadfun" | "nad.fun" => Ok(Self::NadFun),
+            "uniswap_v2" | "uniswap-v2" => Ok(Self::UniswapV2),
+            other => Err(anyhow!("unknown venue {:?}; expected nadfun or uniswap_v2", other)),
+        }
+    }
+}
+
+/// Where a token's trades are quoted and sent: the router a trade goes through and what it
+/// returns, its gas, and the buy or sell itself, broadcast at the nonce and gas of a
+/// [`TxOptions`] without waiting for it to be mined.
+pub trait Venue: Sync {
+    fn quote(
+        &self,
+        token: Address,
+        amount: U256,
+        is_buy: bool,
+    ) -> impl Future<Output = Result<(Address, U256)>> + Send;
+
+    fn estimate_gas(
+        &self,
+        router: Address,
+        params: GasEstimationParams,
+    ) -> impl Future<Output = Result<U256>> + Send;
+
+    fn buy(
+        &self,
+        router: Address,
+        params: BuyParams,
+        tx: TxOptions,
+    ) -> impl Future<Output = Result<H256>> + Send;
+
+    fn sell(
+        &self,
+        router: Address,
+        params: SellParams,
+        tx: TxOptions,
+    ) -> impl Future<Output = Result<H256>> + Send;
+}
+
+impl Venue for Trade {
+    async fn quote(&self, token: Address, amount: U256, is_buy: bool) -> Result<(Address, U256)> {
+        self.get_amount_out(token, amount, is_buy).await
+    }
+
+    async fn estimate_gas(&self, router: Address, params: GasEstimationParams) -> Result<U256> {
+        Trade::estimate_gas(self, &router, params).await
+    }
+
+    async fn buy(&self, router: Address, params: BuyParams, tx: TxOptions) -> Result<H256> {
+        Trade::buy(self, &router, params, tx).await
+    }
+
+    async fn sell(&self, router: Address, params: SellParams, tx: TxOptions) -> Result<H256> {
+        Trade::sell(self, &router, params, tx).await
+    }
+}
+
+/// One of the venues a client was opened with. A new venue is a [`VenueKind`], its
+/// [`Venue`] implementation and a variant here.
+pub enum AnyVenue<'a> {
+    NadFun(&'a Trade),
+    UniswapV2(&'a UniswapV2),
+}
+
+impl Venue for AnyVenue<'_> {
+    async fn quote(&self, token: Address, amount: U256, is_buy: bool) -> Result<(Address, U256)> {
+        match self {
+            Self::NadFun(venue) => venue.quote(token, amount, is_buy).await,
+            Self::UniswapV2(venue) => venue.quote(token, amount, is_buy).await,
+        }
+    }
+
+    async fn estimate_gas(&self, router: Address, params: GasEstimationParams) -> Result<U256> {
+        match self {
+            Self::NadFun(venue) => Venue::estimate_gas(*venue, router, params).await,
+            Self::UniswapV2(venue) => venue.estimate_gas(router, params).await,
+        }
+    }
+
+    async fn buy(&self, router: Address, params: BuyParams, tx: TxOptions) -> Result<H256> {
+        match self {
+            Self::NadFun(venue) => Venue::buy(*venue, router, params, tx).await,
+            Self::UniswapV2(venue) => venue.buy(router, params, tx).await,
+        }
+    }
+
+    async fn sell(&self, router: Address, params: SellParams, tx: TxOptions) -> Result<H256> {
+        match self {
+            Self::NadFun(venue) => Venue::sell(*venue, router, params, tx).await,
+            Self::UniswapV2(venue) => venue.sell(router, params, tx).await,
+        }
+    }
+}
+
+/// Which venue each token trades on: its `[[token]]` section's `venue`, else the one in
+/// `[defaults]` or `VENUE`.
+#[derive(Debug, Clone, Default)]
+pub struct VenueMap {
+    default: VenueKind,
+    tokens: HashMap<Address, VenueKind>,
+}
+
+impl VenueMap {
+    pub fn new(default: VenueKind, tokens: impl IntoIterator<Item = (Address, VenueKind)>) -> Self {
+        Self {
+            default,
+            tokens: tokens.into_iter().filter(|(_, kind)| *kind != default).collect(),
+        }
+    }
+
+    pub fn get(&self, token: Address) -> VenueKind {
+        self.tokens.get(&token).copied().unwrap_or(self.default)
+    }
+
+    /// Whether any token trades on `kind`.
+    pub fn uses(&self, kind: VenueKind) -> bool {
+        self.default == kind || self.tokens.values().any(|used| *used == kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn venues_parse_by_name() {
+        assert_eq!("nadfun".parse::<VenueKind>().unwrap(), VenueKind::NadFun);
+        assert_eq!(" Uniswap-V2 ".parse::<VenueKind>().unwrap(), VenueKind::UniswapV2);
+        assert!("kuru".parse::<VenueKind>().is_err());
+    }
+
+    #[test]
+    fn tokens_without_a_venue_use_the_default() {
+        let (listed, other) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let venues = VenueMap::new(
+            VenueKind::NadFun,
+            [(listed, VenueKind::UniswapV2), (other, VenueKind::NadFun)],
+        );
+        assert_eq!(venues.get(listed), VenueKind::UniswapV2);
+        assert_eq!(venues.get(other), VenueKind::NadFun);
+        assert_eq!(venues.get(Address::repeat_byte(3)), VenueKind::NadFun);
+        assert!(venues.uses(VenueKind::UniswapV2));
+        assert!(!VenueMap::new(VenueKind::NadFun, []).uses(VenueKind::UniswapV2));
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, U256};
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::trading::apply_slippage;
+use crate::caches;
+use crate::chain;
+use crate::curve::{CurveState, CurveTracker};
+use crate::entry::Entry;
+use crate::nadfun::{GasEstimationParams, Trade};
+use crate::orders::{OrderBook, OrderSide};
+use crate::state;
+use crate::app::AppConfig;
+
+static WARM: RwLock<BTreeMap<Address, WarmState>> = RwLock::new(BTreeMap::new());
+static MAX_AGE: RwLock<Option<Duration>> = RwLock::new(None);
+
+/// The last background quote of a watchlisted token.
+#[derive(Debug, Clone)]
+struct WarmState {
+    amount_in: U256,
+    router: Address,
+    quoted_out: U256,
+    buy_gas: U256,
+    curve: Option<CurveState>,
+    at: Instant,
+}
+
+pub struct WarmerConfig {
+    pub interval: Duration,
+    /// Warm state older than this is ignored and the caller quotes cold.
+    pub max_age: Duration,
+}
+
+impl WarmerConfig {
+    /// Enabled by `WARM_QUOTES_SECS`; `WARM_QUOTE_MAX_AGE_MS` defaults to two blocks.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(secs) = env::var("WARM_QUOTES_SECS").ok() else {
+            return Ok(None);
+        };
+        let interval = Duration::from_secs(secs.parse().context("invalid WARM_QUOTES_SECS")?);
+        if interval.is_zero() {
+            return Err(anyhow!("WARM_QUOTES_SECS must be positive"));
+        }
+        let max_age = env::var("WARM_QUOTE_MAX_AGE_MS")
+            .ok()
+            .map(|v| v.parse().context("invalid WARM_QUOTE_MAX_AGE_MS"))
+            .transpose()?
+            .map(Duration::from_millis)
+            .unwrap_or(chain::profile().block_time * 2);
+        Ok(Some(Self { interval, max_age }))
+    }
+}
+
+/// The warm entry for buying `token` with `amount_in`, if one is fresh enough.
+pub fn entry(token: Address, amount_in: U256, slippage_bps: u64) -> Option<Entry> {
+    let warm = fresh(token)?;
+    if warm.amount_in != amount_in {
+        return None;
+    }
+    info!(
+        "Using warm quote for {:?} from {}ms ago",
+        token,
+        warm.at.elapsed().as_millis()
+    );
+    Some(Entry {
+        router: warm.router,
+        quoted_out: warm.quoted_out,
+        amount_out_min: apply_slippage(warm.quoted_out, slippage_bps),
+        buy_gas: warm.buy_gas,
+    })
+}
+
+/// The warm curve state of `token`, if fresh enough.
+pub fn curve_state(token: Address) -> Option<CurveState> {
+    fresh(token)?.curve
+}
+
+fn fresh(token: Address) -> Option<WarmState> {
+    let max_age = (*MAX_AGE.read().ok()?)?;
+    let warm = WARM.read().ok()?.get(&token).cloned()?;
+    (warm.at.elapsed() <= max_age).then_some(warm)
+}
+
+/// Refreshes the buy quote, gas estimate and curve state of every configured target
+/// and resting limit buy every `interval`, so triggers can skip the cold quote.
+pub async fn start(cfg: &AppConfig, warmer: &WarmerConfig) -> Result<()> {
+    let trade = Trade::new(cfg.rpc_url.clone(), cfg.private_key.clone())
+        .await
+        .context("failed to initialize Trade client")?;
+    let curve = cfg
+        .bonding_curve
+        .map(|address| CurveTracker::new(&cfg.rpc_url, address))
+        .transpose()?;
+    let targets: Vec<(Address, U256)> = cfg
+        .targets
+        .iter()
+        .map(|target| (target.token, target.params.amount_in))
+        .collect();
+    let orders = OrderBook::new(PathBuf::from(cfg.orders.path()));
+    let recipient = cfg.recipient.unwrap_or_else(|| trade.wallet_address());
+    let deadline_secs = cfg.deadline_secs_from_now;
+    let interval = warmer.interval;
+    if let Ok(mut max_age) = MAX_AGE.write() {
+        *max_age = Some(warmer.max_age);
+    }
+
+    tokio::spawn(async move {
+        loop {
+            let mut watchlist = targets.clone();
+            match orders.list() {
+                Ok(resting) => watchlist.extend(
+                    resting
+                        .iter()
+                        .filter(|order| order.side == OrderSide::Buy)
+                        .map(|order| (order.token, order.amount)),
+                ),
+                Err(err) => warn!("Warm quotes skip limit orders: {:#}", err),
+            }
+            let watched: BTreeSet<Address> = watchlist.iter().map(|(token, _)| *token).collect();
+            for (token, amount_in) in watchlist {
+                let deadline = U256::from(state::unix_now() + deadline_secs);
+                match warm(&trade, curve.as_ref(), token, amount_in, recipient, deadline).await {
+                    Ok(state) => {
+                        if let Ok(mut cache) = WARM.write() {
+                            cache.insert(token, state);
+                        }
+                    }
+                    Err(err) => warn!("Warm quote for {:?} failed: {:#}", token, err),
+                }
+            }
+            // Filled or cancelled limit buys drop out of the watchlist; so does their state.
+            if let Ok(mut cache) = WARM.write() {
+                cache.retain(|token, _| watched.contains(token));
+                caches::observe("warm_quotes", cache.len());
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+    Ok(())
+}
+
+async fn warm(
+    trade: &Trade,
+    curve: Option<&CurveTracker>,
+    token: Address,
+    amount_in: U256,
+    recipient: Address,
+    deadline: U256,
+) -> Result<WarmState> {
+    let (router, quoted_out) = trade
+        .get_amount_out(token, amount_in, true)
+        .await
+        .context("failed to query quote")?;
+    if quoted_out.is_zero() {
+        return Err(anyhow!("quote returned zero tokens"));
+    }
+    let buy_gas = trade
+        .estimate_gas(
+            &router,
+            GasEstimationParams::Buy {
+                token,
+                amount_in,
+                amount_out_min: U256::zero(),
+                to: recipient,
+                deadline,
+            },
+        )
+        .await
+        .context("failed to estimate buy gas")?;
+    let curve = match curve {
+        Some(curve) => Some(curve.state(token).await?),
+        None => None,
+    };
+    Ok(WarmState {
+        amount_in,
+        router,
+        quoted_out,
+        buy_gas,
+        curve,
+        at: Instant::now(),
+    })
+}
@@ -0,0 +1,343 @@
+//! Drives a full round trip through `Engine` against an in-memory chain.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use ethers::types::{Address, Bytes, Log, TransactionReceipt, H256, U256};
+use ethers::utils::keccak256;
+
+use nadfun_trading_bot::cli::Cli;
+use nadfun_trading_bot::{
+    BuyParams, Engine, ExecOrder, ExecutionClient, GasEstimationParams, RetryPolicy, SellParams,
+    TimeInForce, TxChain, TxManager,
+};
+
+const GAS_USED: u64 = 100_000;
+const GAS_PRICE: u64 = 1_000_000_000;
+
+/// A token that quotes 1000 tokens per native on the way in and half again what was paid
+/// on the way out, so every position is an immediate take-profit.
+#[derive(Default)]
+struct Chain {
+    block: u64,
+    /// The wallet's native balance from each block on.
+    native: Vec<(u64, U256)>,
+    tokens: U256,
+    receipts: HashMap<H256, TransactionReceipt>,
+    sells: usize,
+    /// The wallet's next nonce; every transaction mined uses one.
+    nonce: u64,
+    /// Leave buys pending instead of mining them.
+    stall_buys: bool,
+    /// The buy left pending at the current nonce.
+    pending: Option<H256>,
+    cancels: usize,
+}
+
+struct FakeClient {
+    wallet: Address,
+    router: Address,
+    chain: Mutex<Chain>,
+    policy: RetryPolicy,
+}
+
+impl FakeClient {
+    fn new() -> Self {
+        Self {
+            wallet: Address::repeat_byte(0x11),
+            router: Address::repeat_byte(0x22),
+            chain: Mutex::new(Chain {
+                block: 100,
+                native: vec![(0, U256::exp10(20))],
+                ..Chain::default()
+            }),
+            policy: RetryPolicy {
+                max_attempts: 2,
+                base_delay: Duration::from_millis(10),
+                confirm_timeout: Duration::from_secs(1),
+                bump_pct: 130,
+                confirmations: 1,
+                buy_tif: TimeInForce::Retry,
+                sell_tif: TimeInForce::Retry,
+            },
+        }
+    }
+
+    fn sell_value(tokens: U256) -> U256 {
+        tokens * 3 / 2000
+    }
+
+    /// Mines a transaction that changes the wallet's balance to `native` before its gas.
+    fn mine(&self, chain: &mut Chain, native: U256, logs: Vec<Log>) -> TransactionReceipt {
+        chain.block += 1;
+        chain.nonce += 1;
+        chain.pending = None;
+        let gas = U256::from(GAS_USED * GAS_PRICE);
+        chain.native.push((chain.block, native - gas));
+        let hash = H256::from_low_u64_be(chain.block);
+        let receipt = TransactionReceipt {
+            transaction_hash: hash,
+            block_number: Some(chain.block.into()),
+            status: Some(1u64.into()),
+            gas_used: Some(GAS_USED.into()),
+            effective_gas_price: Some(GAS_PRICE.into()),
+            logs,
+            ..TransactionReceipt::default()
+        };
+        chain.receipts.insert(hash, receipt.clone());
+        receipt
+    }
+}
+
+fn transfer(token: Address, from: Address, to: Address, amount: U256) -> Log {
+    let mut data = [0u8; 32];
+    amount.to_big_endian(&mut data);
+    Log {
+        address: token,
+        topics: vec![
+            H256::from(keccak256("Transfer(address,address,uint256)")),
+            H256::from(from),
+            H256::from(to),
+        ],
+        data: Bytes::from(data.to_vec()),
+        ..Log::default()
+    }
+}
+
+impl ExecutionClient for FakeClient {
+    fn wallet(&self) -> Address {
+        self.wallet
+    }
+
+    async fn native_balance(&self, _owner: Address, block: Option<u64>) -> Result<U256> {
+        let chain = self.chain.lock().unwrap();
+        let block = block.unwrap_or(chain.block);
+        let (_, balance) = chain.native.iter().rev().find(|(at, _)| *at <= block).unwrap();
+        Ok(*balance)
+    }
+
+    async fn token_balance(&self, _token: Address, _owner: Address) -> Result<U256> {
+        Ok(self.chain.lock().unwrap().tokens)
+    }
+
+    async fn allowance(&self, _token: Address, _owner: Address, _spender: Address) -> Result<U256> {
+        Ok(U256::MAX)
+    }
+
+    async fn approve(&self, _token: Address, _spender: Address, _amount: U256) -> Result<H256> {
+        Err(anyhow!("the allowance is already unlimited"))
+    }
+
+    async fn gas_price(&self) -> Result<U256> {
+        Ok(GAS_PRICE.into())
+    }
+
+    async fn block_number(&self) -> Result<u64> {
+        Ok(self.chain.lock().unwrap().block)
+    }
+
+    async fn code(&self, _address: Address) -> Result<Bytes> {
+        Ok(Bytes::from(vec![0x60, 0x80]))
+    }
+
+    async fn quote(&self, _token: Address, amount: U256, is_buy: bool) -> Result<(Address, U256)> {
+        let out = if is_buy {
+            amount * 1000
+        } else {
+            Self::sell_value(amount)
+        };
+        Ok((self.router, out))
+    }
+
+    async fn estimate_gas(&self, _router: Address, _params: GasEstimationParams) -> Result<U256> {
+        Ok(GAS_USED.into())
+    }
+
+    async fn receipt(&self, tx_hash: H256) -> Result<TransactionReceipt> {
+        let chain = self.chain.lock().unwrap();
+        chain
+            .receipts
+            .get(&tx_hash)
+            .cloned()
+            .ok_or_else(|| anyhow!("unknown transaction {:?}", tx_hash))
+    }
+
+    async fn buy(
+        &self,
+        router: Address,
+        params: BuyParams,
+        tif: TimeInForce,
+    ) -> Result<TransactionReceipt> {
+        assert_eq!(router, self.router);
+        let params = &params;
+        let txs = TxManager::new(self, &self.policy);
+        txs.submit("buy", tif, |nonce| async move {
+            let mut chain = self.chain.lock().unwrap();
+            assert_eq!(nonce, chain.nonce.into());
+            if chain.stall_buys {
+                let hash = H256::from_low_u64_be(1_000_000 + chain.nonce);
+                chain.pending = Some(hash);
+                return Ok(hash);
+            }
+            let out = params.amount_in * 1000;
+            assert!(out >= params.amount_out_min);
+            let native = chain.native.last().unwrap().1 - params.amount_in;
+            chain.tokens += out;
+            let logs = vec![transfer(params.token, router, params.recipient, out)];
+            Ok(self.mine(&mut chain, native, logs).transaction_hash)
+        })
+        .await
+    }
+
+    async fn sell(
+        &self,
+        router: Address,
+        params: SellParams,
+        tif: TimeInForce,
+    ) -> Result<TransactionReceipt> {
+        assert_eq!(router, self.router);
+        let params = &params;
+        let txs = TxManager::new(self, &self.policy);
+        txs.submit("sell", tif, |nonce| async move {
+            let mut chain = self.chain.lock().unwrap();
+            assert_eq!(nonce, chain.nonce.into());
+            let native = chain.native.last().unwrap().1 + Self::sell_value(params.amount_in);
+            chain.tokens -= params.amount_in;
+            chain.sells += 1;
+            Ok(self.mine(&mut chain, native, Vec::new()).transaction_hash)
+        })
+        .await
+    }
+}
+
+impl TxChain for FakeClient {
+    fn address(&self) -> Address {
+        self.wallet
+    }
+
+    async fn nonce(&self, pending: bool) -> Result<U256> {
+        let chain = self.chain.lock().unwrap();
+        let queued = u64::from(pending && chain.pending.is_some());
+        Ok((chain.nonce + queued).into())
+    }
+
+    async fn gas_price(&self) -> Result<U256> {
+        Ok(GAS_PRICE.into())
+    }
+
+    async fn block_number(&self) -> Result<u64> {
+        Ok(self.chain.lock().unwrap().block)
+    }
+
+    async fn transaction_receipt(&self, tx_hash: H256) -> Result<Option<TransactionReceipt>> {
+        Ok(self.chain.lock().unwrap().receipts.get(&tx_hash).cloned())
+    }
+
+    async fn offered_fee(&self, tx_hash: H256) -> Result<Option<U256>> {
+        let chain = self.chain.lock().unwrap();
+        Ok((chain.pending == Some(tx_hash)).then(|| GAS_PRICE.into()))
+    }
+
+    /// Mined straight away, in place of the pending buy.
+    async fn send_cancel(&self, nonce: U256, gas_price: U256) -> Result<H256> {
+        let mut chain = self.chain.lock().unwrap();
+        assert_eq!(nonce, chain.nonce.into());
+        assert!(gas_price > GAS_PRICE.into());
+        chain.cancels += 1;
+        let native = chain.native.last().unwrap().1;
+        Ok(self.mine(&mut chain, native, Vec::new()).transaction_hash)
+    }
+
+    async fn revert_reason(&self, _receipt: &TransactionReceipt) -> Option<String> {
+        None
+    }
+}
+
+#[tokio::test]
+async fn round_trip_buys_holds_and_sells_through_the_client() {
+    let dir = env::temp_dir().join(format!("nadfun-engine-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = |name: &str| dir.join(name).display().to_string();
+    for (name, value) in [
+        ("RPC_URL", "http://127.0.0.1:1".to_string()),
+        ("PRIVATE_KEY", format!("0x{}", "01".repeat(32))),
+        ("AMOUNT_IN_MON", "1".to_string()),
+        ("TAKE_PROFIT_PCT", "20".to_string()),
+        ("STOP_LOSS_PCT", "10".to_string()),
+        ("EXIT_CHECK_INTERVAL_SECS", "0".to_string()),
+        ("TOKEN_PIN_POLICY", "off".to_string()),
+        ("STATE_FILE", file("positions.json")),
+        ("TRADE_LEDGER_FILE", file("trades.jsonl")),
+        ("EXECUTION_LOG_FILE", file("execution.jsonl")),
+        ("LIMIT_ORDERS_FILE", file("orders.json")),
+        ("LOCKED_WALLETS_FILE", file("locked_wallets.json")),
+    ] {
+        env::set_var(name, value);
+    }
+
+    let env_file = file(".env");
+    let cli = Cli::parse_from(["nadfun_trading_bot", "--env-file", env_file.as_str()]);
+    let engine = Engine::with_client(&cli, FakeClient::new()).unwrap();
+    let token = Address::repeat_byte(0x33);
+    engine
+        .round_trip(ExecOrder {
+            token,
+            amount_in: None,
+            slippage_bps: None,
+        })
+        .await
+        .unwrap();
+
+    let client = engine.client();
+    {
+        let chain = client.chain.lock().unwrap();
+        assert_eq!(chain.sells, 1);
+        assert!(chain.tokens.is_zero());
+    }
+    assert!(engine.positions().open().unwrap().is_empty());
+
+    let ledger = fs::read_to_string(dir.join("trades.jsonl")).unwrap();
+    let records: Vec<serde_json::Value> = ledger
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 1);
+    let one = U256::exp10(18);
+    let proceeds: U256 = serde_json::from_value(records[0]["proceeds"].clone()).unwrap();
+    assert_eq!(proceeds, one * 3 / 2);
+    let gas: U256 = serde_json::from_value(records[0]["gas_spent"].clone()).unwrap();
+    assert_eq!(gas, U256::from(2 * GAS_USED * GAS_PRICE));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn ioc_buy_is_cancelled_once_its_blocks_pass() {
+    let client = FakeClient::new();
+    client.chain.lock().unwrap().stall_buys = true;
+    let params = BuyParams {
+        token: Address::repeat_byte(0x33),
+        amount_in: U256::exp10(18),
+        amount_out_min: U256::zero(),
+        recipient: client.wallet,
+        deadline: U256::MAX,
+    };
+
+    let err = client
+        .buy(client.router, params, TimeInForce::Ioc(2))
+        .await
+        .unwrap_err();
+    assert!(format!("{:#}", err).contains("not filled within its time in force (ioc:2)"));
+
+    let chain = client.chain.lock().unwrap();
+    // One attempt only, and its nonce cleared by the cancellation rather than the buy.
+    assert_eq!(chain.cancels, 1);
+    assert_eq!(chain.nonce, 1);
+    assert!(chain.pending.is_none());
+    assert!(chain.tokens.is_zero());
+}